@@ -0,0 +1,141 @@
+/// Friendly names of the Pods, e.g. `canary` for `payments-api-7d9f8b6c4d-x2kpl`,
+/// given to the Pods whose names match a glob.
+///
+/// An alias is shown in front of the meta of the lines when they are drawn,
+/// so it applies to the lines queued before it was given as well,
+/// and the meta of the lines stays as it was made.
+#[derive(Clone, Default)]
+pub struct Aliases {
+    /// Glob of the Pod names and alias, the first matching one applying.
+    globs: Vec<(String, String)>,
+}
+
+impl Aliases {
+    /// Rejects the same glob or the same alias given twice,
+    /// which would make the lines of different Pods look alike.
+    pub fn new(globs: Vec<(String, String)>) -> anyhow::Result<Self> {
+        for (i, (glob, alias)) in globs.iter().enumerate() {
+            if let Some((other, _)) = globs[..i].iter().find(|(_, other)| other == alias) {
+                return Err(anyhow::anyhow!(
+                    "alias '{alias}' is given to both '{other}' and '{glob}'"
+                ));
+            }
+            if globs[..i].iter().any(|(other, _)| other == glob) {
+                return Err(anyhow::anyhow!("'{glob}' is given more than one alias"));
+            }
+        }
+        Ok(Self { globs })
+    }
+
+    /// Alias of the Pod, if any.
+    pub fn get(&self, pod: &str) -> Option<&str> {
+        self.globs
+            .iter()
+            .find(|(glob, _)| glob_matches(glob, pod))
+            .map(|(_, alias)| alias.as_str())
+    }
+}
+
+/// Parses `GLOB=ALIAS`, e.g. `payments-api-7d9f*=canary`.
+pub fn parse(s: &str) -> Result<(String, String), String> {
+    let (glob, alias) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected POD_GLOB=ALIAS, got '{s}'"))?;
+    if glob.is_empty() || alias.trim().is_empty() {
+        return Err(format!("expected POD_GLOB=ALIAS, got '{s}'"));
+    }
+    Ok((glob.to_string(), alias.trim().to_string()))
+}
+
+/// Whether the name matches the glob, where `*` matches any characters and `?` any one.
+fn glob_matches(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut g, mut n) = (0, 0);
+    // Position of the last `*` and of the name where it started matching, to backtrack to.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, n));
+                g += 1;
+            }
+            Some('?') => {
+                g += 1;
+                n += 1;
+            }
+            Some(&ch) if ch == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_g, star_n)) => {
+                    g = star_g + 1;
+                    n = star_n + 1;
+                    star = Some((star_g, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&ch| ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(glob, alias)| (glob.to_string(), alias.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn colliding_aliases_are_rejected() {
+        let err = Aliases::new(globs(&[("api-*", "canary"), ("worker-*", "canary")]))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "alias 'canary' is given to both 'api-*' and 'worker-*'"
+        );
+        let err = Aliases::new(globs(&[("api-*", "canary"), ("api-*", "stable")]))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "'api-*' is given more than one alias");
+
+        let aliases = Aliases::new(globs(&[("api-7d9f*", "canary"), ("api-*", "stable")])).unwrap();
+        // The first matching glob applies.
+        assert_eq!(aliases.get("api-7d9f8b6c4d-x2kpl"), Some("canary"));
+        assert_eq!(aliases.get("api-5c6b7d8e9f-q4wzt"), Some("stable"));
+        assert_eq!(aliases.get("worker-0"), None);
+    }
+
+    #[test]
+    fn globs_backtrack_over_their_stars() {
+        // The first `b` the star stops at isn't the one to match from.
+        assert!(glob_matches("a*b*c", "aXbYbZc"));
+        assert!(glob_matches("a*bc", "abXbc"));
+        assert!(!glob_matches("a*b*c", "aXbYbZ"));
+        assert!(glob_matches("*-api-*", "payments-api-7d9f8b6c4d-x2kpl"));
+        assert!(!glob_matches("*-api-*", "payments-api"));
+        assert!(glob_matches("api-?", "api-0"));
+        assert!(!glob_matches("api-?", "api-10"));
+        assert!(glob_matches("api-0*", "api-0"));
+        assert!(glob_matches("api-0**", "api-0"));
+        assert!(!glob_matches("api-0", "api-01"));
+    }
+
+    #[test]
+    fn aliases_are_parsed_from_glob_and_name() {
+        assert_eq!(
+            parse("payments-api-7d9f*= canary "),
+            Ok((String::from("payments-api-7d9f*"), String::from("canary")))
+        );
+        for invalid in ["canary", "=canary", "api-*=", "api-*= "] {
+            assert!(parse(invalid).is_err(), "{invalid}");
+        }
+    }
+}
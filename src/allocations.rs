@@ -0,0 +1,56 @@
+//! Counts the allocations made on each thread in the tests,
+//! to tell the cost of an operation or the memory a structure holds.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+struct Counting;
+
+thread_local! {
+    static COUNT: Cell<usize> = const { Cell::new(0) };
+    /// Bytes allocated on the thread and not freed since, freed elsewhere or not.
+    static LIVE: Cell<isize> = const { Cell::new(0) };
+}
+
+fn counted(bytes: isize) {
+    let _ = COUNT.try_with(|count| count.set(count.get() + 1));
+    let _ = LIVE.try_with(|live| live.set(live.get() + bytes));
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        counted(layout.size() as isize);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE.try_with(|live| live.set(live.get() - layout.size() as isize));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        counted(new_size as isize - layout.size() as isize);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Number of allocations made on this thread while running `f`.
+pub fn count(f: impl FnOnce()) -> usize {
+    let before = COUNT.with(Cell::get);
+    f();
+    COUNT.with(Cell::get) - before
+}
+
+/// What `f` returns, with the bytes it allocated on this thread and left allocated,
+/// i.e. the memory held by what it returns if built on this thread only.
+#[cfg_attr(not(feature = "compress"), allow(dead_code))]
+pub fn live<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = LIVE.with(Cell::get);
+    let value = f();
+    (value, (LIVE.with(Cell::get) - before).max(0) as usize)
+}
@@ -0,0 +1,255 @@
+use chrono::{DateTime, Utc};
+use tokio::{
+    sync::{mpsc, Mutex, Notify},
+    time::{sleep_until, Duration, Instant},
+};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::container::ContainerLog;
+
+/// Holds back the backlogs of the streams launched together, to show them merged by time
+/// instead of interleaved by the timing of their connections.
+///
+/// Every line of a stream is held until the stream catches up with its backlog (see
+/// `StreamSettings::backlog_quiet_time`), ends, or is skipped. Once all of them did,
+/// or the window elapsed so that a slow stream doesn't hold back the others, the lines are
+/// sent ordered by their timestamps, and the lines coming after that are sent as they come.
+///
+/// Lines without a timestamp take the one of the line before them in their stream,
+/// and come first, stream by stream, if none of the lines before them had one.
+pub struct Backfill {
+    state: Mutex<State>,
+    caught_up: Notify,
+}
+
+struct State {
+    /// Held lines with their time and the index of their stream, in order of arrival.
+    lines: Vec<(Option<DateTime<Utc>>, usize, ContainerLog)>,
+    /// Number of streams which haven't caught up yet.
+    pending: usize,
+    flushed: bool,
+}
+
+impl Backfill {
+    pub fn new(streams: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                lines: Vec::new(),
+                pending: streams,
+                flushed: false,
+            }),
+            caught_up: Notify::new(),
+        }
+    }
+
+    /// Holds the line of the stream back, or gives it back to be sent if the lines were flushed.
+    pub async fn hold(
+        &self,
+        stream: usize,
+        at: Option<DateTime<Utc>>,
+        log: ContainerLog,
+    ) -> Option<ContainerLog> {
+        let mut state = self.state.lock().await;
+        if state.flushed {
+            return Some(log);
+        }
+        state.lines.push((at, stream, log));
+        None
+    }
+
+    /// Marks a stream as caught up with its backlog. Called once per stream.
+    pub async fn caught_up(&self) {
+        let mut state = self.state.lock().await;
+        state.pending = state.pending.saturating_sub(1);
+        if state.pending == 0 {
+            self.caught_up.notify_one();
+        }
+    }
+
+    /// Sends the held lines merged by time once all the streams caught up,
+    /// or the window elapsed, and lets the next lines through.
+    /// Once canceled, nothing waits: the held lines without room in the channel are given up.
+    pub async fn flush(
+        &self,
+        log_stream_tx: &mpsc::Sender<ContainerLog>,
+        window: Duration,
+        canceled: &CancellationToken,
+    ) {
+        let deadline = Instant::now() + window;
+        loop {
+            if self.state.lock().await.pending == 0 {
+                break;
+            }
+            tokio::select! {
+                _ = self.caught_up.notified() => (),
+                _ = sleep_until(deadline) => break,
+                _ = canceled.cancelled() => break,
+            }
+        }
+        // Sent under the lock so that no line let through comes before the held ones.
+        let mut state = self.state.lock().await;
+        state.flushed = true;
+        for log in merge(std::mem::take(&mut state.lines)) {
+            let permit = tokio::select! {
+                biased;
+                permit = log_stream_tx.reserve() => permit,
+                _ = canceled.cancelled() => break,
+            };
+            match permit {
+                Ok(permit) => permit.send(log),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Orders the lines by time, keeping the order of each stream.
+fn merge(lines: Vec<(Option<DateTime<Utc>>, usize, ContainerLog)>) -> Vec<ContainerLog> {
+    let mut last: Vec<Option<DateTime<Utc>>> = Vec::new();
+    let mut lines: Vec<(Option<DateTime<Utc>>, usize, ContainerLog)> = lines
+        .into_iter()
+        .map(|(at, stream, log)| {
+            if last.len() <= stream {
+                last.resize(stream + 1, None);
+            }
+            if at.is_some() {
+                last[stream] = at;
+            }
+            (last[stream], stream, log)
+        })
+        .collect();
+    // Stable, so that the lines of a stream with the same time keep their order.
+    lines.sort_by_key(|(at, stream, _)| (at.is_some(), *at, at.map_or(*stream, |_| 0)));
+    lines.into_iter().map(|(_, _, log)| log).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::grapheme::StyledGraphemes;
+
+    use super::*;
+    use crate::container::LogKind;
+
+    fn log(pod: &str, body: &str) -> ContainerLog {
+        ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from(pod),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from(pod),
+            body: StyledGraphemes::from(body),
+            continuation: Vec::new(),
+            received: None,
+            at: None,
+        }
+    }
+
+    fn at(seconds: u32) -> Option<DateTime<Utc>> {
+        Some(
+            DateTime::parse_from_rfc3339(&format!("2024-05-01T12:00:{seconds:02}Z"))
+                .unwrap()
+                .with_timezone(&Utc),
+        )
+    }
+
+    fn bodies(logs: &[ContainerLog]) -> Vec<String> {
+        logs.iter().map(|log| log.body.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_interleaves_the_streams_by_time() {
+        let lines = vec![
+            (at(1), 0, log("api-0", "a1")),
+            (at(4), 0, log("api-0", "a4")),
+            (at(2), 1, log("api-1", "b2")),
+            (at(3), 1, log("api-1", "b3")),
+            (at(4), 1, log("api-1", "b4")),
+        ];
+        // The lines of the same time keep the order they arrived in.
+        assert_eq!(bodies(&merge(lines)), ["a1", "b2", "b3", "a4", "b4"]);
+    }
+
+    #[test]
+    fn merge_gives_the_lines_without_a_time_the_one_before_them() {
+        let lines = vec![
+            (at(1), 0, log("api-0", "a1")),
+            (None, 0, log("api-0", "a1 continued")),
+            (at(3), 0, log("api-0", "a3")),
+            (at(2), 1, log("api-1", "b2")),
+            (None, 1, log("api-1", "b2 continued")),
+        ];
+        assert_eq!(
+            bodies(&merge(lines)),
+            ["a1", "a1 continued", "b2", "b2 continued", "a3"]
+        );
+    }
+
+    #[test]
+    fn merge_puts_the_lines_without_an_earlier_time_first() {
+        let lines = vec![
+            (at(1), 0, log("api-0", "a1")),
+            (None, 1, log("api-1", "b")),
+            (None, 0, log("api-0", "a1 continued")),
+            (None, 2, log("api-2", "c")),
+            (at(0), 1, log("api-1", "b0")),
+            (None, 1, log("api-1", "b0 continued")),
+        ];
+        // Stream by stream.
+        assert_eq!(
+            bodies(&merge(lines)),
+            ["b", "c", "b0", "b0 continued", "a1", "a1 continued"]
+        );
+    }
+
+    #[tokio::test]
+    async fn flush_once_all_the_streams_caught_up() {
+        let backfill = Backfill::new(2);
+        let (log_stream_tx, mut log_stream_rx) = mpsc::channel(8);
+        assert!(backfill.hold(1, at(2), log("api-1", "b2")).await.is_none());
+        assert!(backfill.hold(0, at(1), log("api-0", "a1")).await.is_none());
+        backfill.caught_up().await;
+        backfill.caught_up().await;
+
+        let started = Instant::now();
+        backfill
+            .flush(
+                &log_stream_tx,
+                Duration::from_secs(60),
+                &CancellationToken::new(),
+            )
+            .await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        drop(log_stream_tx);
+        let mut flushed = Vec::new();
+        while let Some(log) = log_stream_rx.recv().await {
+            flushed.push(log);
+        }
+        assert_eq!(bodies(&flushed), ["a1", "b2"]);
+    }
+
+    #[tokio::test]
+    async fn flush_when_the_window_elapses() {
+        let backfill = Backfill::new(2);
+        let (log_stream_tx, mut log_stream_rx) = mpsc::channel(8);
+        assert!(backfill.hold(0, at(3), log("api-0", "a3")).await.is_none());
+        assert!(backfill.hold(1, at(1), log("api-1", "b1")).await.is_none());
+        // The second stream is still reading its backlog.
+        backfill.caught_up().await;
+
+        let window = Duration::from_millis(100);
+        let started = Instant::now();
+        backfill
+            .flush(&log_stream_tx, window, &CancellationToken::new())
+            .await;
+        assert!(started.elapsed() >= window);
+        assert_eq!(log_stream_rx.recv().await.unwrap().body.to_string(), "b1");
+        assert_eq!(log_stream_rx.recv().await.unwrap().body.to_string(), "a3");
+
+        // The lines of the slow stream are let through as they come from then on.
+        let late = backfill.hold(1, at(2), log("api-1", "b2")).await;
+        assert_eq!(late.unwrap().body.to_string(), "b2");
+    }
+}
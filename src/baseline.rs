@@ -0,0 +1,222 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use regex::Regex;
+
+use promkit::grapheme::StyledGraphemes;
+
+use crate::{
+    container::{ContainerLog, LogKind},
+    style::StyleConfig,
+};
+
+/// How a log line relates to the lines emitted by the baseline container.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Novelty {
+    /// The line was emitted by the baseline container itself.
+    Baseline,
+    /// The (normalized) line was also emitted by the baseline container.
+    Known,
+    /// The (normalized) line has never been emitted by the baseline container.
+    Novel,
+}
+
+impl Novelty {
+    /// Restyles the body of a log line according to its novelty:
+    /// lines also seen in the baseline are dimmed and novel lines are marked.
+    pub fn restyle(&self, body: &StyledGraphemes, style: &StyleConfig) -> StyledGraphemes {
+        match self {
+            Novelty::Baseline => body.clone(),
+            Novelty::Known => StyledGraphemes::from_str(body.to_string(), style.dim),
+            Novelty::Novel => StyledGraphemes::from_iter([
+                StyledGraphemes::from_str("+ ", style.novel),
+                body.clone(),
+            ]),
+        }
+    }
+}
+
+/// Keeps track of the lines emitted by a designated baseline container
+/// in order to tell which lines of the other containers are novel,
+/// e.g. when comparing a canary against a reference Pod.
+pub struct Baseline {
+    /// Namespace of the Pod, if given, to tell it from Pods of the same name in others.
+    namespace: Option<String>,
+    pod: String,
+    container: String,
+    normalizer: Option<Regex>,
+    hashes: HashSet<u64>,
+}
+
+impl Baseline {
+    /// Creates a baseline from the target given as `[<NAMESPACE>/]<POD>/<CONTAINER>`.
+    /// The parts of lines matching `normalizer` (e.g. timestamps or ids)
+    /// are ignored in comparison. An empty `normalizer` disables normalization.
+    pub fn try_new(target: &str, normalizer: &str) -> anyhow::Result<Self> {
+        let (namespace, pod, container) = match target.split('/').collect::<Vec<_>>()[..] {
+            [pod, container] => (None, pod, container),
+            [namespace, pod, container] => (Some(namespace.to_string()), pod, container),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "baseline must be given as [<NAMESPACE>/]<POD>/<CONTAINER>, got '{target}'"
+                ))
+            }
+        };
+        Ok(Self {
+            namespace,
+            pod: pod.to_string(),
+            container: container.to_string(),
+            normalizer: match normalizer {
+                "" => None,
+                pattern => Some(Regex::new(pattern)?),
+            },
+            hashes: HashSet::new(),
+        })
+    }
+
+    fn hash(&self, body: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        match &self.normalizer {
+            Some(normalizer) => normalizer.replace_all(body, "").trim().hash(&mut hasher),
+            None => body.trim().hash(&mut hasher),
+        }
+        hasher.finish()
+    }
+
+    fn is_baseline(&self, log: &ContainerLog) -> bool {
+        log.pod == self.pod
+            && log.container == self.container
+            && self
+                .namespace
+                .as_ref()
+                .is_none_or(|namespace| *namespace == log.namespace)
+    }
+
+    /// Records the line if it comes from the baseline container, and classifies it.
+    pub fn observe(&mut self, log: &ContainerLog) -> Novelty {
+        if log.kind != LogKind::Log {
+            return self.classify(log);
+        }
+        if self.is_baseline(log) {
+            let hashed = self.hash(&log.body.to_string());
+            self.hashes.insert(hashed);
+            Novelty::Baseline
+        } else {
+            self.classify(log)
+        }
+    }
+
+    /// Classifies the line against the lines recorded so far.
+    pub fn classify(&self, log: &ContainerLog) -> Novelty {
+        // Annotations are not compared, and shown as they are.
+        if log.kind != LogKind::Log || self.is_baseline(log) {
+            Novelty::Baseline
+        } else if self.hashes.contains(&self.hash(&log.body.to_string())) {
+            Novelty::Known
+        } else {
+            Novelty::Novel
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(namespace: &str, pod: &str, body: &str) -> ContainerLog {
+        ContainerLog {
+            namespace: String::from(namespace),
+            pod: String::from(pod),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from(pod),
+            body: StyledGraphemes::from(body),
+            continuation: Vec::new(),
+            received: None,
+            at: None,
+        }
+    }
+
+    #[test]
+    fn targets_are_parsed_with_and_without_a_namespace() {
+        let baseline = Baseline::try_new("api-0/api", "").unwrap();
+        assert!(baseline.is_baseline(&log("default", "api-0", "")));
+        assert!(baseline.is_baseline(&log("staging", "api-0", "")));
+        assert!(!baseline.is_baseline(&log("default", "api-1", "")));
+
+        let baseline = Baseline::try_new("staging/api-0/api", "").unwrap();
+        assert!(baseline.is_baseline(&log("staging", "api-0", "")));
+        assert!(!baseline.is_baseline(&log("default", "api-0", "")));
+
+        for invalid in ["api-0", "a/b/c/d"] {
+            let err = Baseline::try_new(invalid, "").err().unwrap();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "baseline must be given as [<NAMESPACE>/]<POD>/<CONTAINER>, got '{invalid}'"
+                )
+            );
+        }
+        assert!(Baseline::try_new("api-0/api", "(").is_err());
+    }
+
+    #[test]
+    fn lines_are_classified_against_the_baseline() {
+        let mut baseline = Baseline::try_new("default/api-0/api", "").unwrap();
+        assert!(baseline.observe(&log("default", "api-0", "GET /orders 200")) == Novelty::Baseline);
+        assert!(baseline.observe(&log("default", "api-1", "GET /orders 200 ")) == Novelty::Known);
+        assert!(baseline.observe(&log("default", "api-1", "GET /orders 500")) == Novelty::Novel);
+        // A Pod of the same name in another namespace is compared, not recorded.
+        assert!(baseline.observe(&log("staging", "api-0", "GET /users 200")) == Novelty::Novel);
+        assert!(baseline.classify(&log("default", "api-1", "GET /users 200")) == Novelty::Novel);
+
+        let mut event = log("default", "api-0", "Normal Pulled: on api-0");
+        event.kind = LogKind::Event;
+        assert!(baseline.observe(&event) == Novelty::Baseline);
+        event.pod = String::from("api-1");
+        assert!(baseline.classify(&event) == Novelty::Baseline);
+    }
+
+    #[test]
+    fn normalized_parts_are_ignored_in_comparison() {
+        let mut baseline = Baseline::try_new("api-0/api", r"\d{2}:\d{2}:\d{2}|id=\w+").unwrap();
+        baseline.observe(&log("default", "api-0", "12:00:01 GET /orders id=a1b2"));
+        assert!(
+            baseline.classify(&log("default", "api-1", "12:30:45 GET /orders id=c3d4"))
+                == Novelty::Known
+        );
+        assert!(
+            baseline.classify(&log("default", "api-1", "12:30:45 GET /users id=c3d4"))
+                == Novelty::Novel
+        );
+
+        // Without a normalizer, only the surrounding whitespace is ignored.
+        let mut baseline = Baseline::try_new("api-0/api", "").unwrap();
+        baseline.observe(&log("default", "api-0", "12:00:01 GET /orders"));
+        assert!(
+            baseline.classify(&log("default", "api-1", "12:30:45 GET /orders")) == Novelty::Novel
+        );
+    }
+
+    #[test]
+    fn lines_are_restyled_by_their_novelty() {
+        let style = StyleConfig::default();
+        let body = StyledGraphemes::from("GET /orders 500");
+        assert_eq!(
+            Novelty::Baseline.restyle(&body, &style).to_string(),
+            "GET /orders 500"
+        );
+        assert_eq!(
+            Novelty::Known.restyle(&body, &style).to_string(),
+            "GET /orders 500"
+        );
+        assert_eq!(
+            Novelty::Novel.restyle(&body, &style).to_string(),
+            "+ GET /orders 500"
+        );
+    }
+}
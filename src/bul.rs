@@ -1,151 +1,1451 @@
-use std::{collections::VecDeque, sync::Arc};
+use std::{
+    borrow::Cow,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
 
+use chrono::{DateTime, Local};
 use futures::StreamExt;
-use k8s_openapi::api::core::v1::Pod;
-use kube::Api;
 use tokio::{
-    sync::{mpsc, RwLock},
+    sync::{
+        mpsc,
+        oneshot::{self, error::TryRecvError},
+        RwLock,
+    },
     task::JoinHandle,
     time::{self, Duration},
 };
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
 
 use promkit::{
-    crossterm::{self, event, style::Color},
+    crossterm::{
+        self,
+        event::{self, Event},
+    },
     grapheme::StyledGraphemes,
-    style::StyleBuilder,
     switch::ActiveKeySwitcher,
-    text_editor, PaneFactory,
+    text_editor,
 };
 
 mod keymap;
+mod render;
+mod split;
 use crate::{
-    container::{ContainerLog, ContainerLogStreamer, ContainerStateMatcher},
+    alias::Aliases,
+    baseline::Baseline,
+    change::ChangeFilter,
+    container::{is_unauthorized, ContainerLog, ContainerLogStreamer, StreamSettings},
+    events,
+    notice::Notice,
+    queue::{Capture, LogQueue},
+    record::Recorder,
+    rollout,
+    sink::Sinks,
+    style::StyleConfig,
     terminal::Terminal,
+    wrap::wrap,
     Signal,
 };
+use render::Frame;
+use split::{Columns, Filters};
+
+/// Separator drawn when the container of consecutive lines changes.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Separator {
+    None,
+    Blank,
+    Rule,
+}
+
+/// Edge of the screen where the newest lines of the live view are drawn,
+/// with the filter at the opposite one.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Orientation {
+    Bottom,
+    Top,
+}
+
+impl Orientation {
+    /// Joins the newer rows to the older ones in the order they are drawn in,
+    /// keeping the newest ones which fit in the height.
+    fn join(
+        self,
+        mut older: Vec<StyledGraphemes>,
+        mut newer: Vec<StyledGraphemes>,
+        height: usize,
+    ) -> Vec<StyledGraphemes> {
+        match self {
+            Orientation::Bottom => {
+                older.append(&mut newer);
+                older.split_off(older.len().saturating_sub(height))
+            }
+            Orientation::Top => {
+                newer.append(&mut older);
+                newer.truncate(height);
+                newer
+            }
+        }
+    }
+}
+
+/// Whether the live view follows the tail of the stream, always shown in the prompt.
+/// While paused, the matching lines are kept but not drawn so that the view stays where it is,
+/// e.g. scrolled back in the terminal or at the lines resumed from dig.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Follow {
+    Following,
+    /// Paused at the local time.
+    Paused(DateTime<Local>),
+}
+
+impl Follow {
+    /// Resuming from dig anchors the view at the lines from there.
+    fn initial(backlog: &[ContainerLog]) -> Self {
+        if backlog.is_empty() {
+            Follow::Following
+        } else {
+            Follow::Paused(Local::now())
+        }
+    }
+
+    fn toggled(self) -> Self {
+        match self {
+            Follow::Following => Follow::Paused(Local::now()),
+            Follow::Paused(_) => Follow::Following,
+        }
+    }
+
+    fn status(&self) -> String {
+        match self {
+            Follow::Following => String::from("▶ following"),
+            Follow::Paused(at) => format!("⏸ paused @ {}", at.format("%H:%M:%S")),
+        }
+    }
+}
+
+/// Whether a paused view following the matches jumps to a new match of the query,
+/// unless the query is the whole stream or the last jump was too recent to keep up with.
+fn jumps(
+    follow_matches: bool,
+    query: &str,
+    followed_at: Option<time::Instant>,
+    throttle: Duration,
+) -> bool {
+    follow_matches && !query.is_empty() && followed_at.is_none_or(|at| at.elapsed() >= throttle)
+}
+
+/// Settings of the live view which stay the same across reconnects.
+pub struct Settings {
+    /// Tuning of the log streams.
+    pub stream: StreamSettings,
+    /// Interval at which the log stream is rendered.
+    pub render_interval: Duration,
+    /// Window to press Ctrl+C again to quit. Zero quits immediately.
+    pub quit_confirm_window: Duration,
+    /// Maximum number of log entries to store in memory.
+    pub queue_capacity: usize,
+    /// Number of the most recent entries kept uncompressed, beyond which they are compressed.
+    /// None are compressed if `None`.
+    pub compress_queue: Option<usize>,
+    /// Whether to show how many lines were drawn at once in a render.
+    pub burst_counter: bool,
+    /// Whether a line matching the filter makes a paused view follow again, initially.
+    pub follow_matches: bool,
+    /// Minimum interval between jumps to new matches while paused.
+    pub follow_throttle: Duration,
+    /// Separator between the lines of different containers.
+    pub separator: Separator,
+    /// Edge where the newest lines are drawn. Always the bottom with `split`.
+    pub orientation: Orientation,
+    /// Percentage of the width taken by the left column if the view is split into two columns,
+    /// each with its own filter.
+    pub split: Option<u16>,
+    /// Number of recent warnings/errors kept in the sticky region. Zero disables the region.
+    pub notice_capacity: usize,
+    /// Interval to refresh the header showing the status of the only streamed container.
+    /// No header is shown if `None`, or while more than one container is streamed.
+    pub header_interval: Option<Duration>,
+    /// Optional baseline container to compare the other containers' logs with.
+    pub baseline: Option<Arc<Mutex<Baseline>>>,
+    /// Destinations which every received line is fanned out to, regardless of the filter.
+    pub sinks: Sinks,
+    /// Filter admitting only the lines which change a tracked field, shared across reconnects
+    /// so that the last values are kept.
+    pub changes: Option<Arc<Mutex<ChangeFilter>>>,
+    /// Recorder of the lines as displayed, shared across reconnects.
+    pub recorder: Option<Arc<Mutex<Recorder>>>,
+    /// Kubernetes context, to render the kubectl commands equivalent to the streams.
+    pub context: String,
+    /// Watcher of a rollout to annotate the log stream with.
+    pub rollout: Option<rollout::Watcher>,
+    /// Watcher of the Events of the streamed Pods to merge into the log stream.
+    pub events: Option<events::Watcher>,
+    /// Aliases of the Pods, shown in front of the meta of their lines.
+    pub aliases: Aliases,
+    pub style: StyleConfig,
+}
+
+/// Streamer of the live view, which is built in the background on startup
+/// so that the view is drawn before the client of the cluster is ready.
+pub enum Connection {
+    /// Receiver of the streamer once its client is built and checked against the API server,
+    /// or of the error if that fails.
+    Pending(oneshot::Receiver<anyhow::Result<ContainerLogStreamer>>),
+    Ready(Box<ContainerLogStreamer>),
+    /// Building the client failed, and it is built again if the user retries.
+    Failed,
+}
+
+/// Error which ended the live view, given back with the lines captured until then
+/// and the query typed, so that they can be kept all the same.
+pub struct Failure {
+    pub error: anyhow::Error,
+    /// Lines captured so far, `None` if they were lost with the task keeping them.
+    pub capture: Option<Capture>,
+    pub query: String,
+}
 
 /// Run the main application logic.
 ///
 /// Set up and manages the text editor, terminal, and log streaming for container logs.
 /// It handles user input and updates the display accordingly. The function continues to run until
 /// a specific signal (`Signal::GoToDig` or `Signal::GoToBul`) is received, indicating a transition
-/// to another part of the application.
+/// to another part of the application, or until the log streams are rejected as unauthorized
+/// (`Signal::Reauthenticate`).
+///
+/// While the connection is pending, the view shows that it is connecting and the query can be
+/// typed already. If connecting fails, the error is shown and the function returns on
+/// `Signal::GoToBul` to retry or `Signal::Interrupt` to quit, with the connection `Failed`.
 ///
 /// # Arguments
 /// * `text_editor` - State of the text editor used within the terminal.
-/// * `api_pod` - Kubernetes API client configured for Pod resources.
-/// * `pod_query` - Optional query string to filter pods.
-/// * `container_state_matcher` - Matcher to filter containers based on their state.
-/// * `pod_log_stream_timeout_duration` - Duration to wait before timing out the log stream.
-/// * `render_interval_duration` - Interval at which the log stream is rendered.
-/// * `queue_capacity` - Maximum number of log entries to store in memory.
+/// * `notice` - Optional message rendered once before the log stream starts.
+/// * `capture` - Lines captured by the earlier runs, kept in the queue before the new ones.
+/// * `anchor` - Index in `capture` of the line selected in dig, rendered with the lines
+///   after it before going live.
+/// * `connection` - Streamer of the logs of the selected containers, once connected.
+/// * `settings` - Settings of the live view.
 ///
 /// # Returns
-/// Returns a tuple containing the exit signal and a deque of `ContainerLog` entries if successful.
+/// Returns a tuple containing the exit signal, the `Capture` of the lines with the counts
+/// of the ones left out, and the query active at exit if successful.
 ///
 /// # Errors
 /// This function can return an error if there are issues creating the terminal, reading from the event stream,
-/// or interacting with the Kubernetes API.
+/// or interacting with the Kubernetes API, as a `Failure` holding the capture and the query.
+/// Failures to write to the terminal stop the log streams and end with an `io::Error`.
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     text_editor: text_editor::State,
-    api_pod: Api<Pod>,
-    pod_query: Option<String>,
-    container_state_matcher: ContainerStateMatcher,
-    log_retrieval_timeout: Duration,
-    render_interval: Duration,
-    queue_capacity: usize,
-) -> anyhow::Result<(Signal, VecDeque<ContainerLog>)> {
+    notice: Option<String>,
+    capture: Capture,
+    anchor: Option<usize>,
+    connection: &mut Connection,
+    settings: &Settings,
+) -> Result<(Signal, Capture, String), Failure> {
+    let mut capture = Some(capture);
+    let mut query = String::new();
+    show(
+        text_editor,
+        notice,
+        &mut capture,
+        &mut query,
+        anchor,
+        connection,
+        settings,
+    )
+    .await
+    .map_err(|error| Failure {
+        error,
+        capture,
+        query,
+    })
+}
+
+/// Body of `run`, leaving the capture and the query in `capture` and `query`
+/// as far as they are known when it fails.
+async fn show(
+    text_editor: text_editor::State,
+    notice: Option<String>,
+    capture: &mut Option<Capture>,
+    query: &mut String,
+    anchor: Option<usize>,
+    connection: &mut Connection,
+    settings: &Settings,
+) -> anyhow::Result<(Signal, Capture, String)> {
     let keymap = ActiveKeySwitcher::new("default", keymap::default);
     let size = crossterm::terminal::size()?;
 
-    let pane = text_editor.create_pane(size.0, size.1);
-    let mut term = Terminal::new(&pane)?;
+    let backlog: Vec<ContainerLog> = match (anchor, capture.as_ref()) {
+        (Some(anchor), Some(capture)) => capture
+            .lines
+            .iter_from(anchor)
+            .map(Cow::into_owned)
+            .collect(),
+        _ => Vec::new(),
+    };
+    let initial = Follow::initial(&backlog);
+    let mut filters = Filters::new(text_editor, settings.split.is_some());
+    *query = filters.query();
+    filters.set_status(&initial.status());
+    if let Connection::Pending(_) = connection {
+        filters.set_progress(Some(format!("connecting to {}…", settings.context)));
+    }
+    let pane = filters.create_pane(size.0, size.1);
+    let mut term = Terminal::new(
+        &pane,
+        settings.orientation,
+        settings.notice_capacity,
+        settings.recorder.clone(),
+        settings.style.clone(),
+    )?;
     term.draw_pane(&pane)?;
+    if let Some(notice) = notice {
+        term.draw_stream_and_pane(
+            vec![StyledGraphemes::from_str(notice, settings.style.dim)],
+            &pane,
+        )?;
+    }
+    // Resume from the line selected in dig so that it and everything after it
+    // are shown in order, followed by the live logs.
+    if !backlog.is_empty() {
+        let rule = |label: &str| vec![StyledGraphemes::from_str(label, settings.style.dim)];
+        term.draw_stream_and_pane(rule("── from dig ──"), &pane)?;
+        for log in backlog.iter() {
+            term.draw_stream_and_pane(
+                wrap(
+                    &log.row(&log.body, &settings.aliases, &settings.style),
+                    size.0 as usize,
+                    size.1 as usize,
+                    &settings.style,
+                ),
+                &pane,
+            )?;
+        }
+        term.draw_stream_and_pane(rule("── live ──"), &pane)?;
+    }
 
-    let shared_term = Arc::new(RwLock::new(term));
-    let shared_text_editor = Arc::new(RwLock::new(text_editor));
-    let readonly_term = Arc::clone(&shared_term);
+    // Keys are taken while connecting, so that the query can be typed in the meantime.
+    let mut container_log_streamer = loop {
+        if let Some(streamer) =
+            poll_connection(connection, &mut term, &mut filters, &settings.style)?
+        {
+            break streamer;
+        }
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let signal = keymap.get()(&event::read()?, filters.focused_mut())?;
+        match signal {
+            Signal::GoToBul if matches!(connection, Connection::Failed) => (),
+            Signal::Interrupt => (),
+            _ => {
+                let size = term.size()?;
+                term.draw_pane(&filters.create_pane(size.0, size.1))?;
+                continue;
+            }
+        }
+        return Ok((signal, capture.take().unwrap_or_default(), filters.query()));
+    };
+    filters.set_states(container_log_streamer.container_states());
+    let size = term.size()?;
+    term.draw_pane(&filters.create_pane(size.0, size.1))?;
+
+    let shared_text_editor = Arc::new(RwLock::new(filters));
     let readonly_text_editor = Arc::clone(&shared_text_editor);
+    let noticed_text_editor = Arc::clone(&shared_text_editor);
+    let paused = Arc::new(AtomicBool::new(false));
+    let readonly_paused = Arc::clone(&paused);
+    let follow = Arc::new(Mutex::new(initial));
+    let readonly_follow = Arc::clone(&follow);
+    let follow_matches = Arc::new(AtomicBool::new(settings.follow_matches));
+    let readonly_follow_matches = Arc::clone(&follow_matches);
 
-    let (log_stream_tx, mut log_stream_rx) = mpsc::channel(1);
-    let container_log_streamer =
-        ContainerLogStreamer::try_new(api_pod, pod_query, container_state_matcher)?;
+    // Buffer some lines so that the ones arriving between renders can be drawn at once.
+    let (log_stream_tx, mut log_stream_rx) = mpsc::channel(256);
+    // Warnings and errors go through a dedicated channel
+    // so that a clogged log pipeline can't hide them.
+    let (notice_tx, mut notice_rx) = mpsc::channel(16);
     let canceler = CancellationToken::new();
+    // Stop the streams on any early return as well, e.g. when the terminal can't be read.
+    let _cancel_on_return = canceler.clone().drop_guard();
+    let unauthorized = CancellationToken::new();
+    // Streams already open trip it as well, not only the launch.
+    container_log_streamer.set_unauthorized_notifier(unauthorized.clone());
+    // Tripped when the terminal can't be written to anymore (e.g. the SSH connection dropped),
+    // after which nothing can be shown and the session is over.
+    let terminal_lost = CancellationToken::new();
+    // Every frame goes through the render task, which alone writes to the terminal.
+    let (frames, frames_rx) = mpsc::channel(render::QUEUED_FRAMES);
+    let rendering = tokio::spawn(render::render(term, frames_rx, terminal_lost.clone()));
+
+    let header_keeping: Option<JoinHandle<anyhow::Result<()>>> =
+        settings.header_interval.map(|header_interval| {
+            let streamer = container_log_streamer.clone();
+            let header_frames = frames.clone();
+            let header_text_editor = Arc::clone(&shared_text_editor);
+            let header_notice_tx = notice_tx.clone();
+            tokio::spawn(async move {
+                let mut interval = time::interval(header_interval);
+                loop {
+                    interval.tick().await;
+                    // Keep the last header if the status can't be fetched this time.
+                    let Ok(header) = streamer.describe_single(&header_notice_tx).await else {
+                        continue;
+                    };
+                    let size = crossterm::terminal::size()?;
+                    let pane = header_text_editor.read().await.create_pane(size.0, size.1);
+                    if header_frames
+                        .send(Frame::Header(header, pane))
+                        .await
+                        .is_err()
+                    {
+                        return Ok(());
+                    }
+                }
+            })
+        });
+
+    // Shows how many streams are opened while they are launched.
+    let launch_progressing: JoinHandle<anyhow::Result<()>> = {
+        let streamer = container_log_streamer.clone();
+        let progress_frames = frames.clone();
+        let progress_text_editor = Arc::clone(&shared_text_editor);
+        let canceled = canceler.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_millis(100));
+            let mut shown = None;
+            while !canceled.is_cancelled() {
+                interval.tick().await;
+                let progress = streamer.launch_progress();
+                if progress == shown {
+                    continue;
+                }
+                let mut text_editor = progress_text_editor.write().await;
+                text_editor.set_progress(
+                    progress.map(|(opened, total)| format!("opening streams {opened}/{total}")),
+                );
+                let size = crossterm::terminal::size()?;
+                let pane = text_editor.create_pane(size.0, size.1);
+                drop(text_editor);
+                if progress_frames.send(Frame::Pane(pane)).await.is_err() {
+                    break;
+                }
+                // Launching is over once the progress is gone again.
+                if shown.is_some() && progress.is_none() {
+                    break;
+                }
+                shown = progress;
+            }
+            Ok(())
+        })
+    };
+
+    let rollout_watching = settings.rollout.clone().map(|watcher| {
+        let client = container_log_streamer.client();
+        let log_stream_tx = log_stream_tx.clone();
+        let notice_tx = notice_tx.clone();
+        let style = settings.style.clone();
+        let canceled = canceler.clone();
+        tokio::spawn(async move {
+            watcher
+                .run(client, log_stream_tx, notice_tx, &style, canceled)
+                .await
+        })
+    });
+
+    // Launched streams watch the Events themselves, to merge them with their backlogs.
+    let enforcing = settings.stream.enforce_interval.is_some();
+    let events_watching = settings
+        .events
+        .clone()
+        .filter(|_| enforcing)
+        .map(|watcher| {
+            let streamer = container_log_streamer.clone();
+            let log_stream_tx = log_stream_tx.clone();
+            let notice_tx = notice_tx.clone();
+            let style = settings.style.clone();
+            let canceled = canceler.clone();
+            tokio::spawn(async move {
+                watcher
+                    .run(streamer, log_stream_tx, notice_tx, &style, canceled, None)
+                    .await
+            })
+        });
 
     let canceled = canceler.clone();
+    let unauthorized_notifier = unauthorized.clone();
+    let stream_settings = settings.stream;
+    let stream_style = settings.style.clone();
+    let launch_notice_tx = notice_tx.clone();
+    let commands_streamer = container_log_streamer.clone();
+    let states_streamer = container_log_streamer.clone();
+    let keeping_streamer = container_log_streamer.clone();
+    let events = settings.events.clone();
     let log_streaming = tokio::spawn(async move {
-        container_log_streamer
-            .launch_log_streams(log_stream_tx, log_retrieval_timeout, canceled)
-            .await?
-            .collect::<Vec<_>>()
-            .await;
+        let streamed = match stream_settings.enforce_interval {
+            Some(_) => {
+                container_log_streamer
+                    .enforce_log_streams(
+                        log_stream_tx,
+                        launch_notice_tx.clone(),
+                        stream_settings,
+                        &stream_style,
+                        canceled,
+                    )
+                    .await
+            }
+            None => match container_log_streamer
+                .launch_log_streams(
+                    log_stream_tx,
+                    launch_notice_tx.clone(),
+                    stream_settings,
+                    &stream_style,
+                    canceled,
+                    events.as_ref(),
+                )
+                .await
+            {
+                Ok(streams) => {
+                    streams.collect::<Vec<_>>().await;
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            },
+        };
+        match streamed {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                // Let the event loop know that the client needs to be rebuilt
+                // instead of leaving the user with silently dead streams.
+                if is_unauthorized(&e) {
+                    unauthorized_notifier.cancel();
+                }
+                let _ = launch_notice_tx
+                    .try_send(Notice::error(format!("failed to launch log streams: {e}")));
+                Err(e)
+            }
+        }
+    });
+
+    let notice_frames = frames.clone();
+    let notice_keeping: JoinHandle<anyhow::Result<()>> = tokio::spawn(async move {
+        while let Some(notice) = notice_rx.recv().await {
+            let size = crossterm::terminal::size()?;
+            let pane = noticed_text_editor.read().await.create_pane(size.0, size.1);
+            if notice_frames
+                .send(Frame::Notice(notice, pane))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
         Ok(())
     });
 
-    let log_keeping: JoinHandle<anyhow::Result<VecDeque<ContainerLog>>> =
-        tokio::spawn(async move {
-            let mut queue = VecDeque::with_capacity(queue_capacity);
-            let interval = time::interval(render_interval);
-            futures::pin_mut!(interval);
+    let render_interval = settings.render_interval;
+    let queue_capacity = settings.queue_capacity;
+    let compress_queue = settings.compress_queue;
+    let baseline = settings.baseline.clone();
+    let sinks = settings.sinks.clone();
+    let recorder = settings.recorder.clone();
+    let changes = settings.changes.clone();
+    let render_frames = frames.clone();
+    let burst_counter = settings.burst_counter;
+    let separator = settings.separator;
+    let orientation = settings.orientation;
+    let follow_throttle = settings.follow_throttle;
+    let style = settings.style.clone();
+    let aliases = settings.aliases.clone();
+    let mut columns = settings.split.map(Columns::new);
+    // The lines from dig start both columns, as the filters start empty.
+    if let Some(columns) = columns.as_mut() {
+        let (left_width, right_width) = columns.widths(size.0);
+        let rows = |width: usize| {
+            backlog
+                .iter()
+                .flat_map(|log| {
+                    wrap(
+                        &log.row(&log.body, &aliases, &style),
+                        width,
+                        size.1 as usize,
+                        &style,
+                    )
+                })
+                .collect()
+        };
+        columns.push(rows(left_width), rows(right_width), size.1 as usize);
+    }
+    let captured = capture.take().unwrap_or_default();
+    let log_keeping: JoinHandle<anyhow::Result<Capture>> = tokio::spawn(async move {
+        let mut queue =
+            LogQueue::with_capture(queue_capacity, compress_queue, style.clone(), captured);
+        let interval = time::interval(render_interval);
+        futures::pin_mut!(interval);
+        // Source of the last drawn line, to tell when the container changes.
+        let mut last_source: Option<(String, String, String)> = None;
+        // Rows which matched while paused, drawn once following again.
+        let mut paused_rows: Vec<StyledGraphemes> = Vec::new();
+        let mut followed_at: Option<time::Instant> = None;
+        // Filter of the last burst, to trace when it changes.
+        let mut last_query: Option<String> = None;
 
-            loop {
-                interval.tick().await;
-                let maybe_log = log_stream_rx.recv().await;
-                match maybe_log {
-                    Some(log) => {
-                        let text_editor = readonly_text_editor.read().await;
-                        let size = crossterm::terminal::size()?;
-
-                        if queue.len() > queue_capacity {
-                            queue.pop_front().unwrap();
+        loop {
+            interval.tick().await;
+            // Stop receiving while paused so that the stream tasks are held back.
+            if readonly_paused.load(Ordering::SeqCst) {
+                continue;
+            }
+            let maybe_log = log_stream_rx.recv().await;
+            match maybe_log {
+                Some(log) => {
+                    // Drain the lines which arrived since the last render
+                    // so that bursts are drawn at once instead of one line per tick.
+                    let mut logs = vec![log];
+                    while let Ok(log) = log_stream_rx.try_recv() {
+                        logs.push(log);
+                    }
+                    let received = logs.len();
+                    let started = time::Instant::now();
+                    // Stamped once per burst, which is as precise as the render interval.
+                    let now = Local::now();
+                    for log in logs.iter_mut() {
+                        log.received.get_or_insert(now);
+                        // Only now, so that relaunched streams fetch the lines left on their way.
+                        keeping_streamer.consumed(log);
+                    }
+
+                    // Hold the locks only as long as needed so that typing doesn't stall
+                    // behind the highlighting and wrapping of a burst.
+                    let (query, right_query) = {
+                        let filters = readonly_text_editor.read().await;
+                        (filters.query(), filters.right_query())
+                    };
+                    let size = crossterm::terminal::size()?;
+
+                    let mut rows = Vec::new();
+                    let mut right_rows = Vec::new();
+                    let mut rendered = 0;
+                    // A sink which failed is reported once and dropped
+                    // rather than failing on every line.
+                    sinks.lock().unwrap().retain_mut(|sink| {
+                        logs.iter()
+                            .try_for_each(|log| sink.send(log))
+                            .map_err(|e| {
+                                warn!(error = %e, "dropped a sink");
+                                let _ = notice_tx
+                                    .try_send(Notice::warning(format!("dropped a sink: {e}")));
+                            })
+                            .is_ok()
+                    });
+                    for log in logs {
+                        queue.push(log.clone());
+
+                        let body = match &baseline {
+                            Some(baseline) => baseline
+                                .lock()
+                                .unwrap()
+                                .observe(&log)
+                                .restyle(&log.body, &style),
+                            None => log.body.clone(),
+                        };
+                        // Suppressed lines are still kept for dig and the sinks.
+                        if changes
+                            .as_ref()
+                            .is_some_and(|changes| !changes.lock().unwrap().admits(&log))
+                        {
+                            continue;
                         }
-                        queue.push_back(log.clone());
-
-                        if let Some(body) = log.body.highlight(
-                            &text_editor.texteditor.text_without_cursor().to_string(),
-                            StyleBuilder::new()
-                                .bgc(Color::Yellow)
-                                .fgc(Color::Black)
-                                .build(),
-                        ) {
-                            let merge = StyledGraphemes::from_iter([
-                                log.meta,
-                                StyledGraphemes::from(" "),
-                                body,
-                            ])
-                            .matrixify(size.0 as usize, size.1 as usize, 0)
-                            .0;
-                            let term = readonly_term.read().await;
-                            term.draw_stream_and_pane(
-                                merge,
-                                &text_editor.create_pane(size.0, size.1),
-                            )?;
+                        // A stack trace matching only in its grouped lines is shown collapsed.
+                        let matched = |query: &str| {
+                            body.clone().highlight(query, style.highlight).or_else(|| {
+                                log.continuation
+                                    .iter()
+                                    .any(|line| line.to_string().contains(query))
+                                    .then(|| body.clone())
+                            })
+                        };
+                        if let (Some(columns), Some(right_query)) = (&columns, &right_query) {
+                            let (left_width, right_width) = columns.widths(size.0);
+                            for (query, width, rows) in [
+                                (&query, left_width, &mut rows),
+                                (right_query, right_width, &mut right_rows),
+                            ] {
+                                if let Some(body) = matched(query) {
+                                    rendered += 1;
+                                    rows.extend(wrap(
+                                        &log.row(&body, &aliases, &style),
+                                        width,
+                                        size.1 as usize,
+                                        &style,
+                                    ));
+                                }
+                            }
+                            continue;
+                        }
+                        if let Some(body) = matched(&query) {
+                            rendered += 1;
+                            let source = (
+                                log.namespace.clone(),
+                                log.pod.clone(),
+                                log.container.clone(),
+                            );
+                            let mut line = wrap(
+                                &log.row(&body, &aliases, &style),
+                                size.0 as usize,
+                                size.1 as usize,
+                                &style,
+                            );
+                            if last_source.as_ref().is_some_and(|last| *last != source) {
+                                let rule = match separator {
+                                    Separator::None => None,
+                                    Separator::Blank => Some(StyledGraphemes::default()),
+                                    Separator::Rule => Some(StyledGraphemes::from_str(
+                                        "─".repeat(size.0 as usize),
+                                        style.separator,
+                                    )),
+                                };
+                                // Between the line and the older ones, wherever they are.
+                                match (rule, orientation) {
+                                    (Some(rule), Orientation::Bottom) => line.insert(0, rule),
+                                    (Some(rule), Orientation::Top) => line.push(rule),
+                                    (None, _) => (),
+                                }
+                            }
+                            last_source = Some(source);
+                            match orientation {
+                                Orientation::Bottom => rows.append(&mut line),
+                                // Reversed back once the burst is done,
+                                // so that the newest line comes first but keeps its rows in order.
+                                Orientation::Top => rows.extend(line.into_iter().rev()),
+                            }
                         }
                     }
-                    None => break,
+                    if orientation == Orientation::Top {
+                        rows.reverse();
+                    }
+                    if last_query.as_ref() != Some(&query) {
+                        debug!(query, "applied filter");
+                        last_query = Some(query.clone());
+                    }
+                    debug!(
+                        received,
+                        shown = rendered,
+                        elapsed = ?started.elapsed(),
+                        "filtered burst"
+                    );
+                    if let Some(columns) = columns.as_mut() {
+                        if rows.is_empty() && right_rows.is_empty() {
+                            continue;
+                        }
+                        let recorded = rows.iter().chain(right_rows.iter()).cloned().collect();
+                        columns.push(rows, right_rows, size.1 as usize);
+                        // Paused, the columns keep up but the view stays where it is.
+                        let following = *readonly_follow.lock().unwrap() == Follow::Following;
+                        let pane = if following {
+                            Some(
+                                readonly_text_editor
+                                    .read()
+                                    .await
+                                    .create_pane(size.0, size.1),
+                            )
+                        } else {
+                            None
+                        };
+                        let frame = Frame::Columns {
+                            recorded,
+                            left: columns.left.clone(),
+                            right: columns.right.clone(),
+                            left_width: columns.widths(size.0).0,
+                            pane,
+                        };
+                        if render_frames.send(frame).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    if rows.is_empty() {
+                        continue;
+                    }
+                    if *readonly_follow.lock().unwrap() != Follow::Following {
+                        let follows = jumps(
+                            readonly_follow_matches.load(Ordering::SeqCst),
+                            &query,
+                            followed_at,
+                            follow_throttle,
+                        );
+                        paused_rows = orientation.join(
+                            std::mem::take(&mut paused_rows),
+                            std::mem::take(&mut rows),
+                            size.1 as usize,
+                        );
+                        if !follows {
+                            continue;
+                        }
+                        followed_at = Some(time::Instant::now());
+                        *readonly_follow.lock().unwrap() = Follow::Following;
+                        readonly_text_editor
+                            .write()
+                            .await
+                            .set_status(&Follow::Following.status());
+                    }
+                    // Rows kept while paused come before the new ones,
+                    // and rows beyond the screen would be pushed off immediately.
+                    let rows =
+                        orientation.join(std::mem::take(&mut paused_rows), rows, size.1 as usize);
+
+                    let pane = readonly_text_editor
+                        .read()
+                        .await
+                        .create_pane(size.0, size.1);
+                    let frame = Frame::Stream {
+                        rows,
+                        pane,
+                        counter: (burst_counter && rendered > 1).then_some(rendered),
+                    };
+                    // The lines received so far are kept to be returned with the queue.
+                    if render_frames.send(frame).await.is_err() {
+                        break;
+                    }
+                    if let Some(e) = recorder
+                        .as_ref()
+                        .and_then(|recorder| recorder.lock().unwrap().take_failure())
+                    {
+                        let _ =
+                            notice_tx.try_send(Notice::warning(format!("stopped recording: {e}")));
+                    }
                 }
+                None => break,
             }
-            Ok(queue)
-        });
-
-    let mut signal: Signal;
-    loop {
-        let event = event::read()?;
-        let mut text_editor = shared_text_editor.write().await;
-        signal = keymap.get()(&event, &mut text_editor)?;
-        if signal == Signal::GoToDig || signal == Signal::GoToBul {
-            break;
         }
+        Ok(queue.into_capture(keeping_streamer.dropped()))
+    });
+
+    // Failures end the keys only, so that the capture is taken back from the task keeping it.
+    let handled: anyhow::Result<Signal> = async {
+        let mut signal: Signal;
+        let mut interrupted_at: Option<time::Instant> = None;
+        loop {
+            if terminal_lost.is_cancelled() {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "lost the terminal").into());
+            }
+            if unauthorized.is_cancelled() {
+                signal = Signal::Reauthenticate;
+                break;
+            }
+            if interrupted_at.is_some_and(|at| at.elapsed() > settings.quit_confirm_window) {
+                interrupted_at = None;
+                paused.store(false, Ordering::SeqCst);
+            }
+            // Poll instead of blocking on read
+            // so that credential errors from the log streams are noticed without user input.
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let pressed = time::Instant::now();
 
-        let size = crossterm::terminal::size()?;
-        let pane = text_editor.create_pane(size.0, size.1);
-        let mut term = shared_term.write().await;
-        term.draw_pane(&pane)?;
+            let mut filters = shared_text_editor.write().await;
+            signal = apply_keys(*keymap.get(), &mut filters, event::read()?, || {
+                Ok(if event::poll(Duration::ZERO)? {
+                    Some(event::read()?)
+                } else {
+                    None
+                })
+            })?;
+            if signal == Signal::Interrupt {
+                if settings.quit_confirm_window.is_zero() || interrupted_at.is_some() {
+                    // Resume so that the lines the streams still send are received, not waited on.
+                    paused.store(false, Ordering::SeqCst);
+                    break;
+                }
+                // Pause streaming on the first Ctrl+C and wait for the confirmation.
+                interrupted_at = Some(time::Instant::now());
+                paused.store(true, Ordering::SeqCst);
+                let size = crossterm::terminal::size()?;
+                draw(
+                    &frames,
+                    Frame::Stream {
+                        rows: vec![StyledGraphemes::from_str(
+                            "paused: press Ctrl+C again to quit, or any other key to resume",
+                            settings.style.warning,
+                        )],
+                        pane: filters.create_pane(size.0, size.1),
+                        counter: None,
+                    },
+                )
+                .await?;
+                continue;
+            } else if interrupted_at.take().is_some() {
+                paused.store(false, Ordering::SeqCst);
+            }
+            if signal == Signal::GoToDig || signal == Signal::GoToBul {
+                break;
+            }
+            if signal == Signal::CycleStates {
+                filters.set_states(states_streamer.cycle_container_states());
+                // The streams are checked against the states periodically while enforcing them,
+                // and otherwise launched again to apply them.
+                if settings.stream.enforce_interval.is_none() {
+                    break;
+                }
+            }
+
+            if signal == Signal::SwitchFocus {
+                filters.switch_focus();
+            }
+            if signal == Signal::ToggleHold {
+                let toggled = {
+                    let mut follow = follow.lock().unwrap();
+                    *follow = follow.toggled();
+                    *follow
+                };
+                filters.set_status(&toggled.status());
+            }
+            let size = crossterm::terminal::size()?;
+            let pane = filters.create_pane(size.0, size.1);
+            if signal == Signal::DismissNotices {
+                draw(&frames, Frame::ClearNotices(pane.clone())).await?;
+            }
+            let status = match signal {
+                Signal::ToggleFollow if follow_matches.fetch_xor(true, Ordering::SeqCst) => {
+                    Some("staying where paused")
+                }
+                Signal::ToggleFollow => Some("following new matches while paused"),
+                _ => None,
+            };
+            if let Some(status) = status {
+                draw(
+                    &frames,
+                    Frame::Stream {
+                        rows: vec![StyledGraphemes::from_str(status, settings.style.warning)],
+                        pane: pane.clone(),
+                        counter: None,
+                    },
+                )
+                .await?;
+            }
+            if signal == Signal::CopyCommands {
+                // The commands are shown as well since the terminal may not support the clipboard.
+                let commands = commands_streamer.kubectl_commands(&settings.context);
+                let status = if commands.is_empty() {
+                    String::from("no streams are open to copy the kubectl commands of")
+                } else {
+                    copy_commands(&commands)
+                };
+                let mut rows = vec![StyledGraphemes::from_str(status, settings.style.warning)];
+                for command in commands {
+                    rows.extend(
+                        StyledGraphemes::from_str(command, settings.style.body)
+                            .matrixify(size.0 as usize, size.1 as usize, 0)
+                            .0,
+                    );
+                }
+                draw(
+                    &frames,
+                    Frame::Stream {
+                        rows,
+                        pane: pane.clone(),
+                        counter: None,
+                    },
+                )
+                .await?;
+            }
+            draw(&frames, Frame::Pane(pane)).await?;
+            // Until the pane is queued for the render task, which traces the rest.
+            debug!(elapsed = ?pressed.elapsed(), "handled keys");
+        }
+        Ok(signal)
     }
+    .await;
 
     canceler.cancel();
     let _: anyhow::Result<(), anyhow::Error> = log_streaming.await?;
+    *capture = Some(log_keeping.await??);
+    notice_keeping.abort();
+    launch_progressing.abort();
+    if let Some(header_keeping) = header_keeping {
+        header_keeping.abort();
+    }
+    if let Some(rollout_watching) = rollout_watching {
+        let _ = rollout_watching.await;
+    }
+    if let Some(events_watching) = events_watching {
+        let _ = events_watching.await;
+    }
+    // The render task is done once the frames sent so far are drawn.
+    drop(frames);
+    let _ = rendering.await;
+
+    *query = shared_text_editor.read().await.query();
+    let signal = handled?;
+
+    Ok((
+        signal,
+        capture.take().unwrap_or_default(),
+        std::mem::take(query),
+    ))
+}
+
+/// Sends the frame to the render task, which is gone only if the terminal was lost.
+async fn draw(frames: &mpsc::Sender<Frame>, frame: Frame) -> io::Result<()> {
+    frames
+        .send(frame)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "lost the terminal"))
+}
+
+/// Takes the streamer once connected, drawing the view without the progress of connecting,
+/// or draws why connecting failed and how to retry. Returns `None` until connected.
+fn poll_connection(
+    connection: &mut Connection,
+    term: &mut Terminal,
+    filters: &mut Filters,
+    style: &StyleConfig,
+) -> anyhow::Result<Option<ContainerLogStreamer>> {
+    let received = match connection {
+        Connection::Ready(streamer) => return Ok(Some(streamer.as_ref().clone())),
+        Connection::Failed => return Ok(None),
+        Connection::Pending(receiver) => match receiver.try_recv() {
+            Ok(received) => received,
+            Err(TryRecvError::Empty) => return Ok(None),
+            Err(TryRecvError::Closed) => Err(anyhow::anyhow!("connecting was abandoned")),
+        },
+    };
+    let size = term.size()?;
+    match received {
+        Ok(streamer) => {
+            *connection = Connection::Ready(Box::new(streamer.clone()));
+            filters.set_progress(None);
+            term.draw_pane(&filters.create_pane(size.0, size.1))?;
+            Ok(Some(streamer))
+        }
+        Err(e) => {
+            warn!(error = %e, "failed to connect");
+            *connection = Connection::Failed;
+            filters.set_progress(Some(String::from(
+                "failed to connect: Ctrl+R to retry, Ctrl+C to quit",
+            )));
+            term.draw_stream_and_pane(
+                wrap(
+                    &StyledGraphemes::from_str(format!("{e:#}"), style.error),
+                    size.0 as usize,
+                    size.1 as usize,
+                    style,
+                ),
+                &filters.create_pane(size.0, size.1),
+            )?;
+            Ok(None)
+        }
+    }
+}
+
+/// Copies the kubectl commands to the clipboard, returning the status shown above them.
+#[cfg(feature = "clipboard")]
+fn copy_commands(commands: &[String]) -> String {
+    match crate::clipboard::copy(&commands.join("\n")) {
+        Ok(()) => format!("copied the kubectl commands of {} streams:", commands.len()),
+        Err(e) => format!("failed to copy the kubectl commands ({e}):"),
+    }
+}
+
+/// Only shows the kubectl commands, as this build leaves out the clipboard.
+#[cfg(not(feature = "clipboard"))]
+fn copy_commands(commands: &[String]) -> String {
+    format!("kubectl commands of {} streams:", commands.len())
+}
+
+/// Applies the key, then the keys already pending (e.g. repeated by holding Backspace)
+/// until one of them does more than edit the filter, so that the pane is drawn once for all,
+/// which is then skipped if the pane is drawn as it is already.
+fn apply_keys(
+    keymap: fn(&Event, &mut text_editor::State) -> anyhow::Result<Signal>,
+    filters: &mut Filters,
+    first: Event,
+    mut pending: impl FnMut() -> io::Result<Option<Event>>,
+) -> anyhow::Result<Signal> {
+    let mut signal = keymap(&first, filters.focused_mut())?;
+    while signal == Signal::Continue {
+        let Some(event) = pending()? else {
+            break;
+        };
+        signal = keymap(&event, filters.focused_mut())?;
+    }
+    Ok(signal)
+}
 
-    Ok((signal, log_keeping.await??))
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use promkit::{
+        crossterm::{
+            event::{KeyCode, KeyEvent, KeyModifiers},
+            style::Color,
+        },
+        style::StyleBuilder,
+    };
+
+    use super::*;
+    use crate::{
+        container::LogKind,
+        terminal::{grid::Screen, Backend},
+    };
+
+    /// Filters of the live view as `run` starts them.
+    fn filters(style: &StyleConfig) -> Filters {
+        let mut filters = Filters::new(
+            text_editor::State {
+                texteditor: Default::default(),
+                history: Default::default(),
+                prefix: String::from("❯❯ "),
+                mask: Default::default(),
+                prefix_style: style.bul_prefix,
+                active_char_style: style.cursor,
+                inactive_char_style: StyleBuilder::new().build(),
+                edit_mode: Default::default(),
+                word_break_chars: Default::default(),
+                lines: Default::default(),
+            },
+            false,
+        );
+        filters.set_status(&Follow::Following.status());
+        filters
+    }
+
+    fn terminal(screen: &Screen, filters: &Filters, orientation: Orientation) -> Terminal {
+        let (width, height) = screen.size();
+        Terminal::with_backend(
+            Backend::Grid(screen.clone()),
+            &filters.create_pane(width, height),
+            orientation,
+            3,
+            None,
+            StyleConfig::default(),
+        )
+        .unwrap()
+    }
+
+    fn log(body: &str) -> ContainerLog {
+        ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from("payments-api-7d9f"),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from_str(
+                "payments-api",
+                StyleBuilder::new().fgc(Color::Cyan).build(),
+            ),
+            body: StyledGraphemes::from(body),
+            continuation: Vec::new(),
+            received: None,
+            at: None,
+        }
+    }
+
+    #[test]
+    fn initial_pane_layout() {
+        let style = StyleConfig::default();
+        let screen = Screen::new(30, 4);
+        let filters = filters(&style);
+        let mut term = terminal(&screen, &filters, Orientation::Bottom);
+        term.draw_pane(&filters.create_pane(30, 4)).unwrap();
+        assert_eq!(screen.rows(), ["", "", "", "▶ following ❯❯"]);
+        assert_eq!(screen.style_of(3, "▶ following ❯❯"), Some(style.bul_prefix));
+
+        let screen = Screen::new(30, 4);
+        let mut term = terminal(&screen, &filters, Orientation::Top);
+        term.draw_pane(&filters.create_pane(30, 4)).unwrap();
+        assert_eq!(screen.rows(), ["▶ following ❯❯", "", "", ""]);
+    }
+
+    #[test]
+    fn line_with_a_highlight() {
+        let style = StyleConfig::default();
+        let screen = Screen::new(50, 4);
+        let filters = filters(&style);
+        let mut term = terminal(&screen, &filters, Orientation::Bottom);
+        let log = log("GET /orders timeout after 30s");
+        let body = log
+            .body
+            .clone()
+            .highlight("timeout", style.highlight)
+            .unwrap();
+        term.draw_stream_and_pane(
+            vec![log.row(&body, &Aliases::default(), &style)],
+            &filters.create_pane(50, 4),
+        )
+        .unwrap();
+        assert_eq!(
+            screen.rows(),
+            [
+                "",
+                "",
+                "payments-api GET /orders timeout after 30s",
+                "▶ following ❯❯",
+            ]
+        );
+        assert_eq!(
+            screen
+                .style_of(2, "payments-api")
+                .and_then(|style| style.foreground_color),
+            Some(Color::Cyan)
+        );
+        assert_eq!(screen.style_of(2, "timeout"), Some(style.highlight));
+        assert_eq!(screen.style_of(2, "GET /orders "), Some(Default::default()));
+    }
+
+    #[test]
+    fn wrapped_line() {
+        let style = StyleConfig::default();
+        let screen = Screen::new(20, 5);
+        let filters = filters(&style);
+        let mut term = terminal(&screen, &filters, Orientation::Bottom);
+        let log = log("connection reset by peer");
+        let rows = wrap(
+            &log.row(&log.body, &Aliases::default(), &style),
+            20,
+            5,
+            &style,
+        );
+        term.draw_stream_and_pane(rows, &filters.create_pane(20, 5))
+            .unwrap();
+        assert_eq!(
+            screen.rows(),
+            [
+                "",
+                "",
+                "payments-api connect",
+                "↪ ion reset by peer",
+                "▶ following ❯❯",
+            ]
+        );
+        assert_eq!(screen.style_of(3, "↪"), Some(style.continuation));
+    }
+
+    #[test]
+    fn pending_connection() {
+        let style = StyleConfig::default();
+        let screen = Screen::new(80, 4);
+        let mut filters = filters(&style);
+        let mut term = terminal(&screen, &filters, Orientation::Bottom);
+        let (_connected_tx, connected_rx) = oneshot::channel();
+        let mut connection = Connection::Pending(connected_rx);
+        let polled = poll_connection(&mut connection, &mut term, &mut filters, &style).unwrap();
+        assert!(polled.is_none());
+        assert!(matches!(connection, Connection::Pending(_)));
+    }
+
+    #[test]
+    fn failed_connection() {
+        let style = StyleConfig::default();
+        let screen = Screen::new(80, 4);
+        let mut filters = filters(&style);
+        let mut term = terminal(&screen, &filters, Orientation::Bottom);
+        let (connected_tx, connected_rx) = oneshot::channel();
+        let mut connection = Connection::Pending(connected_rx);
+        let _ = connected_tx.send(Err(anyhow::anyhow!("current_context is not set")));
+        let polled = poll_connection(&mut connection, &mut term, &mut filters, &style).unwrap();
+        assert!(polled.is_none());
+        assert!(matches!(connection, Connection::Failed));
+        assert_eq!(
+            screen.rows(),
+            [
+                "",
+                "",
+                "current_context is not set",
+                "▶ following failed to connect: Ctrl+R to retry, Ctrl+C to quit ❯❯",
+            ]
+        );
+        assert_eq!(
+            screen.style_of(2, "current_context is not set"),
+            Some(style.error)
+        );
+
+        // Nothing changes until the user retries.
+        let polled = poll_connection(&mut connection, &mut term, &mut filters, &style).unwrap();
+        assert!(polled.is_none());
+        assert!(matches!(connection, Connection::Failed));
+    }
+
+    #[test]
+    fn abandoned_connection() {
+        let style = StyleConfig::default();
+        let screen = Screen::new(80, 4);
+        let mut filters = filters(&style);
+        let mut term = terminal(&screen, &filters, Orientation::Bottom);
+        let (connected_tx, connected_rx) = oneshot::channel();
+        let mut connection = Connection::Pending(connected_rx);
+        drop(connected_tx);
+        let polled = poll_connection(&mut connection, &mut term, &mut filters, &style).unwrap();
+        assert!(polled.is_none());
+        assert!(matches!(connection, Connection::Failed));
+        assert_eq!(screen.rows()[2], "connecting was abandoned");
+    }
+
+    #[test]
+    fn follow_toggles_with_its_status() {
+        let paused = Follow::Following.toggled();
+        assert!(matches!(paused, Follow::Paused(_)));
+        assert_eq!(paused.toggled().status(), "▶ following");
+        let at = Local::now();
+        assert_eq!(
+            Follow::Paused(at).status(),
+            format!("⏸ paused @ {}", at.format("%H:%M:%S"))
+        );
+    }
+
+    #[test]
+    fn resuming_from_dig_starts_paused() {
+        assert!(Follow::initial(&[]) == Follow::Following);
+        assert!(matches!(
+            Follow::initial(&[log("from dig")]),
+            Follow::Paused(_)
+        ));
+    }
+
+    #[test]
+    fn paused_view_jumps_to_new_matches() {
+        let throttle = Duration::from_secs(60);
+        assert!(jumps(true, "error", None, throttle));
+        // Staying where paused, or matching the whole stream.
+        assert!(!jumps(false, "error", None, throttle));
+        assert!(!jumps(true, "", None, throttle));
+        // Too soon after the last jump.
+        let now = Some(time::Instant::now());
+        assert!(!jumps(true, "error", now, throttle));
+        assert!(jumps(true, "error", now, Duration::ZERO));
+    }
+
+    #[test]
+    fn paused_status_in_the_prompt() {
+        let style = StyleConfig::default();
+        let screen = Screen::new(40, 4);
+        let mut filters = filters(&style);
+        let mut term = terminal(&screen, &filters, Orientation::Bottom);
+        let at = Local::now();
+        filters.set_status(&Follow::Paused(at).status());
+        term.draw_pane(&filters.create_pane(40, 4)).unwrap();
+        assert_eq!(
+            screen.rows()[3],
+            format!("⏸ paused @ {} ❯❯", at.format("%H:%M:%S"))
+        );
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+        Event::Key(KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn keys_typed_fast_are_drawn_once() {
+        let style = StyleConfig::default();
+        let screen = Screen::new(40, 4);
+        let mut filters = filters(&style);
+        let mut term = terminal(&screen, &filters, Orientation::Bottom);
+        term.draw_pane(&filters.create_pane(40, 4)).unwrap();
+        let drawn = screen.frames();
+
+        // Typed, then held Backspace down, 50 keys in all.
+        let mut keys: VecDeque<Event> = "connection refused by upstream"
+            .chars()
+            .map(|ch| key(KeyCode::Char(ch), KeyModifiers::NONE))
+            .chain((0..20).map(|_| key(KeyCode::Backspace, KeyModifiers::NONE)))
+            .collect();
+        assert_eq!(keys.len(), 50);
+        let first = keys.pop_front().unwrap();
+        let signal = apply_keys(
+            keymap::default,
+            &mut filters,
+            first,
+            || Ok(keys.pop_front()),
+        )
+        .unwrap();
+        assert!(signal == Signal::Continue);
+        assert_eq!(filters.query(), "connection");
+
+        term.draw_pane(&filters.create_pane(40, 4)).unwrap();
+        assert_eq!(screen.frames(), drawn + 1);
+        assert_eq!(screen.rows()[3], "▶ following ❯❯ connection");
+        // Unchanged, the pane isn't drawn again.
+        term.draw_pane(&filters.create_pane(40, 4)).unwrap();
+        assert_eq!(screen.frames(), drawn + 1);
+    }
+
+    #[test]
+    fn keys_pending_after_a_signal_are_left() {
+        let style = StyleConfig::default();
+        let mut filters = filters(&style);
+        let mut keys: VecDeque<Event> = VecDeque::from([
+            key(KeyCode::Char('r'), KeyModifiers::NONE),
+            key(KeyCode::Char('f'), KeyModifiers::CONTROL),
+            key(KeyCode::Char('x'), KeyModifiers::NONE),
+        ]);
+        let signal = apply_keys(
+            keymap::default,
+            &mut filters,
+            key(KeyCode::Char('e'), KeyModifiers::NONE),
+            || Ok(keys.pop_front()),
+        )
+        .unwrap();
+        // Going to dig is handled before the keys after it.
+        assert!(signal == Signal::GoToDig);
+        assert_eq!(filters.query(), "er");
+        assert_eq!(keys.len(), 1);
+    }
+
+    #[test]
+    fn launch_progress_in_the_prompt() {
+        let style = StyleConfig::default();
+        let screen = Screen::new(50, 4);
+        let mut filters = filters(&style);
+        let mut term = terminal(&screen, &filters, Orientation::Bottom);
+        filters.set_progress(Some(String::from("opening streams 37/112")));
+        term.draw_pane(&filters.create_pane(50, 4)).unwrap();
+        assert_eq!(screen.rows()[3], "▶ following opening streams 37/112 ❯❯");
+        // Gone once launched, while the status stays.
+        filters.set_progress(None);
+        term.draw_pane(&filters.create_pane(50, 4)).unwrap();
+        assert_eq!(screen.rows()[3], "▶ following ❯❯");
+    }
+
+    #[test]
+    fn rows_kept_while_paused() {
+        let rows = |range: std::ops::Range<usize>| -> Vec<StyledGraphemes> {
+            range
+                .map(|i| StyledGraphemes::from(i.to_string()))
+                .collect()
+        };
+        let text = |rows: Vec<StyledGraphemes>| -> Vec<String> {
+            rows.iter().map(|row| row.to_string()).collect()
+        };
+        // The newest rows which fit, in the order they are drawn in.
+        assert_eq!(
+            text(Orientation::Bottom.join(rows(0..3), rows(3..5), 4)),
+            ["1", "2", "3", "4"]
+        );
+        assert_eq!(
+            text(Orientation::Top.join(rows(0..3), rows(3..5), 4)),
+            ["3", "4", "0", "1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn drawing_once_the_terminal_is_lost() {
+        let (frames, frames_rx) = mpsc::channel(1);
+        // The render task is gone once it failed to draw.
+        drop(frames_rx);
+        let error = draw(
+            &frames,
+            Frame::Pane(promkit::pane::Pane::new(Vec::new(), 0)),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+    }
 }
@@ -0,0 +1,447 @@
+use std::{collections::VecDeque, time::Instant};
+
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, debug_span};
+
+use promkit::{grapheme::StyledGraphemes, pane::Pane};
+
+use crate::{notice::Notice, terminal::Terminal};
+
+/// Number of frames waiting for the render task before their senders wait as well.
+pub const QUEUED_FRAMES: usize = 64;
+
+/// Frame drawn by the render task, each with the pane as it was when the frame was sent.
+pub enum Frame {
+    /// Redraws the sticky region and the pane.
+    Pane(Pane),
+    /// Draws the rows in the stream area as the newest ones, the sticky region and the pane,
+    /// and the counter of the lines drawn at once, if any.
+    Stream {
+        rows: Vec<StyledGraphemes>,
+        pane: Pane,
+        counter: Option<usize>,
+    },
+    /// Records the rows new to the columns of the split view, and redraws the columns
+    /// with the pane unless the view is paused.
+    Columns {
+        recorded: Vec<StyledGraphemes>,
+        left: VecDeque<StyledGraphemes>,
+        right: VecDeque<StyledGraphemes>,
+        left_width: usize,
+        pane: Option<Pane>,
+    },
+    /// Keeps the notice in the sticky region and redraws the pane.
+    Notice(Notice, Pane),
+    /// Pins the header, or removes it if `None`, and redraws the pane.
+    Header(Option<String>, Pane),
+    /// Dismisses the notices and redraws the pane.
+    ClearNotices(Pane),
+}
+
+impl Frame {
+    fn kind(&self) -> &'static str {
+        match self {
+            Frame::Pane(_) => "pane",
+            Frame::Stream { .. } => "stream",
+            Frame::Columns { .. } => "columns",
+            Frame::Notice(..) => "notice",
+            Frame::Header(..) => "header",
+            Frame::ClearNotices(_) => "clear notices",
+        }
+    }
+}
+
+/// Draws the frames sent by the log-keeping task, the event loop and the other tasks
+/// of the live view in the order they are received, until all their senders are gone.
+///
+/// The render task owns the terminal, so that a frame is always written as a whole
+/// between two others, rather than the cursor of one being moved by another
+/// (which left ghost cursor cells and half-overwritten rows near the pane).
+/// Stops at the first failure to write, tripping `lost` so that the session ends.
+///
+/// Bursts waiting one after another are drawn as one, so that a frame sent by a key
+/// waits for at most a screen of rows however far the drawing has fallen behind the lines.
+/// The time taken by each frame and the frames waiting behind it are traced,
+/// which is how a redraw lagging behind the keys shows in the `--log-file`.
+pub async fn render(
+    mut term: Terminal,
+    mut frames: mpsc::Receiver<Frame>,
+    lost: CancellationToken,
+) -> anyhow::Result<()> {
+    let mut next = None;
+    loop {
+        let frame = match next.take() {
+            Some(frame) => frame,
+            None => match frames.recv().await {
+                Some(frame) => frame,
+                None => return Ok(()),
+            },
+        };
+        let span = debug_span!("frame", kind = frame.kind(), waiting = frames.len());
+        let _entered = span.enter();
+        let started = Instant::now();
+        let frame = match frame {
+            Frame::Stream {
+                rows,
+                pane,
+                counter,
+            } => {
+                let (merged, after) = merge_bursts(&term, &mut frames, rows, pane, counter);
+                next = after;
+                merged
+            }
+            frame => frame,
+        };
+        let drawn = draw(&mut term, frame);
+        debug!(elapsed = ?started.elapsed(), "drew frame");
+        if let Err(e) = drawn {
+            lost.cancel();
+            return Err(e);
+        }
+    }
+}
+
+/// Joins the bursts waiting right after the one received, keeping the rows which fit
+/// on the screen, the latest pane and the lines counted in all of them.
+/// Returns the joined burst and the first other frame taken, if any.
+fn merge_bursts(
+    term: &Terminal,
+    frames: &mut mpsc::Receiver<Frame>,
+    mut rows: Vec<StyledGraphemes>,
+    mut pane: Pane,
+    mut counter: Option<usize>,
+) -> (Frame, Option<Frame>) {
+    let height = term.size().map_or(usize::MAX, |size| size.1 as usize);
+    let mut merged = 0;
+    let mut after = None;
+    while let Ok(frame) = frames.try_recv() {
+        match frame {
+            Frame::Stream {
+                rows: newer,
+                pane: latest,
+                counter: more,
+            } => {
+                rows = term.orientation().join(rows, newer, height);
+                pane = latest;
+                counter = match (counter, more) {
+                    (None, None) => None,
+                    (counter, more) => Some(counter.unwrap_or(0) + more.unwrap_or(0)),
+                };
+                merged += 1;
+            }
+            frame => {
+                after = Some(frame);
+                break;
+            }
+        }
+    }
+    if merged > 0 {
+        debug!(merged, "merged bursts");
+    }
+    let frame = Frame::Stream {
+        rows,
+        pane,
+        counter,
+    };
+    (frame, after)
+}
+
+fn draw(term: &mut Terminal, frame: Frame) -> anyhow::Result<()> {
+    match frame {
+        Frame::Pane(pane) => term.draw_pane(&pane),
+        Frame::Stream {
+            rows,
+            pane,
+            counter,
+        } => {
+            term.draw_stream_and_pane(rows, &pane)?;
+            match counter {
+                Some(counter) => term.draw_counter(&format!("(+{counter} lines)")),
+                None => Ok(()),
+            }
+        }
+        Frame::Columns {
+            recorded,
+            left,
+            right,
+            left_width,
+            pane,
+        } => {
+            term.record(&recorded);
+            match pane {
+                Some(pane) => term.draw_columns(&left, &right, left_width, &pane),
+                None => Ok(()),
+            }
+        }
+        Frame::Notice(notice, pane) => {
+            term.push_notice(notice);
+            term.draw_pane(&pane)
+        }
+        Frame::Header(header, pane) => {
+            term.set_header(header);
+            term.draw_pane(&pane)
+        }
+        Frame::ClearNotices(pane) => {
+            term.clear_notices();
+            term.draw_pane(&pane)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bul::Orientation,
+        style::StyleConfig,
+        terminal::{grid::Screen, Backend},
+    };
+
+    fn pane(text: &str) -> Pane {
+        Pane::new(vec![StyledGraphemes::from(text)], 0)
+    }
+
+    /// Renders the frames sent at once by a stream of lines and by a prompt redrawn
+    /// between them, then the final pane, returning the rows of the screen.
+    async fn interleave(orientation: Orientation, lines: usize) -> Vec<String> {
+        let screen = Screen::new(30, 12);
+        let term = Terminal::with_backend(
+            Backend::Grid(screen.clone()),
+            &pane("❯❯ "),
+            orientation,
+            3,
+            None,
+            StyleConfig::default(),
+        )
+        .unwrap();
+        // Small enough for the producers to wait on each other.
+        let (frames, frames_rx) = mpsc::channel(2);
+        let rendering = tokio::spawn(render(term, frames_rx, CancellationToken::new()));
+
+        let stream_frames = frames.clone();
+        let streaming = tokio::spawn(async move {
+            for i in 0..lines {
+                let frame = Frame::Stream {
+                    rows: vec![StyledGraphemes::from(format!("line {i}"))],
+                    pane: pane(&format!("❯❯ {}", "q".repeat(i % 7))),
+                    counter: (i % 5 == 0).then_some(2),
+                };
+                stream_frames.send(frame).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+        let typing_frames = frames.clone();
+        let typing = tokio::spawn(async move {
+            for i in 0..lines {
+                let frame = match i % 3 {
+                    0 => Frame::Header(Some(format!("header {i}")), pane("❯❯ typed")),
+                    1 => Frame::Header(None, pane("❯❯ typed longer")),
+                    _ => Frame::Pane(pane(&format!("❯❯ {}", "t".repeat(i % 11)))),
+                };
+                typing_frames.send(frame).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+        streaming.await.unwrap();
+        typing.await.unwrap();
+        frames
+            .send(Frame::Header(
+                Some(String::from("header")),
+                pane("❯❯ final"),
+            ))
+            .await
+            .unwrap();
+        drop(frames);
+        rendering.await.unwrap().unwrap();
+        screen.rows()
+    }
+
+    /// Numbers of the lines in the rows, which must be blank or a whole line each.
+    fn lines(rows: &[String]) -> Vec<usize> {
+        rows.iter()
+            .filter(|row| !row.is_empty())
+            .map(|row| {
+                row.strip_prefix("line ")
+                    .and_then(|i| i.parse().ok())
+                    .unwrap_or_else(|| panic!("stray cells in {row:?}"))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn interleaved_frames_from_the_bottom() {
+        let rows = interleave(Orientation::Bottom, 60).await;
+        let (stream, sticky) = rows.split_at(rows.len() - 2);
+        assert_eq!(sticky, ["header", "❯❯ final"]);
+        let lines = lines(stream);
+        // The newest lines are above the sticky region, in order and none covered by it,
+        // though the rows it left when shorter may still be blank.
+        let oldest = *lines.first().unwrap();
+        assert_eq!(lines, (oldest..60).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn interleaved_frames_from_the_top() {
+        let rows = interleave(Orientation::Top, 60).await;
+        let (sticky, stream) = rows.split_at(2);
+        assert_eq!(sticky, ["❯❯ final", "header"]);
+        let lines = lines(stream);
+        assert_eq!(lines, (50..60).rev().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn waiting_bursts_drawn_as_one() {
+        let screen = Screen::new(30, 6);
+        let term = Terminal::with_backend(
+            Backend::Grid(screen.clone()),
+            &pane("❯❯ "),
+            Orientation::Bottom,
+            3,
+            None,
+            StyleConfig::default(),
+        )
+        .unwrap();
+        let (frames, frames_rx) = mpsc::channel(8);
+        for burst in 0..4 {
+            let frame = Frame::Stream {
+                rows: (burst * 3..burst * 3 + 3)
+                    .map(|i| StyledGraphemes::from(format!("line {i}")))
+                    .collect(),
+                pane: pane(&format!("❯❯ {burst}")),
+                counter: Some(3),
+            };
+            frames.send(frame).await.unwrap();
+        }
+        drop(frames);
+        render(term, frames_rx, CancellationToken::new())
+            .await
+            .unwrap();
+        let rows = screen.rows();
+        let (stream, prompt) = rows.split_at(rows.len() - 1);
+        assert_eq!(lines(stream), (7..12).collect::<Vec<_>>());
+        assert!(prompt[0].starts_with("❯❯ 3 "));
+        assert!(prompt[0].ends_with("(+12 lines)"));
+    }
+
+    /// Keys typed while the lines come faster than they are drawn, in bursts as the
+    /// log-keeping task sends them, must each be drawn right after the rows of one frame
+    /// rather than after all the bursts queued before them.
+    #[tokio::test]
+    async fn keys_drawn_soon_while_lines_are_flowing() {
+        const KEYS: usize = 40;
+        const BURSTS: usize = 10;
+        let screen = Screen::new(80, 24);
+        let term = Terminal::with_backend(
+            Backend::Grid(screen.clone()),
+            &pane("❯❯ "),
+            Orientation::Bottom,
+            3,
+            None,
+            StyleConfig::default(),
+        )
+        .unwrap();
+        // Queued before the render task starts, as if it had fallen far behind the lines.
+        let (frames, frames_rx) = mpsc::channel(KEYS * (BURSTS + 1));
+        let mut typed = String::from("❯❯ ");
+        let mut sent = 0;
+        for key in 0..KEYS {
+            // The bursts carry the pane as typed so far, as the log-keeping task builds it.
+            for _ in 0..BURSTS {
+                let frame = Frame::Stream {
+                    rows: (sent..sent + 50)
+                        .map(|i| StyledGraphemes::from(format!("{i} {}", "line ".repeat(12))))
+                        .collect(),
+                    pane: pane(&typed),
+                    counter: Some(50),
+                };
+                frames.send(frame).await.unwrap();
+                sent += 50;
+            }
+            typed = format!("❯❯ key {key}");
+            frames.send(Frame::Pane(pane(&typed))).await.unwrap();
+        }
+        drop(frames);
+        render(term, frames_rx, CancellationToken::new())
+            .await
+            .unwrap();
+
+        // The prompt is followed by the counter of the burst drawn last, if any.
+        let bottoms = screen.bottoms();
+        let drawn_at = |key: usize| {
+            let typed = format!("❯❯ key {key}");
+            bottoms
+                .iter()
+                .position(|row| row == &typed || row.starts_with(&format!("{typed} ")))
+                .unwrap_or_else(|| panic!("{typed:?} never drawn"))
+        };
+        let mut last = 0;
+        for key in 0..KEYS {
+            let at = drawn_at(key);
+            // The bursts queued before the key, drawn as one along with their counter.
+            assert!(at - last <= 3, "key {key} drawn {} frames late", at - last);
+            last = at;
+        }
+        assert!(screen.rows()[22].starts_with(&format!("{} ", sent - 1)));
+    }
+
+    #[tokio::test]
+    async fn senders_gone_end_the_render_task() {
+        let screen = Screen::new(30, 6);
+        let term = Terminal::with_backend(
+            Backend::Grid(screen.clone()),
+            &pane("❯❯ "),
+            Orientation::Bottom,
+            3,
+            None,
+            StyleConfig::default(),
+        )
+        .unwrap();
+        let (frames, frames_rx) = mpsc::channel(2);
+        let lost = CancellationToken::new();
+        let rendering = tokio::spawn(render(term, frames_rx, lost.clone()));
+        frames.send(Frame::Pane(pane("❯❯ last"))).await.unwrap();
+        drop(frames);
+        rendering.await.unwrap().unwrap();
+        assert!(!lost.is_cancelled());
+        assert_eq!(screen.rows().last().unwrap(), "❯❯ last");
+    }
+
+    #[tokio::test]
+    async fn lost_terminal_ends_the_render_task() {
+        let screen = Screen::new(30, 6);
+        let term = Terminal::with_backend(
+            Backend::Grid(screen.clone()),
+            &pane("❯❯ "),
+            Orientation::Bottom,
+            3,
+            None,
+            StyleConfig::default(),
+        )
+        .unwrap();
+        screen.fail_after(20);
+        let (frames, frames_rx) = mpsc::channel(2);
+        let lost = CancellationToken::new();
+        let rendering = tokio::spawn(render(term, frames_rx, lost.clone()));
+        // Sent until the render task is gone, which is when it failed to draw.
+        let mut sent = 0;
+        while sent < 1000 {
+            let frame = Frame::Stream {
+                rows: vec![StyledGraphemes::from(format!("line {sent}"))],
+                pane: pane("❯❯ "),
+                counter: None,
+            };
+            if frames.send(frame).await.is_err() {
+                break;
+            }
+            sent += 1;
+        }
+        assert!(sent < 1000);
+        let error = rendering.await.unwrap().unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<std::io::Error>().unwrap().kind(),
+            std::io::ErrorKind::BrokenPipe
+        );
+        assert!(lost.is_cancelled());
+    }
+}
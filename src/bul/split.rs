@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+
+use promkit::{
+    crossterm::style::ContentStyle, grapheme::StyledGraphemes, pane::Pane, text_editor, PaneFactory,
+};
+
+/// Filters of the live view: one for the whole view,
+/// or one for each column of the split view, of which one is edited at a time.
+pub struct Filters {
+    /// Prefixes of the filters as given, shown after the status.
+    prefixes: (String, String),
+    /// Status of the view, followed by the accepted container states
+    /// and the progress of launching the streams, if any.
+    status: (String, Option<String>, Option<String>),
+    left: text_editor::State,
+    /// Filter of the right column, if split.
+    right: Option<text_editor::State>,
+    right_focused: bool,
+    /// Style of the cursor, shown only in the focused filter.
+    cursor: ContentStyle,
+}
+
+impl Filters {
+    pub fn new(text_editor: text_editor::State, split: bool) -> Self {
+        let cursor = text_editor.active_char_style;
+        if !split {
+            return Self {
+                prefixes: (text_editor.prefix.clone(), String::new()),
+                status: Default::default(),
+                left: text_editor,
+                right: None,
+                right_focused: false,
+                cursor,
+            };
+        }
+        let prefixes = (
+            format!("[left] {}", text_editor.prefix),
+            format!("[right] {}", text_editor.prefix),
+        );
+        let mut left = text_editor.clone();
+        left.prefix = prefixes.0.clone();
+        let mut right = text_editor;
+        right.prefix = prefixes.1.clone();
+        right.active_char_style = right.inactive_char_style;
+        Self {
+            prefixes,
+            status: Default::default(),
+            left,
+            right: Some(right),
+            right_focused: false,
+            cursor,
+        }
+    }
+
+    /// Shows the status of the view in front of the prefixes.
+    pub fn set_status(&mut self, status: &str) {
+        self.status.0 = status.to_string();
+        self.update_prefixes();
+    }
+
+    /// Shows the accepted container states after the status, unless `None`.
+    pub fn set_states(&mut self, states: Option<String>) {
+        self.status.1 = states.map(|states| format!("[{states}]"));
+        self.update_prefixes();
+    }
+
+    /// Shows the progress of launching the streams after the status, until `None`.
+    pub fn set_progress(&mut self, progress: Option<String>) {
+        self.status.2 = progress;
+        self.update_prefixes();
+    }
+
+    fn update_prefixes(&mut self) {
+        let (status, states, progress) = &self.status;
+        let status = std::iter::once(status)
+            .chain(states)
+            .chain(progress)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.left.prefix = format!("{status} {}", self.prefixes.0);
+        if let Some(right) = self.right.as_mut() {
+            right.prefix = format!("{status} {}", self.prefixes.1);
+        }
+    }
+
+    /// Filter edited by the keys.
+    pub fn focused_mut(&mut self) -> &mut text_editor::State {
+        match &mut self.right {
+            Some(right) if self.right_focused => right,
+            _ => &mut self.left,
+        }
+    }
+
+    /// Moves the editing to the other filter of the split view.
+    pub fn switch_focus(&mut self) {
+        let Some(right) = self.right.as_mut() else {
+            return;
+        };
+        self.right_focused = !self.right_focused;
+        let (focused, unfocused) = if self.right_focused {
+            (right, &mut self.left)
+        } else {
+            (&mut self.left, right)
+        };
+        focused.active_char_style = self.cursor;
+        unfocused.active_char_style = unfocused.inactive_char_style;
+    }
+
+    /// Query of the whole view, or of the left column if split.
+    pub fn query(&self) -> String {
+        self.left.texteditor.text_without_cursor().to_string()
+    }
+
+    /// Query of the right column, if split.
+    pub fn right_query(&self) -> Option<String> {
+        self.right
+            .as_ref()
+            .map(|right| right.texteditor.text_without_cursor().to_string())
+    }
+
+    /// Creates the pane of the filters, one above the other if split.
+    pub fn create_pane(&self, width: u16, height: u16) -> Pane {
+        let left = self.left.create_pane(width, height);
+        let Some(right) = &self.right else {
+            return left;
+        };
+        let right = right.create_pane(width, height);
+        let mut rows = left.extract(left.visible_row_count());
+        rows.extend(right.extract(right.visible_row_count()));
+        Pane::new(rows, 0)
+    }
+}
+
+/// Rows of the two columns of the split view, the newest last,
+/// each kept to the height of the screen.
+pub struct Columns {
+    /// Percentage of the width taken by the left column.
+    ratio: u16,
+    pub left: VecDeque<StyledGraphemes>,
+    pub right: VecDeque<StyledGraphemes>,
+}
+
+impl Columns {
+    pub fn new(ratio: u16) -> Self {
+        Self {
+            ratio,
+            left: VecDeque::new(),
+            right: VecDeque::new(),
+        }
+    }
+
+    /// Widths of the left and right columns in a terminal of the width,
+    /// leaving one to the divider between them.
+    pub fn widths(&self, width: u16) -> (usize, usize) {
+        let usable = width.saturating_sub(1) as usize;
+        let left = usable * self.ratio as usize / 100;
+        (left, usable - left)
+    }
+
+    pub fn push(&mut self, left: Vec<StyledGraphemes>, right: Vec<StyledGraphemes>, height: usize) {
+        for (column, rows) in [(&mut self.left, left), (&mut self.right, right)] {
+            column.extend(rows);
+            while column.len() > height {
+                column.pop_front();
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::{
+    container::{ContainerLog, LogKind},
+    field,
+};
+
+/// Scope in which the value of the tracked field is compared with the previous one.
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChangeScope {
+    /// Compare with the previous line of the same container.
+    Container,
+    /// Compare with the previous line of any container.
+    Global,
+}
+
+/// Admits only the lines where the value of a field differs from its previous value,
+/// e.g. only when `state` changes, suppressing the lines repeating it.
+///
+/// Lines without the field are suppressed as well, since they can't change it.
+/// Annotations such as rollout progress are always admitted.
+pub struct ChangeFilter {
+    pattern: Regex,
+    scope: ChangeScope,
+    /// Last value of the field, keyed by the container in the container scope.
+    last: HashMap<(String, String, String), String>,
+}
+
+impl ChangeFilter {
+    pub fn new(field: &str, scope: ChangeScope) -> anyhow::Result<Self> {
+        Ok(Self {
+            pattern: field::pattern(field)?,
+            scope,
+            last: HashMap::new(),
+        })
+    }
+
+    /// Whether the line changes the value of the field, remembering the value if so.
+    pub fn admits(&mut self, log: &ContainerLog) -> bool {
+        if log.kind != LogKind::Log {
+            return true;
+        }
+        let body = log.body.to_string();
+        let Some(value) = field::value(&self.pattern, &body) else {
+            return false;
+        };
+        let key = match self.scope {
+            ChangeScope::Container => (
+                log.namespace.clone(),
+                log.pod.clone(),
+                log.container.clone(),
+            ),
+            ChangeScope::Global => Default::default(),
+        };
+        if self.last.get(&key).is_some_and(|last| last == value) {
+            return false;
+        }
+        self.last.insert(key, value.to_string());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::grapheme::StyledGraphemes;
+
+    use super::*;
+
+    fn log(pod: &str, body: &str) -> ContainerLog {
+        ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from(pod),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from(pod),
+            body: StyledGraphemes::from(body),
+            continuation: Vec::new(),
+            received: None,
+            at: None,
+        }
+    }
+
+    fn admitted(filter: &mut ChangeFilter, lines: &[(&str, &str)]) -> Vec<bool> {
+        lines
+            .iter()
+            .map(|(pod, body)| filter.admits(&log(pod, body)))
+            .collect()
+    }
+
+    const LINES: [(&str, &str); 5] = [
+        ("api-0", "state=starting"),
+        ("api-1", "state=starting"),
+        ("api-0", r#"{"state":"starting"}"#),
+        ("api-1", "state=ready"),
+        ("api-0", "state=ready"),
+    ];
+
+    #[test]
+    fn changes_are_compared_per_container() {
+        let mut filter = ChangeFilter::new("state", ChangeScope::Container).unwrap();
+        assert_eq!(
+            admitted(&mut filter, &LINES),
+            [true, true, false, true, true]
+        );
+    }
+
+    #[test]
+    fn changes_are_compared_across_containers() {
+        let mut filter = ChangeFilter::new("state", ChangeScope::Global).unwrap();
+        assert_eq!(
+            admitted(&mut filter, &LINES),
+            [true, false, false, true, false]
+        );
+    }
+
+    #[test]
+    fn lines_without_the_field_are_suppressed_but_annotations_are_not() {
+        let mut filter = ChangeFilter::new("state", ChangeScope::Container).unwrap();
+        assert_eq!(
+            admitted(
+                &mut filter,
+                &[
+                    ("api-0", "state=ready"),
+                    ("api-0", "GET /orders 200"),
+                    ("api-0", "restate=starting"),
+                    ("api-0", "state=ready"),
+                ]
+            ),
+            [true, false, false, false]
+        );
+        let mut event = log("api-0", "Normal Pulled: on api-0");
+        event.kind = LogKind::Event;
+        assert!(filter.admits(&event));
+        assert!(filter.admits(&event));
+    }
+}
@@ -0,0 +1,12 @@
+use std::io::{self, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Copies the text to the clipboard through the terminal with the OSC 52 escape sequence,
+/// which needs no clipboard on the host and works over SSH as well.
+/// Terminals without support for it silently ignore the sequence.
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
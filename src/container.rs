@@ -1,25 +1,538 @@
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    fmt,
+    future::Future,
     hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
-use futures::{stream::FuturesUnordered, AsyncBufReadExt, StreamExt};
-use k8s_openapi::api::{self, core::v1::Pod};
-use kube::api::{Api, ListParams, LogParams};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use futures::{
+    stream::{BoxStream, FuturesUnordered},
+    AsyncBufReadExt, StreamExt,
+};
+use k8s_openapi::{
+    api::{
+        self,
+        core::v1::{Event, Namespace, Pod},
+    },
+    apimachinery::pkg::apis::meta::v1::ObjectMeta,
+};
+use kube::{
+    api::{Api, ListParams, LogParams},
+    runtime::{watcher, WatchStreamExt},
+    Client,
+};
 use regex::Regex;
 use tokio::{
-    sync::mpsc,
+    sync::{mpsc, Semaphore},
     task::JoinHandle,
-    time::{timeout, Duration},
+    time::{sleep_until, Duration, Instant},
 };
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use promkit::{crossterm::style::ContentStyle, grapheme::StyledGraphemes, style::StyleBuilder};
+
+use crate::{
+    alias::Aliases,
+    backfill::Backfill,
+    dedup::RecentLines,
+    events,
+    meta::MetaFormat,
+    notice::Notice,
+    outbox::{Outbox, Overflow},
+    queue::Stats,
+    skew::SkewEstimator,
+    style::StyleConfig,
+    suggest::similar_names,
+};
+
+/// Flattens the line into a single row and strips ANSI escape sequences from it.
+/// Most lines contain neither, in which case the line is returned as it is
+/// without any allocation.
+fn sanitize(line: String) -> anyhow::Result<String> {
+    let mut bytes = line.into_bytes();
+    // Replacing ASCII bytes with ASCII bytes in place keeps the UTF-8 valid.
+    for byte in bytes.iter_mut() {
+        if *byte == b'\n' || *byte == b'\t' {
+            *byte = b' ';
+        }
+    }
+    let line = String::from_utf8(bytes)?;
+    if line.as_bytes().contains(&0x1b) {
+        Ok(strip_ansi_escapes::strip_str(line))
+    } else {
+        Ok(line)
+    }
+}
+
+/// Strips the `\r` left by a CRLF line ending, and the trailing whitespace unless kept.
+/// Returns whether the line was changed.
+fn normalize_line_end(line: &mut String, keep_trailing_whitespace: bool) -> bool {
+    let len = if keep_trailing_whitespace {
+        line.strip_suffix('\r').map_or(line.len(), str::len)
+    } else {
+        line.trim_end().len()
+    };
+    let normalized = len < line.len();
+    line.truncate(len);
+    normalized
+}
+
+/// Escapes the control characters of the line visibly, e.g. `\x1b` and `\t`,
+/// so that the raw output of the container can be inspected without garbling the terminal.
+fn escape_control(line: String) -> String {
+    if !line.chars().any(char::is_control) {
+        return line;
+    }
+    let mut escaped = String::with_capacity(line.len());
+    for ch in line.chars() {
+        match ch {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            ch if ch.is_control() => escaped.push_str(&format!("\\x{:02x}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Splits off the timestamp which the API server prefixes each line with
+/// when `LogParams::timestamps` is set.
+fn split_timestamp(mut line: String) -> (Option<DateTime<Utc>>, String) {
+    let end = line.find(' ').unwrap_or(line.len());
+    match DateTime::parse_from_rfc3339(&line[..end]) {
+        Ok(at) => {
+            line.drain(..(end + 1).min(line.len()));
+            (Some(at.with_timezone(&Utc)), line)
+        }
+        Err(_) => (None, line),
+    }
+}
+
+/// Splits off the prefix of a line in the CRI log format, i.e. `<time> <stdout|stderr> <P|F> `.
+/// Lines not in the format are returned as they are.
+fn split_cri_prefix(mut line: String) -> (Option<LogStream>, String) {
+    let mut parts = line.splitn(4, ' ');
+    let (Some(time), Some(stream), Some(tag), Some(_)) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return (None, line);
+    };
+    let prefix = time.len() + stream.len() + tag.len() + 3;
+    let stream = match stream {
+        "stdout" => LogStream::Stdout,
+        "stderr" => LogStream::Stderr,
+        _ => return (None, line),
+    };
+    if DateTime::parse_from_rfc3339(time).is_err() || !matches!(tag, "P" | "F") {
+        return (None, line);
+    }
+    line.drain(..prefix);
+    (Some(stream), line)
+}
+
+/// Formats the seconds as the two most significant units, e.g. `3d4h`, `1h2m` or `5m6s`.
+fn format_uptime(seconds: i64) -> String {
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+    if days > 0 {
+        format!("{days}d{hours}h")
+    } else if hours > 0 {
+        format!("{hours}h{minutes}m")
+    } else {
+        format!("{minutes}m{}s", seconds % 60)
+    }
+}
+
+/// Identity of the container which the state of its stream is kept under across relaunches.
+fn container_key(namespace: &str, pod: &str, container: &str) -> String {
+    format!("{namespace}/{pod} {container}")
+}
+
+fn record_last_seen(
+    last_seen: &Mutex<HashMap<String, DateTime<Utc>>>,
+    key: &str,
+    at: DateTime<Utc>,
+) {
+    let mut last_seen = last_seen.lock().unwrap();
+    match last_seen.get_mut(key) {
+        Some(last) => *last = at,
+        None => {
+            last_seen.insert(key.to_string(), at);
+        }
+    }
+}
+
+/// Returns whether the line was already received from the container before its stream was
+/// resumed after `resume_after`, remembering it otherwise.
+///
+/// Since streams resume after the last line consumed, only the lines stamped with exactly
+/// that time may have been received already. The lines after it were at most read,
+/// and are received again. Without a window, every line up to `resume_after` is taken
+/// as received, which also drops the unreceived lines stamped with exactly the same time.
+fn is_replayed(
+    recent: &Mutex<HashMap<String, RecentLines>>,
+    key: &str,
+    window: usize,
+    resume_after: Option<DateTime<Utc>>,
+    at: DateTime<Utc>,
+    line: &str,
+) -> bool {
+    if window == 0 {
+        return resume_after.is_some_and(|after| at <= after);
+    }
+    if resume_after.is_some_and(|after| at < after) {
+        return true;
+    }
+    let mut recent = recent.lock().unwrap();
+    let inserted = match recent.get_mut(key) {
+        Some(lines) => lines.insert(at, line),
+        None => {
+            let mut lines = RecentLines::new(window);
+            lines.insert(at, line);
+            recent.insert(key.to_string(), lines);
+            true
+        }
+    };
+    !inserted && resume_after.is_some_and(|after| at == after)
+}
+
+/// How a stream is launched, which decides how far back it looks.
+#[derive(Clone, Copy)]
+pub enum LaunchMode {
+    /// Attached at startup.
+    Startup,
+    /// Attached to a Pod discovered after startup.
+    Discovered,
+    /// Resumed after the last line consumed, e.g. when returning from dig.
+    Resumed(DateTime<Utc>),
+}
+
+/// Builds the parameters to stream the logs of the container.
+///
+/// Streams attached at startup look back `since_seconds` (or the whole history if unset),
+/// whereas streams attached to Pods discovered later only look back `discovery_since_seconds`,
+/// so that a Pod which has existed for a while doesn't bury the current logs under its history.
+/// Resumed streams only fetch the logs since the last line, regardless of both.
+///
+/// Each line is prefixed with its timestamp to know where to resume from.
+pub fn effective_log_params(
+    container: &str,
+    since_seconds: Option<i64>,
+    discovery_since_seconds: i64,
+    mode: LaunchMode,
+) -> LogParams {
+    let (since_seconds, since_time) = match mode {
+        LaunchMode::Startup => (since_seconds, None),
+        LaunchMode::Discovered => (Some(discovery_since_seconds), None),
+        LaunchMode::Resumed(last_seen) => (None, Some(last_seen)),
+    };
+    LogParams {
+        container: Some(container.to_string()),
+        follow: true,
+        since_seconds,
+        since_time,
+        timestamps: true,
+        ..Default::default()
+    }
+}
+
+/// Renders the `kubectl logs` command which streams the same lines as `params`.
+fn kubectl_command(context: &str, namespace: &str, pod: &str, params: &LogParams) -> String {
+    let mut command = format!("kubectl --context {context} logs -n {namespace} {pod}");
+    if let Some(container) = &params.container {
+        command.push_str(&format!(" -c {container}"));
+    }
+    command.push_str(&kubectl_flags(params));
+    command
+}
 
-use promkit::{crossterm::style::Color, grapheme::StyledGraphemes, style::StyleBuilder};
+/// Renders the parameters other than the container as `kubectl logs` flags,
+/// each preceded by a space.
+pub fn kubectl_flags(params: &LogParams) -> String {
+    let mut command = String::new();
+    if params.follow {
+        command.push_str(" -f");
+    }
+    if let Some(since) = params.since_seconds {
+        command.push_str(&format!(" --since={since}s"));
+    }
+    if let Some(since) = params.since_time {
+        command.push_str(&format!(
+            " --since-time={}",
+            since.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ));
+    }
+    if let Some(tail) = params.tail_lines {
+        command.push_str(&format!(" --tail={tail}"));
+    }
+    if params.previous {
+        command.push_str(" --previous");
+    }
+    if let Some(limit) = params.limit_bytes {
+        command.push_str(&format!(" --limit-bytes={limit}"));
+    }
+    // The timestamps are left out since they are only used to resume and never shown.
+    command
+}
+
+/// Returns whether the error indicates that the credentials of the client were rejected,
+/// e.g. because a token issued by an exec plugin (aws eks get-token, gke-gcloud-auth-plugin) expired.
+/// The causes of the error are looked through as well, e.g. those of the I/O error
+/// ending a log stream.
+pub fn is_unauthorized(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let cause = cause
+            .downcast_ref::<std::io::Error>()
+            .and_then(|e| e.get_ref())
+            .map_or(cause, |inner| inner as &(dyn std::error::Error + 'static));
+        matches!(
+            cause.downcast_ref::<kube::Error>(),
+            Some(kube::Error::Api(resp)) if resp.code == 401
+        ) || matches!(
+            cause.downcast_ref::<kube::Error>(),
+            Some(kube::Error::Auth(_))
+        ) || matches!(
+            cause.downcast_ref::<kube::core::ErrorResponse>(),
+            Some(resp) if resp.code == 401
+        )
+    })
+}
+
+/// Returns whether the error indicates that the user is not allowed to access the resource.
+pub fn is_forbidden(err: &kube::Error) -> bool {
+    matches!(err, kube::Error::Api(resp) if resp.code == 403)
+}
 
 #[derive(Clone)]
 pub struct ContainerLog {
+    pub namespace: String,
+    pub pod: String,
+    pub container: String,
+    /// Restart count of the container when its stream was attached.
+    pub restarts: i32,
+    /// Output stream of the line, if known (see `StreamSettings::raw_cri`).
+    pub stream: Option<LogStream>,
+    /// Whether the line was written by the container or annotates the stream.
+    pub kind: LogKind,
     pub meta: StyledGraphemes,
     pub body: StyledGraphemes,
+    /// Lines grouped under the body, e.g. the frames of a stack trace
+    /// (see `StreamSettings::group_stacktraces`). Shown collapsed unless expanded.
+    pub continuation: Vec<StyledGraphemes>,
+    /// When the line entered the queue of the live view, by the local clock.
+    /// Unrelated to the timestamp of the API server, which is only used to resume streams.
+    pub received: Option<DateTime<Local>>,
+    /// Timestamp of the API server of the last chunk or frame of the line,
+    /// to resume its stream after it once the line is consumed.
+    pub at: Option<DateTime<Utc>>,
+}
+
+impl ContainerLog {
+    /// Text of the body followed by the grouped lines, one per line.
+    pub fn message(&self) -> String {
+        let mut message = self.body.to_string();
+        for line in self.continuation.iter() {
+            message.push('\n');
+            message.push_str(&line.to_string());
+        }
+        message
+    }
+
+    /// Style the body of the line is received with, by its kind and stream.
+    pub fn body_style(&self, style: &StyleConfig) -> ContentStyle {
+        match (self.kind, self.stream) {
+            (LogKind::Rollout, _) => style.annotation,
+            (LogKind::Event, _) => style.event,
+            (LogKind::Termination, _) => style.error,
+            (LogKind::Marker, _) => style.dim,
+            (LogKind::Log, Some(LogStream::Stderr)) => style.stderr,
+            (LogKind::Log, _) => style.body,
+        }
+    }
+
+    /// Whether the body is empty or only whitespace, with no grouped lines.
+    pub fn is_blank(&self) -> bool {
+        self.continuation.is_empty() && self.body.to_string().trim().is_empty()
+    }
+
+    /// Row of the log shown in the lists: the meta and the body with the given style,
+    /// followed by the number of grouped lines while they are collapsed.
+    /// A blank body is shown as a dim `⏎`, so that the line is visibly intentional.
+    pub fn row(
+        &self,
+        body: &StyledGraphemes,
+        aliases: &Aliases,
+        style: &StyleConfig,
+    ) -> StyledGraphemes {
+        let placeholder;
+        let body = if self.is_blank() {
+            placeholder = StyledGraphemes::from_str("⏎", style.dim);
+            &placeholder
+        } else {
+            body
+        };
+        let mut row = StyledGraphemes::from_iter([&self.meta, &StyledGraphemes::from(" "), body]);
+        // Applied when drawn, so that the meta stays as made whatever the aliases.
+        if let Some(alias) = aliases.get(&self.pod) {
+            row = StyledGraphemes::from_iter([
+                &StyledGraphemes::from_str(format!(" {alias} "), style.header),
+                &StyledGraphemes::from(" "),
+                &row,
+            ]);
+        }
+        if !self.continuation.is_empty() {
+            row = StyledGraphemes::from_iter([
+                &row,
+                &StyledGraphemes::from_str(
+                    format!(" (+{} lines)", self.continuation.len()),
+                    style.dim,
+                ),
+            ]);
+        }
+        row
+    }
+
+    /// Whether the line continues the stack trace in `group`, e.g. an indented frame,
+    /// `Caused by:` in Java or the `Traceback` and the final exception in Python.
+    fn continues(group: &ContainerLog, line: &str) -> bool {
+        if line.starts_with(char::is_whitespace)
+            || line.starts_with("at ")
+            || line.starts_with("Caused by:")
+            || line.starts_with("Traceback (most recent call last):")
+        {
+            return true;
+        }
+        // The exception closing a Python traceback is not indented, e.g. `ValueError: ...`.
+        group.continuation.last().is_some_and(|last| {
+            last.to_string().starts_with(char::is_whitespace)
+                && group
+                    .continuation
+                    .iter()
+                    .any(|line| line.to_string().starts_with("Traceback"))
+                && line.split_once(':').is_some_and(|(name, _)| {
+                    !name.contains(' ') && (name.ends_with("Error") || name.ends_with("Exception"))
+                })
+        })
+    }
+}
+
+/// Groups the log into the stack trace being collected if it continues it,
+/// otherwise starts a new group with it and returns the previous one to send.
+fn group_stacktrace(group: &mut Option<ContainerLog>, log: ContainerLog) -> Option<ContainerLog> {
+    match group {
+        Some(head) if ContainerLog::continues(head, &log.body.to_string()) => {
+            head.continuation.push(log.body);
+            head.at = log.at.or(head.at);
+            None
+        }
+        _ => group.replace(log),
+    }
+}
+
+/// Grace period of the Pods being deleted which don't tell theirs, as by default in Kubernetes.
+const DEFAULT_GRACE_PERIOD: i64 = 30;
+
+/// Time without any line after which the stack trace being grouped is considered complete.
+const STACKTRACE_WINDOW: Duration = Duration::from_millis(100);
+
+/// Time the kubelet is given to report the container terminated once its stream ended,
+/// before its termination message is fetched.
+const TERMINATION_SETTLE: Duration = Duration::from_secs(1);
+
+/// Minimum time between two fetches of the termination message of the same container,
+/// so that a container in a crash loop doesn't have its Pod fetched on every restart.
+const TERMINATION_FETCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Termination message of the container as reported by the kubelet, if any,
+/// e.g. the final panic of a crashing app with `terminationMessagePolicy: FallbackToLogsOnError`.
+fn termination_message(pod: &Pod, container: &str) -> Option<String> {
+    let status = pod.status.as_ref()?;
+    status
+        .container_statuses
+        .iter()
+        .chain(status.init_container_statuses.iter())
+        .flatten()
+        .find(|status| status.name == container)
+        .and_then(|status| {
+            // The current state if the container is not restarted yet, else the last one.
+            [status.state.as_ref(), status.last_state.as_ref()]
+                .into_iter()
+                .flatten()
+                .find_map(|state| state.terminated.as_ref()?.message.clone())
+        })
+        .filter(|message| !message.trim().is_empty())
+}
+
+/// Style of the meta of the container identified by the key, picked from the palette by its hash.
+pub fn meta_style(key: &str, style: &StyleConfig) -> ContentStyle {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let hashed = hasher.finish();
+    let color = style.palette[hashed as usize % style.palette.len()];
+    let mut meta_style = StyleBuilder::new().fgc(color).build();
+    if !style.meta_attributes.is_empty() {
+        // Use the bits not consumed by the color so that the attribute varies
+        // independently of it.
+        meta_style.attributes = style.meta_attributes
+            [(hashed as usize / style.palette.len()) % style.meta_attributes.len()];
+    }
+    meta_style
+}
+
+/// Badge telling the namespace apart at a glance: its initials on a color picked for it,
+/// independently of the colors of the containers.
+/// e.g. `kube-system` is `ks` and `payments` is `pa`.
+pub fn namespace_badge(namespace: &str, style: &StyleConfig) -> StyledGraphemes {
+    let initials: String = if namespace.contains(['-', '_']) {
+        namespace
+            .split(['-', '_'])
+            .filter_map(|part| part.chars().next())
+            .take(2)
+            .collect()
+    } else {
+        namespace.chars().take(2).collect()
+    };
+    let color = style
+        .namespace_colors
+        .get(namespace)
+        .copied()
+        .unwrap_or_else(|| {
+            let mut hasher = DefaultHasher::new();
+            namespace.hash(&mut hasher);
+            style.palette[hasher.finish() as usize % style.palette.len()]
+        });
+    let mut badge_style = style.badge;
+    badge_style.background_color = Some(color);
+    StyledGraphemes::from_str(format!(" {initials} "), badge_style)
+}
+
+/// What a line in the log stream is.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LogKind {
+    /// A line written by the container.
+    Log,
+    /// An annotation of the progress of a rollout (see `rollout::Watcher`).
+    Rollout,
+    /// A Kubernetes Event of the Pod (see `events::Watcher`).
+    Event,
+    /// A line of the termination message of the container, shown when its stream ends.
+    Termination,
+    /// A line marking what happened to the stream rather than written by the container,
+    /// e.g. `── stream ended ──` or `── attached: new Pod ──`.
+    Marker,
+}
+
+/// Output stream of the container which a line was written to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
 }
 
 #[derive(Clone, clap::ValueEnum, Debug, PartialEq)]
@@ -30,18 +543,64 @@ pub enum ContainerState {
     Waiting,
 }
 
-pub struct ContainerStateMatcher(Vec<ContainerState>);
+#[derive(Clone)]
+pub struct ContainerStateMatcher {
+    states: Vec<ContainerState>,
+    /// Additionally requires the container to be ready, regardless of the accepted states.
+    ready_only: bool,
+}
 
 impl ContainerStateMatcher {
-    pub fn new(states: Vec<ContainerState>) -> Self {
-        Self(states)
+    pub fn new(states: Vec<ContainerState>, ready_only: bool) -> Self {
+        Self { states, ready_only }
+    }
+
+    pub fn matches(&self, status: &api::core::v1::ContainerStatus) -> bool {
+        if self.ready_only && !status.ready {
+            return false;
+        }
+        status
+            .state
+            .as_ref()
+            .is_some_and(|state| self.matches_state(state))
     }
 
-    pub fn matches(&self, state: &api::core::v1::ContainerState) -> bool {
-        if self.0.contains(&ContainerState::All) {
+    /// Accepts the next of all, running, terminated and waiting containers,
+    /// or all of them if several states were accepted.
+    pub fn cycle(&mut self) {
+        let next = match self.states.as_slice() {
+            [ContainerState::All] => ContainerState::Running,
+            [ContainerState::Running] => ContainerState::Terminated,
+            [ContainerState::Terminated] => ContainerState::Waiting,
+            _ => ContainerState::All,
+        };
+        self.states = vec![next];
+    }
+
+    /// Accepted states as shown in the status, e.g. `running,terminated`,
+    /// or `None` if all states are accepted.
+    pub fn label(&self) -> Option<String> {
+        if self.states.is_empty() || self.states.contains(&ContainerState::All) {
+            return None;
+        }
+        let states: Vec<&str> = self
+            .states
+            .iter()
+            .map(|state| match state {
+                ContainerState::All => "all",
+                ContainerState::Running => "running",
+                ContainerState::Terminated => "terminated",
+                ContainerState::Waiting => "waiting",
+            })
+            .collect();
+        Some(states.join(","))
+    }
+
+    fn matches_state(&self, state: &api::core::v1::ContainerState) -> bool {
+        if self.states.contains(&ContainerState::All) {
             true
         } else {
-            self.0.iter().any(|accept| match accept {
+            self.states.iter().any(|accept| match accept {
                 ContainerState::Running => state.running.is_some(),
                 ContainerState::Terminated => state.terminated.is_some(),
                 ContainerState::Waiting => state.waiting.is_some(),
@@ -51,164 +610,3132 @@ impl ContainerStateMatcher {
     }
 }
 
-pub struct ContainerLogStreamer {
-    api_pod: Api<Pod>,
-    pod_regex: Option<Regex>,
-    container_state_matcher: ContainerStateMatcher,
-    colors: Vec<Color>,
+/// Namespaces to look for Pods in.
+#[derive(Clone)]
+pub enum NamespaceSelector {
+    /// The given namespaces.
+    Names(Vec<String>),
+    /// The namespaces whose names match the regex, resolved when streams are launched.
+    Regex(Regex),
+    /// All the namespaces, whose Pods are listed at once across the cluster.
+    All,
 }
 
-impl ContainerLogStreamer {
-    pub fn try_new(
-        api_pod: Api<Pod>,
-        pod_query: Option<String>,
-        container_state_matcher: ContainerStateMatcher,
-    ) -> anyhow::Result<Self> {
-        Ok(Self {
-            api_pod,
-            pod_regex: match pod_query {
-                Some(query) => Some(Regex::new(&query)?),
-                None => None,
-            },
-            container_state_matcher,
-            colors: vec![
-                Color::Red,
-                Color::DarkRed,
-                Color::Green,
-                Color::DarkGreen,
-                Color::Yellow,
-                Color::DarkYellow,
-                Color::Blue,
-                Color::DarkBlue,
-                Color::Magenta,
-                Color::DarkMagenta,
-                Color::Cyan,
-                Color::DarkCyan,
-            ],
-        })
+impl NamespaceSelector {
+    /// Returns whether logs from more than one namespace may be merged,
+    /// in which case the namespace is shown in the meta of each line.
+    pub fn is_multi(&self) -> bool {
+        match self {
+            NamespaceSelector::Names(names) => names.len() > 1,
+            NamespaceSelector::Regex(_) | NamespaceSelector::All => true,
+        }
     }
+}
 
-    /// Retrieves a vector of pairs of pod and container names
-    /// that match specific criteria from a list of Pods obtained via the API.
-    ///
-    /// The function operates as follows:
-    /// 1. Initializes an empty vector `ret`.
-    /// 2. Uses `api_pod.list` to fetch a list of Pods with default list parameters.
-    /// 3. For each Pod retrieved, it performs the following checks:
-    ///    - Whether the Pod's name matches the regular expression `pod_regex`, if it is set.
-    ///    - Whether the Pod's status exists and if any of the container statuses
-    ///      match specific states defined by `container_state_matcher`.
-    /// 4. For each container that matches the conditions, adds a pair of the Pod's name and the container's name to the vector `ret`.
-    /// 5. After checking all Pods and their containers, returns the vector `ret`.
-    async fn get_pod_and_containers(&self) -> anyhow::Result<Vec<(String, String)>> {
-        let mut ret = Vec::new();
+/// Tuning of the log streams which stays the same across reconnects.
+#[derive(Clone, Copy)]
+pub struct StreamSettings {
+    /// Time without any line after which the backlog of a stream is taken as received,
+    /// so that the following lines are live.
+    pub backlog_quiet_time: Duration,
+    /// Duration without any line after which a stream is considered dead and reopened.
+    pub idle_timeout: Option<Duration>,
+    /// How to rejoin the lines split by the kubelet. Disabled if `None`.
+    pub line_merge: Option<LineMerge>,
+    /// Whether the lines come in the CRI log format (`<time> <stream> <tag> <message>`),
+    /// as passed through by some clusters, which tells stdout and stderr apart.
+    pub raw_cri: bool,
+    /// Whether to escape the control characters of the lines visibly
+    /// instead of flattening them and stripping ANSI escape sequences.
+    pub escape_control: bool,
+    /// Whether to hide the meta when only one container is streamed, since it can't vary.
+    pub single: bool,
+    /// Whether to group the lines continuing a stack trace into the line which started it.
+    pub group_stacktraces: bool,
+    /// Interval to re-check the container states at, attaching and detaching streams
+    /// as containers enter and leave the accepted states. Checked only at launch if `None`.
+    pub enforce_interval: Option<Duration>,
+    /// Whether the streams are attached to Pods discovered after startup,
+    /// which look back `discovery_since_seconds` instead of `since_seconds`.
+    pub discovered: bool,
+    /// Whether to watch the Pods for the ones appearing after launch and attach streams to them,
+    /// unless the states are enforced, which discovers them on every check.
+    pub watch_pods: bool,
+    /// Number of recent lines remembered per container to drop the ones replayed
+    /// when its stream is resumed (see `RecentLines`). Compares the timestamps only if 0.
+    pub dedup_window: usize,
+    /// Whether to drop the lines whose bodies are empty or only whitespace.
+    pub skip_empty: bool,
+    /// Whether to keep the trailing whitespace of the lines, but the `\r` of CRLF line endings.
+    pub keep_trailing_whitespace: bool,
+    /// Number of lines each stream keeps waiting while the channel shared by the streams is full
+    /// (see `Outbox`), and what it does with the next ones.
+    pub stream_buffer: usize,
+    pub overflow: Overflow,
+    /// Maximum number of streams being opened at once when launching them.
+    pub max_concurrent_streams: usize,
+    /// Time to hold back the backlogs of the streams launched together
+    /// to merge them by time (see `Backfill`). Not merged if `None` or zero.
+    pub backfill_window: Option<Duration>,
+}
 
-        for pod in self.api_pod.list(&ListParams::default()).await? {
-            if let Some(pod_name) = pod.metadata.name {
-                if let Some(pod_regex) = &self.pod_regex {
-                    if !pod_regex.is_match(&pod_name) {
-                        continue;
-                    }
-                }
-                if let Some(pod_status) = pod.status {
-                    if let Some(container_statuses) = pod_status.container_statuses {
-                        for container in container_statuses.iter().filter(|status| {
-                            status
-                                .state
-                                .as_ref()
-                                .map_or(false, |state| self.container_state_matcher.matches(state))
-                        }) {
-                            ret.push((pod_name.clone(), container.name.clone()));
-                        }
-                    }
-                }
-                if let Some(containers) = pod.spec.map(|spec| spec.containers) {
-                    for container in containers {
-                        ret.push((pod_name.clone(), container.name));
-                    }
+/// Changes to the streams of `enforce_log_streams` to keep them in line
+/// with the containers accepted by the last check.
+#[derive(Debug, Default, PartialEq)]
+struct Reconciliation {
+    /// Keys of the open streams whose containers left the accepted states.
+    detach: Vec<String>,
+    /// Keys of the streams which ended by themselves, e.g. with their deleted Pods,
+    /// and have nothing to detach.
+    forget: Vec<String>,
+    /// Indices of the accepted containers to launch a stream for.
+    launch: Vec<usize>,
+}
+
+/// Reconciles the streams, given whether the stream of each key has ended,
+/// with the key of each accepted container and whether its Pod is being deleted.
+///
+/// The stream of a Pod being deleted is not relaunched once it ended,
+/// as its container is gone or about to be.
+fn reconcile(streams: &HashMap<String, bool>, accepted: &[(String, bool)]) -> Reconciliation {
+    let keys: HashSet<&str> = accepted.iter().map(|(key, _)| key.as_str()).collect();
+    let mut reconciliation = Reconciliation::default();
+    for (key, &ended) in streams {
+        if keys.contains(key.as_str()) {
+            continue;
+        }
+        if ended {
+            reconciliation.forget.push(key.clone());
+        } else {
+            reconciliation.detach.push(key.clone());
+        }
+    }
+    reconciliation.forget.sort();
+    reconciliation.detach.sort();
+    reconciliation.launch = accepted
+        .iter()
+        .enumerate()
+        .filter(|(_, (key, terminating))| {
+            streams.get(key).is_none_or(|&ended| ended && !terminating)
+        })
+        .map(|(index, _)| index)
+        .collect();
+    reconciliation
+}
+
+/// Runs the launch of each of `count` targets, given its index, up to `limit` at once,
+/// and returns what the ones not skipped launched, in the order they are done.
+/// `progress` counts the launches done, and all of them, until they are all done.
+/// The launches still pending are dropped on cancellation, and when one fails.
+async fn launch_concurrently<T, F>(
+    count: usize,
+    limit: usize,
+    progress: &Mutex<Option<(usize, usize)>>,
+    canceled: &CancellationToken,
+    launch: impl Fn(usize) -> F,
+) -> anyhow::Result<Vec<(usize, T)>>
+where
+    F: Future<Output = anyhow::Result<Option<T>>>,
+{
+    let semaphore = Semaphore::new(limit.max(1));
+    *progress.lock().unwrap() = Some((0, count));
+    let mut launches: FuturesUnordered<_> = (0..count)
+        .map(|index| {
+            let (semaphore, launch) = (&semaphore, &launch);
+            async move {
+                // If cancellation is detected (e.g. pressing ctrl+c immediately after execution),
+                // give up early to avoid creating unnecessary futures,
+                // even if a permit was released by a launch given up as well.
+                let _permit = tokio::select! {
+                    biased;
+                    _ = canceled.cancelled() => return Ok(None),
+                    permit = semaphore.acquire() => permit.expect("the semaphore is never closed"),
+                };
+                let launched = tokio::select! {
+                    biased;
+                    _ = canceled.cancelled() => return Ok(None),
+                    launched = launch(index) => launched,
+                };
+                if let Some((done, _)) = progress.lock().unwrap().as_mut() {
+                    *done += 1;
                 }
+                launched.map(|launched| launched.map(|launched| (index, launched)))
             }
+        })
+        .collect();
+    let mut launched = Vec::new();
+    let done = async {
+        while let Some(launch) = launches.next().await {
+            launched.extend(launch?);
         }
+        anyhow::Ok(())
+    }
+    .await;
+    *progress.lock().unwrap() = None;
+    done.map(|()| launched)
+}
 
-        Ok(ret)
+/// Heuristic to rejoin long lines which the kubelet splits into chunks of a fixed size
+/// (16KiB with some runtimes): a line of exactly the chunk size is held back
+/// and joined with the next line of the same stream if it arrives within the window.
+#[derive(Clone, Copy)]
+pub struct LineMerge {
+    /// Size of the chunks in bytes.
+    pub chunk_size: usize,
+    /// Duration to wait for the continuation before giving up on merging.
+    pub window: Duration,
+    /// Size in bytes beyond which a merged line is emitted even if it seems to continue.
+    pub max_size: usize,
+}
+
+/// Line of a stream, with the output stream it was written to if known.
+type Line = (Option<LogStream>, String);
+
+/// Rejoins the lines of a stream split by the kubelet (see `LineMerge`),
+/// holding back the head of a split line until its continuation comes.
+struct LineMerger {
+    merge: Option<LineMerge>,
+    /// Head of a line split by the kubelet, waiting for its continuation.
+    pending: Option<Line>,
+}
+
+impl LineMerger {
+    fn new(merge: Option<LineMerge>) -> Self {
+        Self {
+            merge,
+            pending: None,
+        }
     }
 
-    /// Initiates log streams for pods and containers that match specified criteria.
-    pub async fn launch_log_streams(
-        &self,
-        log_stream_tx: mpsc::Sender<ContainerLog>,
-        log_retrieval_timeout: Duration,
-        canceled: CancellationToken,
-    ) -> anyhow::Result<FuturesUnordered<JoinHandle<Result<(), anyhow::Error>>>> {
-        let futures = FuturesUnordered::new();
-        let pod_containers = self.get_pod_and_containers().await?;
-
-        for (pod, container) in pod_containers.iter() {
-            // If cancellation is detected (e.g. pressing ctrl+c immediately after execution),
-            // break early to avoid creating unnecessary futures.
-            if canceled.is_cancelled() {
-                break;
-            }
-
-            let log_stream_tx = log_stream_tx.clone();
-            let colors = self.colors.clone();
-
-            let mut pod_log_stream = self
-                .api_pod
-                .log_stream(
-                    pod,
-                    &LogParams {
-                        container: Some(container.clone()),
-                        follow: true,
-                        ..Default::default()
-                    },
-                )
-                .await?
-                .lines();
+    /// Time the held line waits for its continuation after the last chunk, if a line is held.
+    fn window(&self) -> Option<Duration> {
+        self.pending
+            .as_ref()
+            .and(self.merge)
+            .map(|merge| merge.window)
+    }
 
-            let mut hasher = DefaultHasher::new();
-            let key = format!("{} {}", &pod, &container);
-            key.hash(&mut hasher);
-            let hashed = hasher.finish();
-            let canceled = canceled.clone();
-            let color = colors[hashed as usize % colors.len()];
+    /// Joins the chunk to the held line, if any, and gives back the line
+    /// unless it may continue in the next chunk.
+    fn push(&mut self, stream: Option<LogStream>, chunk: String) -> Option<Line> {
+        let split = self
+            .merge
+            .is_some_and(|merge| chunk.len() == merge.chunk_size);
+        let line = match self.pending.take() {
+            Some((stream, mut head)) => {
+                head.push_str(&chunk);
+                (stream, head)
+            }
+            None => (stream, chunk),
+        };
+        if split
+            && self
+                .merge
+                .is_some_and(|merge| line.1.len() < merge.max_size)
+        {
+            self.pending = Some(line);
+            return None;
+        }
+        Some(line)
+    }
 
-            futures.push(tokio::spawn(async move {
-                while !canceled.is_cancelled() {
-                    // Set a timeout to ensure non-blocking behavior,
-                    // especially responsive to user inputs like ctrl+c.
-                    // Continuously retry until cancellation to prevent loss of logs.
-                    let ret = timeout(log_retrieval_timeout, pod_log_stream.next()).await;
-                    if ret.is_err() {
-                        continue;
-                    }
+    /// Gives back the held line if the window elapsed since the last chunk:
+    /// the continuation didn't come, so the line just happened to be as long as a chunk.
+    fn expire(&mut self, elapsed: Duration) -> Option<Line> {
+        if self.merge.is_some_and(|merge| elapsed >= merge.window) {
+            self.pending.take()
+        } else {
+            None
+        }
+    }
 
-                    let ret = ret?;
-
-                    match ret {
-                        Some(Ok(line)) => {
-                            let escaped =
-                                strip_ansi_escapes::strip_str(line.replace(['\n', '\t'], " "));
-                            log_stream_tx
-                                .send(ContainerLog {
-                                    meta: StyledGraphemes::from_str(
-                                        &key,
-                                        StyleBuilder::new().fgc(color).build(),
-                                    ),
-                                    body: StyledGraphemes::from_str(
-                                        &escaped,
-                                        StyleBuilder::new().fgc(Color::Reset).build(),
-                                    ),
-                                })
-                                .await?;
-                        }
-                        _ => break,
-                    }
+    /// Gives back the held line, e.g. once the stream is over.
+    fn take(&mut self) -> Option<Line> {
+        self.pending.take()
+    }
+}
+
+/// Sends the line of the stream through its outbox, unless it is held back to be merged
+/// with the backlogs of the other streams, with the time of the last line of the stream
+/// stamped so far.
+async fn send(
+    log_stream_tx: &mpsc::Sender<ContainerLog>,
+    outbox: &mut Outbox,
+    backfill: Option<&(Arc<Backfill>, usize)>,
+    at: Option<DateTime<Utc>>,
+    log: ContainerLog,
+) -> anyhow::Result<()> {
+    let log = match backfill {
+        Some((backfill, stream)) => match backfill.hold(*stream, at, log).await {
+            Some(log) => log,
+            None => return Ok(()),
+        },
+        None => log,
+    };
+    outbox.send(log_stream_tx, log).await
+}
+
+/// Keeps only the containers matching the preference among `targets[from..]`,
+/// all of the same Pod, if any of them matches and any doesn't,
+/// noting the ones left out in each kept target. Otherwise they are all kept.
+fn prefer_containers(targets: &mut Vec<Target>, from: usize, preference: &Regex) {
+    let (preferred, others): (Vec<Target>, Vec<Target>) = targets
+        .drain(from..)
+        .partition(|target| preference.is_match(&target.container));
+    if preferred.is_empty() || others.is_empty() {
+        targets.extend(preferred.into_iter().chain(others));
+        return;
+    }
+    let left_out: Vec<String> = others.into_iter().map(|target| target.container).collect();
+    for mut target in preferred {
+        debug!(
+            namespace = target.namespace,
+            pod = target.pod,
+            container = target.container,
+            preferred_over = ?left_out,
+            "preferred container"
+        );
+        target.preferred_over = left_out.clone();
+        targets.push(target);
+    }
+}
+
+/// Pods to stream the containers of.
+#[derive(Clone)]
+pub enum PodSelector {
+    All,
+    /// The Pod of exactly this name, selected by the API server.
+    Name(String),
+    /// The Pods whose names, or values in the scope, match the regex.
+    Regex(Regex, MatchScope),
+}
+
+/// Values of a Pod which the regex of `PodSelector::Regex` is tested against besides its name.
+#[derive(Clone, Copy)]
+pub struct MatchScope {
+    pub labels: bool,
+    pub annotations: bool,
+}
+
+/// Requirements on the annotations of the Pods to stream, e.g. `team=payments,tier!=batch`,
+/// in the syntax of label selectors with `=`, `==` and `!=`.
+///
+/// The API server doesn't select by annotations, so the Pods are filtered after listing.
+#[derive(Clone, Default)]
+pub struct AnnotationSelector {
+    /// Key, value and whether the value must equal it (or differ from it).
+    requirements: Vec<(String, String, bool)>,
+}
+
+impl AnnotationSelector {
+    pub fn parse(selector: &str) -> Result<Self, String> {
+        let mut requirements = Vec::new();
+        for requirement in selector.split(',').map(str::trim) {
+            let (key, value, equal) = if let Some((key, value)) = requirement.split_once("!=") {
+                (key, value, false)
+            } else if let Some((key, value)) = requirement.split_once("==") {
+                (key, value, true)
+            } else if let Some((key, value)) = requirement.split_once('=') {
+                (key, value, true)
+            } else {
+                return Err(format!(
+                    "invalid requirement '{requirement}', expected KEY=VALUE or KEY!=VALUE"
+                ));
+            };
+            if key.trim().is_empty() {
+                return Err(format!("missing key in '{requirement}'"));
+            }
+            requirements.push((key.trim().to_string(), value.trim().to_string(), equal));
+        }
+        Ok(Self { requirements })
+    }
+
+    /// Whether the Pod meets all the requirements.
+    /// A missing annotation differs from any value.
+    fn matches(&self, metadata: &ObjectMeta) -> bool {
+        self.requirements.iter().all(|(key, value, equal)| {
+            let actual = metadata
+                .annotations
+                .as_ref()
+                .and_then(|annotations| annotations.get(key));
+            (actual == Some(value)) == *equal
+        })
+    }
+}
+
+/// Label selector of the Pods to stream, e.g. `app=nginx,tier in (frontend,edge)`,
+/// passed to the API server as it is once validated.
+///
+/// The requirements are `KEY`, `!KEY`, `KEY=VALUE`, `KEY==VALUE`, `KEY!=VALUE`,
+/// `KEY in (VALUE,...)` and `KEY notin (VALUE,...)`, separated by commas.
+#[derive(Clone)]
+pub struct LabelSelector(String);
+
+impl LabelSelector {
+    /// Validates the selector, so that a typo fails at startup
+    /// rather than with an error of the API server on every discovery.
+    pub fn parse(selector: &str) -> Result<Self, String> {
+        let mut requirements = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (at, ch) in selector.char_indices() {
+            match ch {
+                '(' => depth += 1,
+                ')' if depth == 0 => return Err(format!("unbalanced ')' in '{selector}'")),
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    requirements.push(&selector[start..at]);
+                    start = at + 1;
                 }
-                Ok(())
-            }));
+                _ => (),
+            }
+        }
+        if depth > 0 {
+            return Err(format!("missing ')' in '{selector}'"));
         }
+        requirements.push(&selector[start..]);
+        for requirement in requirements.into_iter().map(str::trim) {
+            validate_requirement(requirement)?;
+        }
+        Ok(Self(selector.trim().to_string()))
+    }
+}
 
-        Ok(futures)
+fn validate_requirement(requirement: &str) -> Result<(), String> {
+    let set = |key: &str, values: &str| {
+        let values = values
+            .trim()
+            .strip_prefix('(')
+            .and_then(|values| values.strip_suffix(')'))
+            .ok_or_else(|| format!("expected (VALUE,...) in '{requirement}'"))?;
+        validate_label_key(key.trim())?;
+        if values.trim().is_empty() {
+            return Err(format!("empty set of values in '{requirement}'"));
+        }
+        values
+            .split(',')
+            .try_for_each(|value| validate_label_value(value.trim()))
+    };
+    if let Some((key, values)) = requirement.split_once(" notin ") {
+        set(key, values)
+    } else if let Some((key, values)) = requirement.split_once(" in ") {
+        set(key, values)
+    } else if let Some((key, value)) = requirement
+        .split_once("!=")
+        .or_else(|| requirement.split_once("=="))
+        .or_else(|| requirement.split_once('='))
+    {
+        validate_label_key(key.trim())?;
+        validate_label_value(value.trim())
+    } else {
+        validate_label_key(requirement.strip_prefix('!').unwrap_or(requirement).trim())
+    }
+}
+
+/// Validates a key as `[PREFIX/]NAME`, where the prefix is a DNS subdomain.
+fn validate_label_key(key: &str) -> Result<(), String> {
+    let name = match key.rsplit_once('/') {
+        Some((prefix, name)) => {
+            let valid_prefix = !prefix.is_empty()
+                && prefix.len() <= 253
+                && prefix.split('.').all(|part| {
+                    !part.is_empty()
+                        && part.starts_with(|ch: char| ch.is_ascii_alphanumeric())
+                        && part.ends_with(|ch: char| ch.is_ascii_alphanumeric())
+                        && part
+                            .chars()
+                            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-')
+                });
+            if !valid_prefix {
+                return Err(format!("invalid prefix of the label key '{key}'"));
+            }
+            name
+        }
+        None => key,
+    };
+    if name.is_empty() {
+        return Err(format!("missing label key in '{key}'"));
+    }
+    validate_label_value(name).map_err(|_| format!("invalid label key '{key}'"))
+}
+
+/// Validates a value as up to 63 alphanumerics, `-`, `_` and `.`,
+/// beginning and ending with an alphanumeric. An empty value is valid.
+fn validate_label_value(value: &str) -> Result<(), String> {
+    let valid = value.is_empty()
+        || (value.len() <= 63
+            && value.starts_with(|ch: char| ch.is_ascii_alphanumeric())
+            && value.ends_with(|ch: char| ch.is_ascii_alphanumeric())
+            && value
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || "-_.".contains(ch)));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid label value '{value}'"))
+    }
+}
+
+/// Field selector of the Pods to stream, e.g. `spec.nodeName=node-1,status.phase=Running`,
+/// passed to the API server as it is once validated.
+///
+/// The requirements are `FIELD=VALUE`, `FIELD==VALUE` and `FIELD!=VALUE`, separated by commas.
+/// Which fields are supported is up to the API server.
+#[derive(Clone)]
+pub struct FieldSelector(String);
+
+impl FieldSelector {
+    pub fn parse(selector: &str) -> Result<Self, String> {
+        for requirement in selector.split(',').map(str::trim) {
+            let (field, _) = requirement
+                .split_once("!=")
+                .or_else(|| requirement.split_once("=="))
+                .or_else(|| requirement.split_once('='))
+                .ok_or_else(|| {
+                    format!(
+                        "invalid requirement '{requirement}', expected FIELD=VALUE or FIELD!=VALUE"
+                    )
+                })?;
+            let field = field.trim();
+            if field.is_empty()
+                || !field
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || "._-".contains(ch))
+            {
+                return Err(format!("invalid field '{field}' in '{requirement}'"));
+            }
+        }
+        Ok(Self(selector.trim().to_string()))
+    }
+}
+
+impl PodSelector {
+    /// Field selector of the Pod, if the API server can narrow the Pods down to it.
+    fn field_selector(&self) -> Option<String> {
+        match self {
+            PodSelector::Name(name) => Some(format!("metadata.name={name}")),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, metadata: &ObjectMeta) -> bool {
+        let name = metadata.name.as_deref().unwrap_or_default();
+        match self {
+            PodSelector::All => true,
+            PodSelector::Name(expected) => name == expected,
+            PodSelector::Regex(regex, scope) => {
+                let matches_any = |values: &Option<BTreeMap<String, String>>| {
+                    values
+                        .iter()
+                        .flatten()
+                        .any(|(_, value)| regex.is_match(value))
+                };
+                regex.is_match(name)
+                    || (scope.labels && matches_any(&metadata.labels))
+                    || (scope.annotations && matches_any(&metadata.annotations))
+            }
+        }
+    }
+}
+
+impl fmt::Display for NamespaceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NamespaceSelector::Names(names) => write!(f, "{}", names.join(",")),
+            NamespaceSelector::Regex(regex) => write!(f, "/{regex}/"),
+            NamespaceSelector::All => write!(f, "all"),
+        }
+    }
+}
+
+/// A container to stream the logs of.
+pub struct Target {
+    pub namespace: String,
+    pub pod: String,
+    pub container: String,
+    /// Number of times the container had restarted when the target was resolved.
+    pub restarts: i32,
+    /// State of the container when the target was resolved.
+    pub state: &'static str,
+    /// Labels of the Pod used in the meta format.
+    pub labels: HashMap<String, String>,
+    /// Containers of the same Pod left out in favor of this one (see `set_container_preference`).
+    pub preferred_over: Vec<String>,
+    /// Grace period in seconds of the Pod, if it is being deleted.
+    pub terminating: Option<i64>,
+}
+
+/// Namespace, Pod and parameters an open stream was requested with.
+type OpenedStream = (String, String, LogParams);
+
+#[derive(Clone)]
+pub struct ContainerLogStreamer {
+    client: Client,
+    namespaces: NamespaceSelector,
+    pods: PodSelector,
+    /// Accepted states of the containers, shared by the clones of the streamer
+    /// so that they can be changed while streaming.
+    container_state_matcher: Arc<Mutex<ContainerStateMatcher>>,
+    since_seconds: Option<i64>,
+    discovery_since_seconds: i64,
+    meta_format: MetaFormat,
+    /// Canceled once the credentials of the client are rejected by any stream,
+    /// so that the client can be rebuilt.
+    unauthorized: CancellationToken,
+    /// Labels the Pods must have besides being selected by `pods`, selected by the API server.
+    label_selector: Option<LabelSelector>,
+    /// Fields the Pods must have besides being selected by `pods`, selected by the API server.
+    field_selector: Option<FieldSelector>,
+    /// Annotations the Pods must have besides being selected by `pods`.
+    annotation_selector: AnnotationSelector,
+    /// Meta styles assigned so far, keyed by the identity of the container,
+    /// shared by the clones of the streamer so that a reconnected stream
+    /// keeps its color for the rest of the session.
+    meta_styles: Arc<Mutex<HashMap<String, ContentStyle>>>,
+    /// Timestamp of the last line of each container consumed by the view, keyed like `meta_styles`,
+    /// so that relaunched streams continue from there instead of replaying their backlog.
+    last_seen: Arc<Mutex<HashMap<String, DateTime<Utc>>>>,
+    /// Recent lines of each container, keyed like `meta_styles`,
+    /// to drop the lines received again when its stream is resumed.
+    recent: Arc<Mutex<HashMap<String, RecentLines>>>,
+    /// Namespace, Pod and parameters of the streams currently open, keyed like `meta_styles`,
+    /// to tell how to reproduce them with kubectl.
+    opened: Arc<Mutex<BTreeMap<String, OpenedStream>>>,
+    /// Skew of the cluster clock estimated from the lines of all the streams.
+    skew: Arc<Mutex<SkewEstimator>>,
+    /// Whether similar Pod names were suggested since --pod-query last matched,
+    /// so that they are not repeated on every discovery.
+    suggested: Arc<AtomicBool>,
+    /// Number of streams opened (or skipped) so far and in total while they are launched.
+    launching: Arc<Mutex<Option<(usize, usize)>>>,
+    /// Containers streamed in place of the others of their Pod, if any of them is accepted.
+    container_preference: Option<Regex>,
+    /// Lines dropped by the streams before reaching the queue, for the whole session.
+    dropped: Arc<Mutex<Stats>>,
+    /// Whether the Pods being deleted are left out instead of streamed until they are gone.
+    stop_on_terminating: bool,
+    /// Pods marked as terminating in the stream, keyed by namespace and name.
+    terminating: Arc<Mutex<HashSet<(String, String)>>>,
+    /// When the termination message of each container was last fetched, keyed like `meta_styles`.
+    termination_fetched: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl ContainerLogStreamer {
+    pub fn new(
+        client: Client,
+        namespaces: NamespaceSelector,
+        pods: PodSelector,
+        container_state_matcher: ContainerStateMatcher,
+        since_seconds: Option<i64>,
+        discovery_since_seconds: i64,
+        meta_format: MetaFormat,
+    ) -> Self {
+        Self {
+            client,
+            namespaces,
+            pods,
+            container_state_matcher: Arc::new(Mutex::new(container_state_matcher)),
+            since_seconds,
+            discovery_since_seconds,
+            meta_format,
+            unauthorized: CancellationToken::new(),
+            label_selector: None,
+            field_selector: None,
+            annotation_selector: Default::default(),
+            meta_styles: Default::default(),
+            last_seen: Default::default(),
+            recent: Default::default(),
+            opened: Default::default(),
+            skew: Arc::new(Mutex::new(SkewEstimator::new(true))),
+            suggested: Default::default(),
+            launching: Default::default(),
+            container_preference: None,
+            dropped: Default::default(),
+            stop_on_terminating: false,
+            terminating: Default::default(),
+            termination_fetched: Default::default(),
+        }
+    }
+
+    /// Sets the token canceled when the credentials of the client are rejected,
+    /// even by a stream which was already open.
+    pub fn set_unauthorized_notifier(&mut self, notifier: CancellationToken) {
+        self.unauthorized = notifier;
+    }
+
+    /// Leaves out the Pods being deleted, detaching their streams while enforcing the states.
+    pub fn set_stop_on_terminating(&mut self, stop: bool) {
+        self.stop_on_terminating = stop;
+    }
+
+    /// Marker line for the Pod of the target the first time it is seen being deleted,
+    /// e.g. `── pod terminating (grace 30s) ──`, so that its shutdown stands out.
+    fn terminating_marker(&self, target: &Target, style: &StyleConfig) -> Option<ContainerLog> {
+        let grace = target.terminating?;
+        if !self
+            .terminating
+            .lock()
+            .unwrap()
+            .insert((target.namespace.clone(), target.pod.clone()))
+        {
+            return None;
+        }
+        info!(
+            namespace = target.namespace,
+            pod = target.pod,
+            grace,
+            "pod terminating"
+        );
+        Some(ContainerLog {
+            namespace: target.namespace.clone(),
+            pod: target.pod.clone(),
+            container: target.container.clone(),
+            restarts: target.restarts,
+            stream: None,
+            kind: LogKind::Marker,
+            meta: self.meta(target, style),
+            body: StyledGraphemes::from_str(
+                format!("── pod terminating (grace {grace}s) ──"),
+                style.dim,
+            ),
+            continuation: Vec::new(),
+            received: None,
+            at: None,
+        })
+    }
+
+    /// Counts of the lines dropped by the streams so far (see `Stats`).
+    pub fn dropped(&self) -> Stats {
+        self.dropped.lock().unwrap().clone()
+    }
+
+    /// Remembers the line as consumed, so that its stream is resumed after it once relaunched.
+    /// The lines still on their way when the streams stopped are fetched again then.
+    pub fn consumed(&self, log: &ContainerLog) {
+        if let Some(at) = log.at {
+            let key = container_key(&log.namespace, &log.pod, &log.container);
+            record_last_seen(&self.last_seen, &key, at);
+        }
+    }
+
+    /// Number of streams opened so far and in total, while they are being launched.
+    pub fn launch_progress(&self) -> Option<(usize, usize)> {
+        *self.launching.lock().unwrap()
+    }
+
+    /// Client the streams are opened through, e.g. to watch other resources of the cluster.
+    pub fn client(&self) -> Client {
+        self.client.clone()
+    }
+
+    /// Replaces the client, e.g. after it was rebuilt to refresh the credentials.
+    pub fn set_client(&mut self, client: Client) {
+        self.client = client;
+    }
+
+    /// Narrows down the Pods to stream to the ones with the labels.
+    pub fn set_label_selector(&mut self, selector: LabelSelector) {
+        self.label_selector = Some(selector);
+    }
+
+    /// Narrows down the Pods to stream to the ones with the fields.
+    pub fn set_field_selector(&mut self, selector: FieldSelector) {
+        self.field_selector = Some(selector);
+    }
+
+    /// Parameters to list the Pods with, narrowed down by the API server as far as possible.
+    fn list_params(&self) -> ListParams {
+        let mut params = ListParams::default();
+        let fields: Vec<String> = self
+            .pods
+            .field_selector()
+            .into_iter()
+            .chain(self.field_selector.iter().map(|FieldSelector(s)| s.clone()))
+            .collect();
+        if !fields.is_empty() {
+            params = params.fields(&fields.join(","));
+        }
+        if let Some(LabelSelector(selector)) = &self.label_selector {
+            params = params.labels(selector);
+        }
+        params
+    }
+
+    /// Asks the API server to list a Pod with the label and field selectors, if given,
+    /// so that a selector it rejects fails once on connecting rather than on every discovery.
+    /// Namespaces whose Pods can't be listed are skipped.
+    pub async fn check_selectors(&self) -> anyhow::Result<()> {
+        if self.label_selector.is_none() && self.field_selector.is_none() {
+            return Ok(());
+        }
+        let params = self.list_params().limit(1);
+        for namespace in self.get_namespaces().await? {
+            match self.pods_api(namespace.as_deref()).list(&params).await {
+                Ok(_) => return Ok(()),
+                Err(e) if namespace.is_some() && is_forbidden(&e) => continue,
+                Err(kube::Error::Api(resp)) if resp.code == 400 => {
+                    return Err(anyhow::anyhow!(
+                        "the API server rejected the selectors of the Pods: {}",
+                        resp.message
+                    ))
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Narrows down the Pods to stream to the ones with the annotations.
+    pub fn set_annotation_selector(&mut self, selector: AnnotationSelector) {
+        self.annotation_selector = selector;
+    }
+
+    /// Streams only the containers matching the regex of the Pods which have any,
+    /// e.g. the `<app>-log` sidecars which carry the logs of quiet app containers.
+    pub fn set_container_preference(&mut self, preference: Regex) {
+        self.container_preference = Some(preference);
+    }
+
+    /// Accepts the next container states (see `ContainerStateMatcher::cycle`),
+    /// applied when the containers are resolved next. Returns the label of the new states.
+    pub fn cycle_container_states(&self) -> Option<String> {
+        let mut matcher = self.container_state_matcher.lock().unwrap();
+        matcher.cycle();
+        matcher.label()
+    }
+
+    /// Label of the accepted container states, or `None` if all are accepted.
+    pub fn container_states(&self) -> Option<String> {
+        self.container_state_matcher.lock().unwrap().label()
+    }
+
+    /// Sets whether the times compared with the cluster are corrected by the estimated skew.
+    pub fn set_skew_correction(&mut self, correct: bool) {
+        self.skew.lock().unwrap().set_correct(correct);
+    }
+
+    /// Style of the meta of the container identified by `key`, picked from the palette
+    /// the first time and kept for the rest of the session.
+    fn meta_style(&self, key: &str, style: &StyleConfig) -> ContentStyle {
+        *self
+            .meta_styles
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_insert_with(|| meta_style(key, style))
+    }
+
+    /// Resolves the namespaces to look for Pods in.
+    async fn get_namespaces(&self) -> anyhow::Result<Vec<Option<String>>> {
+        match &self.namespaces {
+            NamespaceSelector::Names(names) => Ok(names.iter().cloned().map(Some).collect()),
+            NamespaceSelector::Regex(regex) => Ok(Api::<Namespace>::all(self.client.clone())
+                .list(&ListParams::default())
+                .await?
+                .into_iter()
+                .filter_map(|namespace| namespace.metadata.name)
+                .filter(|name| regex.is_match(name))
+                .map(Some)
+                .collect()),
+            NamespaceSelector::All => Ok(vec![None]),
+        }
+    }
+
+    /// Api of the Pods in the namespace, or across the cluster if `None`.
+    fn pods_api(&self, namespace: Option<&str>) -> Api<Pod> {
+        match namespace {
+            Some(namespace) => Api::namespaced(self.client.clone(), namespace),
+            None => Api::all(self.client.clone()),
+        }
+    }
+
+    /// Retrieves a vector of containers
+    /// that match specific criteria from a list of Pods obtained via the API.
+    ///
+    /// The function operates as follows:
+    /// 1. Initializes an empty vector `ret`.
+    /// 2. Resolves the namespaces, and uses `list` to fetch a list of Pods
+    ///    in each namespace, selected by name and by `label_selector` and `field_selector`.
+    ///    Namespaces whose Pods are not allowed to be listed are reported and skipped.
+    /// 3. For each Pod retrieved, it performs the following checks:
+    ///    - Whether the Pod's name is selected by `pods`,
+    ///      and its annotations by `annotation_selector` (client-side).
+    ///    - Whether the Pod's status exists and if any of the container statuses
+    ///      match specific states defined by `container_state_matcher`.
+    /// 4. For each container that matches the conditions, adds a target of the Pod's namespace,
+    ///    the Pod's name and the container's name to the vector `ret`.
+    /// 5. After checking all Pods and their containers, returns the vector `ret`.
+    ///
+    /// If `--pod-query` matched no Pod, the names closest to it are suggested in a notice.
+    pub async fn get_pod_and_containers(
+        &self,
+        notice_tx: &mpsc::Sender<Notice>,
+    ) -> anyhow::Result<Vec<Target>> {
+        let mut ret = Vec::new();
+        let mut names = Vec::new();
+        let params = self.list_params();
+
+        for namespace in self.get_namespaces().await? {
+            let pods = match self.pods_api(namespace.as_deref()).list(&params).await {
+                Ok(pods) => pods,
+                Err(e) if is_forbidden(&e) && namespace.is_none() => {
+                    return Err(anyhow::anyhow!(
+                        "not allowed to list the Pods across all namespaces: {e}"
+                    ))
+                }
+                Err(e) if is_forbidden(&e) => {
+                    let namespace = namespace.unwrap_or_default();
+                    warn!(namespace, error = %e, "skipped namespace");
+                    let _ = notice_tx.try_send(Notice::warning(format!(
+                        "skipped namespace {namespace}: {e}"
+                    )));
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let found = ret.len();
+            names.extend(pods.iter().filter_map(|pod| pod.metadata.name.clone()));
+            match &namespace {
+                Some(namespace) => self.collect_targets(namespace, pods, &mut ret),
+                // Listed across the cluster, each Pod goes with its own namespace.
+                None => {
+                    for pod in pods {
+                        let namespace = pod.metadata.namespace.clone().unwrap_or_default();
+                        self.collect_targets(&namespace, [pod], &mut ret);
+                    }
+                }
+            }
+            debug!(
+                namespace = namespace.as_deref().unwrap_or("*"),
+                containers = ret.len() - found,
+                "discovered containers"
+            );
+        }
+
+        if !ret.is_empty() {
+            self.suggested.store(false, Ordering::SeqCst);
+        } else if let PodSelector::Regex(regex, _) = &self.pods {
+            if !self.suggested.swap(true, Ordering::SeqCst) {
+                let similar = similar_names(regex.as_str(), &names, 5);
+                let message = if similar.is_empty() {
+                    format!("no Pods matched '{}'", regex.as_str())
+                } else {
+                    format!(
+                        "no Pods matched '{}'; did you mean: {}?",
+                        regex.as_str(),
+                        similar.join(", ")
+                    )
+                };
+                let _ = notice_tx.try_send(Notice::warning(message));
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn collect_targets(
+        &self,
+        namespace: &str,
+        pods: impl IntoIterator<Item = Pod>,
+        ret: &mut Vec<Target>,
+    ) {
+        let container_state_matcher = self.container_state_matcher.lock().unwrap().clone();
+        for pod in pods {
+            let found = ret.len();
+            // Applied once all the containers of the Pod are in, whatever way they got in.
+            let prefer = |ret: &mut Vec<Target>| {
+                if let Some(preference) = &self.container_preference {
+                    prefer_containers(ret, found, preference);
+                }
+            };
+            if !self.pods.matches(&pod.metadata) || !self.annotation_selector.matches(&pod.metadata)
+            {
+                continue;
+            }
+            // The containers of a Pod being deleted are streamed whatever their states,
+            // as their shutdown is what it logs last.
+            let terminating = pod.metadata.deletion_timestamp.as_ref().map(|_| {
+                pod.metadata
+                    .deletion_grace_period_seconds
+                    .unwrap_or(DEFAULT_GRACE_PERIOD)
+            });
+            let accepts = |status: &api::core::v1::ContainerStatus| {
+                terminating.is_some() || container_state_matcher.matches(status)
+            };
+            if terminating.is_some() && self.stop_on_terminating {
+                debug!(
+                    namespace,
+                    pod = pod.metadata.name,
+                    "skipped terminating Pod"
+                );
+                continue;
+            }
+            if let Some(pod_name) = pod.metadata.name {
+                for status in pod
+                    .status
+                    .iter()
+                    .flat_map(|status| status.container_statuses.iter().flatten())
+                    .filter(|status| !accepts(status))
+                {
+                    debug!(
+                        namespace,
+                        pod = pod_name,
+                        container = status.name,
+                        "rejected container by its state"
+                    );
+                }
+                let pod_labels = pod.metadata.labels.unwrap_or_default();
+                let labels: HashMap<String, String> = self
+                    .meta_format
+                    .labels()
+                    .filter_map(|label| {
+                        pod_labels
+                            .get(label)
+                            .map(|value| (label.to_string(), value.clone()))
+                    })
+                    .collect();
+                let container_statuses = pod
+                    .status
+                    .and_then(|status| status.container_statuses)
+                    .unwrap_or_default();
+                for container in container_statuses.iter().filter(|status| accepts(status)) {
+                    debug!(
+                        namespace,
+                        pod = pod_name,
+                        container = container.name,
+                        "accepted container"
+                    );
+                    ret.push(Target {
+                        namespace: namespace.to_string(),
+                        pod: pod_name.clone(),
+                        container: container.name.clone(),
+                        restarts: container.restart_count,
+                        state: match &container.state {
+                            Some(state) if state.running.is_some() => "running",
+                            Some(state) if state.terminated.is_some() => "terminated",
+                            Some(state) if state.waiting.is_some() => "waiting",
+                            _ => "unknown",
+                        },
+                        labels: labels.clone(),
+                        preferred_over: Vec::new(),
+                        terminating,
+                    });
+                }
+                // Containers whose statuses are not reported yet are attached as they are,
+                // except when readiness is required since they can't be known to be ready.
+                if container_state_matcher.ready_only {
+                    prefer(ret);
+                    continue;
+                }
+                if let Some(containers) = pod.spec.map(|spec| spec.containers) {
+                    for container in containers.into_iter().filter(|container| {
+                        !container_statuses
+                            .iter()
+                            .any(|status| status.name == container.name)
+                    }) {
+                        ret.push(Target {
+                            namespace: namespace.to_string(),
+                            pod: pod_name.clone(),
+                            container: container.name,
+                            restarts: 0,
+                            state: "unknown",
+                            labels: labels.clone(),
+                            preferred_over: Vec::new(),
+                            terminating,
+                        });
+                    }
+                }
+                prefer(ret);
+            }
+        }
+    }
+
+    /// Describes the only container to stream, e.g. for a header row,
+    /// as `pod/container on node · state · up 1h2m · 3 restarts`.
+    /// Returns `None` if there isn't exactly one container.
+    pub async fn describe_single(
+        &self,
+        notice_tx: &mpsc::Sender<Notice>,
+    ) -> anyhow::Result<Option<String>> {
+        let targets = self.get_pod_and_containers(notice_tx).await?;
+        let [target] = targets.as_slice() else {
+            return Ok(None);
+        };
+        let pod = Api::<Pod>::namespaced(self.client.clone(), &target.namespace)
+            .get(&target.pod)
+            .await?;
+        let node = pod
+            .spec
+            .and_then(|spec| spec.node_name)
+            .unwrap_or_else(|| String::from("unscheduled"));
+        let started_at = pod
+            .status
+            .and_then(|status| status.container_statuses)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|status| status.name == target.container)
+            .and_then(|status| status.state)
+            .and_then(|state| state.running)
+            .and_then(|running| running.started_at);
+        let mut description = format!(
+            "{}/{} on {} · {}",
+            target.pod, target.container, node, target.state
+        );
+        if let Some(started_at) = started_at {
+            let now = Utc::now() + self.skew.lock().unwrap().offset();
+            let uptime = (now - started_at.0).num_seconds().max(0);
+            description.push_str(&format!(" · up {}", format_uptime(uptime)));
+        }
+        description.push_str(&format!(" · {} restarts", target.restarts));
+        Ok(Some(description))
+    }
+
+    /// Renders the `kubectl logs` commands equivalent to the streams currently open,
+    /// one per container, in the order of their keys.
+    pub fn kubectl_commands(&self, context: &str) -> Vec<String> {
+        self.opened
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(namespace, pod, params)| kubectl_command(context, namespace, pod, params))
+            .collect()
+    }
+
+    /// Watches the Events of the Pods in the namespaces of the streams,
+    /// narrowed down to the Pod by the API server if it is selected by name.
+    /// Returns the number of watches, each of which lists the past Events before the new ones.
+    /// The Events of the Pods not streamed are left to `is_streamed`.
+    pub async fn watch_pod_events(
+        &self,
+    ) -> anyhow::Result<(
+        usize,
+        BoxStream<'static, Result<watcher::Event<Event>, watcher::Error>>,
+    )> {
+        let mut fields = String::from("involvedObject.kind=Pod");
+        if let PodSelector::Name(name) = &self.pods {
+            fields.push_str(&format!(",involvedObject.name={name}"));
+        }
+        let config = watcher::Config::default().fields(&fields);
+        let namespaces = self.get_namespaces().await?;
+        let watches = namespaces.len();
+        let events = futures::stream::select_all(namespaces.iter().map(|namespace| {
+            let api = match namespace {
+                Some(namespace) => Api::<Event>::namespaced(self.client.clone(), namespace),
+                None => Api::<Event>::all(self.client.clone()),
+            };
+            watcher(api, config.clone()).default_backoff().boxed()
+        }));
+        Ok((watches, events.boxed()))
+    }
+
+    /// Whether a stream of a container of the Pod was opened.
+    pub fn is_streamed(&self, namespace: &str, pod: &str) -> bool {
+        self.opened
+            .lock()
+            .unwrap()
+            .values()
+            .any(|(opened_namespace, opened_pod, _)| {
+                opened_namespace == namespace && opened_pod == pod
+            })
+    }
+
+    /// Parameters the stream of the target is opened with next,
+    /// resuming after its last line if it was streamed before.
+    pub fn log_params(&self, target: &Target, discovered: bool) -> LogParams {
+        let mode = match self.last_seen.lock().unwrap().get(&self.key(target)) {
+            Some(&last_seen) => LaunchMode::Resumed(last_seen),
+            None if discovered => LaunchMode::Discovered,
+            None => LaunchMode::Startup,
+        };
+        effective_log_params(
+            &target.container,
+            self.since_seconds,
+            self.discovery_since_seconds,
+            mode,
+        )
+    }
+
+    /// Initiates log streams for pods and containers that match specified criteria.
+    ///
+    /// If an idle timeout is given, a stream which yields nothing for that long is reopened,
+    /// since a connection dropped silently on a flaky network looks just like a quiet container.
+    /// The reopened stream looks back to the last received line so that no line is lost.
+    ///
+    /// Up to `settings.max_concurrent_streams` streams are opened at once,
+    /// counted in `launch_progress` until all of them are opened or skipped.
+    /// The setups still pending are dropped on cancellation, and when one fails.
+    ///
+    /// The Events of the streamed Pods are watched as well once the streams are opened,
+    /// if a watcher is given, their backlog merged by time with those of the streams.
+    pub async fn launch_log_streams(
+        &self,
+        log_stream_tx: mpsc::Sender<ContainerLog>,
+        notice_tx: mpsc::Sender<Notice>,
+        settings: StreamSettings,
+        style: &StyleConfig,
+        canceled: CancellationToken,
+        events: Option<&events::Watcher>,
+    ) -> anyhow::Result<FuturesUnordered<JoinHandle<Result<(), anyhow::Error>>>> {
+        let futures = FuturesUnordered::new();
+        let targets = self.get_pod_and_containers(&notice_tx).await?;
+        let settings = StreamSettings {
+            single: settings.single && targets.len() == 1,
+            ..settings
+        };
+
+        let backfill = settings
+            .backfill_window
+            .filter(|window| !window.is_zero())
+            .map(|window| {
+                // The Events are one more stream to wait for.
+                let streams = targets.len() + usize::from(events.is_some());
+                let backfill = Arc::new(Backfill::new(streams));
+                let flushing = (backfill.clone(), log_stream_tx.clone(), canceled.clone());
+                futures.push(tokio::spawn(async move {
+                    flushing.0.flush(&flushing.1, window, &flushing.2).await;
+                    Ok(())
+                }));
+                backfill
+            });
+
+        for target in targets.iter() {
+            if let Some(marker) = self.terminating_marker(target, style) {
+                let _ = log_stream_tx.send(marker).await;
+            }
+        }
+
+        let launched = launch_concurrently(
+            targets.len(),
+            settings.max_concurrent_streams,
+            &self.launching,
+            &canceled,
+            |index| {
+                let (log_stream_tx, notice_tx) = (log_stream_tx.clone(), notice_tx.clone());
+                let backfill = backfill.clone().map(|backfill| (backfill, index));
+                let (target, canceled) = (&targets[index], canceled.clone());
+                async move {
+                    let launched = self
+                        .launch_log_stream(
+                            target,
+                            log_stream_tx,
+                            notice_tx,
+                            settings,
+                            style,
+                            canceled,
+                            backfill.clone(),
+                        )
+                        .await;
+                    // A skipped stream has no backlog to wait for.
+                    if let (Ok(None), Some((backfill, _))) = (&launched, &backfill) {
+                        backfill.caught_up().await;
+                    }
+                    launched
+                }
+            },
+        )
+        .await?;
+        // Only the containers actually streamed are known to the watch,
+        // so that the skipped ones (e.g. still being created) are attached once they run.
+        let mut known = HashSet::new();
+        for (index, future) in launched {
+            known.insert(self.key(&targets[index]));
+            futures.push(future);
+        }
+
+        if let Some(events) = events.cloned() {
+            let (streamer, log_stream_tx, notice_tx) =
+                (self.clone(), log_stream_tx.clone(), notice_tx.clone());
+            let (style, canceled) = (style.clone(), canceled.clone());
+            let backfill = backfill.map(|backfill| (backfill, targets.len()));
+            futures.push(tokio::spawn(async move {
+                events
+                    .run(
+                        streamer,
+                        log_stream_tx,
+                        notice_tx,
+                        &style,
+                        canceled,
+                        backfill,
+                    )
+                    .await;
+                Ok(())
+            }));
+        }
+
+        if settings.watch_pods {
+            futures.push(tokio::spawn(self.clone().watch_new_pods(
+                known,
+                log_stream_tx,
+                notice_tx,
+                settings,
+                style.clone(),
+                canceled,
+            )));
+        }
+
+        Ok(futures)
+    }
+
+    /// Watches the Pods for the ones appearing after launch, e.g. with a rollout,
+    /// a scale-up or a CronJob run, and attaches streams to their accepted containers
+    /// until canceled, each announced with a marker line.
+    ///
+    /// `known` are the keys of the containers whose streams were opened by the launch.
+    /// A container is attached once it runs (or already ran), since a waiting one can't be streamed,
+    /// and again if its Pod is deleted and created anew under the same name.
+    /// A watch which isn't allowed is reported once and retried with a backoff.
+    async fn watch_new_pods(
+        self,
+        mut known: HashSet<String>,
+        log_stream_tx: mpsc::Sender<ContainerLog>,
+        notice_tx: mpsc::Sender<Notice>,
+        settings: StreamSettings,
+        style: StyleConfig,
+        canceled: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let params = self.list_params();
+        let mut config = watcher::Config::default();
+        if let Some(fields) = &params.field_selector {
+            config = config.fields(fields);
+        }
+        if let Some(labels) = &params.label_selector {
+            config = config.labels(labels);
+        }
+        let mut events =
+            futures::stream::select_all(self.get_namespaces().await?.iter().map(|namespace| {
+                watcher(self.pods_api(namespace.as_deref()), config.clone())
+                    .default_backoff()
+                    .boxed()
+            }));
+        let settings = StreamSettings {
+            single: false,
+            discovered: true,
+            ..settings
+        };
+        let mut streams = FuturesUnordered::new();
+        let mut reported = false;
+
+        loop {
+            let event = tokio::select! {
+                _ = canceled.cancelled() => break,
+                Some(_) = streams.next(), if !streams.is_empty() => continue,
+                event = events.next() => event,
+            };
+            let pod = match event {
+                // The Pods listed when the watch (re)starts come as well as the changed ones.
+                Some(Ok(watcher::Event::Apply(pod) | watcher::Event::InitApply(pod))) => pod,
+                Some(Ok(watcher::Event::Init | watcher::Event::InitDone)) => continue,
+                Some(Ok(watcher::Event::Delete(pod))) => {
+                    let namespace = pod.metadata.namespace.unwrap_or_default();
+                    let prefix =
+                        format!("{}/{} ", namespace, pod.metadata.name.unwrap_or_default());
+                    known.retain(|key| !key.starts_with(&prefix));
+                    continue;
+                }
+                Some(Err(e)) => {
+                    warn!(error = %e, "failed to watch Pods");
+                    if !reported {
+                        reported = true;
+                        let _ = notice_tx.try_send(Notice::warning(format!(
+                            "failed to watch for new Pods: {e}"
+                        )));
+                    }
+                    continue;
+                }
+                None => break,
+            };
+            let mut targets = Vec::new();
+            let namespace = pod.metadata.namespace.clone().unwrap_or_default();
+            self.collect_targets(&namespace, [pod], &mut targets);
+            for target in targets.iter() {
+                let key = self.key(target);
+                if known.contains(&key) || matches!(target.state, "waiting" | "unknown") {
+                    continue;
+                }
+                let Some(stream) = self
+                    .launch_log_stream(
+                        target,
+                        log_stream_tx.clone(),
+                        notice_tx.clone(),
+                        settings,
+                        &style,
+                        canceled.clone(),
+                        None,
+                    )
+                    .await?
+                else {
+                    continue;
+                };
+                info!(container = key, "attached stream: new Pod");
+                let _ = log_stream_tx
+                    .send(ContainerLog {
+                        namespace: target.namespace.clone(),
+                        pod: target.pod.clone(),
+                        container: target.container.clone(),
+                        restarts: target.restarts,
+                        stream: None,
+                        kind: LogKind::Marker,
+                        meta: self.meta(target, &style),
+                        body: StyledGraphemes::from_str("── attached: new Pod ──", style.dim),
+                        continuation: Vec::new(),
+                        received: None,
+                        at: None,
+                    })
+                    .await;
+                known.insert(key);
+                streams.push(stream);
+            }
+        }
+
+        while streams.next().await.is_some() {}
+        Ok(())
+    }
+
+    /// Keeps the log streams in line with the accepted container states until canceled,
+    /// re-checking the Pods every `settings.enforce_interval`.
+    ///
+    /// A container entering the accepted states gets a stream, resuming after its last line,
+    /// and one leaving them has its stream detached with a marker line.
+    /// A stream which ended while its container is still accepted is relaunched.
+    /// Since the states are only sampled, a container flapping faster than the interval
+    /// is seen at most once per check and never leaves more than one stream behind.
+    pub async fn enforce_log_streams(
+        &self,
+        log_stream_tx: mpsc::Sender<ContainerLog>,
+        notice_tx: mpsc::Sender<Notice>,
+        settings: StreamSettings,
+        style: &StyleConfig,
+        canceled: CancellationToken,
+    ) -> anyhow::Result<()> {
+        // Streams by key, with the token to detach them and the meta for their marker lines.
+        let mut streams: HashMap<
+            String,
+            (
+                CancellationToken,
+                JoinHandle<anyhow::Result<()>>,
+                ContainerLog,
+            ),
+        > = HashMap::new();
+        let mut interval =
+            tokio::time::interval(settings.enforce_interval.unwrap_or(Duration::from_secs(5)));
+        let mut launching = true;
+        let marker = |mut log: ContainerLog, text: &str| {
+            log.body = StyledGraphemes::from_str(format!("── {text} ──"), style.dim);
+            log
+        };
+
+        while !canceled.is_cancelled() {
+            tokio::select! {
+                _ = canceled.cancelled() => break,
+                _ = interval.tick() => (),
+            }
+            let targets = match self.get_pod_and_containers(&notice_tx).await {
+                Ok(targets) => targets,
+                Err(e) if is_unauthorized(&e) => return Err(e),
+                Err(e) => {
+                    let _ = notice_tx.try_send(Notice::warning(format!(
+                        "failed to check container states: {e}"
+                    )));
+                    continue;
+                }
+            };
+
+            for target in targets.iter() {
+                if let Some(marker) = self.terminating_marker(target, style) {
+                    let _ = log_stream_tx.send(marker).await;
+                }
+            }
+
+            let ended: HashMap<String, bool> = streams
+                .iter()
+                .map(|(key, (_, stream, _))| (key.clone(), stream.is_finished()))
+                .collect();
+            let accepted: Vec<(String, bool)> = targets
+                .iter()
+                .map(|target| (self.key(target), target.terminating.is_some()))
+                .collect();
+            let reconciliation = reconcile(&ended, &accepted);
+            for key in reconciliation.forget {
+                streams.remove(&key);
+            }
+            for key in reconciliation.detach {
+                let (detach, _, log) = streams.remove(&key).unwrap();
+                detach.cancel();
+                info!(container = key, "detached stream: left the accepted states");
+                let _ = log_stream_tx
+                    .send(marker(log, "detached: left the accepted states"))
+                    .await;
+            }
+
+            let settings = StreamSettings {
+                single: settings.single && targets.len() == 1,
+                ..settings
+            };
+            for index in reconciliation.launch {
+                let target = &targets[index];
+                let key = self.key(target);
+                let log = ContainerLog {
+                    namespace: target.namespace.clone(),
+                    pod: target.pod.clone(),
+                    container: target.container.clone(),
+                    restarts: target.restarts,
+                    stream: None,
+                    kind: LogKind::Marker,
+                    meta: self.meta(target, style),
+                    body: StyledGraphemes::default(),
+                    continuation: Vec::new(),
+                    received: None,
+                    at: None,
+                };
+                let detach = canceled.child_token();
+                let settings = StreamSettings {
+                    discovered: !launching && !streams.contains_key(&key),
+                    ..settings
+                };
+                let Some(stream) = self
+                    .launch_log_stream(
+                        target,
+                        log_stream_tx.clone(),
+                        notice_tx.clone(),
+                        settings,
+                        style,
+                        detach.clone(),
+                        None,
+                    )
+                    .await?
+                else {
+                    continue;
+                };
+                // Streams attached at launch or relaunched go without a marker.
+                if !launching && !streams.contains_key(&key) {
+                    info!(
+                        container = key,
+                        "attached stream: entered the accepted states"
+                    );
+                    let _ = log_stream_tx
+                        .send(marker(log.clone(), "attached: entered the accepted states"))
+                        .await;
+                }
+                streams.insert(key, (detach, stream, log));
+            }
+            launching = false;
+        }
+
+        for (_, stream, _) in streams.into_values() {
+            let _ = stream.await;
+        }
+        Ok(())
+    }
+
+    /// Identity of the container, keying its stream, meta style, last line and recent lines.
+    /// The namespace is always part of it, since Pods of the same name (e.g. copies of a chart
+    /// installed per team) are distinct containers.
+    fn key(&self, target: &Target) -> String {
+        container_key(&target.namespace, &target.pod, &target.container)
+    }
+
+    /// Name of the container shown in notices, without the namespace if only one is streamed.
+    fn name(&self, target: &Target) -> String {
+        if self.namespaces.is_multi() {
+            self.key(target)
+        } else {
+            format!("{} {}", target.pod, target.container)
+        }
+    }
+
+    /// Renders the meta of the target in the style assigned to it for the session,
+    /// behind the badge of its namespace while streaming multiple namespaces.
+    fn meta(&self, target: &Target, style: &StyleConfig) -> StyledGraphemes {
+        let meta_style = self.meta_style(&self.key(target), style);
+        let meta = StyledGraphemes::from_str(self.meta_format.render(target), meta_style);
+        if !self.namespaces.is_multi() {
+            return meta;
+        }
+        StyledGraphemes::from_iter([
+            &namespace_badge(&target.namespace, style),
+            &StyledGraphemes::from(" "),
+            &meta,
+        ])
+    }
+
+    /// Initiates the log stream of the target, or returns `None` if it can't be streamed.
+    #[allow(clippy::too_many_arguments)]
+    async fn launch_log_stream(
+        &self,
+        target: &Target,
+        log_stream_tx: mpsc::Sender<ContainerLog>,
+        notice_tx: mpsc::Sender<Notice>,
+        settings: StreamSettings,
+        style: &StyleConfig,
+        canceled: CancellationToken,
+        backfill: Option<(Arc<Backfill>, usize)>,
+    ) -> anyhow::Result<Option<JoinHandle<Result<(), anyhow::Error>>>> {
+        let Target {
+            namespace,
+            pod,
+            container,
+            restarts,
+            ..
+        } = target;
+        let key = self.key(target);
+        let name = self.name(target);
+
+        // A container which can't be streamed (e.g. still being created or failing to pull
+        // its image) is reported and skipped so that it doesn't hold back the others.
+        // Rejected credentials still abort the launch so that the client can be rebuilt.
+        let api = Api::<Pod>::namespaced(self.client.clone(), namespace);
+        let log_params = self.log_params(target, settings.discovered);
+        let mut resume_after = log_params.since_time;
+        let mut pod_log_stream = match api.log_stream(pod, &log_params).await {
+            Ok(stream) => stream.lines(),
+            Err(e) => {
+                let e = anyhow::Error::from(e);
+                if is_unauthorized(&e) {
+                    return Err(e);
+                }
+                warn!(container = key, error = %e, "skipped stream");
+                let _ = notice_tx.try_send(Notice::warning(format!("{name}: skipped: {e}")));
+                return Ok(None);
+            }
+        };
+        info!(container = key, since_time = ?log_params.since_time, "opened stream");
+        let opened = self.opened.clone();
+        opened.lock().unwrap().insert(
+            key.clone(),
+            (namespace.clone(), pod.clone(), log_params.clone()),
+        );
+
+        let unauthorized = self.unauthorized.clone();
+        let recent = self.recent.clone();
+        let dropped = self.dropped.clone();
+        let skew = self.skew.clone();
+        let termination_fetched = self.termination_fetched.clone();
+        let terminating = self.terminating.clone();
+        // The marker of the end of the stream tells the container apart even with --single.
+        let marker_meta = self.meta(target, style);
+        // Styled once per stream rather than per line.
+        let meta = if settings.single {
+            StyledGraphemes::default()
+        } else {
+            marker_meta.clone()
+        };
+        let body_style = style.body;
+        let stderr_style = style.stderr;
+        let termination_style = style.error;
+        let dim_style = style.dim;
+        let namespace = namespace.clone();
+        let pod = pod.clone();
+        let container = container.clone();
+        let restarts = *restarts;
+
+        Ok(Some(tokio::spawn(async move {
+            let to_log = |(stream, mut line): (Option<LogStream>, String),
+                          at: Option<DateTime<Utc>>| {
+                // Normalized once the chunks are merged, as splitting them is told by their raw size,
+                // but before anything else looks at the line.
+                if normalize_line_end(&mut line, settings.keep_trailing_whitespace) {
+                    *dropped
+                        .lock()
+                        .unwrap()
+                        .normalized
+                        .entry(name.clone())
+                        .or_default() += 1;
+                }
+                anyhow::Ok(ContainerLog {
+                    namespace: namespace.clone(),
+                    pod: pod.clone(),
+                    container: container.clone(),
+                    restarts,
+                    stream,
+                    kind: LogKind::Log,
+                    meta: meta.clone(),
+                    body: StyledGraphemes::from_str(
+                        if settings.escape_control {
+                            escape_control(line)
+                        } else {
+                            sanitize(line)?
+                        },
+                        match stream {
+                            Some(LogStream::Stderr) => stderr_style,
+                            _ => body_style,
+                        },
+                    ),
+                    continuation: Vec::new(),
+                    // Stamped here rather than by the consumer,
+                    // so that the lines waiting in the outbox keep the time they arrived.
+                    received: Some(Local::now()),
+                    // Time of the last chunk read, which the line ends with.
+                    at,
+                })
+            };
+            let grouped = |group: &mut Option<ContainerLog>, log: ContainerLog| {
+                // Checked once sanitized, so that lines of only escape sequences are dropped too.
+                if settings.skip_empty && log.is_blank() {
+                    *dropped
+                        .lock()
+                        .unwrap()
+                        .blank
+                        .entry(name.clone())
+                        .or_default() += 1;
+                    return None;
+                }
+                if settings.group_stacktraces {
+                    group_stacktrace(group, log)
+                } else {
+                    Some(log)
+                }
+            };
+            let mut last_received = Instant::now();
+            // Whether the backlog was received, after which the lines are live.
+            let mut caught_up = false;
+            let mut merger = LineMerger::new(settings.line_merge);
+            // Line which may be followed by a stack trace, waiting for its frames.
+            let mut group: Option<ContainerLog> = None;
+            let mut outbox = Outbox::new(
+                settings.stream_buffer,
+                settings.overflow,
+                name.clone(),
+                dropped.clone(),
+                canceled.clone(),
+            );
+            // Whether the stream ended by itself, e.g. with its container terminated.
+            let mut ended = false;
+            // Why the stream failed, if it did.
+            let mut failed: Option<String> = None;
+            loop {
+                // Wake up without a line only when something waits on time:
+                // the end of the backlog, a split line or a stack trace to flush, or the idle timeout.
+                // A quiet stream otherwise sleeps until its next line or the cancellation.
+                let deadline = [
+                    (!caught_up).then_some(settings.backlog_quiet_time),
+                    merger.window(),
+                    group.as_ref().map(|_| STACKTRACE_WINDOW),
+                    settings.idle_timeout,
+                ]
+                .into_iter()
+                .flatten()
+                .min()
+                .map(|after| last_received + after);
+                let ret = tokio::select! {
+                    _ = canceled.cancelled() => break,
+                    // Room in the channel for the lines waiting in the outbox.
+                    permit = log_stream_tx.reserve(), if !outbox.is_empty() => {
+                        outbox.forward(permit?);
+                        continue;
+                    }
+                    ret = pod_log_stream.next() => Some(ret),
+                    _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => None,
+                };
+                let Some(ret) = ret else {
+                    if !caught_up && last_received.elapsed() >= settings.backlog_quiet_time {
+                        caught_up = true;
+                        if let Some((backfill, _)) = &backfill {
+                            backfill.caught_up().await;
+                        }
+                    }
+                    if let Some(line) = merger.expire(last_received.elapsed()) {
+                        if let Some(log) = grouped(&mut group, to_log(line, resume_after)?) {
+                            send(
+                                &log_stream_tx,
+                                &mut outbox,
+                                backfill.as_ref(),
+                                resume_after,
+                                log,
+                            )
+                            .await?;
+                        }
+                    }
+                    if last_received.elapsed() >= STACKTRACE_WINDOW {
+                        if let Some(log) = group.take() {
+                            send(
+                                &log_stream_tx,
+                                &mut outbox,
+                                backfill.as_ref(),
+                                resume_after,
+                                log,
+                            )
+                            .await?;
+                        }
+                    }
+                    if settings
+                        .idle_timeout
+                        .is_some_and(|idle| last_received.elapsed() >= idle)
+                    {
+                        let log_params = match resume_after {
+                            Some(_) => LogParams {
+                                since_seconds: None,
+                                since_time: resume_after,
+                                ..log_params.clone()
+                            },
+                            None => LogParams {
+                                since_seconds: Some(last_received.elapsed().as_secs() as i64 + 1),
+                                ..log_params.clone()
+                            },
+                        };
+                        info!(container = key, "reopening idle stream");
+                        match api.log_stream(&pod, &log_params).await {
+                            Ok(stream) => pod_log_stream = stream.lines(),
+                            Err(e) => {
+                                warn!(container = key, error = %e, "failed to reopen idle stream");
+                                let _ = notice_tx.try_send(Notice::error(format!(
+                                    "{name}: failed to reopen idle stream: {e}"
+                                )));
+                                failed = Some(format!("failed to reopen idle stream: {e}"));
+                                break;
+                            }
+                        }
+                        last_received = Instant::now();
+                    }
+                    continue;
+                };
+
+                match ret {
+                    Some(Ok(chunk)) => {
+                        last_received = Instant::now();
+                        let (at, chunk) = split_timestamp(chunk);
+                        if let Some(at) = at {
+                            // Skip the lines already received before the stream was resumed,
+                            // since `since_time` only has a precision of seconds.
+                            if is_replayed(
+                                &recent,
+                                &key,
+                                settings.dedup_window,
+                                resume_after,
+                                at,
+                                &chunk,
+                            ) {
+                                debug!(container = key, "dropped replayed line");
+                                *dropped
+                                    .lock()
+                                    .unwrap()
+                                    .replayed
+                                    .entry(name.clone())
+                                    .or_default() += 1;
+                                continue;
+                            }
+                            resume_after = Some(at);
+                            if caught_up {
+                                let warning = skew.lock().unwrap().observe(at, Utc::now());
+                                if let Some(warning) = warning {
+                                    info!(warning, "estimated clock skew");
+                                    let _ = notice_tx.try_send(Notice::warning(warning));
+                                }
+                            }
+                        }
+                        let (stream, chunk) = if settings.raw_cri {
+                            split_cri_prefix(chunk)
+                        } else {
+                            (None, chunk)
+                        };
+                        let Some(line) = merger.push(stream, chunk) else {
+                            continue;
+                        };
+                        if let Some(log) = grouped(&mut group, to_log(line, resume_after)?) {
+                            send(
+                                &log_stream_tx,
+                                &mut outbox,
+                                backfill.as_ref(),
+                                resume_after,
+                                log,
+                            )
+                            .await?;
+                        }
+                    }
+                    // Notices are sent without waiting
+                    // so that a full notice channel never holds back the stream task.
+                    Some(Err(e)) => {
+                        let e = anyhow::Error::from(e);
+                        if is_unauthorized(&e) {
+                            unauthorized.cancel();
+                        }
+                        warn!(container = key, error = %e, "stream failed");
+                        let _ = notice_tx
+                            .try_send(Notice::error(format!("{name}: stream failed: {e}")));
+                        failed = Some(format!("stream failed: {e}"));
+                        break;
+                    }
+                    None => {
+                        info!(container = key, "stream ended");
+                        let _ =
+                            notice_tx.try_send(Notice::warning(format!("{name}: stream ended")));
+                        ended = true;
+                        break;
+                    }
+                }
+            }
+            // Don't lose the head of a split line or a stack trace when the stream is over.
+            if let Some(line) = merger.take() {
+                if let Some(log) = grouped(&mut group, to_log(line, resume_after)?) {
+                    let _ = send(
+                        &log_stream_tx,
+                        &mut outbox,
+                        backfill.as_ref(),
+                        resume_after,
+                        log,
+                    )
+                    .await;
+                }
+            }
+            if let Some(log) = group {
+                let _ = send(
+                    &log_stream_tx,
+                    &mut outbox,
+                    backfill.as_ref(),
+                    resume_after,
+                    log,
+                )
+                .await;
+            }
+            // The termination message is often the only place the final panic of a crashing app lands,
+            // so it follows the last lines of the container as a block of its own.
+            // Fetched on a best-effort basis: a failure is logged, never fatal to the stream.
+            let due = ended && {
+                let mut fetched = termination_fetched.lock().unwrap();
+                let due = fetched
+                    .get(&key)
+                    .is_none_or(|at| at.elapsed() >= TERMINATION_FETCH_INTERVAL);
+                if due {
+                    fetched.insert(key.clone(), Instant::now());
+                }
+                due
+            };
+            let fetched = if due {
+                tokio::select! {
+                    _ = canceled.cancelled() => None,
+                    fetched = async {
+                        tokio::time::sleep(TERMINATION_SETTLE).await;
+                        api.get(&pod).await
+                    } => Some(fetched),
+                }
+            } else {
+                None
+            };
+            // The Pod is known to be deleted if seen terminating, or gone when fetched.
+            let mut deleted = terminating
+                .lock()
+                .unwrap()
+                .contains(&(namespace.clone(), pod.clone()));
+            let message = match fetched {
+                Some(Ok(fetched)) => termination_message(&fetched, &container),
+                Some(Err(kube::Error::Api(resp))) if resp.code == 404 => {
+                    deleted = true;
+                    None
+                }
+                Some(Err(e)) => {
+                    warn!(container = key, error = %e, "failed to fetch termination message");
+                    None
+                }
+                None => None,
+            };
+            if let Some(message) = message {
+                info!(container = key, "termination message");
+                let block = std::iter::once(String::from("── termination message ──"))
+                    .chain(message.trim_end().lines().map(String::from));
+                for line in block {
+                    let log = ContainerLog {
+                        namespace: namespace.clone(),
+                        pod: pod.clone(),
+                        container: container.clone(),
+                        restarts,
+                        stream: None,
+                        kind: LogKind::Termination,
+                        meta: meta.clone(),
+                        body: StyledGraphemes::from_str(sanitize(line)?, termination_style),
+                        continuation: Vec::new(),
+                        received: Some(Local::now()),
+                        at: None,
+                    };
+                    let _ = send(
+                        &log_stream_tx,
+                        &mut outbox,
+                        backfill.as_ref(),
+                        resume_after,
+                        log,
+                    )
+                    .await;
+                }
+            }
+            // A stream which went away by itself ends with a marker line, kept in the queue like
+            // any other line, so that a quiet container isn't mistaken for a live one.
+            // The streams detached or canceled on purpose go without it.
+            let end = match failed {
+                Some(failed) => Some(failed),
+                None if ended && deleted => Some(String::from("stream ended (pod deleted)")),
+                None if ended => Some(String::from("stream ended")),
+                None => None,
+            };
+            if let Some(end) = end {
+                let log = ContainerLog {
+                    namespace: namespace.clone(),
+                    pod: pod.clone(),
+                    container: container.clone(),
+                    restarts,
+                    stream: None,
+                    kind: LogKind::Marker,
+                    meta: marker_meta,
+                    body: StyledGraphemes::from_str(format!("── {} ──", sanitize(end)?), dim_style),
+                    continuation: Vec::new(),
+                    received: Some(Local::now()),
+                    at: None,
+                };
+                let _ = send(
+                    &log_stream_tx,
+                    &mut outbox,
+                    backfill.as_ref(),
+                    resume_after,
+                    log,
+                )
+                .await;
+            }
+            let _ = outbox.flush(&log_stream_tx).await;
+            if let (false, Some((backfill, _))) = (caught_up, &backfill) {
+                backfill.caught_up().await;
+            }
+            opened.lock().unwrap().remove(&key);
+            debug!(container = key, "closed stream");
+            Ok(())
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use promkit::crossterm::style::Color;
+
+    use super::*;
+    use crate::allocations;
+
+    #[test]
+    fn sanitize_is_equivalent_to_replacing_and_stripping() {
+        for line in [
+            "plain line",
+            "tab\tseparated\tcolumns",
+            "multi\nline",
+            "\x1b[31merror\x1b[0m: failed",
+            "\x1b[1m\tbold\x1b[22m and\ttabs\n",
+            "ünïcödé 日本語 👩‍👩‍👧",
+            "",
+        ] {
+            assert_eq!(
+                sanitize(line.to_string()).unwrap(),
+                strip_ansi_escapes::strip_str(line.replace(['\n', '\t'], " ")),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn sanitize_keeps_the_plain_lines_as_they_are() {
+        let line = String::from("GET /health 200 tab\there");
+        let ptr = line.as_ptr();
+        let sanitized = sanitize(line).unwrap();
+        assert_eq!(sanitized, "GET /health 200 tab here");
+        // Not reallocated.
+        assert_eq!(sanitized.as_ptr(), ptr);
+    }
+
+    fn response(code: u16) -> kube::core::ErrorResponse {
+        kube::core::ErrorResponse {
+            status: String::from("Failure"),
+            message: String::from("rejected"),
+            reason: String::from(if code == 401 {
+                "Unauthorized"
+            } else {
+                "Forbidden"
+            }),
+            code,
+        }
+    }
+
+    #[test]
+    fn rejected_credentials_are_unauthorized() {
+        let api = |code| anyhow::Error::new(kube::Error::Api(response(code)));
+        assert!(is_unauthorized(&api(401)));
+        // Allowed to authenticate, but not to do that.
+        assert!(!is_unauthorized(&api(403)));
+        assert!(is_unauthorized(&anyhow::Error::new(kube::Error::Auth(
+            kube::client::AuthError::ExecPluginFailed
+        ))));
+
+        // Ending a log stream, as its body is read through an I/O error.
+        let stream = |code| anyhow::Error::new(std::io::Error::other(response(code)));
+        assert!(is_unauthorized(&stream(401)));
+        assert!(!is_unauthorized(&stream(403)));
+        assert!(is_unauthorized(
+            &stream(401).context("failed to read the log stream")
+        ));
+        assert!(!is_unauthorized(&anyhow::anyhow!("connection reset")));
+    }
+
+    /// Allocations of the body and the meta of 1,000 received lines,
+    /// replacing, stripping and styling the meta per line as before,
+    /// and sanitizing in place with the meta styled once per stream as now.
+    #[test]
+    fn received_lines_allocate_less_than_replacing_and_stripping_them() {
+        let style = StyleBuilder::new().fgc(Color::Reset).build();
+        let received = || -> Vec<String> {
+            (0..1_000)
+                .map(|line| format!("GET /orders/{line} 200 12ms"))
+                .collect()
+        };
+        let (lines, mut rows) = (received(), Vec::with_capacity(1_000));
+        let before = allocations::count(|| {
+            for line in lines {
+                let escaped = strip_ansi_escapes::strip_str(line.replace(['\n', '\t'], " "));
+                rows.push((
+                    StyledGraphemes::from_str("api-0 api", style),
+                    StyledGraphemes::from_str(&escaped, style),
+                ));
+            }
+        });
+        let (lines, mut rows) = (received(), Vec::with_capacity(1_000));
+        let meta = StyledGraphemes::from_str("api-0 api", style);
+        let after = allocations::count(|| {
+            for line in lines {
+                rows.push((
+                    meta.clone(),
+                    StyledGraphemes::from_str(sanitize(line).unwrap(), style),
+                ));
+            }
+        });
+        // Half of them, the others growing the styled body.
+        assert!(after * 2 <= before, "{after} of {before}");
+    }
+
+    #[test]
+    fn container_states_parse() {
+        use clap::ValueEnum;
+        let states: Vec<ContainerState> = ["all", "running", "terminated", "waiting"]
+            .into_iter()
+            .map(|state| ContainerState::from_str(state, false).unwrap())
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ContainerState::All,
+                ContainerState::Running,
+                ContainerState::Terminated,
+                ContainerState::Waiting,
+            ]
+        );
+        assert!(ContainerState::from_str("ready", false).is_err());
+    }
+
+    #[test]
+    fn container_state_matcher_matches_the_accepted_states() {
+        let all = ContainerStateMatcher::new(vec![ContainerState::All], false);
+        let running = ContainerStateMatcher::new(vec![ContainerState::Running], false);
+        let stopped = ContainerStateMatcher::new(
+            vec![ContainerState::Terminated, ContainerState::Waiting],
+            false,
+        );
+        for (state, expected) in [
+            ("running", [true, true, false]),
+            ("terminated", [true, false, true]),
+            ("waiting", [true, false, true]),
+        ] {
+            let status = status(state, false);
+            assert_eq!(
+                [&all, &running, &stopped].map(|matcher| matcher.matches(&status)),
+                expected,
+                "{state}"
+            );
+        }
+        // A container without any state yet.
+        let status = api::core::v1::ContainerStatus {
+            state: None,
+            ..status("running", true)
+        };
+        assert!(!all.matches(&status));
+    }
+
+    #[test]
+    fn container_state_matcher_requires_readiness_on_top_of_the_states() {
+        let running = ContainerStateMatcher::new(vec![ContainerState::Running], true);
+        assert!(running.matches(&status("running", true)));
+        assert!(!running.matches(&status("running", false)));
+        assert!(!running.matches(&status("waiting", true)));
+
+        // Still combined with the states of stopped containers.
+        let stopped = ContainerStateMatcher::new(
+            vec![ContainerState::Terminated, ContainerState::Waiting],
+            true,
+        );
+        assert!(stopped.matches(&status("terminated", true)));
+        assert!(!stopped.matches(&status("terminated", false)));
+        assert!(!stopped.matches(&status("running", true)));
+    }
+
+    #[tokio::test]
+    async fn meta_style_is_kept_across_reconnects() {
+        let client = |url: &str| Client::try_from(kube::Config::new(url.parse().unwrap())).unwrap();
+        let streamer = ContainerLogStreamer::new(
+            client("http://127.0.0.1:1"),
+            NamespaceSelector::Names(vec![String::from("default")]),
+            PodSelector::All,
+            ContainerStateMatcher::new(vec![ContainerState::All], false),
+            None,
+            60,
+            MetaFormat::parse("{pod} {container}", &[]).unwrap(),
+        );
+        let style = StyleConfig::default();
+        let meta_style = streamer.meta_style("web-0 web", &style);
+
+        // Reconnected with a new client, even if the color picked by hashing
+        // would differ by then.
+        let mut reconnected = streamer.clone();
+        reconnected.set_client(client("http://127.0.0.1:2"));
+        let repainted = StyleConfig {
+            palette: vec![Color::White],
+            ..StyleConfig::default()
+        };
+        assert_eq!(reconnected.meta_style("web-0 web", &repainted), meta_style);
+        // Other containers get theirs from the palette of the time.
+        assert_eq!(
+            reconnected
+                .meta_style("web-1 web", &repainted)
+                .foreground_color,
+            Some(Color::White)
+        );
+    }
+
+    fn status(state: &str, ready: bool) -> api::core::v1::ContainerStatus {
+        use api::core::v1::{
+            ContainerStateRunning, ContainerStateTerminated, ContainerStateWaiting,
+        };
+        let state = match state {
+            "running" => api::core::v1::ContainerState {
+                running: Some(ContainerStateRunning::default()),
+                ..Default::default()
+            },
+            "terminated" => api::core::v1::ContainerState {
+                terminated: Some(ContainerStateTerminated::default()),
+                ..Default::default()
+            },
+            _ => api::core::v1::ContainerState {
+                waiting: Some(ContainerStateWaiting::default()),
+                ..Default::default()
+            },
+        };
+        api::core::v1::ContainerStatus {
+            name: String::from("app"),
+            ready,
+            state: Some(state),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn container_state_matcher_follows_the_transitions() {
+        let matcher = ContainerStateMatcher::new(vec![ContainerState::Running], false);
+        // A container crash-looping in and out of the accepted state.
+        let accepted: Vec<bool> = ["waiting", "running", "terminated", "waiting", "running"]
+            .into_iter()
+            .map(|state| matcher.matches(&status(state, false)))
+            .collect();
+        assert_eq!(accepted, [false, true, false, false, true]);
+    }
+
+    #[test]
+    fn container_state_matcher_cycles_through_the_states() {
+        let mut matcher = ContainerStateMatcher::new(vec![ContainerState::All], false);
+        let mut labels = vec![matcher.label()];
+        for _ in 0..4 {
+            matcher.cycle();
+            labels.push(matcher.label());
+        }
+        assert_eq!(
+            labels,
+            [
+                None,
+                Some(String::from("running")),
+                Some(String::from("terminated")),
+                Some(String::from("waiting")),
+                None,
+            ]
+        );
+        assert_eq!(matcher.states, [ContainerState::All]);
+
+        // Back to all from several states.
+        let mut matcher = ContainerStateMatcher::new(
+            vec![ContainerState::Running, ContainerState::Waiting],
+            true,
+        );
+        assert_eq!(matcher.label().as_deref(), Some("running,waiting"));
+        matcher.cycle();
+        assert_eq!(matcher.states, [ContainerState::All]);
+        // Readiness is kept apart from the states.
+        assert!(!matcher.matches(&status("running", false)));
+    }
+
+    /// Applies the reconciliation of the streams with the accepted containers,
+    /// as `enforce_log_streams` does, returning the keys attached and detached.
+    fn enforce(
+        streams: &mut HashMap<String, bool>,
+        accepted: &[(&str, bool)],
+    ) -> (Vec<String>, Vec<String>) {
+        let accepted: Vec<(String, bool)> = accepted
+            .iter()
+            .map(|(key, terminating)| (key.to_string(), *terminating))
+            .collect();
+        let reconciliation = reconcile(streams, &accepted);
+        for key in reconciliation.forget.iter().chain(&reconciliation.detach) {
+            streams.remove(key);
+        }
+        let mut attached = Vec::new();
+        for index in reconciliation.launch {
+            let key = accepted[index].0.clone();
+            if streams.insert(key.clone(), false).is_none() {
+                attached.push(key);
+            }
+        }
+        (attached, reconciliation.detach)
+    }
+
+    #[test]
+    fn reconcile_attaches_and_detaches_the_streams() {
+        let mut streams = HashMap::new();
+        let (attached, detached) = enforce(&mut streams, &[("ns/a app", false)]);
+        assert_eq!(
+            (attached, detached),
+            (vec![String::from("ns/a app")], vec![])
+        );
+
+        let (attached, detached) = enforce(&mut streams, &[("ns/b app", false)]);
+        assert_eq!(
+            (attached, detached),
+            (
+                vec![String::from("ns/b app")],
+                vec![String::from("ns/a app")]
+            )
+        );
+        assert_eq!(streams.len(), 1);
+
+        // Nothing to do while the accepted containers stay the same.
+        assert_eq!(
+            reconcile(&streams, &[(String::from("ns/b app"), false)]),
+            Reconciliation::default()
+        );
+    }
+
+    #[test]
+    fn reconcile_relaunches_the_ended_streams() {
+        let accepted = [(String::from("ns/a app"), false)];
+        let streams = HashMap::from([(String::from("ns/a app"), true)]);
+        assert_eq!(reconcile(&streams, &accepted).launch, [0]);
+
+        // Not for a Pod being deleted.
+        let accepted = [(String::from("ns/a app"), true)];
+        assert_eq!(reconcile(&streams, &accepted), Reconciliation::default());
+
+        // Forgotten without a marker once their containers are not accepted anymore.
+        assert_eq!(
+            reconcile(&streams, &[]),
+            Reconciliation {
+                forget: vec![String::from("ns/a app")],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn reconcile_keeps_one_stream_per_container_while_flapping() {
+        let mut streams = HashMap::new();
+        let (mut attached, mut detached) = (0, 0);
+        for check in 0..100 {
+            // The container flaps in and out of the accepted states on every check,
+            // while another one stays in.
+            let accepted: &[(&str, bool)] = if check % 2 == 0 {
+                &[("ns/a app", false), ("ns/b app", false)]
+            } else {
+                &[("ns/b app", false)]
+            };
+            let (a, d) = enforce(&mut streams, accepted);
+            attached += a.len();
+            detached += d.len();
+            assert_eq!(streams.len(), accepted.len());
+        }
+        // Every stream attached to the flapping container was detached, leaving none behind.
+        assert_eq!((attached, detached), (51, 50));
+    }
+
+    fn annotated(annotations: &[(&str, &str)]) -> ObjectMeta {
+        ObjectMeta {
+            annotations: Some(
+                annotations
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn pod_metadata(name: &str, labels: &[(&str, &str)]) -> ObjectMeta {
+        ObjectMeta {
+            name: Some(name.to_string()),
+            labels: Some(
+                labels
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            ),
+            ..annotated(&[("git-commit", "4f2a9c1")])
+        }
+    }
+
+    #[test]
+    fn pod_selector_matches_the_name_by_default() {
+        let scope = MatchScope {
+            labels: false,
+            annotations: false,
+        };
+        let selector = PodSelector::Regex(Regex::new("^web-").unwrap(), scope);
+        assert!(selector.matches(&pod_metadata("web-0", &[])));
+        // Not by the label values outside of the scope.
+        let selector = PodSelector::Regex(Regex::new("^4f2a").unwrap(), scope);
+        assert!(!selector.matches(&pod_metadata(
+            "web-0",
+            &[("app.kubernetes.io/version", "4f2a9c1")]
+        )));
+    }
+
+    #[test]
+    fn pod_selector_matches_the_values_in_the_scope() {
+        let labels = MatchScope {
+            labels: true,
+            annotations: false,
+        };
+        let version = [("app.kubernetes.io/version", "4f2a9c1")];
+        let selector = PodSelector::Regex(Regex::new("^4f2a").unwrap(), labels);
+        // Matched only by a label value.
+        assert!(selector.matches(&pod_metadata("web-0", &version)));
+        // Matched only by the name.
+        assert!(PodSelector::Regex(Regex::new("^web-").unwrap(), labels)
+            .matches(&pod_metadata("web-0", &version)));
+        // Matched by neither.
+        assert!(!PodSelector::Regex(Regex::new("^api-").unwrap(), labels)
+            .matches(&pod_metadata("web-0", &version)));
+        // Nor by the keys.
+        assert!(
+            !PodSelector::Regex(Regex::new("kubernetes").unwrap(), labels)
+                .matches(&pod_metadata("web-0", &version))
+        );
+
+        // The annotation values only if they are in the scope too.
+        assert!(!selector.matches(&pod_metadata("web-0", &[])));
+        let annotations = MatchScope {
+            labels: true,
+            annotations: true,
+        };
+        assert!(
+            PodSelector::Regex(Regex::new("^4f2a").unwrap(), annotations)
+                .matches(&pod_metadata("web-0", &[]))
+        );
+    }
+
+    #[test]
+    fn pod_selector_by_name() {
+        let selector = PodSelector::Name(String::from("web-0"));
+        assert!(selector.matches(&pod_metadata("web-0", &[])));
+        assert!(!selector.matches(&pod_metadata("web-01", &[])));
+        assert!(PodSelector::All.matches(&pod_metadata("web-0", &[])));
+    }
+
+    #[test]
+    fn log_params_at_startup() {
+        let params = effective_log_params("app", Some(600), 30, LaunchMode::Startup);
+        assert_eq!(params.container.as_deref(), Some("app"));
+        assert!(params.follow && params.timestamps);
+        assert_eq!(params.since_seconds, Some(600));
+        assert_eq!(params.since_time, None);
+
+        // The whole history without --since.
+        let params = effective_log_params("app", None, 30, LaunchMode::Startup);
+        assert_eq!(params.since_seconds, None);
+        assert_eq!(params.since_time, None);
+    }
+
+    #[test]
+    fn log_params_of_discovered_pods() {
+        for since_seconds in [Some(600), None] {
+            let params = effective_log_params("app", since_seconds, 30, LaunchMode::Discovered);
+            assert!(params.follow && params.timestamps);
+            assert_eq!(params.since_seconds, Some(30));
+            assert_eq!(params.since_time, None);
+        }
+    }
+
+    #[test]
+    fn log_params_of_resumed_streams() {
+        let last_seen = DateTime::parse_from_rfc3339("2024-05-01T12:00:00.5Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        for since_seconds in [Some(600), None] {
+            let params =
+                effective_log_params("app", since_seconds, 30, LaunchMode::Resumed(last_seen));
+            assert!(params.follow && params.timestamps);
+            assert_eq!(params.since_seconds, None);
+            assert_eq!(params.since_time, Some(last_seen));
+        }
+    }
+
+    #[test]
+    fn annotation_selector_parses_the_requirements() {
+        let selector =
+            AnnotationSelector::parse(" team = payments , tier!=batch,owner==sre").unwrap();
+        assert_eq!(
+            selector.requirements,
+            [
+                (String::from("team"), String::from("payments"), true),
+                (String::from("tier"), String::from("batch"), false),
+                (String::from("owner"), String::from("sre"), true),
+            ]
+        );
+        // An empty value is a value.
+        let selector = AnnotationSelector::parse("team=").unwrap();
+        assert_eq!(
+            selector.requirements,
+            [(String::from("team"), String::new(), true)]
+        );
+    }
+
+    #[test]
+    fn annotation_selector_rejects_invalid_requirements() {
+        assert_eq!(
+            AnnotationSelector::parse("team").err(),
+            Some(String::from(
+                "invalid requirement 'team', expected KEY=VALUE or KEY!=VALUE"
+            ))
+        );
+        assert_eq!(
+            AnnotationSelector::parse("team=payments, =batch").err(),
+            Some(String::from("missing key in '=batch'"))
+        );
+        assert!(AnnotationSelector::parse("team=payments,").is_err());
+    }
+
+    #[test]
+    fn annotation_selector_matches_all_the_requirements() {
+        let selector = AnnotationSelector::parse("team=payments,tier!=batch").unwrap();
+        assert!(selector.matches(&annotated(&[("team", "payments")])));
+        assert!(selector.matches(&annotated(&[("team", "payments"), ("tier", "web")])));
+        assert!(!selector.matches(&annotated(&[("team", "payments"), ("tier", "batch")])));
+        assert!(!selector.matches(&annotated(&[("team", "search")])));
+        // A missing annotation differs from any value.
+        assert!(!selector.matches(&ObjectMeta::default()));
+        assert!(AnnotationSelector::parse("tier!=batch")
+            .unwrap()
+            .matches(&ObjectMeta::default()));
+        // The default selector has no requirements.
+        assert!(AnnotationSelector::default().matches(&ObjectMeta::default()));
+    }
+
+    #[test]
+    fn label_selector_accepts_the_requirements() {
+        for selector in [
+            "app=nginx",
+            "app==nginx",
+            "app!=nginx",
+            "!canary",
+            "tier in (frontend,edge)",
+            "tier notin (batch)",
+            "app.kubernetes.io/name=api",
+            "example.com/team",
+            " app = nginx , tier in ( frontend , edge ) , !canary ",
+            // An empty value is a value.
+            "app=",
+        ] {
+            assert!(LabelSelector::parse(selector).is_ok(), "{selector}");
+        }
+        assert_eq!(LabelSelector::parse(" app=nginx ").unwrap().0, "app=nginx");
+    }
+
+    #[test]
+    fn label_selector_rejects_invalid_requirements() {
+        for (selector, error) in [
+            ("app=nginx,", "missing label key in ''"),
+            ("=nginx", "missing label key in ''"),
+            ("tier in (a,b", "missing ')' in 'tier in (a,b'"),
+            ("tier in a,b)", "unbalanced ')' in 'tier in a,b)'"),
+            (
+                "tier in frontend",
+                "expected (VALUE,...) in 'tier in frontend'",
+            ),
+            ("tier in ()", "empty set of values in 'tier in ()'"),
+            ("tier notin ( )", "empty set of values in 'tier notin ( )'"),
+            ("app=-nginx", "invalid label value '-nginx'"),
+            ("app=ngi nx", "invalid label value 'ngi nx'"),
+            ("-app=nginx", "invalid label key '-app'"),
+            (
+                "Example.com/app=nginx",
+                "invalid prefix of the label key 'Example.com/app'",
+            ),
+            ("/app=nginx", "invalid prefix of the label key '/app'"),
+            ("example.com/=nginx", "missing label key in 'example.com/'"),
+        ] {
+            assert_eq!(
+                LabelSelector::parse(selector).err().as_deref(),
+                Some(error),
+                "{selector}"
+            );
+        }
+        let long = "a".repeat(64);
+        assert!(LabelSelector::parse(&format!("app={long}")).is_err());
+        assert!(LabelSelector::parse(&format!("{long}=nginx")).is_err());
+    }
+
+    #[test]
+    fn field_selector_accepts_the_requirements() {
+        for selector in [
+            "spec.nodeName=node-1",
+            "status.phase==Running",
+            "status.phase!=Succeeded",
+            "spec.nodeName=node-1,status.phase=Running,metadata.name!=api-0",
+            " spec.nodeName = node-1 , status.phase != Failed ",
+            // An empty value is a value.
+            "spec.nodeName=",
+        ] {
+            assert!(FieldSelector::parse(selector).is_ok(), "{selector}");
+        }
+        assert_eq!(
+            FieldSelector::parse(" status.phase=Running ").unwrap().0,
+            "status.phase=Running"
+        );
+    }
+
+    #[test]
+    fn field_selector_rejects_invalid_requirements() {
+        for (selector, error) in [
+            (
+                "status.phase",
+                "invalid requirement 'status.phase', expected FIELD=VALUE or FIELD!=VALUE",
+            ),
+            (
+                "spec.nodeName=node-1,",
+                "invalid requirement '', expected FIELD=VALUE or FIELD!=VALUE",
+            ),
+            ("=Running", "invalid field '' in '=Running'"),
+            ("status.phase=Running, !=x", "invalid field '' in '!=x'"),
+            (
+                "status phase=Running",
+                "invalid field 'status phase' in 'status phase=Running'",
+            ),
+        ] {
+            assert_eq!(
+                FieldSelector::parse(selector).err().as_deref(),
+                Some(error),
+                "{selector}"
+            );
+        }
+    }
+
+    fn target(pod: &str, container: &str) -> Target {
+        Target {
+            namespace: String::from("default"),
+            pod: pod.to_string(),
+            container: container.to_string(),
+            restarts: 0,
+            state: "running",
+            labels: HashMap::new(),
+            preferred_over: Vec::new(),
+            terminating: None,
+        }
+    }
+
+    /// Streamer of all the Pods in the namespaces, with a client which is never connected.
+    fn streamer(namespaces: &[&str], states: Vec<ContainerState>) -> ContainerLogStreamer {
+        let config = kube::Config::new("http://127.0.0.1:1".parse().unwrap());
+        ContainerLogStreamer::new(
+            Client::try_from(config).unwrap(),
+            NamespaceSelector::Names(namespaces.iter().map(|name| name.to_string()).collect()),
+            PodSelector::All,
+            ContainerStateMatcher::new(states, false),
+            None,
+            30,
+            MetaFormat::parse("{pod} {container}", &[]).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn same_named_pods_in_different_namespaces_are_distinct() {
+        let streamer = streamer(&["team-a", "team-b"], vec![ContainerState::All]);
+        let style = StyleConfig::default();
+        let a = Target {
+            namespace: String::from("team-a"),
+            ..target("web-0", "web")
+        };
+        let b = Target {
+            namespace: String::from("team-b"),
+            ..target("web-0", "web")
+        };
+        assert_eq!(streamer.key(&a), "team-a/web-0 web");
+        assert_eq!(streamer.key(&b), "team-b/web-0 web");
+        // The stats are kept by the names in notices, which tell the namespaces apart too.
+        assert_ne!(streamer.name(&a), streamer.name(&b));
+
+        let (meta_a, meta_b) = (streamer.meta(&a, &style), streamer.meta(&b, &style));
+        assert_eq!(meta_a.to_string(), " ta  web-0 web");
+        assert_eq!(meta_b.to_string(), " tb  web-0 web");
+        let meta_styles = streamer.meta_styles.lock().unwrap();
+        assert_eq!(meta_styles.len(), 2);
+        assert_ne!(
+            meta_styles["team-a/web-0 web"],
+            meta_styles["team-b/web-0 web"]
+        );
+    }
+
+    #[tokio::test]
+    async fn namespace_badge_only_with_multiple_namespaces() {
+        let style = StyleConfig::default();
+        let target = target("web-0", "web");
+        let single = streamer(&["default"], vec![ContainerState::All]);
+        assert_eq!(single.meta(&target, &style).to_string(), "web-0 web");
+        assert_eq!(single.name(&target), "web-0 web");
+
+        let multi = streamer(&["default", "kube-system"], vec![ContainerState::All]);
+        assert_eq!(multi.meta(&target, &style).to_string(), " de  web-0 web");
+        assert_eq!(multi.name(&target), "default/web-0 web");
+        // The color of the container is the same either way.
+        assert_eq!(
+            single.meta_styles.lock().unwrap()["default/web-0 web"],
+            multi.meta_styles.lock().unwrap()["default/web-0 web"]
+        );
+    }
+
+    #[tokio::test]
+    async fn streams_resume_after_the_last_line_consumed() {
+        let streamer = streamer(&["default"], vec![ContainerState::All]);
+        let target = target("web-0", "web");
+        let at = |seconds: i64| DateTime::from_timestamp(1_714_564_800 + seconds, 0).unwrap();
+        let log = |at: Option<DateTime<Utc>>| ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from("web-0"),
+            container: String::from("web"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from("web-0 web"),
+            body: StyledGraphemes::from("ready"),
+            continuation: Vec::new(),
+            received: None,
+            at,
+        };
+        assert_eq!(streamer.log_params(&target, false).since_time, None);
+        // Lines of bul itself don't move where the stream resumes.
+        streamer.consumed(&log(None));
+        assert_eq!(streamer.log_params(&target, false).since_time, None);
+        streamer.consumed(&log(Some(at(1))));
+        streamer.consumed(&log(Some(at(2))));
+        assert_eq!(streamer.log_params(&target, false).since_time, Some(at(2)));
+    }
+
+    #[test]
+    fn only_the_lines_at_the_resumed_time_may_be_replayed() {
+        let recent = Mutex::new(HashMap::new());
+        let at = |seconds: i64| DateTime::from_timestamp(1_714_564_800 + seconds, 0).unwrap();
+        // Read by the stream before it was relaunched, but only the first one consumed.
+        for (seconds, line) in [(1, "consumed"), (2, "read")] {
+            assert!(!is_replayed(
+                &recent,
+                "web",
+                8,
+                Some(at(seconds - 1)),
+                at(seconds),
+                line
+            ));
+        }
+        let resumed = Some(at(1));
+        assert!(is_replayed(&recent, "web", 8, resumed, at(0), "older"));
+        assert!(is_replayed(&recent, "web", 8, resumed, at(1), "consumed"));
+        assert!(!is_replayed(&recent, "web", 8, resumed, at(1), "same time"));
+        assert!(!is_replayed(&recent, "web", 8, resumed, at(2), "read"));
+    }
+
+    #[test]
+    fn blank_line_shown_as_a_placeholder() {
+        let style = StyleConfig::default();
+        let log = |body: &str, continuation: Vec<StyledGraphemes>| ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from("api-0"),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from("api-0"),
+            body: StyledGraphemes::from(body),
+            continuation,
+            received: None,
+            at: None,
+        };
+        for blank in ["", " \t "] {
+            let blank = log(blank, Vec::new());
+            assert!(blank.is_blank());
+            assert_eq!(
+                blank
+                    .row(&blank.body, &Aliases::default(), &style)
+                    .to_string(),
+                "api-0 ⏎"
+            );
+        }
+        // Grouped lines are something to show.
+        let grouped = log("", vec![StyledGraphemes::from("  at main")]);
+        assert!(!grouped.is_blank());
+        assert!(!log("GET /health 200", Vec::new()).is_blank());
+    }
+
+    /// Launch which takes a while, as when the API server is slow, tracking how many
+    /// are running at once. Every third target is skipped.
+    async fn slow_launch(
+        index: usize,
+        running: &AtomicUsize,
+        most: &AtomicUsize,
+    ) -> anyhow::Result<Option<usize>> {
+        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+        most.fetch_max(now, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        running.fetch_sub(1, Ordering::SeqCst);
+        Ok((!index.is_multiple_of(3)).then_some(index))
+    }
+
+    #[tokio::test]
+    async fn launches_run_concurrently_up_to_the_limit() {
+        let (running, most) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let progress = Mutex::new(None);
+        let started = Instant::now();
+        let launched = launch_concurrently(12, 4, &progress, &CancellationToken::new(), |index| {
+            slow_launch(index, &running, &most)
+        })
+        .await
+        .unwrap();
+        // In 3 rounds of 4 rather than 12 one after another, which would take 600ms.
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert_eq!(most.load(Ordering::SeqCst), 4);
+        let mut launched: Vec<usize> = launched.into_iter().map(|(index, _)| index).collect();
+        launched.sort();
+        assert_eq!(launched, [1, 2, 4, 5, 7, 8, 10, 11]);
+        assert_eq!(*progress.lock().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn progress_counts_the_launches_done() {
+        let progress = Arc::new(Mutex::new(None));
+        // Each launch waits for a permit, given one at a time.
+        let gate = Arc::new(Semaphore::new(0));
+        let launching = tokio::spawn({
+            let (progress, gate) = (progress.clone(), gate.clone());
+            async move {
+                launch_concurrently(5, 2, &progress, &CancellationToken::new(), |index| {
+                    let gate = gate.clone();
+                    async move {
+                        gate.acquire().await.unwrap().forget();
+                        Ok(Some(index))
+                    }
+                })
+                .await
+            }
+        });
+        let progress_of = |done: usize| {
+            let progress = &progress;
+            async move {
+                let started = Instant::now();
+                while *progress.lock().unwrap() != Some((done, 5)) {
+                    assert!(
+                        started.elapsed() < Duration::from_secs(5),
+                        "stuck before {done}/5"
+                    );
+                    tokio::task::yield_now().await;
+                }
+            }
+        };
+        progress_of(0).await;
+        for done in 1..=5 {
+            gate.add_permits(1);
+            if done < 5 {
+                progress_of(done).await;
+            }
+        }
+        assert_eq!(launching.await.unwrap().unwrap().len(), 5);
+        assert_eq!(*progress.lock().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn cancellation_drops_the_pending_launches() {
+        let started = AtomicUsize::new(0);
+        let progress = Mutex::new(None);
+        let canceled = CancellationToken::new();
+        let canceling = canceled.clone();
+        let (launched, ()) = tokio::join!(
+            launch_concurrently(10, 3, &progress, &canceled, |_| async {
+                started.fetch_add(1, Ordering::SeqCst);
+                std::future::pending::<anyhow::Result<Option<()>>>().await
+            }),
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                canceling.cancel();
+            },
+        );
+        assert!(launched.unwrap().is_empty());
+        // Only the launches which got a permit started.
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+        assert_eq!(*progress.lock().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn failed_launch_drops_the_pending_ones() {
+        let started = AtomicUsize::new(0);
+        let progress = Mutex::new(None);
+        let launched = launch_concurrently(10, 2, &progress, &CancellationToken::new(), |index| {
+            let started = &started;
+            async move {
+                started.fetch_add(1, Ordering::SeqCst);
+                if index == 1 {
+                    anyhow::bail!("rejected credentials");
+                }
+                std::future::pending().await
+            }
+        })
+        .await;
+        assert_eq!(
+            launched
+                .map(|launched: Vec<(usize, ())>| launched.len())
+                .unwrap_err()
+                .to_string(),
+            "rejected credentials"
+        );
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+        assert_eq!(*progress.lock().unwrap(), None);
+    }
+
+    /// Containers and the ones they were preferred over.
+    fn preferred(targets: &[Target]) -> Vec<(&str, Vec<&str>)> {
+        targets
+            .iter()
+            .map(|target| {
+                (
+                    target.container.as_str(),
+                    target.preferred_over.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prefer_containers_keeps_the_matching_ones() {
+        let preference = Regex::new("-log$").unwrap();
+        let mut targets = vec![
+            target("web-0", "web"),
+            target("web-0", "web-log"),
+            target("web-0", "istio-proxy"),
+        ];
+        prefer_containers(&mut targets, 0, &preference);
+        assert_eq!(
+            preferred(&targets),
+            [("web-log", vec!["web", "istio-proxy"])]
+        );
+    }
+
+    #[test]
+    fn prefer_containers_keeps_all_unless_some_match_and_some_dont() {
+        let preference = Regex::new("-log$").unwrap();
+        let mut targets = vec![target("web-0", "web"), target("web-0", "istio-proxy")];
+        prefer_containers(&mut targets, 0, &preference);
+        assert_eq!(
+            preferred(&targets),
+            [("web", vec![]), ("istio-proxy", vec![])]
+        );
+
+        let mut targets = vec![target("web-0", "web-log"), target("web-0", "audit-log")];
+        prefer_containers(&mut targets, 0, &preference);
+        assert_eq!(
+            preferred(&targets),
+            [("web-log", vec![]), ("audit-log", vec![])]
+        );
+    }
+
+    #[test]
+    fn prefer_containers_leaves_the_earlier_pods_alone() {
+        let preference = Regex::new("-log$").unwrap();
+        let mut targets = vec![
+            target("api-0", "api"),
+            target("api-0", "api-sidecar"),
+            target("web-0", "web"),
+            target("web-0", "web-log"),
+        ];
+        prefer_containers(&mut targets, 2, &preference);
+        assert_eq!(
+            preferred(&targets),
+            [
+                ("api", vec![]),
+                ("api-sidecar", vec![]),
+                ("web-log", vec!["web"]),
+            ]
+        );
+    }
+
+    /// Pod of a single container in the state, deleted with the grace period if any.
+    fn pod(name: &str, state: &str, deleted: Option<Option<i64>>) -> Pod {
+        use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+        Pod {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                deletion_timestamp: deleted.map(|_| Time(Utc::now())),
+                deletion_grace_period_seconds: deleted.flatten(),
+                ..Default::default()
+            },
+            status: Some(api::core::v1::PodStatus {
+                container_statuses: Some(vec![status(state, true)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn terminating_pods_are_streamed_whatever_their_states() {
+        let streamer = streamer(&["default"], vec![ContainerState::Running]);
+        let mut targets = Vec::new();
+        streamer.collect_targets(
+            "default",
+            [
+                pod("web-0", "terminated", None),
+                pod("web-1", "terminated", Some(None)),
+                pod("web-2", "running", Some(Some(5))),
+            ],
+            &mut targets,
+        );
+        let terminating: Vec<(&str, Option<i64>)> = targets
+            .iter()
+            .map(|target| (target.pod.as_str(), target.terminating))
+            .collect();
+        // The grace period defaults to the one of Kubernetes.
+        assert_eq!(terminating, [("web-1", Some(30)), ("web-2", Some(5))]);
+
+        let mut streamer = streamer;
+        streamer.set_stop_on_terminating(true);
+        let mut targets = Vec::new();
+        streamer.collect_targets(
+            "default",
+            [pod("web-1", "running", Some(None))],
+            &mut targets,
+        );
+        assert!(targets.is_empty());
+    }
+
+    #[tokio::test]
+    async fn terminating_marker_when_the_deletion_first_appears() {
+        let streamer = streamer(&["default"], vec![ContainerState::All]);
+        let style = StyleConfig::default();
+        let check = |pod: Pod| {
+            let mut targets = Vec::new();
+            streamer.collect_targets("default", [pod], &mut targets);
+            streamer.terminating_marker(&targets[0], &style)
+        };
+        // Streamed for a while before the Pod is deleted.
+        assert!(check(pod("web-0", "running", None)).is_none());
+        let marker = check(pod("web-0", "running", Some(None))).unwrap();
+        assert!(marker.kind == LogKind::Marker);
+        assert_eq!(marker.body.to_string(), "── pod terminating (grace 30s) ──");
+        assert_eq!(marker.meta.to_string(), "web-0 app");
+        // Only once for the Pod.
+        assert!(check(pod("web-0", "running", Some(None))).is_none());
+    }
+
+    /// Normalizes the line end of `line`, returning the line and whether it changed.
+    fn line_end(line: &str, keep_trailing_whitespace: bool) -> (String, bool) {
+        let mut line = line.to_string();
+        let normalized = normalize_line_end(&mut line, keep_trailing_whitespace);
+        (line, normalized)
+    }
+
+    #[test]
+    fn normalize_line_end_trims_the_trailing_whitespace() {
+        assert_eq!(line_end("ready\r", false), (String::from("ready"), true));
+        assert_eq!(line_end("ready \t\r", false), (String::from("ready"), true));
+        assert_eq!(line_end("ready  ", false), (String::from("ready"), true));
+        assert_eq!(line_end("  ready", false), (String::from("  ready"), false));
+        assert_eq!(line_end(" \r", false), (String::new(), true));
+        assert_eq!(line_end("", false), (String::new(), false));
+    }
+
+    #[test]
+    fn normalize_line_end_keeps_the_trailing_whitespace() {
+        assert_eq!(line_end("ready\r", true), (String::from("ready"), true));
+        assert_eq!(line_end("ready \r", true), (String::from("ready "), true));
+        assert_eq!(line_end("ready  ", true), (String::from("ready  "), false));
+        // Only a single `\r` of a CRLF line ending.
+        assert_eq!(line_end("ready\r\r", true), (String::from("ready\r"), true));
+    }
+
+    #[test]
+    fn normalize_line_end_leaves_a_lone_cr_mid_line() {
+        // A progress bar redrawing itself over the same line.
+        for keep in [false, true] {
+            assert_eq!(
+                line_end("10%\r50%\r100%", keep),
+                (String::from("10%\r50%\r100%"), false)
+            );
+        }
+    }
+
+    fn merger() -> LineMerger {
+        LineMerger::new(Some(LineMerge {
+            chunk_size: 4,
+            window: Duration::from_millis(100),
+            max_size: 10,
+        }))
+    }
+
+    fn line(text: &str) -> Option<Line> {
+        Some((None, String::from(text)))
+    }
+
+    #[test]
+    fn line_of_exactly_a_chunk_is_merged_with_the_next() {
+        let mut merger = merger();
+        assert_eq!(merger.push(None, String::from("abcd")), None);
+        assert_eq!(merger.window(), Some(Duration::from_millis(100)));
+        assert_eq!(merger.push(None, String::from("ef")), line("abcdef"));
+        assert_eq!(merger.window(), None);
+    }
+
+    #[test]
+    fn line_of_a_chunk_plus_one_is_not_held() {
+        let mut merger = merger();
+        assert_eq!(merger.push(None, String::from("abcde")), line("abcde"));
+        assert_eq!(merger.push(None, String::from("abc")), line("abc"));
+        assert_eq!(merger.take(), None);
+    }
+
+    #[test]
+    fn held_line_is_given_back_once_the_window_elapses() {
+        let mut merger = merger();
+        assert_eq!(merger.push(None, String::from("abcd")), None);
+        assert_eq!(merger.expire(Duration::from_millis(99)), None);
+        assert_eq!(merger.expire(Duration::from_millis(100)), line("abcd"));
+        // Not merged with the line coming after the window.
+        assert_eq!(merger.push(None, String::from("ef")), line("ef"));
+    }
+
+    #[test]
+    fn merged_line_is_given_back_at_the_max_size() {
+        let mut merger = merger();
+        assert_eq!(merger.push(None, String::from("abcd")), None);
+        assert_eq!(merger.push(None, String::from("efgh")), None);
+        // 12 bytes, beyond the max size, even though another chunk seems to follow.
+        assert_eq!(
+            merger.push(None, String::from("ijkl")),
+            line("abcdefghijkl")
+        );
+        assert_eq!(merger.take(), None);
+    }
+
+    #[test]
+    fn lines_are_not_merged_without_the_heuristic() {
+        let mut merger = LineMerger::new(None);
+        assert_eq!(merger.push(None, String::from("abcd")), line("abcd"));
+        assert_eq!(merger.window(), None);
     }
 }
@@ -0,0 +1,50 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+};
+
+use chrono::{DateTime, Utc};
+
+/// Remembers the most recent lines of a container by the hash of their timestamp and content,
+/// to drop the lines replayed by the API server when its stream is resumed.
+///
+/// Resuming with `since_time` only has a precision of seconds, so the lines already received
+/// within that second come again, while other lines stamped with the same time may not have
+/// been received yet. Telling them apart by content keeps the latter.
+///
+/// This is best effort: a line replayed after more than `capacity` newer lines is let through,
+/// and two identical lines stamped with the same time are taken as one.
+pub struct RecentLines {
+    capacity: usize,
+    order: VecDeque<u64>,
+    seen: HashSet<u64>,
+}
+
+impl RecentLines {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            seen: HashSet::with_capacity(capacity),
+        }
+    }
+
+    /// Remembers the line, evicting the oldest one if full.
+    /// Returns whether it was not among the recent lines.
+    pub fn insert(&mut self, at: DateTime<Utc>, line: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        at.hash(&mut hasher);
+        line.hash(&mut hasher);
+        let hash = hasher.finish();
+        if !self.seen.insert(hash) {
+            return false;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.order.push_back(hash);
+        true
+    }
+}
@@ -1,52 +1,1169 @@
-use std::collections::VecDeque;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use rayon::prelude::*;
+use tracing::debug;
 
 use promkit::{
-    crossterm::{event::Event, style::Color},
+    crossterm::{
+        cursor,
+        event::{self, Event, KeyCode, KeyEvent},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    },
     grapheme::StyledGraphemes,
     listbox,
     pane::Pane,
     snapshot::Snapshot,
-    style::StyleBuilder,
     switch::ActiveKeySwitcher,
-    text_editor, PaneFactory, Prompt, PromptSignal,
+    terminal::Terminal,
+    text_editor, Finalizer, PaneFactory, PromptSignal, Renderer,
 };
 
-use crate::container::ContainerLog;
+use crate::{
+    alias::Aliases,
+    baseline::Baseline,
+    container::ContainerLog,
+    queue::{Capture, Lines, Seen, Stats},
+    style::StyleConfig,
+    wrap::wrap,
+};
 
+mod complete;
+use complete::{Completion, Values};
+mod index;
+use index::Index;
 mod keymap;
+mod query;
+pub use query::Limits as QueryLimits;
+mod signature;
+use signature::Cluster;
+mod words;
+use words::Picked;
+
+/// Action requested by the dig keymap.
+#[derive(PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    Quit,
+    /// Return to bul and resume live tailing from the selected line.
+    GoLive,
+    /// Toggle between digging all captured lines and the ones matching the bul query.
+    ToggleScope,
+    /// Show or hide the lines surrounding the selected line in the queue.
+    ToggleContext,
+    /// Toggle between literal queries and queries with field predicates.
+    ToggleMode,
+    /// Toggle between keeping the selected line in the middle of the list and scrolling freely.
+    ToggleCenter,
+    /// Toggle between the lines matching the dig query and the ones it filters out.
+    ToggleInvert,
+    /// Show or hide the time each line was received in front of it.
+    ToggleReceived,
+    /// Show or hide the summary of the lines received per container and the ones not captured.
+    ToggleSummary,
+    /// Show or hide the most frequent signatures of the matching lines, see `signature::normalize`.
+    ToggleSignatures,
+    /// Query the lines with the selected signature.
+    PickSignature,
+    /// Complete the term before the cursor, or cycle its candidates,
+    /// and otherwise toggle the context of the selected line.
+    Complete,
+    /// Accept the completion shown, or move the cursor one character to the right.
+    Forward,
+    /// Insert the next word of the selected line into the query,
+    /// replacing the one inserted last if picked again right away.
+    PickWord,
+    /// Dig further within the lines matching the query, starting over with an empty query.
+    PushLevel,
+    /// Return to the lines the last pushed query was applied to, restoring that query.
+    PopLevel,
+}
+
+/// How the dig query is interpreted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// The query is a substring of the lines.
+    Literal,
+    /// The query is parsed into field predicates, see `query::Expr`.
+    Query,
+}
+
+/// Lines of the queue to dig in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// All captured lines.
+    All,
+    /// Only the lines matching the query which was active in bul.
+    Prefiltered,
+}
+
+/// Query pushed onto the stack of dig, whose matching lines are the ones dug in above it.
+struct Level {
+    query: String,
+    mode: Mode,
+    inverted: bool,
+    /// Indices into the queue of the lines which matched, the base of the next level.
+    indices: Vec<usize>,
+}
+
+/// Settings of dig which stay the same across visits.
+pub struct Settings {
+    /// Number of lines shown before and after the selected line when its context is expanded.
+    pub context_lines: usize,
+    /// Whether to ring the terminal bell when the query narrows the lines down to none.
+    pub bell: bool,
+    /// Number of lines kept visible above and below the selected line while scrolling.
+    pub scrolloff: usize,
+    /// Limits on the regexes of the query mode.
+    pub query_limits: QueryLimits,
+    /// Number of signatures shown, the most frequent ones.
+    pub signatures: usize,
+    /// Aliases of the Pods, shown in front of the meta of their lines.
+    pub aliases: Aliases,
+    pub style: StyleConfig,
+}
+
+/// Query, mode and selection of dig as it was left, to restore it from a session.
+#[derive(Clone, Default)]
+pub struct View {
+    pub query: String,
+    /// Whether the query has field predicates.
+    pub query_mode: bool,
+    /// Whether only the lines matching the bul query were dug in.
+    pub prefiltered: bool,
+    pub inverted: bool,
+    pub centered: bool,
+    /// Index into the queue of the selected line, if any line matched.
+    pub selected: Option<usize>,
+}
 
 pub struct Digger {
     keymap: ActiveKeySwitcher<keymap::Keymap>,
     text_editor_snapshot: Snapshot<text_editor::State>,
-    log_queue: VecDeque<ContainerLog>,
+    /// Shared with the thread verifying the lines against the query.
+    /// The packed lines are only unpacked as they are shown.
+    log_queue: Arc<Lines>,
+    /// Body of each line of `log_queue` as text, to verify against the queries
+    /// without converting the styled body on every keystroke.
+    bodies: Arc<Vec<String>>,
+    /// Index over `log_queue` to narrow down the lines to verify against the query.
+    index: Index,
+    /// Restyled bodies of the lines shown so far, keyed by their index into `log_queue`,
+    /// so that the rows kept while narrowing the query are not restyled again.
+    /// Only the rows rendered are restyled, and at most `RESTYLED_CAPACITY` are kept.
+    restyled: RefCell<HashMap<usize, StyledGraphemes>>,
+    /// Query the rows are highlighted with.
+    highlighted: String,
+    /// Scope, mode, inversion and query of the last filtering,
+    /// to tell when the query only narrows it.
+    last_filter: Option<(Scope, Mode, bool, String)>,
+    /// Only moves through the lines, whose items are left empty:
+    /// the rows are built from `indices` for the lines rendered.
     logs_snapshot: Snapshot<listbox::State>,
+    /// Index into `log_queue` of each item in the listbox.
+    indices: Vec<usize>,
+    go_live: bool,
+    baseline: Option<Arc<Mutex<Baseline>>>,
+    /// Query which was active in bul, applied beneath the dig query in the prefiltered scope.
+    bul_query: String,
+    scope: Scope,
+    mode: Mode,
+    /// Whether the lines in the scope which don't match the dig query are shown instead.
+    inverted: bool,
+    /// Error of the query which failed to parse, shown in place of the listbox.
+    query_error: Option<String>,
+    query_limits: QueryLimits,
+    /// Why the regexes of the query are matched as literals, if they exceeded the limits.
+    literal_fallback: Option<&'static str>,
+    /// Prefix of the text editor without the scope indicator.
+    prefix: String,
+    /// Number of lines shown before and after the selected line in the context overlay.
+    context_lines: usize,
+    bell: bool,
+    scrolloff: usize,
+    /// Whether the selected line is kept in the middle of the list.
+    centered: bool,
+    /// Whether the time each line was received is shown in front of it.
+    show_received: bool,
+    /// Index of the item at the top of the list as last rendered,
+    /// which only moves as far as needed to keep the selected line in view.
+    top: Cell<usize>,
+    aliases: Aliases,
+    style: StyleConfig,
+    /// Lines surrounding the selected line, shown in place of the listbox while present.
+    context_snapshot: Option<Snapshot<listbox::State>>,
+    /// Values of the fields to complete in the query mode.
+    values: Values,
+    /// Completion shown after the cursor, until accepted or the query changes.
+    completion: Option<Completion>,
+    /// Queries pushed to dig within their matches, the innermost last.
+    levels: Vec<Level>,
+    /// Whether the last filtering was aborted with Esc, leaving the lines of the one before.
+    aborted: bool,
+    /// Lines being verified against the query, shown once they all are.
+    verifying: Option<Verifying>,
+    /// Word last picked from the selected line into the query.
+    picked: Option<Picked>,
+    /// Counts of the lines received but not captured.
+    stats: Stats,
+    /// Lines received per container, and when.
+    seen: Seen,
+    /// Whether the summary of `seen` and `stats` is shown in place of the listbox.
+    show_summary: bool,
+    /// Most frequent signatures of the matching lines, shown in place of the listbox while present.
+    signatures: Option<(Vec<Cluster>, Snapshot<listbox::State>)>,
+    top_signatures: usize,
+}
+
+/// Verification of the lines against a query on another thread,
+/// so that the keys are handled meanwhile, e.g. to move through the lines of the last query.
+struct Verifying {
+    /// Scope, mode, inversion and query of the filtering (see `Digger::last_filter`).
+    filter: (Scope, Mode, bool, String),
+    narrowing: bool,
+    /// Set to abort the verification, checked line by line.
+    cancel: Arc<AtomicBool>,
+    verified: thread::JoinHandle<Vec<usize>>,
+}
+
+/// Number of restyled rows kept, beyond which they are restyled again when shown.
+const RESTYLED_CAPACITY: usize = 4096;
+
+/// Time to wait for a key while the lines are verified, before looking whether they all are.
+const VERIFYING_POLL: Duration = Duration::from_millis(20);
+
+impl Digger {
+    /// Shows the scope in the prefix of the text editor.
+    fn update_prefix(&mut self) {
+        let mut scope = match self.scope {
+            Scope::All => String::from("[all]"),
+            Scope::Prefiltered => format!("[bul: {}]", self.bul_query),
+        };
+        if self.mode == Mode::Query {
+            scope.push_str(" [query]");
+        }
+        if self.inverted {
+            scope.push_str(" [inverted]");
+        }
+        if self.aborted {
+            scope.push_str(" [aborted]");
+        }
+        if let Some(reason) = self.literal_fallback {
+            scope.push_str(&format!(" [literal: regex {reason}]"));
+        }
+        // e.g. `error ▸ checkout ▸ ` in front of the query being typed.
+        let chain: String = self
+            .levels
+            .iter()
+            .map(|level| format!("{} ▸ ", level.query))
+            .collect();
+        self.text_editor_snapshot.after_mut().prefix =
+            format!("{} {}{}", scope, chain, self.prefix);
+    }
+
+    /// Makes the lines matching the query the base of a new level with an empty query.
+    /// Returns whether there was a query to push.
+    fn push_level(&mut self) -> bool {
+        // The level is made of the lines matching the query typed, not of the last one shown.
+        self.wait_verified();
+        let texteditor = &mut self.text_editor_snapshot.after_mut().texteditor;
+        let query = texteditor.text_without_cursor().to_string();
+        if query.is_empty() || self.query_error.is_some() {
+            return false;
+        }
+        *texteditor = Default::default();
+        self.levels.push(Level {
+            query,
+            mode: self.mode,
+            inverted: self.inverted,
+            indices: self.indices.clone(),
+        });
+        self.mode = Mode::Literal;
+        self.inverted = false;
+        true
+    }
+
+    /// Drops the innermost level, restoring its query over the lines it was applied to.
+    /// Returns whether there was a level to pop.
+    fn pop_level(&mut self) -> bool {
+        let Some(level) = self.levels.pop() else {
+            return false;
+        };
+        let texteditor = &mut self.text_editor_snapshot.after_mut().texteditor;
+        *texteditor = Default::default();
+        for ch in level.query.chars() {
+            texteditor.insert(ch);
+        }
+        self.mode = level.mode;
+        self.inverted = level.inverted;
+        true
+    }
+
+    /// Starts verifying the lines in the scope against the dig query, to rebuild the listbox
+    /// from the ones which match, or which don't if inverted, once they all are verified.
+    /// The verification of an earlier query is superseded.
+    fn filter(&mut self) {
+        let query = self
+            .text_editor_snapshot
+            .after()
+            .texteditor
+            .text_without_cursor()
+            .to_string();
+        let bul_query = match self.scope {
+            Scope::All => None,
+            Scope::Prefiltered => Some(self.bul_query.clone()),
+        };
+        // In the query mode, the lines are selected by the predicates and not highlighted.
+        let (query, expr) = match self.mode {
+            Mode::Literal => (query, None),
+            Mode::Query => match query::parse(&query, &self.query_limits) {
+                Ok(expr) => (String::new(), Some(expr)),
+                Err(e) => {
+                    self.abort_verifying();
+                    self.query_error = Some(e.to_string());
+                    if self.literal_fallback.take().is_some() {
+                        self.update_prefix();
+                    }
+                    self.last_filter = None;
+                    self.indices.clear();
+                    self.logs_snapshot.after_mut().listbox = listbox::Listbox::default();
+                    return;
+                }
+            },
+        };
+        self.query_error = None;
+
+        // A literal query extending the last one can only match a subset of its lines.
+        // Inverted, it matches a superset, so all the lines are verified.
+        let narrowing = self.mode == Mode::Literal
+            && !self.inverted
+            && self
+                .last_filter
+                .as_ref()
+                .is_some_and(|(scope, mode, inverted, last)| {
+                    *scope == self.scope
+                        && *mode == self.mode
+                        && !inverted
+                        && query.contains(last.as_str())
+                });
+        // Only the lines which may contain the query are verified if the index can tell them.
+        let candidates = if narrowing {
+            self.indices.clone()
+        } else if let Some(level) = self.levels.last() {
+            level.indices.clone()
+        } else if self.inverted {
+            (0..self.log_queue.len()).collect()
+        } else {
+            self.index
+                .candidates(&query)
+                .unwrap_or_else(|| (0..self.log_queue.len()).collect())
+        };
+        let (expr, literal_fallback) =
+            self.time_box(expr, &candidates, &query, bul_query.as_deref());
+        if self.literal_fallback != literal_fallback {
+            self.literal_fallback = literal_fallback;
+            self.update_prefix();
+        }
+
+        self.abort_verifying();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let verified = {
+            let (bodies, log_queue) = (self.bodies.clone(), self.log_queue.clone());
+            let (cancel, query, inverted) = (cancel.clone(), query.clone(), self.inverted);
+            thread::spawn(move || {
+                log_queue.par_filter(candidates, |index, log| {
+                    let body = &bodies[index];
+                    !cancel.load(Ordering::Relaxed)
+                        && bul_query.as_deref().is_none_or(|q| body.contains(q))
+                        && verify(log, body, &query, expr.as_ref()) != inverted
+                })
+            })
+        };
+        self.verifying = Some(Verifying {
+            filter: (self.scope, self.mode, self.inverted, query),
+            narrowing,
+            cancel,
+            verified,
+        });
+    }
+
+    /// Shows the lines once they are all verified against the query.
+    /// Returns whether they were shown.
+    fn verified(&mut self) -> bool {
+        if !self
+            .verifying
+            .as_ref()
+            .is_some_and(|verifying| verifying.verified.is_finished())
+        {
+            return false;
+        }
+        self.wait_verified();
+        true
+    }
+
+    /// Waits for the lines being verified against the query, if any, to show them.
+    fn wait_verified(&mut self) {
+        let Some(verifying) = self.verifying.take() else {
+            return;
+        };
+        let indices = verifying
+            .verified
+            .join()
+            .expect("verifying the lines doesn't panic");
+        self.show(verifying.filter, verifying.narrowing, indices);
+    }
+
+    /// Stops verifying the lines against the query, keeping the lines of the last one shown.
+    /// Returns whether they were being verified.
+    fn abort_verifying(&mut self) -> bool {
+        let Some(verifying) = self.verifying.take() else {
+            return false;
+        };
+        verifying.cancel.store(true, Ordering::Relaxed);
+        // Quick once canceled, as only the lines being verified are finished.
+        let _ = verifying.verified.join();
+        true
+    }
+
+    /// Rebuilds the listbox from the lines verified against the query of the filtering.
+    fn show(&mut self, filter: (Scope, Mode, bool, String), narrowing: bool, indices: Vec<usize>) {
+        if std::mem::take(&mut self.aborted) {
+            self.update_prefix();
+        }
+        self.highlighted.clone_from(&filter.3);
+        self.last_filter = Some(filter);
+
+        // Ring only on the transition from some matches to none, not on every keystroke.
+        if self.bell && indices.is_empty() && !self.indices.is_empty() {
+            let _ = io::stdout().write_all(b"\x07");
+        }
+        debug!(
+            query = self.highlighted,
+            narrowing,
+            matched = indices.len(),
+            "applied dig query"
+        );
+        // The items of the listbox are kept while there are as many lines, e.g. while narrowing
+        // doesn't leave out any, as they are all empty.
+        let listbox = &mut self.logs_snapshot.after_mut().listbox;
+        if listbox.items().len() == indices.len() {
+            listbox.move_to_head();
+        } else {
+            *listbox = listbox::Listbox::from_iter(std::iter::repeat_n("", indices.len()));
+        }
+        self.indices = indices;
+    }
+
+    /// Builds the row of the line at the position in the listbox,
+    /// restyling its body unless it was already.
+    fn row(&self, position: usize) -> StyledGraphemes {
+        let index = self.indices[position];
+        let log = self
+            .log_queue
+            .get(index)
+            .expect("the lines shown are in the queue");
+        let mut restyled = self.restyled.borrow_mut();
+        if restyled.len() >= RESTYLED_CAPACITY && !restyled.contains_key(&index) {
+            restyled.clear();
+        }
+        let body = restyled.entry(index).or_insert_with(|| {
+            let baseline = self.baseline.as_ref().map(|b| b.lock().unwrap());
+            restyle(&log, baseline.as_deref(), &self.style)
+        });
+        let body = if self.highlighted.is_empty() {
+            body.clone()
+        } else {
+            body.clone()
+                .highlight(&self.highlighted, self.style.highlight)
+                .unwrap_or_else(|| body.clone())
+        };
+        let row = log.row(&body, &self.aliases, &self.style);
+        if self.show_received {
+            with_received(&log, row, &self.style)
+        } else {
+            row
+        }
+    }
+
+    /// Verifies the first batch of the candidates against the query,
+    /// and matches its regexes as literals if that takes longer than the limit,
+    /// so that verifying all of them can't freeze dig.
+    /// Returns the query to verify the lines against, and why its regexes are literals, if so.
+    fn time_box(
+        &self,
+        expr: Option<query::Expr>,
+        candidates: &[usize],
+        query: &str,
+        bul_query: Option<&str>,
+    ) -> (Option<query::Expr>, Option<&'static str>) {
+        const FIRST_BATCH: usize = 1024;
+
+        let Some(expr) = expr else {
+            return (None, None);
+        };
+        if expr.has_literal() {
+            return (Some(expr), Some("too large"));
+        }
+        if !expr.has_regex() {
+            return (Some(expr), None);
+        }
+        let started = Instant::now();
+        let slow = candidates.iter().take(FIRST_BATCH).any(|&index| {
+            let body = &self.bodies[index];
+            if bul_query.is_none_or(|q| body.contains(q)) {
+                let log = self
+                    .log_queue
+                    .header(index)
+                    .expect("the candidates are in the queue");
+                verify(&log, body, query, Some(&expr));
+            }
+            started.elapsed() > self.query_limits.first_batch
+        });
+        if slow {
+            debug!(elapsed = ?started.elapsed(), "matching the dig query regexes as literals");
+            (Some(expr.into_literal()), Some("too slow"))
+        } else {
+            (Some(expr), None)
+        }
+    }
+
+    /// Creates the pane of the matching lines from the top of the viewport,
+    /// rather than from the selected line as the listbox does,
+    /// so that the lines below the selected line stay in view as well.
+    fn create_logs_pane(&self, width: u16, height: u16) -> Pane {
+        let state = self.logs_snapshot.after();
+        let position = state.listbox.position();
+        // Leave a row to the text editor.
+        let visible = (height as usize).saturating_sub(1).max(1);
+        let indent = " ".repeat(state.cursor.chars().count());
+        let wrapped = |index: usize| {
+            let cursor = if index == position {
+                state.cursor.as_str()
+            } else {
+                indent.as_str()
+            };
+            wrap(
+                &StyledGraphemes::from_iter([&StyledGraphemes::from(cursor), &self.row(index)]),
+                width as usize,
+                visible,
+                &self.style,
+            )
+        };
+        let mut top = viewport_top(
+            self.top.get(),
+            position,
+            self.indices.len(),
+            visible,
+            self.scrolloff,
+            self.centered,
+        );
+        // Wrapped lines take more than a row, so move down until the selected line fits.
+        while top < position
+            && (top..=position)
+                .map(|index| wrapped(index).len())
+                .sum::<usize>()
+                > visible
+        {
+            top += 1;
+        }
+        self.top.set(top);
+
+        let mut rows: Vec<StyledGraphemes> = Vec::new();
+        for index in top..self.indices.len() {
+            if rows.len() >= visible {
+                break;
+            }
+            rows.extend(wrapped(index));
+        }
+        rows.truncate(visible);
+        Pane::new(rows, 0)
+    }
+
+    /// Creates the pane summarizing when the lines of each container were received,
+    /// and what was received but is not among the lines to dig in.
+    fn create_summary_pane(&self, width: u16, height: u16) -> Pane {
+        let stats = &self.stats;
+        let mut rows = vec![StyledGraphemes::from_str(
+            "Lines received per container (Ctrl+G to go back)",
+            self.style.warning,
+        )];
+        let mut row = |text: String| rows.push(StyledGraphemes::from_str(text, self.style.body));
+        if self.seen.is_empty() {
+            row(String::from("  none"));
+        }
+        // The namespace is only told when there are several.
+        let mut namespaces = self.seen.keys().map(|(namespace, _, _)| namespace);
+        let first = namespaces.next();
+        let namespaced = namespaces.any(|namespace| Some(namespace) != first);
+        for ((namespace, pod, container), span) in self.seen.iter() {
+            let name = if namespaced {
+                format!("{namespace}/{pod}/{container}")
+            } else {
+                format!("{pod}/{container}")
+            };
+            row(format!(
+                "  {name}: {} lines, {} – {}",
+                grouped_digits(span.lines),
+                span.first.format("%H:%M:%S"),
+                span.last.format("%H:%M:%S")
+            ));
+        }
+        rows.push(StyledGraphemes::from_str(
+            "Lines received but not captured",
+            self.style.warning,
+        ));
+        let mut row = |text: String| rows.push(StyledGraphemes::from_str(text, self.style.body));
+        if stats.is_empty() {
+            row(String::from("  none: every line received is here"));
+        }
+        if stats.evicted > 0 {
+            let range = match stats.evicted_range {
+                Some((oldest, newest)) => format!(
+                    ", received {} - {}",
+                    oldest.format("%H:%M:%S"),
+                    newest.format("%H:%M:%S")
+                ),
+                None => String::new(),
+            };
+            row(format!(
+                "  {} evicted over --queue-capacity{range}",
+                stats.evicted
+            ));
+        }
+        for (dropped, why) in [
+            (&stats.replayed, "received again when resumed"),
+            (&stats.blank, "blank, with --skip-empty"),
+            (
+                &stats.overflowed,
+                "over --stream-buffer while the view was busy",
+            ),
+        ] {
+            if dropped.is_empty() {
+                continue;
+            }
+            row(format!("  {} {why}:", dropped.values().sum::<usize>()));
+            for (container, count) in dropped.iter() {
+                row(format!("    {count:>8}  {container}"));
+            }
+        }
+        if !stats.normalized.is_empty() {
+            row(format!(
+                "  {} captured with a trailing \\r or whitespace stripped",
+                stats.normalized.values().sum::<usize>()
+            ));
+        }
+
+        let mut state = self.logs_snapshot.after().clone();
+        state.cursor = String::new();
+        state.listbox = listbox::Listbox::from_iter(rows);
+        state.create_pane(width, height)
+    }
+
+    /// Creates the pane of the most frequent signatures of the matching lines,
+    /// each with its count and the first line with it.
+    fn create_signatures_pane(&self, width: u16, height: u16) -> Pane {
+        let Some((clusters, signatures_snapshot)) = &self.signatures else {
+            return self.create_logs_pane(width, height);
+        };
+        let state = signatures_snapshot.after();
+        let position = state.listbox.position();
+        let mut rows = vec![StyledGraphemes::from_str(
+            format!(
+                "Signatures of the {} matching lines (Enter to query, Ctrl+O to go back)",
+                grouped_digits(self.indices.len())
+            ),
+            self.style.warning,
+        )];
+        if clusters.is_empty() {
+            rows.push(StyledGraphemes::from_str("  none", self.style.body));
+        }
+        // Two rows per signature, below the heading and above the text editor.
+        let visible = ((height as usize).saturating_sub(2) / 2).max(1);
+        let top = viewport_top(0, position, clusters.len(), visible, 0, false);
+        let indent = " ".repeat(state.cursor.chars().count());
+        for (index, cluster) in clusters.iter().enumerate().skip(top).take(visible) {
+            let cursor = if index == position {
+                state.cursor.as_str()
+            } else {
+                indent.as_str()
+            };
+            rows.push(StyledGraphemes::from_str(
+                format!(
+                    "{cursor}{:>8}  {}",
+                    grouped_digits(cluster.count),
+                    cluster.signature
+                ),
+                self.style.body,
+            ));
+            rows.push(StyledGraphemes::from_str(
+                format!("{indent}{:>8}  e.g. {}", "", self.bodies[cluster.example]),
+                self.style.dim,
+            ));
+        }
+
+        let mut state = state.clone();
+        state.cursor = String::new();
+        state.listbox = listbox::Listbox::from_iter(rows);
+        state.create_pane(width, height)
+    }
+
+    /// Creates the pane telling that nothing matched the query,
+    /// so that an empty result isn't mistaken for a blank screen.
+    fn create_placeholder_pane(&self, width: u16, height: u16) -> Pane {
+        let query = self
+            .text_editor_snapshot
+            .after()
+            .texteditor
+            .text_without_cursor()
+            .to_string();
+        let (text, style) = match &self.query_error {
+            Some(e) => (format!("invalid query: {e}"), self.style.error),
+            None => {
+                let mut text = if self.inverted {
+                    format!(
+                        "nothing filtered out by '{}' (searched {} lines)",
+                        query,
+                        self.log_queue.len()
+                    )
+                } else {
+                    format!(
+                        "no matches for '{}' (searched {} lines)",
+                        query,
+                        self.log_queue.len()
+                    )
+                };
+                // The stream is only known when the CRI prefix is parsed.
+                if self.mode == Mode::Query
+                    && query.contains("stream")
+                    && self.log_queue.sources().all(|log| log.stream.is_none())
+                {
+                    text.push_str(", stream is unknown without --raw-cri");
+                }
+                (text, self.style.dim)
+            }
+        };
+        let padding = (width as usize).saturating_sub(text.chars().count()) / 2;
+
+        let mut state = self.logs_snapshot.after().clone();
+        state.cursor = String::new();
+        state.listbox = listbox::Listbox::from_iter([StyledGraphemes::from_str(
+            format!("{}{}", " ".repeat(padding), text),
+            style,
+        )]);
+        state.create_pane(width, height)
+    }
+
+    /// Completes the term before the cursor in the query mode, or shows its next candidate.
+    /// Returns whether there was anything to complete.
+    fn complete(&mut self) -> bool {
+        let texteditor = &self.text_editor_snapshot.after().texteditor;
+        let query = texteditor.text_without_cursor().to_string();
+        // Only the term at the end of the query is completed.
+        if self.mode != Mode::Query || texteditor.position() != query.chars().count() {
+            return false;
+        }
+        match &mut self.completion {
+            Some(completion) if completion.is_for(&query) => completion.cycle(),
+            _ => self.completion = Completion::start(&query, &self.values),
+        }
+        self.completion.is_some()
+    }
+
+    /// Inserts the rest of the completion shown at the cursor.
+    /// Returns whether there was a completion shown.
+    fn accept_completion(&mut self) -> bool {
+        let Some(completion) = self.completion.take() else {
+            return false;
+        };
+        let texteditor = &mut self.text_editor_snapshot.after_mut().texteditor;
+        for ch in completion.remainder().chars() {
+            texteditor.insert(ch);
+        }
+        true
+    }
+
+    /// Inserts the first word of the selected line at the cursor, or replaces the word
+    /// picked last with the next one of the same line if the query wasn't edited since.
+    fn pick_word(&mut self) {
+        let Some(&line) = self
+            .indices
+            .get(self.logs_snapshot.after().listbox.position())
+        else {
+            return;
+        };
+        let breaks = &self.text_editor_snapshot.after().word_break_chars;
+        let words = words::words(&self.bodies[line], breaks);
+        if words.is_empty() {
+            return;
+        }
+        let texteditor = &mut self.text_editor_snapshot.after_mut().texteditor;
+        let query = texteditor.text_without_cursor().to_string();
+        let word = match self.picked.take() {
+            Some(picked) if picked.line == line && picked.query == query => {
+                for _ in 0..picked.len {
+                    texteditor.erase();
+                }
+                (picked.word + 1) % words.len()
+            }
+            _ => 0,
+        };
+        for ch in words[word].chars() {
+            texteditor.insert(ch);
+        }
+        self.picked = Some(Picked {
+            line,
+            word,
+            len: words[word].chars().count(),
+            query: texteditor.text_without_cursor().to_string(),
+        });
+    }
+
+    /// Creates the pane of the text editor with the completion shown dimmed after the cursor,
+    /// which stands on its first character.
+    fn create_completion_pane(&self, completion: &Completion, width: u16, height: u16) -> Pane {
+        let state = self.text_editor_snapshot.after();
+        let remainder = completion.remainder();
+        let split = remainder
+            .char_indices()
+            .nth(1)
+            .map_or(remainder.len(), |(index, _)| index);
+        let (matrix, _) = StyledGraphemes::from_iter([
+            &StyledGraphemes::from_str(&state.prefix, state.prefix_style),
+            &StyledGraphemes::from_str(
+                state.texteditor.text_without_cursor().to_string(),
+                state.inactive_char_style,
+            ),
+            &StyledGraphemes::from_str(&remainder[..split], state.active_char_style),
+            &StyledGraphemes::from_str(&remainder[split..], self.style.dim),
+        ])
+        .matrixify(width as usize, height as usize, 0);
+        Pane::new(matrix, 0)
+    }
+
+    /// Restores the query and modes of the view, before the lines are filtered.
+    fn restore(&mut self, view: View) {
+        let texteditor = &mut self.text_editor_snapshot.after_mut().texteditor;
+        for ch in view.query.chars() {
+            texteditor.insert(ch);
+        }
+        if view.query_mode {
+            self.mode = Mode::Query;
+        }
+        // The prefiltered scope needs the bul query to be there.
+        if !view.prefiltered {
+            self.scope = Scope::All;
+        }
+        self.inverted = view.inverted;
+        self.centered = view.centered;
+    }
+
+    /// Moves the selection to the line at the index into the queue,
+    /// or to the next one matching if it doesn't.
+    fn select(&mut self, index: usize) {
+        let position = self.indices.partition_point(|&matched| matched < index);
+        let listbox = &mut self.logs_snapshot.after_mut().listbox;
+        for _ in 0..position.min(self.indices.len().saturating_sub(1)) {
+            listbox.forward();
+        }
+    }
+
+    /// Clusters the matching lines by their signature to show the most frequent ones.
+    fn open_signatures(&mut self) {
+        let clusters = signature::clusters(&self.bodies, &self.indices, self.top_signatures);
+        let mut state = self.logs_snapshot.after().clone();
+        state.listbox = listbox::Listbox::from_iter(
+            clusters
+                .iter()
+                .map(|cluster| StyledGraphemes::from(cluster.signature.as_str())),
+        );
+        self.signatures = Some((clusters, Snapshot::new(state)));
+    }
+
+    /// Replaces the query with one matching the lines with the selected signature,
+    /// switching to the query mode.
+    fn pick_signature(&mut self) {
+        let Some((clusters, signatures_snapshot)) = self.signatures.take() else {
+            return;
+        };
+        self.keymap.switch("default");
+        let Some(cluster) = clusters.get(signatures_snapshot.after().listbox.position()) else {
+            return;
+        };
+        // Quoted, so that the pattern is a single value whatever its characters.
+        let pattern = signature::pattern(&cluster.signature)
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"");
+        let texteditor = &mut self.text_editor_snapshot.after_mut().texteditor;
+        *texteditor = Default::default();
+        for ch in format!("msg~\"{pattern}\"").chars() {
+            texteditor.insert(ch);
+        }
+        self.mode = Mode::Query;
+        self.completion = None;
+        self.update_prefix();
+        self.filter();
+    }
+
+    /// Builds the context overlay from the lines of the queue around the selected line,
+    /// regardless of whether they match the queries, with the cursor on the selected line.
+    fn open_context(&mut self) {
+        let Some(&index) = self
+            .indices
+            .get(self.logs_snapshot.after().listbox.position())
+        else {
+            return;
+        };
+        let query = self
+            .text_editor_snapshot
+            .after()
+            .texteditor
+            .text_without_cursor()
+            .to_string();
+        let query = match self.mode {
+            Mode::Literal => query,
+            Mode::Query => String::new(),
+        };
+        let start = index.saturating_sub(self.context_lines);
+        let end = (index + self.context_lines + 1).min(self.log_queue.len());
+
+        let baseline = self.baseline.as_ref().map(|b| b.lock().unwrap());
+        let highlight = |body: StyledGraphemes| {
+            body.clone()
+                .highlight(&query, self.style.highlight)
+                .unwrap_or(body)
+        };
+        let mut list = Vec::new();
+        for (position, log) in (start..end).zip(self.log_queue.iter_from(start)) {
+            let body = highlight(restyle(&log, baseline.as_deref(), &self.style));
+            if position != index {
+                list.push(log.row(&body, &self.aliases, &self.style));
+                continue;
+            }
+            // The lines grouped under the selected line are expanded.
+            list.push(StyledGraphemes::from_iter([
+                &log.meta,
+                &StyledGraphemes::from(" "),
+                &body,
+            ]));
+            list.extend(log.continuation.iter().map(|line| highlight(line.clone())));
+        }
+
+        let mut state = self.logs_snapshot.after().clone();
+        state.listbox = listbox::Listbox::from_iter(list);
+        for _ in start..index {
+            state.listbox.forward();
+        }
+        self.context_snapshot = Some(Snapshot::new(state));
+    }
 }
 
 impl promkit::Finalizer for Digger {
-    type Return = ();
+    /// Index of the selected line in the queue if the user asked to go live from there,
+    /// and the view as it was left.
+    type Return = (Option<usize>, View);
 
     fn finalize(&self) -> anyhow::Result<Self::Return> {
-        Ok(())
+        let selected = self
+            .indices
+            .get(self.logs_snapshot.after().listbox.position())
+            .copied();
+        let view = View {
+            query: self
+                .text_editor_snapshot
+                .after()
+                .texteditor
+                .text_without_cursor()
+                .to_string(),
+            query_mode: self.mode == Mode::Query,
+            prefiltered: self.scope == Scope::Prefiltered,
+            inverted: self.inverted,
+            centered: self.centered,
+            selected,
+        };
+        Ok((selected.filter(|_| self.go_live), view))
     }
 }
 
 impl promkit::Renderer for Digger {
     fn create_panes(&self, width: u16, height: u16) -> Vec<Pane> {
         vec![
-            self.logs_snapshot.create_pane(width, height),
-            self.text_editor_snapshot.create_pane(width, height),
+            match &self.context_snapshot {
+                _ if self.show_summary => self.create_summary_pane(width, height),
+                _ if self.signatures.is_some() => self.create_signatures_pane(width, height),
+                Some(context_snapshot) => context_snapshot.create_pane(width, height),
+                None if self.indices.is_empty() => self.create_placeholder_pane(width, height),
+                None => self.create_logs_pane(width, height),
+            },
+            match &self.completion {
+                Some(completion) => self.create_completion_pane(completion, width, height),
+                None => self.text_editor_snapshot.create_pane(width, height),
+            },
         ]
     }
 
     fn evaluate(&mut self, event: &Event) -> anyhow::Result<PromptSignal> {
-        let signal = self.keymap.get()(
+        self.apply_keys(event, || {
+            Ok(if event::poll(Duration::ZERO)? {
+                Some(event::read()?)
+            } else {
+                None
+            })
+        })
+    }
+}
+
+impl Digger {
+    /// Handles the keys until the user quits, like `Prompt::run`,
+    /// but also rendering the lines once they are verified against the query,
+    /// so that the keys stay live while they are.
+    fn prompt(&mut self) -> anyhow::Result<<Self as Finalizer>::Return> {
+        let size = promkit::crossterm::terminal::size()?;
+        let mut terminal = Terminal::start_session(&self.create_panes(size.0, size.1))?;
+        terminal.draw(&self.create_panes(size.0, size.1))?;
+
+        loop {
+            // Errors of `poll`, e.g. without a terminal, end digging as those of `read` do.
+            if self.verifying.is_some() && !event::poll(VERIFYING_POLL)? {
+                if !self.verified() {
+                    continue;
+                }
+            } else {
+                match event::read()? {
+                    Event::Resize(_, _) => {
+                        terminal.position = (0, 0);
+                        execute!(io::stdout(), Clear(ClearType::Purge))?;
+                    }
+                    event => {
+                        if self.evaluate(&event)? == PromptSignal::Quit {
+                            break;
+                        }
+                        self.verified();
+                    }
+                }
+            }
+
+            let size = promkit::crossterm::terminal::size()?;
+            terminal.draw(&self.create_panes(size.0, size.1))?;
+        }
+
+        self.finalize()
+    }
+
+    /// Applies the key, then the keys already pending (e.g. repeated by holding an arrow key)
+    /// before rendering once.
+    fn apply_keys(
+        &mut self,
+        event: &Event,
+        mut unread: impl FnMut() -> std::io::Result<Option<Event>>,
+    ) -> anyhow::Result<PromptSignal> {
+        let mut signal = self.apply(event)?;
+        while matches!(signal, PromptSignal::Continue) {
+            let Some(event) = unread()? else {
+                break;
+            };
+            signal = self.apply(&event)?;
+        }
+        Ok(signal)
+    }
+
+    /// Applies the action of the key to the state.
+    fn apply(&mut self, event: &Event) -> anyhow::Result<PromptSignal> {
+        // Esc aborts the lines being verified, keeping the ones of the last query shown.
+        if matches!(
+            event,
+            Event::Key(KeyEvent {
+                code: KeyCode::Esc,
+                ..
+            })
+        ) && self.abort_verifying()
+        {
+            debug!("aborted dig query");
+            self.aborted = true;
+            self.update_prefix();
+            return Ok(PromptSignal::Continue);
+        }
+        let action = self.keymap.get()(
             event,
             &mut self.text_editor_snapshot,
-            &mut self.logs_snapshot,
-        );
-        if self
+            match (&mut self.context_snapshot, &mut self.signatures) {
+                (Some(context_snapshot), _) => context_snapshot,
+                (None, Some((_, signatures_snapshot))) => signatures_snapshot,
+                (None, None) => &mut self.logs_snapshot,
+            },
+        )?;
+        let action = match action {
+            Action::Complete if self.complete() => Action::Continue,
+            Action::Complete => Action::ToggleContext,
+            Action::PickWord => {
+                self.pick_word();
+                Action::Continue
+            }
+            Action::Forward => {
+                if !self.accept_completion() {
+                    self.text_editor_snapshot.after_mut().texteditor.forward();
+                }
+                Action::Continue
+            }
+            action => action,
+        };
+        if action == Action::ToggleContext {
+            if self.context_snapshot.take().is_some() {
+                self.keymap.switch("default");
+            } else {
+                self.open_context();
+                self.keymap.switch("context");
+            }
+        } else if (action == Action::PushLevel && self.push_level())
+            || (action == Action::PopLevel && self.pop_level())
+        {
+            self.completion = None;
+            self.last_filter = None;
+            self.update_prefix();
+            self.filter();
+        } else if action == Action::ToggleInvert {
+            self.inverted = !self.inverted;
+            self.update_prefix();
+            self.filter();
+        } else if action == Action::ToggleReceived {
+            self.show_received = !self.show_received;
+        } else if action == Action::ToggleSummary {
+            self.show_summary = !self.show_summary;
+        } else if action == Action::ToggleSignatures {
+            if self.signatures.take().is_some() {
+                self.keymap.switch("default");
+            } else {
+                self.open_signatures();
+                self.keymap.switch("signatures");
+            }
+        } else if action == Action::PickSignature {
+            self.pick_signature();
+        } else if action == Action::ToggleCenter {
+            self.centered = !self.centered;
+        } else if action == Action::ToggleMode {
+            self.completion = None;
+            self.mode = match self.mode {
+                Mode::Literal => Mode::Query,
+                Mode::Query => Mode::Literal,
+            };
+            self.update_prefix();
+            self.filter();
+        } else if action == Action::ToggleScope {
+            self.scope = match self.scope {
+                Scope::All => Scope::Prefiltered,
+                Scope::Prefiltered => Scope::All,
+            };
+            self.update_prefix();
+            self.filter();
+        } else if self
             .text_editor_snapshot
             .after()
             .texteditor
@@ -57,62 +1174,788 @@ impl promkit::Renderer for Digger {
                 .texteditor
                 .text_without_cursor()
         {
-            let query = self
-                .text_editor_snapshot
-                .after()
-                .texteditor
-                .text_without_cursor()
-                .to_string();
-
-            let list: Vec<StyledGraphemes> = self
-                .log_queue
-                .par_iter()
-                .filter_map(|log| {
-                    log.body
-                        .clone()
-                        .highlight(
-                            &query,
-                            StyleBuilder::new()
-                                .bgc(Color::Yellow)
-                                .fgc(Color::Black)
-                                .build(),
-                        )
-                        .map(|body| {
-                            StyledGraphemes::from_iter([
-                                &log.meta,
-                                &StyledGraphemes::from(" "),
-                                &body,
-                            ])
-                        })
-                })
-                .collect();
+            self.completion = None;
+            self.filter();
+        }
+
+        match action {
+            Action::Continue
+            | Action::ToggleScope
+            | Action::ToggleContext
+            | Action::ToggleMode
+            | Action::ToggleCenter
+            | Action::ToggleInvert
+            | Action::ToggleReceived
+            | Action::ToggleSummary
+            | Action::ToggleSignatures
+            | Action::PickSignature
+            | Action::Complete
+            | Action::Forward
+            | Action::PickWord
+            | Action::PushLevel
+            | Action::PopLevel => Ok(PromptSignal::Continue),
+            Action::Quit => Ok(PromptSignal::Quit),
+            Action::GoLive => {
+                self.go_live = true;
+                Ok(PromptSignal::Quit)
+            }
+        }
+    }
+}
+
+/// Index of the item at the top of a viewport of `visible` rows over `len` items,
+/// moved from `top` only as far as needed to keep `scrolloff` rows around the cursor
+/// at `position`, or placing the cursor in the middle if `centered`.
+/// Fewer rows are kept at the ends of the list, where there are no more items.
+fn viewport_top(
+    top: usize,
+    position: usize,
+    len: usize,
+    visible: usize,
+    scrolloff: usize,
+    centered: bool,
+) -> usize {
+    let margin = scrolloff.min(visible.saturating_sub(1) / 2);
+    let top = if centered {
+        position.saturating_sub(visible / 2)
+    } else if position < top + margin {
+        position.saturating_sub(margin)
+    } else if position + margin >= top + visible {
+        position + margin + 1 - visible
+    } else {
+        top
+    };
+    top.min(len.saturating_sub(visible))
+}
 
-            self.logs_snapshot.after_mut().listbox = listbox::Listbox::from_iter(list);
+/// Whether the line, whose body is given as text, matches the literal query
+/// and the field predicates, if any.
+fn verify(log: &ContainerLog, body: &str, query: &str, expr: Option<&query::Expr>) -> bool {
+    // A blank line only matches a query asking for something to be there,
+    // not an empty query or only negations, which would match it trivially.
+    if body.trim().is_empty() {
+        return match expr {
+            Some(expr) => expr.is_positive() && expr.matches(log, body),
+            None => !query.is_empty() && body.contains(query),
+        };
+    }
+    expr.is_none_or(|expr| expr.matches(log, body)) && body.contains(query)
+}
+
+/// Formats the number with its digits grouped by thousands, e.g. `4,312`.
+fn grouped_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
         }
-        signal
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Prefixes the row with the time the line was received, or with blanks if unknown.
+fn with_received(log: &ContainerLog, row: StyledGraphemes, style: &StyleConfig) -> StyledGraphemes {
+    let received = log.received.map_or_else(
+        || " ".repeat(12),
+        |at| at.format("%H:%M:%S%.3f").to_string(),
+    );
+    StyledGraphemes::from_iter([
+        &StyledGraphemes::from_str(format!("{received} "), style.dim),
+        &row,
+    ])
+}
+
+/// Restyles the body of the log according to its novelty against the baseline, if any.
+fn restyle(
+    log: &ContainerLog,
+    baseline: Option<&Baseline>,
+    style: &StyleConfig,
+) -> StyledGraphemes {
+    match baseline {
+        Some(baseline) => baseline.classify(log).restyle(&log.body, style),
+        None => log.body.clone(),
     }
 }
 
+/// Runs the digger over the captured logs.
+///
+/// If a query was active in bul, digging starts within the lines matching it,
+/// which can be toggled to all captured lines.
+/// If a view is given, e.g. from a session, dig starts as it was left instead.
+///
+/// Returns the capture given back, the index of the selected line in it
+/// if the user asked to resume live tailing from there, and the view as it was left.
 pub fn run(
     text_editor: text_editor::State,
-    log_queue: VecDeque<ContainerLog>,
-    mut logs: listbox::State,
-) -> anyhow::Result<()> {
-    logs.listbox = listbox::Listbox::from_iter(
-        log_queue
-            .par_iter()
-            .map(|log| {
-                StyledGraphemes::from_iter([&log.meta, &StyledGraphemes::from(" "), &log.body])
-            })
-            .collect::<Vec<StyledGraphemes>>(),
+    capture: Capture,
+    logs: listbox::State,
+    baseline: Option<Arc<Mutex<Baseline>>>,
+    bul_query: String,
+    view: Option<View>,
+    settings: &Settings,
+) -> anyhow::Result<(Capture, Option<usize>, View)> {
+    let mut digger = Digger::new(
+        text_editor,
+        capture,
+        logs,
+        baseline,
+        bul_query,
+        view,
+        settings,
+    );
+    enable_raw_mode()?;
+    execute!(io::stdout(), cursor::Hide)?;
+    let ret = digger.prompt();
+    digger.abort_verifying();
+    // Restored as `Prompt` does when dropped.
+    let _ = execute!(
+        io::stdout(),
+        cursor::Show,
+        event::DisableMouseCapture,
+        cursor::MoveToNextLine(1),
     );
-    Prompt {
-        renderer: Digger {
-            keymap: ActiveKeySwitcher::new("default", keymap::default),
+    let _ = disable_raw_mode();
+    let (anchor, view) = ret?;
+
+    // The verification, holding the other reference to the lines, is done with by now.
+    let capture = Capture {
+        lines: Arc::try_unwrap(digger.log_queue).unwrap_or_else(|lines| (*lines).clone()),
+        stats: digger.stats,
+        seen: digger.seen,
+    };
+    Ok((capture, anchor, view))
+}
+
+impl Digger {
+    fn new(
+        text_editor: text_editor::State,
+        capture: Capture,
+        logs: listbox::State,
+        baseline: Option<Arc<Mutex<Baseline>>>,
+        bul_query: String,
+        view: Option<View>,
+        settings: &Settings,
+    ) -> Self {
+        let Capture {
+            lines: log_queue,
+            stats,
+            seen,
+        } = capture;
+        let bodies = log_queue.messages();
+        let values = Values::new(&log_queue);
+        let mut digger = Digger {
+            keymap: ActiveKeySwitcher::new("default", keymap::default as keymap::Keymap)
+                .register("context", keymap::context as keymap::Keymap)
+                .register("signatures", keymap::signatures as keymap::Keymap),
+            prefix: text_editor.prefix.clone(),
             text_editor_snapshot: Snapshot::new(text_editor),
-            log_queue,
+            index: Index::new(&bodies),
+            bodies: Arc::new(bodies),
+            restyled: RefCell::new(HashMap::new()),
+            highlighted: String::new(),
+            last_filter: None,
+            log_queue: Arc::new(log_queue),
             logs_snapshot: Snapshot::new(logs),
+            indices: Vec::new(),
+            go_live: false,
+            baseline,
+            scope: if bul_query.is_empty() {
+                Scope::All
+            } else {
+                Scope::Prefiltered
+            },
+            bul_query,
+            mode: Mode::Literal,
+            inverted: false,
+            query_error: None,
+            query_limits: settings.query_limits,
+            literal_fallback: None,
+            context_lines: settings.context_lines,
+            bell: settings.bell,
+            scrolloff: settings.scrolloff,
+            centered: false,
+            show_received: false,
+            top: Cell::new(0),
+            aliases: settings.aliases.clone(),
+            style: settings.style.clone(),
+            context_snapshot: None,
+            values,
+            completion: None,
+            levels: Vec::new(),
+            aborted: false,
+            verifying: None,
+            picked: None,
+            stats,
+            seen,
+            show_summary: false,
+            signatures: None,
+            top_signatures: settings.signatures,
+        };
+        let selected = view.as_ref().and_then(|view| view.selected);
+        if let Some(view) = view {
+            digger.restore(view);
+        }
+        digger.update_prefix();
+        digger.filter();
+        digger.wait_verified();
+        if let Some(selected) = selected {
+            digger.select(selected);
+        }
+        digger
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+    use promkit::{
+        crossterm::{
+            event::{KeyCode, KeyEvent, KeyModifiers},
+            style::Color,
         },
+        style::StyleBuilder,
+        Renderer,
+    };
+
+    use super::*;
+    use crate::{allocations, container::LogKind, terminal::grid::Screen};
+
+    fn log(pod: &str, body: &str) -> ContainerLog {
+        ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from(pod),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from_str(pod, StyleBuilder::new().fgc(Color::Cyan).build()),
+            body: StyledGraphemes::from(body),
+            continuation: Vec::new(),
+            received: None,
+            at: None,
+        }
+    }
+
+    fn digger(lines: &[ContainerLog]) -> Digger {
+        digger_with_limits(
+            lines,
+            QueryLimits {
+                size: 1 << 20,
+                dfa_size: 1 << 20,
+                first_batch: Duration::from_millis(100),
+            },
+        )
+    }
+
+    fn digger_with_limits(lines: &[ContainerLog], query_limits: QueryLimits) -> Digger {
+        let style = StyleConfig::default();
+        let text_editor = text_editor::State {
+            texteditor: Default::default(),
+            history: Default::default(),
+            prefix: String::from("❯❯❯ "),
+            mask: Default::default(),
+            prefix_style: style.dig_prefix,
+            active_char_style: style.cursor,
+            inactive_char_style: StyleBuilder::new().build(),
+            edit_mode: Default::default(),
+            word_break_chars: Default::default(),
+            lines: Default::default(),
+        };
+        let logs = listbox::State {
+            listbox: listbox::Listbox::default(),
+            cursor: String::from("❯ "),
+            active_item_style: None,
+            inactive_item_style: None,
+            lines: Default::default(),
+        };
+        let settings = Settings {
+            context_lines: 3,
+            bell: false,
+            scrolloff: 0,
+            query_limits,
+            signatures: 5,
+            aliases: Aliases::default(),
+            style,
+        };
+        let capture = Capture {
+            lines: lines.iter().cloned().collect(),
+            stats: Default::default(),
+            seen: Default::default(),
+        };
+        Digger::new(
+            text_editor,
+            capture,
+            logs,
+            None,
+            String::new(),
+            None,
+            &settings,
+        )
+    }
+
+    fn lines() -> Vec<ContainerLog> {
+        vec![
+            log("api-0", "GET /health 200"),
+            log("api-1", "POST /orders 500"),
+            log("api-0", "GET /orders 200"),
+        ]
+    }
+
+    #[test]
+    fn listbox_with_cursor() {
+        let digger = digger(&lines());
+        let screen = Screen::new(40, 6);
+        screen.draw_panes(&digger.create_panes(40, 6)).unwrap();
+        assert_eq!(
+            screen.rows(),
+            [
+                "❯ api-0 GET /health 200",
+                "  api-1 POST /orders 500",
+                "  api-0 GET /orders 200",
+                "[all] ❯❯❯",
+                "",
+                "",
+            ]
+        );
+        assert_eq!(
+            screen.style_of(3, "❯❯❯"),
+            Some(StyleConfig::default().dig_prefix)
+        );
+    }
+
+    #[test]
+    fn cursor_follows_the_selection() {
+        let mut digger = digger(&lines());
+        digger.select(2);
+        let screen = Screen::new(40, 6);
+        screen.draw_panes(&digger.create_panes(40, 6)).unwrap();
+        assert_eq!(
+            &screen.rows()[..3],
+            [
+                "  api-0 GET /health 200",
+                "  api-1 POST /orders 500",
+                "❯ api-0 GET /orders 200",
+            ]
+        );
+    }
+
+    /// Filters the lines with the query of the query mode,
+    /// returning the bodies of the matching lines.
+    fn query(digger: &mut Digger, query: &str) -> Vec<String> {
+        digger.mode = Mode::Query;
+        for ch in query.chars() {
+            digger
+                .text_editor_snapshot
+                .after_mut()
+                .texteditor
+                .insert(ch);
+        }
+        digger.update_prefix();
+        digger.filter();
+        digger.wait_verified();
+        digger
+            .indices
+            .iter()
+            .map(|&index| digger.bodies[index].clone())
+            .collect()
+    }
+
+    fn prefix(digger: &Digger) -> &str {
+        &digger.text_editor_snapshot.after().prefix
+    }
+
+    #[test]
+    fn regex_over_the_size_limit_is_a_literal() {
+        let mut lines = lines();
+        lines.push(log("api-2", r"matched \w{100}-\w{100} literally"));
+        let mut digger = digger_with_limits(
+            &lines,
+            QueryLimits {
+                size: 1 << 16,
+                dfa_size: 1 << 16,
+                first_batch: Duration::from_secs(60),
+            },
+        );
+        let started = Instant::now();
+        let matched = query(&mut digger, r#"msg~"\\w{100}-\\w{100}""#);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(matched, [r"matched \w{100}-\w{100} literally"]);
+        assert!(prefix(&digger).contains("[literal: regex too large]"));
+    }
+
+    #[test]
+    fn regex_over_the_time_limit_is_a_literal() {
+        let mut lines = lines();
+        lines.push(log("api-2", "GET /orders/[0-9]+ 200"));
+        let limits = QueryLimits {
+            size: 1 << 20,
+            dfa_size: 1 << 20,
+            first_batch: Duration::from_secs(60),
+        };
+        // Under the limit, the regex is a regex.
+        let mut digger = digger_with_limits(&lines, limits);
+        assert!(query(&mut digger, r#"msg~"/orders/[0-9]+""#).is_empty());
+        assert!(!prefix(&digger).contains("[literal"));
+
+        // Any first batch takes longer than no time at all.
+        let mut digger = digger_with_limits(
+            &lines,
+            QueryLimits {
+                first_batch: Duration::ZERO,
+                ..limits
+            },
+        );
+        let matched = query(&mut digger, r#"msg~"/orders/[0-9]+""#);
+        assert_eq!(matched, ["GET /orders/[0-9]+ 200"]);
+        assert!(prefix(&digger).contains("[literal: regex too slow]"));
+    }
+
+    #[test]
+    fn slow_regex_over_many_lines_is_a_literal() {
+        let lines: Vec<ContainerLog> = (0..100_000)
+            .map(|i| {
+                log(
+                    "api-0",
+                    &format!("GET /orders/{i} 200 {}", "héllo wörld ".repeat(16)),
+                )
+            })
+            .collect();
+        let mut digger = digger_with_limits(
+            &lines,
+            QueryLimits {
+                size: 1 << 24,
+                dfa_size: 1 << 10,
+                first_batch: Duration::from_millis(50),
+            },
+        );
+        // Without a lazy DFA to run on, the Unicode classes take minutes over all the lines.
+        let started = Instant::now();
+        let matched = query(&mut digger, r#"msg~"(\\w+\\W+){40}Z""#);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matched.is_empty());
+        assert!(prefix(&digger).contains("[literal: regex too slow]"));
+    }
+
+    /// Filters the lines with the query as it is typed, returning the indices of the matches.
+    fn type_query(digger: &mut Digger, typed: &str) -> Vec<usize> {
+        for ch in typed.chars() {
+            digger
+                .text_editor_snapshot
+                .after_mut()
+                .texteditor
+                .insert(ch);
+        }
+        digger.filter();
+        digger.wait_verified();
+        digger.indices.clone()
+    }
+
+    fn render(digger: &Digger) {
+        digger.create_panes(80, 24);
+    }
+
+    #[test]
+    fn narrowing_reuses_the_restyled_rows() {
+        let mut digger = digger(&[
+            log("api-0", "GET /health 200"),
+            log("api-1", "POST /orders 500"),
+            log("api-0", "GET /orders 200"),
+        ]);
+        // Styled once when dig opens with every line matching and shown.
+        render(&digger);
+        assert_eq!(digger.restyled.borrow().len(), 3);
+        let row = |digger: &Digger| digger.restyled.borrow()[&2].as_slices().0.as_ptr();
+        let styled = row(&digger);
+
+        assert_eq!(type_query(&mut digger, "GET"), [0, 2]);
+        // Only the matches of the last query are verified, and kept as they were styled:
+        // a line which didn't match isn't even looked at again.
+        Arc::make_mut(&mut digger.bodies)[1] = String::from("GET /orders");
+        assert_eq!(type_query(&mut digger, " /orders"), [2]);
+        render(&digger);
+        assert_eq!(row(&digger), styled);
+        assert_eq!(digger.row(0).to_string(), "api-0 GET /orders 200");
+
+        digger.text_editor_snapshot.after_mut().texteditor = Default::default();
+        assert_eq!(type_query(&mut digger, "orders"), [1, 2]);
+        render(&digger);
+        assert_eq!(digger.restyled.borrow().len(), 3);
+        assert_eq!(row(&digger), styled);
+    }
+
+    #[test]
+    fn restyled_rows_are_bounded() {
+        let lines: Vec<ContainerLog> = (0..RESTYLED_CAPACITY + 10)
+            .map(|i| log("api-0", &format!("GET /orders/{i} 200")))
+            .collect();
+        let digger = digger(&lines);
+        for position in 0..lines.len() {
+            digger.row(position);
+            assert!(digger.restyled.borrow().len() <= RESTYLED_CAPACITY);
+        }
+    }
+
+    #[test]
+    fn typing_allocates_for_the_rows_rendered_not_for_the_lines_matching() {
+        let lines: Vec<ContainerLog> = (0..20_000)
+            .map(|i| log("api-0", &format!("GET /orders/{i} 200")))
+            .collect();
+        let mut digger = digger(&lines);
+        render(&digger);
+        let mut counts = Vec::new();
+        for ch in "GET /".chars() {
+            let counted = allocations::count(|| {
+                press(
+                    &mut digger,
+                    Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE)),
+                );
+                render(&digger);
+            });
+            counts.push(counted);
+        }
+        // Every line still matches, yet typing costs about as much as rendering a screen.
+        assert_eq!(digger.indices.len(), lines.len());
+        assert!(counts.iter().all(|&counted| counted < 2_000), "{counts:?}");
+    }
+
+    /// Moves the selection over 20 lines in a list 9 rows high as the keys would,
+    /// returning the line at the top of the list and the row of the selected line.
+    fn scroll(
+        view: &mut (usize, usize),
+        down: bool,
+        times: usize,
+        centered: bool,
+    ) -> (usize, usize) {
+        let (top, position) = view;
+        for _ in 0..times {
+            *position = if down {
+                (*position + 1).min(19)
+            } else {
+                position.saturating_sub(1)
+            };
+            *top = viewport_top(*top, *position, 20, 9, 3, centered);
+        }
+        (*top, *position - *top)
+    }
+
+    #[test]
+    fn lines_kept_around_the_selection() {
+        let mut view = (0, 0);
+        // Near the start, the list stays until 3 lines below the selection are left.
+        assert_eq!(scroll(&mut view, true, 5, false), (0, 5));
+        assert_eq!(scroll(&mut view, true, 1, false), (1, 5));
+        // In the middle, moving back up doesn't scroll until 3 lines above it are left.
+        assert_eq!(scroll(&mut view, true, 4, false), (5, 5));
+        assert_eq!(scroll(&mut view, false, 2, false), (5, 3));
+        assert_eq!(scroll(&mut view, false, 1, false), (4, 3));
+        // Near the end, fewer lines are kept below as there are no more.
+        assert_eq!(scroll(&mut view, true, 12, false), (11, 8));
+    }
+
+    #[test]
+    fn selection_kept_in_the_middle() {
+        let mut view = (0, 0);
+        assert_eq!(scroll(&mut view, true, 2, true), (0, 2));
+        assert_eq!(scroll(&mut view, true, 8, true), (6, 4));
+        assert_eq!(scroll(&mut view, false, 1, true), (5, 4));
+        assert_eq!(scroll(&mut view, true, 10, true), (11, 8));
+    }
+
+    #[test]
+    fn scrolloff_within_a_short_viewport() {
+        // At most the rows which leave the selection in the middle are kept around it.
+        assert_eq!(viewport_top(0, 2, 20, 3, 3, false), 1);
+        assert_eq!(viewport_top(1, 1, 20, 3, 3, false), 0);
+        // A list shorter than the viewport never scrolls.
+        assert_eq!(viewport_top(0, 4, 5, 9, 3, false), 0);
+        assert_eq!(viewport_top(0, 4, 5, 9, 3, true), 0);
+    }
+
+    #[test]
+    fn keys_repeated_fast_are_applied_before_rendering() {
+        let lines: Vec<ContainerLog> = (0..80)
+            .map(|i| log("api-0", &format!("GET /orders/{i} 200")))
+            .collect();
+        let mut digger = digger(&lines);
+        let down = Event::Key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        // Held down, 50 in all.
+        let mut unread = 49;
+        let signal = digger
+            .apply_keys(&down, || {
+                Ok((unread > 0).then(|| {
+                    unread -= 1;
+                    down.clone()
+                }))
+            })
+            .unwrap();
+        assert!(matches!(signal, PromptSignal::Continue));
+        assert_eq!(unread, 0);
+        assert_eq!(digger.logs_snapshot.after().listbox.position(), 50);
+    }
+
+    #[test]
+    fn aborting_the_verification_keeps_the_lines_of_the_last_query() {
+        let lines: Vec<ContainerLog> = (0..20_000)
+            .map(|i| log("api-0", &format!("GET /orders/{i} 200")))
+            .collect();
+        let mut digger = digger(&lines);
+        type_keys(&mut digger, "/orders/1");
+        let shown = digger.indices.clone();
+        assert_eq!(shown.len(), 11_111);
+
+        // Typed while verifying: the keys are applied to the lines shown meanwhile.
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        digger.apply(&key(KeyCode::Char('2'))).unwrap();
+        let cancel = digger.verifying.as_ref().unwrap().cancel.clone();
+        digger.apply(&key(KeyCode::Down)).unwrap();
+        assert_eq!(digger.logs_snapshot.after().listbox.position(), 1);
+
+        digger.apply(&key(KeyCode::Esc)).unwrap();
+        assert!(cancel.load(Ordering::Relaxed));
+        assert!(digger.verifying.is_none());
+        assert_eq!(digger.indices, shown);
+        assert!(prefix(&digger).contains("[aborted]"));
+
+        // The query typed on is verified, clearing the mark.
+        type_keys(&mut digger, "3");
+        assert_eq!(digger.indices.len(), 111);
+        assert!(!prefix(&digger).contains("[aborted]"));
+    }
+
+    fn press(digger: &mut Digger, event: Event) {
+        digger.apply_keys(&event, || Ok(None)).unwrap();
+        digger.wait_verified();
+    }
+
+    fn type_keys(digger: &mut Digger, typed: &str) {
+        for ch in typed.chars() {
+            press(
+                digger,
+                Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE)),
+            );
+        }
+    }
+
+    fn ctrl(ch: char) -> Event {
+        Event::Key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::CONTROL))
+    }
+
+    fn typed(digger: &Digger) -> String {
+        digger
+            .text_editor_snapshot
+            .after()
+            .texteditor
+            .text_without_cursor()
+            .to_string()
+    }
+
+    #[test]
+    fn digging_within_the_matches() {
+        let mut lines = lines();
+        lines.push(log("api-2", "GET /checkout 200"));
+        let mut digger = digger(&lines);
+        type_keys(&mut digger, "GET");
+        assert_eq!(digger.indices, [0, 2, 3]);
+
+        press(&mut digger, ctrl('d'));
+        assert_eq!(typed(&digger), "");
+        assert_eq!(prefix(&digger), "[all] GET ▸ ❯❯❯ ");
+        assert_eq!(digger.indices, [0, 2, 3]);
+        // Only the lines matching the pushed query are filtered, even by a query
+        // which matches the others as well.
+        type_keys(&mut digger, "/");
+        assert_eq!(digger.indices, [0, 2, 3]);
+        type_keys(&mut digger, "orders");
+        assert_eq!(digger.indices, [2]);
+
+        press(&mut digger, ctrl('d'));
+        assert_eq!(prefix(&digger), "[all] GET ▸ /orders ▸ ❯❯❯ ");
+        assert_eq!(digger.indices, [2]);
+    }
+
+    #[test]
+    fn popping_back_restores_the_queries() {
+        let mut digger = digger(&lines());
+        press(&mut digger, ctrl('t'));
+        type_keys(&mut digger, "pod=api-0");
+        press(&mut digger, ctrl('d'));
+        // Each level starts over as a literal.
+        assert!(digger.mode == Mode::Literal);
+        type_keys(&mut digger, "orders");
+        assert_eq!(digger.indices, [2]);
+        press(&mut digger, ctrl('d'));
+
+        press(&mut digger, ctrl('b'));
+        assert_eq!(typed(&digger), "orders");
+        assert_eq!(prefix(&digger), "[all] pod=api-0 ▸ ❯❯❯ ");
+        assert_eq!(digger.indices, [2]);
+
+        press(&mut digger, ctrl('b'));
+        assert_eq!(typed(&digger), "pod=api-0");
+        assert!(digger.mode == Mode::Query);
+        assert_eq!(prefix(&digger), "[all] [query] ❯❯❯ ");
+        assert_eq!(digger.indices, [0, 2]);
+    }
+
+    #[test]
+    fn nothing_to_push_or_pop() {
+        let mut digger = digger(&lines());
+        // Popping the root keeps the query.
+        type_keys(&mut digger, "GET");
+        press(&mut digger, ctrl('b'));
+        assert_eq!(typed(&digger), "GET");
+        assert_eq!(prefix(&digger), "[all] ❯❯❯ ");
+        assert_eq!(digger.indices, [0, 2]);
+
+        // An empty query isn't pushed.
+        digger.text_editor_snapshot.after_mut().texteditor = Default::default();
+        press(&mut digger, ctrl('d'));
+        assert!(digger.levels.is_empty());
+        assert_eq!(prefix(&digger), "[all] ❯❯❯ ");
+    }
+
+    #[test]
+    fn blank_lines_match_only_queries_asking_for_something() {
+        let mut digger = digger(&[
+            log("api-0", "GET /health 200"),
+            log("api-0", ""),
+            log("api-1", "   "),
+        ]);
+        // Not by an empty query, which would match them trivially.
+        assert_eq!(digger.indices, [0]);
+        press(&mut digger, ctrl('n'));
+        assert_eq!(digger.indices, [1, 2]);
+        press(&mut digger, ctrl('n'));
+
+        press(&mut digger, ctrl('t'));
+        type_keys(&mut digger, "NOT msg~healthz");
+        assert_eq!(digger.indices, [0]);
+        digger.text_editor_snapshot.after_mut().texteditor = Default::default();
+        type_keys(&mut digger, "pod=api-0");
+        assert_eq!(digger.indices, [0, 1]);
+    }
+
+    #[test]
+    fn received_time_in_front_of_the_lines() {
+        let mut lines = lines();
+        lines[0].received = Some(Local.with_ymd_and_hms(2026, 10, 16, 14, 2, 13).unwrap());
+        let mut digger = digger(&lines);
+        press(&mut digger, ctrl('r'));
+        let screen = Screen::new(50, 4);
+        screen.draw_panes(&digger.create_panes(50, 4)).unwrap();
+        assert_eq!(
+            &screen.rows()[..2],
+            [
+                "❯ 14:02:13.000 api-0 GET /health 200",
+                // Lines received before the live view kept the time are blank there.
+                "               api-1 POST /orders 500",
+            ]
+        );
+
+        press(&mut digger, ctrl('r'));
+        screen.draw_panes(&digger.create_panes(50, 4)).unwrap();
+        assert_eq!(screen.rows()[0], "❯ api-0 GET /health 200");
     }
-    .run()
 }
@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+
+use crate::queue::Lines;
+
+/// Distinct values of the fields which complete in the query mode,
+/// collected from the lines captured when dig was entered.
+pub struct Values {
+    namespaces: BTreeSet<String>,
+    pods: BTreeSet<String>,
+    containers: BTreeSet<String>,
+}
+
+impl Values {
+    pub fn new(log_queue: &Lines) -> Self {
+        let mut values = Self {
+            namespaces: BTreeSet::new(),
+            pods: BTreeSet::new(),
+            containers: BTreeSet::new(),
+        };
+        // Annotations have no Pod or container to complete.
+        for log in log_queue.sources().filter(|log| !log.pod.is_empty()) {
+            if !values.namespaces.contains(&log.namespace) {
+                values.namespaces.insert(log.namespace.clone());
+            }
+            if !values.pods.contains(&log.pod) {
+                values.pods.insert(log.pod.clone());
+            }
+            if !values.containers.contains(&log.container) {
+                values.containers.insert(log.container.clone());
+            }
+        }
+        values
+    }
+
+    fn of(&self, field: &str) -> Vec<&str> {
+        match field {
+            "namespace" | "ns" => self.namespaces.iter().map(String::as_str).collect(),
+            "pod" => self.pods.iter().map(String::as_str).collect(),
+            "container" => self.containers.iter().map(String::as_str).collect(),
+            "stream" => vec!["stderr", "stdout"],
+            "type" => vec!["event", "log", "marker", "rollout", "termination"],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Completion of the `FIELD:VALUE` term before the cursor,
+/// whose candidates are cycled with Tab and shown dimmed after the cursor until accepted.
+///
+/// It only lives as long as the query stays the same, and restarts from the first candidate
+/// once the term is edited.
+pub struct Completion {
+    /// Query when the completion started.
+    query: String,
+    /// Rest of each value starting with the typed part of the term.
+    remainders: Vec<String>,
+    selected: usize,
+}
+
+impl Completion {
+    /// Starts completing the last term of the query if it's `FIELD:PREFIX` of a field
+    /// with values to complete, showing the first candidate.
+    /// Returns `None` if there is no candidate other than the term itself.
+    pub fn start(query: &str, values: &Values) -> Option<Self> {
+        let term = query
+            .rsplit(|ch: char| ch.is_whitespace() || ch == '(')
+            .next()
+            .unwrap_or_default();
+        let (field, prefix) = term.split_once(':')?;
+        let remainders: Vec<String> = values
+            .of(field)
+            .into_iter()
+            .filter_map(|value| value.strip_prefix(prefix))
+            .filter(|remainder| !remainder.is_empty())
+            .map(String::from)
+            .collect();
+        if remainders.is_empty() {
+            return None;
+        }
+        Some(Self {
+            query: query.to_string(),
+            remainders,
+            selected: 0,
+        })
+    }
+
+    /// Whether the completion was started for the query as it is now.
+    pub fn is_for(&self, query: &str) -> bool {
+        self.query == query
+    }
+
+    /// Shows the next candidate, wrapping around to the first one.
+    pub fn cycle(&mut self) {
+        self.selected = (self.selected + 1) % self.remainders.len();
+    }
+
+    /// Text to insert at the cursor to accept the shown candidate.
+    pub fn remainder(&self) -> &str {
+        &self.remainders[self.selected]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::grapheme::StyledGraphemes;
+
+    use crate::container::{ContainerLog, LogKind};
+
+    use super::*;
+
+    fn values() -> Values {
+        let lines: Lines = ["api-0", "api-1", "worker-0"]
+            .into_iter()
+            .map(|pod| ContainerLog {
+                namespace: String::from("default"),
+                pod: String::from(pod),
+                container: String::from("api"),
+                restarts: 0,
+                stream: None,
+                kind: LogKind::Log,
+                meta: StyledGraphemes::from(pod),
+                body: StyledGraphemes::from("GET /orders 200"),
+                continuation: Vec::new(),
+                received: None,
+                at: None,
+            })
+            .collect();
+        Values::new(&lines)
+    }
+
+    /// Candidates in the order they are cycled, starting from the one shown first.
+    fn candidates(completion: &mut Completion) -> Vec<String> {
+        let mut candidates = Vec::new();
+        loop {
+            candidates.push(completion.remainder().to_string());
+            completion.cycle();
+            if completion.remainder() == candidates[0] {
+                return candidates;
+            }
+        }
+    }
+
+    #[test]
+    fn values_starting_with_the_typed_part_are_completed() {
+        let values = values();
+        let mut completion = Completion::start("pod:api", &values).unwrap();
+        assert_eq!(candidates(&mut completion), ["-0", "-1"]);
+        let mut completion = Completion::start("pod:", &values).unwrap();
+        assert_eq!(candidates(&mut completion), ["api-0", "api-1", "worker-0"]);
+        let mut completion = Completion::start("type:t", &values).unwrap();
+        assert_eq!(candidates(&mut completion), ["ermination"]);
+
+        // Nothing to complete other than the term itself.
+        for query in ["pod:worker-0", "pod:db", "level:", "api", ""] {
+            assert!(Completion::start(query, &values).is_none(), "{query}");
+        }
+    }
+
+    #[test]
+    fn cycling_wraps_around_to_the_first_candidate() {
+        let mut completion = Completion::start("pod:api", &values()).unwrap();
+        assert_eq!(completion.remainder(), "-0");
+        completion.cycle();
+        assert_eq!(completion.remainder(), "-1");
+        completion.cycle();
+        assert_eq!(completion.remainder(), "-0");
+    }
+
+    #[test]
+    fn completion_is_only_for_the_query_it_started_with() {
+        let completion = Completion::start("pod:api", &values()).unwrap();
+        assert!(completion.is_for("pod:api"));
+        assert!(!completion.is_for("pod:api-"));
+        assert!(!completion.is_for("pod:ap"));
+
+        // Once the term is edited, it restarts from the first candidate of the new one.
+        let mut completion = Completion::start("pod:api-", &values()).unwrap();
+        assert_eq!(completion.remainder(), "0");
+        completion.cycle();
+        assert_eq!(completion.remainder(), "1");
+        let completion = Completion::start("pod:api-1 OR pod:a", &values()).unwrap();
+        assert_eq!(completion.remainder(), "pi-0");
+    }
+
+    #[test]
+    fn the_last_term_is_completed_after_whitespace_or_parenthesis() {
+        let values = values();
+        for query in [
+            "ERROR pod:w",
+            "ERROR\tpod:w",
+            "(pod:w",
+            "NOT (container:api AND pod:w",
+        ] {
+            let completion = Completion::start(query, &values).unwrap();
+            assert_eq!(completion.remainder(), "orker-0", "{query}");
+        }
+        // The term before the cursor is done once followed by whitespace.
+        assert!(Completion::start("pod:w ", &values).is_none());
+        assert!(Completion::start("pod:w)", &values).is_none());
+    }
+}
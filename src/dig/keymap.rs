@@ -2,20 +2,22 @@ use promkit::{
     crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers},
     listbox,
     snapshot::Snapshot,
-    text_editor, PromptSignal,
+    text_editor,
 };
 
+use super::Action;
+
 pub type Keymap = fn(
     &Event,
     &mut Snapshot<text_editor::State>,
     &mut Snapshot<listbox::State>,
-) -> anyhow::Result<PromptSignal>;
+) -> anyhow::Result<Action>;
 
 pub fn default(
     event: &Event,
     text_editor_snapshot: &mut Snapshot<text_editor::State>,
     logs_snapshot: &mut Snapshot<listbox::State>,
-) -> anyhow::Result<PromptSignal> {
+) -> anyhow::Result<Action> {
     let text_editor_state = text_editor_snapshot.after_mut();
     let logs_state = logs_snapshot.after_mut();
 
@@ -25,7 +27,91 @@ pub fn default(
             modifiers: KeyModifiers::CONTROL,
             kind: KeyEventKind::Press,
             state: KeyEventState::NONE,
-        }) => return Ok(PromptSignal::Quit),
+        }) => return Ok(Action::Quit),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::GoLive),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('s'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::ToggleScope),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('t'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::ToggleMode),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('l'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::ToggleCenter),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('n'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::ToggleInvert),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('r'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::ToggleReceived),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('g'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::ToggleSummary),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('o'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::ToggleSignatures),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Tab,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::Complete),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('w'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::PickWord),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('d'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::PushLevel),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('b'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::PopLevel),
 
         Event::Key(KeyEvent {
             code: KeyCode::Char('c'),
@@ -48,9 +134,7 @@ pub fn default(
             modifiers: KeyModifiers::NONE,
             kind: KeyEventKind::Press,
             state: KeyEventState::NONE,
-        }) => {
-            text_editor_state.texteditor.forward();
-        }
+        }) => return Ok(Action::Forward),
         Event::Key(KeyEvent {
             code: KeyCode::Char('a'),
             modifiers: KeyModifiers::CONTROL,
@@ -115,5 +199,116 @@ pub fn default(
 
         _ => (),
     }
-    Ok(PromptSignal::Continue)
+    Ok(Action::Continue)
+}
+
+/// Keymap while the context of the selected line is shown,
+/// in which only scrolling through the context is allowed.
+pub fn context(
+    event: &Event,
+    _: &mut Snapshot<text_editor::State>,
+    context_snapshot: &mut Snapshot<listbox::State>,
+) -> anyhow::Result<Action> {
+    let context_state = context_snapshot.after_mut();
+
+    match event {
+        Event::Key(KeyEvent {
+            code: KeyCode::Tab,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        })
+        | Event::Key(KeyEvent {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::ToggleContext),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('c'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Err(anyhow::anyhow!("ctrl-c")),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Up,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => {
+            context_state.listbox.backward();
+        }
+        Event::Key(KeyEvent {
+            code: KeyCode::Down,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => {
+            context_state.listbox.forward();
+        }
+
+        _ => (),
+    }
+    Ok(Action::Continue)
+}
+
+/// Keymap while the signatures of the matching lines are shown,
+/// in which only picking one of them is allowed.
+pub fn signatures(
+    event: &Event,
+    _: &mut Snapshot<text_editor::State>,
+    signatures_snapshot: &mut Snapshot<listbox::State>,
+) -> anyhow::Result<Action> {
+    let signatures_state = signatures_snapshot.after_mut();
+
+    match event {
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('o'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        })
+        | Event::Key(KeyEvent {
+            code: KeyCode::Esc,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::ToggleSignatures),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Enter,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Ok(Action::PickSignature),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Char('c'),
+            modifiers: KeyModifiers::CONTROL,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => return Err(anyhow::anyhow!("ctrl-c")),
+
+        Event::Key(KeyEvent {
+            code: KeyCode::Up,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => {
+            signatures_state.listbox.backward();
+        }
+        Event::Key(KeyEvent {
+            code: KeyCode::Down,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }) => {
+            signatures_state.listbox.forward();
+        }
+
+        _ => (),
+    }
+    Ok(Action::Continue)
 }
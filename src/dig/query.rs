@@ -0,0 +1,571 @@
+use std::{iter::Peekable, str::Chars, time::Duration};
+
+use chrono::NaiveTime;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    container::{ContainerLog, LogKind, LogStream},
+    field,
+};
+
+/// Limits on the regexes of a query, beyond which they are matched as literals
+/// rather than freezing dig, e.g. with huge alternations or many Unicode classes.
+#[derive(Clone, Copy)]
+pub struct Limits {
+    /// Size limit of a compiled regex, in bytes.
+    pub size: usize,
+    /// Size limit of the cache of the lazy DFA of a regex, in bytes.
+    pub dfa_size: usize,
+    /// Time the first batch of lines may take to verify, see `Digger::filter`.
+    pub first_batch: Duration,
+}
+
+/// Query with field predicates such as `level=error AND pod~api AND NOT msg~"healthz"`.
+///
+/// - `FIELD=VALUE` and `FIELD!=VALUE` compare the field with the value.
+/// - `FIELD~REGEX` matches the field with the regex.
+/// - `received>TIME` and `received<TIME` compare the time bul received the line
+///   with a time of day as `HH:MM` or `HH:MM:SS`.
+/// - `FIELD:VALUE` is a shorthand of `FIELD=VALUE` for the fields other than the parsed ones,
+///   whose values complete with Tab.
+/// - A bare `VALUE` is a substring of the message.
+/// - Predicates are combined with `AND` (also implied between adjacent predicates),
+///   `OR`, `NOT` and parentheses.
+///
+/// The fields are `namespace`, `pod`, `container`, `msg`, `stream` (`stdout` or `stderr`,
+/// known only with `--raw-cri`), `type` (`log`, `rollout`, `event`, `termination` or `marker`)
+/// and `received` (`HH:MM:SS`); any other field is looked up in the message
+/// as `key=value` (logfmt) or `"key":"value"` (JSON).
+pub enum Expr {
+    All,
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Predicate(Field, Op),
+}
+
+pub enum Field {
+    Namespace,
+    Pod,
+    Container,
+    Message,
+    Stream,
+    Type,
+    /// Time of day the line was received by bul.
+    Received,
+    /// A field in the message, captured by the regex.
+    Parsed(Regex),
+}
+
+pub enum Op {
+    Eq(String),
+    NotEq(String),
+    Match(Regex),
+    Contains(String),
+    /// Regex matched as a literal, as it exceeded the limits.
+    Literal(String),
+    After(NaiveTime),
+    Before(NaiveTime),
+}
+
+impl Expr {
+    /// Whether the log matches, given its body as text.
+    pub fn matches(&self, log: &ContainerLog, message: &str) -> bool {
+        self.eval(log, message)
+    }
+
+    /// Whether the expression asks for something to be there,
+    /// rather than only for something not to be (e.g. `NOT msg~healthz`) or for nothing.
+    pub fn is_positive(&self) -> bool {
+        match self {
+            Expr::All | Expr::Not(_) | Expr::Predicate(_, Op::NotEq(_)) => false,
+            Expr::And(lhs, rhs) => lhs.is_positive() || rhs.is_positive(),
+            Expr::Or(lhs, rhs) => lhs.is_positive() && rhs.is_positive(),
+            Expr::Predicate(..) => true,
+        }
+    }
+
+    /// Whether any predicate matches a regex.
+    pub fn has_regex(&self) -> bool {
+        match self {
+            Expr::All => false,
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.has_regex() || rhs.has_regex(),
+            Expr::Not(expr) => expr.has_regex(),
+            Expr::Predicate(_, op) => matches!(op, Op::Match(_)),
+        }
+    }
+
+    /// Whether any regex is matched as a literal.
+    pub fn has_literal(&self) -> bool {
+        match self {
+            Expr::All => false,
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.has_literal() || rhs.has_literal(),
+            Expr::Not(expr) => expr.has_literal(),
+            Expr::Predicate(_, op) => matches!(op, Op::Literal(_)),
+        }
+    }
+
+    /// Matches all the regexes as literals.
+    pub fn into_literal(self) -> Expr {
+        match self {
+            Expr::And(lhs, rhs) => {
+                Expr::And(Box::new(lhs.into_literal()), Box::new(rhs.into_literal()))
+            }
+            Expr::Or(lhs, rhs) => {
+                Expr::Or(Box::new(lhs.into_literal()), Box::new(rhs.into_literal()))
+            }
+            Expr::Not(expr) => Expr::Not(Box::new(expr.into_literal())),
+            Expr::Predicate(field, Op::Match(regex)) => {
+                Expr::Predicate(field, Op::Literal(regex.as_str().to_string()))
+            }
+            expr => expr,
+        }
+    }
+
+    fn eval(&self, log: &ContainerLog, message: &str) -> bool {
+        match self {
+            Expr::All => true,
+            Expr::And(lhs, rhs) => lhs.eval(log, message) && rhs.eval(log, message),
+            Expr::Or(lhs, rhs) => lhs.eval(log, message) || rhs.eval(log, message),
+            Expr::Not(expr) => !expr.eval(log, message),
+            Expr::Predicate(Field::Received, Op::After(time)) => {
+                log.received.is_some_and(|received| received.time() > *time)
+            }
+            Expr::Predicate(Field::Received, Op::Before(time)) => {
+                log.received.is_some_and(|received| received.time() < *time)
+            }
+            Expr::Predicate(field, op) => {
+                let value = match field {
+                    Field::Namespace => Some(log.namespace.as_str()),
+                    Field::Pod => Some(log.pod.as_str()),
+                    Field::Container => Some(log.container.as_str()),
+                    Field::Message => Some(message),
+                    Field::Stream => log.stream.map(|stream| match stream {
+                        LogStream::Stdout => "stdout",
+                        LogStream::Stderr => "stderr",
+                    }),
+                    Field::Type => Some(match log.kind {
+                        LogKind::Log => "log",
+                        LogKind::Rollout => "rollout",
+                        LogKind::Event => "event",
+                        LogKind::Termination => "termination",
+                        LogKind::Marker => "marker",
+                    }),
+                    Field::Received => None,
+                    Field::Parsed(pattern) => field::value(pattern, message),
+                };
+                let received = match field {
+                    Field::Received => log
+                        .received
+                        .map(|received| received.format("%H:%M:%S").to_string()),
+                    _ => None,
+                };
+                let value = value.or(received.as_deref());
+                // Lines without the field match no predicate on it.
+                value.is_some_and(|value| match op {
+                    Op::Eq(expected) => value == expected,
+                    Op::NotEq(expected) => value != expected,
+                    Op::Match(regex) => regex.is_match(value),
+                    Op::Contains(part) | Op::Literal(part) => value.contains(part.as_str()),
+                    // Only the received time is ordered, see `Parser::predicate`.
+                    Op::After(_) | Op::Before(_) => false,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    Eq,
+    NotEq,
+    Match,
+    Gt,
+    Lt,
+    Word(String),
+    Quoted(String),
+}
+
+fn tokenize(query: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<Chars> = query.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ch if ch.is_whitespace() => (),
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '=' => tokens.push(Token::Eq),
+            '~' => tokens.push(Token::Match),
+            // Ordered only after `received`, so that e.g. `->` is still a word.
+            '>' if received_before(&tokens) => tokens.push(Token::Gt),
+            '<' if received_before(&tokens) => tokens.push(Token::Lt),
+            '!' if chars.peek() == Some(&'=') => {
+                chars.next();
+                tokens.push(Token::NotEq);
+            }
+            '"' => {
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => quoted.push(escaped),
+                            None => return Err(anyhow::anyhow!("unterminated string")),
+                        },
+                        Some(ch) => quoted.push(ch),
+                        None => return Err(anyhow::anyhow!("unterminated string")),
+                    }
+                }
+                tokens.push(Token::Quoted(quoted));
+            }
+            ch => {
+                let mut word = String::from(ch);
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "()=~\"".contains(next) {
+                        break;
+                    }
+                    if "<>".contains(next) && word == "received" {
+                        break;
+                    }
+                    if next == '!' {
+                        let mut lookahead = chars.clone();
+                        lookahead.next();
+                        if lookahead.peek() == Some(&'=') {
+                            break;
+                        }
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Whether the last token is the `received` field, which `>` and `<` compare.
+fn received_before(tokens: &[Token]) -> bool {
+    matches!(tokens.last(), Some(Token::Word(word)) if word == "received")
+}
+
+/// Parses the query. An empty query matches every line.
+/// Regexes exceeding the size limits are matched as literals, see `Expr::has_literal`.
+pub fn parse(query: &str, limits: &Limits) -> anyhow::Result<Expr> {
+    let tokens = tokenize(query)?;
+    if tokens.is_empty() {
+        return Ok(Expr::All);
+    }
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        limits: *limits,
+    };
+    let expr = parser.or()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(Token::RParen) => Err(anyhow::anyhow!("unbalanced ')'")),
+        Some(_) => Err(anyhow::anyhow!(
+            "unexpected token at {}",
+            parser.position + 1
+        )),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    limits: Limits,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word == keyword)
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.and()?;
+        while self.is_keyword("OR") {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.unary()?;
+        loop {
+            if self.is_keyword("AND") {
+                self.next();
+            } else if self.peek().is_none()
+                || self.is_keyword("OR")
+                || self.peek() == Some(&Token::RParen)
+            {
+                break;
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        if self.is_keyword("NOT") {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(anyhow::anyhow!("missing ')'")),
+                }
+            }
+            Some(Token::Word(word)) => match self.peek() {
+                Some(Token::Eq | Token::NotEq | Token::Match | Token::Gt | Token::Lt) => {
+                    self.predicate(&word)
+                }
+                _ => {
+                    let shorthand = word.split_once(':').and_then(|(name, value)| {
+                        let field = match name {
+                            "namespace" | "ns" => Field::Namespace,
+                            "pod" => Field::Pod,
+                            "container" => Field::Container,
+                            "stream" => Field::Stream,
+                            "type" => Field::Type,
+                            _ => return None,
+                        };
+                        Some(Expr::Predicate(field, Op::Eq(value.to_string())))
+                    });
+                    Ok(shorthand
+                        .unwrap_or_else(|| Expr::Predicate(Field::Message, Op::Contains(word))))
+                }
+            },
+            Some(Token::Quoted(quoted)) => {
+                Ok(Expr::Predicate(Field::Message, Op::Contains(quoted)))
+            }
+            Some(_) => Err(anyhow::anyhow!("unexpected token at {}", self.position)),
+            None => Err(anyhow::anyhow!("unexpected end of query")),
+        }
+    }
+
+    fn predicate(&mut self, name: &str) -> anyhow::Result<Expr> {
+        let op = self.next();
+        let value = match self.next() {
+            Some(Token::Word(value) | Token::Quoted(value)) => value,
+            _ => return Err(anyhow::anyhow!("missing value for '{name}'")),
+        };
+        let field = match name {
+            "namespace" | "ns" => Field::Namespace,
+            "pod" => Field::Pod,
+            "container" => Field::Container,
+            "msg" | "message" => Field::Message,
+            "stream" => Field::Stream,
+            "type" => Field::Type,
+            "received" => Field::Received,
+            name => Field::Parsed(field::pattern(name)?),
+        };
+        let op = match op {
+            Some(Token::Eq) => Op::Eq(value),
+            Some(Token::NotEq) => Op::NotEq(value),
+            Some(Token::Gt | Token::Lt) => {
+                let time = NaiveTime::parse_from_str(&value, "%H:%M:%S")
+                    .or_else(|_| NaiveTime::parse_from_str(&value, "%H:%M"))
+                    .map_err(|_| anyhow::anyhow!("expected HH:MM or HH:MM:SS, got '{value}'"))?;
+                if op == Some(Token::Gt) {
+                    Op::After(time)
+                } else {
+                    Op::Before(time)
+                }
+            }
+            _ => match RegexBuilder::new(&value)
+                .size_limit(self.limits.size)
+                .dfa_size_limit(self.limits.dfa_size)
+                .build()
+            {
+                Ok(regex) => Op::Match(regex),
+                Err(regex::Error::CompiledTooBig(_)) => Op::Literal(value),
+                Err(e) => return Err(e.into()),
+            },
+        };
+        Ok(Expr::Predicate(field, op))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+    use promkit::grapheme::StyledGraphemes;
+
+    use super::*;
+
+    /// Line received at 14:02:13 whose message starts with the timestamp of the container runtime,
+    /// as shown with `--timestamps`.
+    fn log(received: bool) -> ContainerLog {
+        let body = "2026-10-16T09:00:00.000000000Z connected";
+        ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from("api-0"),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from("api-0"),
+            body: StyledGraphemes::from(body),
+            continuation: Vec::new(),
+            received: received.then(|| Local.with_ymd_and_hms(2026, 10, 16, 14, 2, 13).unwrap()),
+            at: None,
+        }
+    }
+
+    fn limits() -> Limits {
+        Limits {
+            size: 1 << 20,
+            dfa_size: 1 << 20,
+            first_batch: Duration::from_millis(100),
+        }
+    }
+
+    fn matches(query: &str, log: &ContainerLog) -> bool {
+        parse(query, &limits())
+            .unwrap()
+            .matches(log, &log.body.to_string())
+    }
+
+    #[test]
+    fn received_time_compared() {
+        let log = log(true);
+        assert!(matches("received>14:02", &log));
+        assert!(matches("received>14:02:12", &log));
+        assert!(!matches("received>14:02:13", &log));
+        assert!(matches("received<14:03", &log));
+        assert!(!matches("received<14:02", &log));
+        assert!(matches("received>14:00 AND received<14:05", &log));
+        // As text, the time of day is `HH:MM:SS`.
+        assert!(matches("received=14:02:13", &log));
+        assert!(matches("received~^14:02", &log));
+    }
+
+    #[test]
+    fn received_time_apart_from_the_runtime_timestamp() {
+        let received = log(true);
+        // The timestamp in the message is the runtime's, at 09:00.
+        assert!(!matches("received<10:00", &received));
+        assert!(!matches("received~^09:00", &received));
+        assert!(matches("msg~T09:00", &received));
+
+        // Lines which were never received by the live view match no predicate on it,
+        // even if their message has a timestamp.
+        let printed = log(false);
+        assert!(!matches("received>00:00", &printed));
+        assert!(!matches("received<23:59", &printed));
+        assert!(!matches("received~.", &printed));
+    }
+
+    #[test]
+    fn ordered_only_after_received() {
+        let log = ContainerLog {
+            body: StyledGraphemes::from("upstream -> api <- cache"),
+            ..log(true)
+        };
+        assert!(matches("->", &log));
+        assert!(matches("<-", &log));
+        let error = parse("received>noon", &limits()).err().unwrap();
+        assert_eq!(error.to_string(), "expected HH:MM or HH:MM:SS, got 'noon'");
+    }
+
+    fn with_message(body: &str) -> ContainerLog {
+        ContainerLog {
+            body: StyledGraphemes::from(body),
+            ..log(true)
+        }
+    }
+
+    fn error(query: &str) -> String {
+        parse(query, &limits()).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let log = with_message("GET /orders 200");
+        // `a OR (b AND c)`, not `(a OR b) AND c`.
+        assert!(matches("orders OR health AND POST", &log));
+        assert!(!matches("health OR orders AND POST", &log));
+        // Adjacent predicates are implied to be ANDed.
+        assert!(matches("GET 200", &log));
+        assert!(!matches("GET 500", &log));
+        assert!(matches("GET 500 OR GET 200", &log));
+    }
+
+    #[test]
+    fn not_and_parentheses() {
+        let log = with_message("GET /orders 200");
+        assert!(matches("NOT health", &log));
+        assert!(!matches("NOT orders", &log));
+        assert!(!matches("(orders OR health) AND POST", &log));
+        assert!(matches("NOT (health OR POST)", &log));
+        assert!(matches("NOT NOT orders", &log));
+        assert!(matches(
+            "pod=api-0 AND NOT (container=db OR ns=kube-system)",
+            &log
+        ));
+    }
+
+    #[test]
+    fn quoted_strings_with_escapes() {
+        let log = with_message(r#"said "hello world" \ done"#);
+        assert!(matches(r#""hello world""#, &log));
+        assert!(matches(r#""\"hello""#, &log));
+        assert!(matches(r#""\\ done""#, &log));
+        // Keywords and operators are literal when quoted.
+        assert!(!matches(r#""OR""#, &log));
+        assert!(matches(r#"msg="said \"hello world\" \\ done""#, &log));
+    }
+
+    #[test]
+    fn not_equal_inside_words() {
+        let log = with_message("level=error");
+        assert!(matches("pod!=api-1", &log));
+        assert!(!matches("pod!=api-0", &log));
+        // A `!` not followed by `=` is part of the word.
+        let log = with_message("done! really");
+        assert!(matches("done!", &log));
+        assert!(matches("pod=api-0 done!", &log));
+    }
+
+    #[test]
+    fn parsed_fields_of_logfmt_and_json() {
+        let logfmt = with_message("ts=1 level=error msg=\"db down\"");
+        assert!(matches("level=error", &logfmt));
+        assert!(!matches("level=info", &logfmt));
+        assert!(matches("level~^err", &logfmt));
+        let json = with_message(r#"{"level":"warn","user":"ada"}"#);
+        assert!(matches("level=warn AND user=ada", &json));
+        assert!(matches("level!=error", &json));
+        // Lines without the field match no predicate on it, even a negated one.
+        assert!(!matches("trace_id!=abc", &json));
+        assert!(matches("NOT trace_id=abc", &json));
+    }
+
+    #[test]
+    fn syntax_errors_are_told() {
+        assert_eq!(error("(a"), "missing ')'");
+        assert_eq!(error("a)"), "unbalanced ')'");
+        assert_eq!(error("a OR"), "unexpected end of query");
+        assert_eq!(error(r#"msg="unterminated"#), "unterminated string");
+        assert_eq!(error(r#""a\"#), "unterminated string");
+        assert_eq!(error("level="), "missing value for 'level'");
+    }
+}
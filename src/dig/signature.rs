@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+/// Lines sharing a signature.
+pub struct Cluster {
+    pub signature: String,
+    /// Number of lines with the signature.
+    pub count: usize,
+    /// Index into the queue of the first line with the signature.
+    pub example: usize,
+}
+
+/// Clusters the lines of the given indices into `bodies` by their signature,
+/// the most frequent first, keeping the `top` ones.
+pub fn clusters(bodies: &[String], indices: &[usize], top: usize) -> Vec<Cluster> {
+    let counts = indices
+        .par_iter()
+        .fold(
+            HashMap::new,
+            |mut counts: HashMap<String, (usize, usize)>, &index| {
+                let entry = counts
+                    .entry(normalize(&bodies[index]))
+                    .or_insert((0, index));
+                entry.0 += 1;
+                entry.1 = entry.1.min(index);
+                counts
+            },
+        )
+        .reduce(HashMap::new, |mut lhs, rhs| {
+            for (signature, (count, example)) in rhs {
+                let entry = lhs.entry(signature).or_insert((0, example));
+                entry.0 += count;
+                entry.1 = entry.1.min(example);
+            }
+            lhs
+        });
+    let mut clusters: Vec<Cluster> = counts
+        .into_iter()
+        .map(|(signature, (count, example))| Cluster {
+            signature,
+            count,
+            example,
+        })
+        .collect();
+    // Ties go to the signature seen first, so that the order is stable.
+    clusters.sort_by(|lhs, rhs| {
+        rhs.count
+            .cmp(&lhs.count)
+            .then(lhs.example.cmp(&rhs.example))
+    });
+    clusters.truncate(top);
+    clusters
+}
+
+/// Placeholders of the parts varying between the lines of a cluster,
+/// with the regexes of what they replace.
+const PLACEHOLDERS: [(&str, &str); 6] = [
+    ("<time>", r"[0-9][0-9T:.,+Z-]*"),
+    ("<uuid>", r"[0-9a-fA-F-]{36}"),
+    ("<ip>", r"[0-9.]+(:[0-9]+)?"),
+    ("<hex>", r"(0x)?[0-9a-fA-F]+"),
+    ("<n>", r"[0-9]+"),
+    ("<str>", r#"[^"']*"#),
+];
+
+/// Signature of a message: the message with its varying parts replaced by placeholders,
+/// so that e.g. `timeout after 30s for req 7f3a9c01` and `timeout after 5s for req 0b11d2e4`
+/// both give `timeout after <n>s for req <hex>`.
+///
+/// - Timestamps such as `2024-05-01T12:00:00.123Z`, `2024-05-01` or `12:00:00` give `<time>`.
+/// - UUIDs give `<uuid>`, and IPv4 addresses, with or without a port, give `<ip>`.
+/// - Hex ids, i.e. `0x` followed by hex digits, or at least 8 hex digits mixing digits
+///   and letters, give `<hex>`, and the other runs of digits give `<n>`,
+///   e.g. in paths like `/var/log/app-12/out.log`.
+/// - The contents of quoted strings give `<str>`, keeping the quotes.
+pub fn normalize(message: &str) -> String {
+    let mut signature = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(ch) = rest.chars().next() {
+        if ch == '"' || (ch == '\'' && !signature.ends_with(char::is_alphanumeric)) {
+            if let Some(end) = rest[1..].find(ch) {
+                signature.push(ch);
+                signature.push_str("<str>");
+                signature.push(ch);
+                rest = &rest[end + 2..];
+                continue;
+            }
+        }
+        let end = rest.find(|ch: char| !is_word(ch)).unwrap_or(rest.len());
+        if end == 0 {
+            signature.push(ch);
+            rest = &rest[ch.len_utf8()..];
+            continue;
+        }
+        normalize_word(&rest[..end], &mut signature);
+        rest = &rest[end..];
+    }
+    signature
+}
+
+/// Regex matching the messages whose signature is the given one.
+pub fn pattern(signature: &str) -> String {
+    let mut pattern = String::from("^");
+    let mut rest = signature;
+    while !rest.is_empty() {
+        let next = PLACEHOLDERS
+            .iter()
+            .filter_map(|(placeholder, regex)| {
+                rest.find(placeholder).map(|at| (at, *placeholder, *regex))
+            })
+            .min_by_key(|(at, _, _)| *at);
+        match next {
+            Some((at, placeholder, regex)) => {
+                pattern.push_str(&regex::escape(&rest[..at]));
+                pattern.push_str(regex);
+                rest = &rest[at + placeholder.len()..];
+            }
+            None => {
+                pattern.push_str(&regex::escape(rest));
+                break;
+            }
+        }
+    }
+    pattern.push('$');
+    pattern
+}
+
+/// Characters of the words whose varying parts are replaced as a whole,
+/// e.g. timestamps, UUIDs, addresses and paths.
+fn is_word(ch: char) -> bool {
+    ch.is_alphanumeric() || "-_.:/+".contains(ch)
+}
+
+fn normalize_word(word: &str, signature: &mut String) {
+    // A trailing `.` or `:` ends a sentence or a label rather than the word.
+    let trimmed = word.trim_end_matches(['.', ':']);
+    let trailing = &word[trimmed.len()..];
+    if is_time(trimmed) {
+        signature.push_str("<time>");
+    } else if is_uuid(trimmed) {
+        signature.push_str("<uuid>");
+    } else if is_ip(trimmed) {
+        signature.push_str("<ip>");
+    } else {
+        // The other words are normalized part by part, e.g. `api-7f9c8d6b5-x2x4z`.
+        let mut start = 0;
+        for (at, ch) in trimmed
+            .char_indices()
+            .filter(|(_, ch)| !ch.is_alphanumeric())
+            .chain([(trimmed.len(), ' ')])
+        {
+            normalize_part(&trimmed[start..at], signature);
+            if at < trimmed.len() {
+                signature.push(ch);
+            }
+            start = at + ch.len_utf8();
+        }
+    }
+    signature.push_str(trailing);
+}
+
+fn normalize_part(part: &str, signature: &mut String) {
+    let hex = part.strip_prefix("0x").is_some_and(|digits| {
+        !digits.is_empty() && digits.chars().all(|ch| ch.is_ascii_hexdigit())
+    }) || (part.len() >= 8
+        && part.chars().all(|ch| ch.is_ascii_hexdigit())
+        && part.chars().any(|ch| ch.is_ascii_digit())
+        && part.chars().any(|ch| ch.is_ascii_alphabetic()));
+    if hex {
+        signature.push_str("<hex>");
+        return;
+    }
+    let mut digits = false;
+    for ch in part.chars() {
+        if ch.is_ascii_digit() {
+            if !digits {
+                signature.push_str("<n>");
+            }
+            digits = true;
+        } else {
+            signature.push(ch);
+            digits = false;
+        }
+    }
+}
+
+/// Whether the word is a date, a time of day or both, e.g. `2024-05-01T12:00:00.123+09:00`.
+fn is_time(word: &str) -> bool {
+    let date = |date: &str| {
+        let parts: Vec<&str> = date.split('-').collect();
+        matches!(parts[..], [y, m, d] if digits(y, 4) && digits(m, 2) && digits(d, 2))
+    };
+    let time = |time: &str| {
+        let time = time.trim_end_matches('Z');
+        let time = time.split_once('+').map_or(time, |(time, _)| time);
+        let (time, fraction) = time
+            .split_once(['.', ','])
+            .map_or((time, None), |(time, fraction)| (time, Some(fraction)));
+        let parts: Vec<&str> = time.split(':').collect();
+        fraction.is_none_or(|fraction| digits(fraction, fraction.len()) && !fraction.is_empty())
+            && matches!(parts[..], [h, m] | [h, m, _] if digits(h, 2) && digits(m, 2))
+            && parts.get(2).is_none_or(|s| digits(s, 2))
+    };
+    match word.split_once('T') {
+        Some((d, t)) => date(d) && time(t),
+        None => date(word) || time(word),
+    }
+}
+
+fn is_uuid(word: &str) -> bool {
+    let parts: Vec<&str> = word.split('-').collect();
+    parts.len() == 5
+        && parts
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(part, len)| part.len() == len && part.chars().all(|ch| ch.is_ascii_hexdigit()))
+}
+
+fn is_ip(word: &str) -> bool {
+    let (address, port) = word
+        .split_once(':')
+        .map_or((word, None), |(address, port)| (address, Some(port)));
+    let octets: Vec<&str> = address.split('.').collect();
+    octets.len() == 4
+        && octets
+            .iter()
+            .all(|octet| (1..=3).contains(&octet.len()) && octet.parse::<u8>().is_ok())
+        && port.is_none_or(|port| !port.is_empty() && port.parse::<u16>().is_ok())
+}
+
+/// Whether the text is `len` ASCII digits.
+fn digits(text: &str, len: usize) -> bool {
+    text.len() == len && text.chars().all(|ch| ch.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_timestamps() {
+        for (message, expected) in [
+            ("2024-05-01T12:00:00.123Z started", "<time> started"),
+            ("2024-05-01T12:00:00+09:00 started", "<time> started"),
+            ("at 2024-05-01 12:00:00.", "at <time> <time>."),
+            ("took until 12:00:00.5", "took until <time>"),
+            ("retry at 12:00: ok", "retry at <time>: ok"),
+            // Not a date nor a time of day.
+            ("build 2024-5-1", "build <n>-<n>-<n>"),
+            ("ratio 1:2", "ratio <n>:<n>"),
+        ] {
+            assert_eq!(normalize(message), expected, "{message}");
+        }
+    }
+
+    #[test]
+    fn normalize_ids() {
+        for (message, expected) in [
+            (
+                "timeout after 30s for req 7f3a9c01",
+                "timeout after <n>s for req <hex>",
+            ),
+            (
+                "request 123e4567-e89b-12d3-a456-426614174000 done",
+                "request <uuid> done",
+            ),
+            ("address 0xdeadBEEF", "address <hex>"),
+            (
+                "pod api-7f9c8d6b5-x2x4z ready",
+                "pod api-<hex>-x<n>x<n>z ready",
+            ),
+            // Too short, or letters only, to be told apart from a word.
+            ("id abc123 and deadbeef", "id abc<n> and deadbeef"),
+            ("retried 3 times", "retried <n> times"),
+        ] {
+            assert_eq!(normalize(message), expected, "{message}");
+        }
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(
+            normalize("open /var/log/app-12/out.log: denied"),
+            "open /var/log/app-<n>/out.log: denied"
+        );
+        assert_eq!(
+            normalize("GET /users/42/orders?limit=10"),
+            "GET /users/<n>/orders?limit=<n>"
+        );
+    }
+
+    #[test]
+    fn normalize_ips() {
+        for (message, expected) in [
+            ("connected to 10.0.0.1", "connected to <ip>"),
+            ("connected to 10.0.0.1:8080.", "connected to <ip>."),
+            ("dial 192.168.1.20:53: refused", "dial <ip>: refused"),
+            // Out of range, not an address.
+            ("version 10.0.0.256", "version <n>.<n>.<n>.<n>"),
+            ("version 1.2.3", "version <n>.<n>.<n>"),
+        ] {
+            assert_eq!(normalize(message), expected, "{message}");
+        }
+    }
+
+    #[test]
+    fn normalize_quoted_strings() {
+        assert_eq!(
+            normalize(r#"user "alice" said 'hi'"#),
+            r#"user "<str>" said '<str>'"#
+        );
+        // An apostrophe doesn't open a string, and an unclosed quote is kept as is.
+        assert_eq!(normalize("don't stop at 3"), "don't stop at <n>");
+        assert_eq!(normalize(r#"missing "quote"#), r#"missing "quote"#);
+    }
+
+    #[test]
+    fn pattern_matches_the_lines_of_the_signature() {
+        for message in [
+            "timeout after 30s for req 7f3a9c01",
+            "2024-05-01T12:00:00.123Z dial 10.0.0.1:8080: refused",
+            r#"user "alice" opened /tmp/a-1.log"#,
+            "request 123e4567-e89b-12d3-a456-426614174000 (1+1)",
+        ] {
+            let pattern = regex::Regex::new(&pattern(&normalize(message))).unwrap();
+            assert!(pattern.is_match(message), "{message}");
+        }
+        let pattern = regex::Regex::new(&pattern("retried <n> times")).unwrap();
+        assert!(pattern.is_match("retried 12 times"));
+        assert!(!pattern.is_match("retried 12 times!"));
+    }
+
+    #[test]
+    fn clusters_by_frequency() {
+        let bodies: Vec<String> = [
+            "connected to 10.0.0.1",
+            "retried 3 times",
+            "connected to 10.0.0.2",
+            "retried 4 times",
+            "connected to 10.0.0.3",
+            "shutting down",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let clusters = clusters(&bodies, &[0, 1, 2, 3, 4, 5], 2);
+        let clusters: Vec<(&str, usize, usize)> = clusters
+            .iter()
+            .map(|cluster| (cluster.signature.as_str(), cluster.count, cluster.example))
+            .collect();
+        assert_eq!(
+            clusters,
+            [("connected to <ip>", 3, 0), ("retried <n> times", 2, 1)]
+        );
+    }
+}
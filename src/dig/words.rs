@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+
+/// Words of the line to pick into the query, in order of appearance and without repeats.
+///
+/// The line is split at whitespace and at the break characters, so that with the default
+/// ones identifiers like `req-7f3a9c`, UUIDs and dotted paths like `io.k8s.api` stay whole
+/// while `id="req-7f3a9c",` gives `id` and `req-7f3a9c`. A trailing `.` or `:` ending
+/// a sentence or a label is dropped, and the parts without any letter or digit are skipped.
+pub fn words<'a>(line: &'a str, breaks: &HashSet<char>) -> Vec<&'a str> {
+    let mut words: Vec<&str> = Vec::new();
+    for word in line
+        .split(|ch: char| ch.is_whitespace() || breaks.contains(&ch))
+        .map(|word| word.trim_end_matches(['.', ':']))
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+    {
+        if !words.contains(&word) {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Word picked from the selected line, replaced by the next one when picked again.
+pub struct Picked {
+    /// Index into the queue of the line the word was picked from.
+    pub line: usize,
+    /// Index of the word among the words of the line.
+    pub word: usize,
+    /// Number of characters inserted into the query.
+    pub len: usize,
+    /// Query right after the word was inserted, to tell when it was edited since.
+    pub query: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The default of --word-break-chars.
+    fn breaks() -> HashSet<char> {
+        "\"'`,;=()[]{}<>|".chars().collect()
+    }
+
+    #[test]
+    fn words_keep_the_identifiers_whole() {
+        assert_eq!(
+            words(
+                r#"level=error msg="request failed" id="req-7f3a9c", trace=4bf92f35-77b3-4da6-a3ce-929d0e0e4736"#,
+                &breaks()
+            ),
+            [
+                "level",
+                "error",
+                "msg",
+                "request",
+                "failed",
+                "id",
+                "req-7f3a9c",
+                "trace",
+                "4bf92f35-77b3-4da6-a3ce-929d0e0e4736",
+            ]
+        );
+        assert_eq!(
+            words("watch of io.k8s.api.core.v1.Pod ended", &breaks()),
+            ["watch", "of", "io.k8s.api.core.v1.Pod", "ended"]
+        );
+    }
+
+    #[test]
+    fn words_drop_the_trailing_punctuation_and_the_symbols() {
+        assert_eq!(
+            words("Error: connection refused. Retrying in 5s...", &breaks()),
+            ["Error", "connection", "refused", "Retrying", "in", "5s"]
+        );
+        assert_eq!(
+            words("-- [] => {} | 10.0.0.1:8080", &breaks()),
+            ["10.0.0.1:8080"]
+        );
+    }
+
+    #[test]
+    fn words_are_not_repeated() {
+        assert_eq!(
+            words("pod web-0 restarted; pod web-0 ready.", &breaks()),
+            ["pod", "web-0", "restarted", "ready"]
+        );
+    }
+
+    #[test]
+    fn words_split_at_the_given_breaks() {
+        assert_eq!(
+            words("a/b-c d", &HashSet::from(['/', '-'])),
+            ["a", "b", "c", "d"]
+        );
+        // Only at whitespace without any.
+        assert_eq!(
+            words(r#"id="req-7f3a9c""#, &HashSet::new()),
+            [r#"id="req-7f3a9c""#]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use tokio::time::Duration;
+
+/// Parses a duration such as `250ms`, `10s`, `5m` or `1h30m`,
+/// where a bare number is taken in `unit`, the historical unit of the flag.
+///
+/// The units are `ms`, `s`, `m`, `h` and `d`, and can be combined from the largest to the smallest.
+fn parse(value: &str, unit: Duration) -> Result<Duration, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(String::from("empty duration"));
+    }
+    if let Ok(count) = value.parse::<u64>() {
+        return count
+            .checked_mul(unit.as_millis() as u64)
+            .map(Duration::from_millis)
+            .ok_or_else(|| format!("duration '{value}' is too long"));
+    }
+
+    let invalid = || format!("invalid duration '{value}', expected e.g. 250ms, 10s, 5m or 1h30m");
+    let mut total = Duration::ZERO;
+    let mut last_unit: Option<Duration> = None;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let count: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let suffix = rest
+            .find(|ch: char| ch.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..suffix] {
+            "ms" => Duration::from_millis(1),
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(60 * 60),
+            "d" => Duration::from_secs(24 * 60 * 60),
+            _ => return Err(invalid()),
+        };
+        if last_unit.is_some_and(|last| last <= unit) {
+            return Err(invalid());
+        }
+        last_unit = Some(unit);
+        rest = &rest[suffix..];
+        total = u32::try_from(count)
+            .ok()
+            .and_then(|count| unit.checked_mul(count))
+            .and_then(|part| total.checked_add(part))
+            .ok_or_else(|| format!("duration '{value}' is too long"))?;
+    }
+    Ok(total)
+}
+
+/// Value parser of the flags whose bare numbers are milliseconds.
+pub fn millis(value: &str) -> Result<Duration, String> {
+    parse(value, Duration::from_millis(1))
+}
+
+/// Value parser of the flags whose bare numbers are seconds.
+pub fn seconds(value: &str) -> Result<Duration, String> {
+    parse(value, Duration::from_secs(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_numbers_in_the_unit_of_the_flag() {
+        assert_eq!(millis("250"), Ok(Duration::from_millis(250)));
+        assert_eq!(seconds("10"), Ok(Duration::from_secs(10)));
+        assert_eq!(seconds(" 0 "), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn unit_suffixes() {
+        for (value, expected) in [
+            ("250ms", Duration::from_millis(250)),
+            ("10s", Duration::from_secs(10)),
+            ("5m", Duration::from_secs(5 * 60)),
+            ("2h", Duration::from_secs(2 * 60 * 60)),
+            ("1d", Duration::from_secs(24 * 60 * 60)),
+        ] {
+            assert_eq!(seconds(value), Ok(expected), "{value}");
+            // The suffix wins over the unit of the flag.
+            assert_eq!(millis(value), Ok(expected), "{value}");
+        }
+    }
+
+    #[test]
+    fn compound_values() {
+        assert_eq!(seconds("1h30m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(
+            seconds("1d2h3m4s5ms"),
+            Ok(Duration::from_millis(
+                (((24 + 2) * 60 + 3) * 60 + 4) * 1000 + 5
+            ))
+        );
+        assert_eq!(seconds("1m500ms"), Ok(Duration::from_millis(60_500)));
+    }
+
+    #[test]
+    fn rejections() {
+        for value in [
+            "", " ", "s", "10x", "1.5s", "-1s", "10 s", "1h1h", "30m1h", "1s1m", "ms10",
+        ] {
+            assert!(seconds(value).is_err(), "{value:?}");
+        }
+        assert_eq!(seconds(""), Err(String::from("empty duration")));
+        assert_eq!(
+            seconds("10x"),
+            Err(String::from(
+                "invalid duration '10x', expected e.g. 250ms, 10s, 5m or 1h30m"
+            ))
+        );
+    }
+
+    #[test]
+    fn too_long() {
+        assert_eq!(
+            seconds(&u64::MAX.to_string()),
+            Err(format!("duration '{}' is too long", u64::MAX))
+        );
+        assert!(seconds("99999999999d").is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use k8s_openapi::api::core::v1::Event;
+use kube::runtime::watcher;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use promkit::grapheme::StyledGraphemes;
+
+use crate::{
+    backfill::Backfill,
+    container::{is_forbidden, ContainerLog, ContainerLogStreamer, LogKind},
+    notice::Notice,
+    style::StyleConfig,
+};
+
+/// Time the event last happened, falling back to when it was first recorded.
+fn happened_at(event: &Event) -> Option<DateTime<Utc>> {
+    event
+        .last_timestamp
+        .as_ref()
+        .map(|time| time.0)
+        .or_else(|| event.event_time.as_ref().map(|time| time.0))
+        .or_else(|| {
+            event
+                .metadata
+                .creation_timestamp
+                .as_ref()
+                .map(|time| time.0)
+        })
+}
+
+/// Whether the watch failed because listing the events is forbidden.
+fn is_watch_forbidden(err: &watcher::Error) -> bool {
+    match err {
+        watcher::Error::InitialListFailed(e)
+        | watcher::Error::WatchStartFailed(e)
+        | watcher::Error::WatchFailed(e) => is_forbidden(e),
+        watcher::Error::WatchError(resp) => resp.code == 403,
+        watcher::Error::NoResourceVersion => false,
+    }
+}
+
+/// Merges the Kubernetes Events of the streamed Pods (e.g. scheduling, image pulls
+/// or failed probes) into the log stream, watching them through the API server,
+/// so that what happened to a Pod shows up next to what it logged.
+///
+/// The events are `ContainerLog`s of the kind `LogKind::Event`,
+/// which can be dug with `type:event` (or left out with `NOT type:event`) in the query mode.
+/// They are timed by when they happened, so that the past ones are merged with the backlogs
+/// of the streams by `Backfill`, and each is shown once per count across relaunches.
+#[derive(Clone, Default)]
+pub struct Watcher {
+    /// Count of each event already shown by its uid, since repeated events are updated in place.
+    shown: Arc<Mutex<HashMap<String, i32>>>,
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Watches the events through the client of the streamer until canceled.
+    ///
+    /// The past events are held in the backfill as the stream of the index, if any,
+    /// and sent ordered by time otherwise.
+    /// A failure of the watch is reported once until it recovers,
+    /// and the events are given up with a warning if watching them is forbidden,
+    /// leaving the log streams unaffected either way.
+    pub async fn run(
+        &self,
+        streamer: ContainerLogStreamer,
+        log_stream_tx: mpsc::Sender<ContainerLog>,
+        notice_tx: mpsc::Sender<Notice>,
+        style: &StyleConfig,
+        canceled: CancellationToken,
+        backfill: Option<(Arc<Backfill>, usize)>,
+    ) {
+        let (mut listing, mut events) = match streamer.watch_pod_events().await {
+            Ok(watch) => watch,
+            Err(e) => {
+                let _ = notice_tx.try_send(Notice::warning(format!("failed to watch events: {e}")));
+                if let Some((backfill, _)) = &backfill {
+                    backfill.caught_up().await;
+                }
+                return;
+            }
+        };
+        // Past events of the watches still listing them.
+        let mut listed = Vec::new();
+        let mut failing = false;
+        loop {
+            let event = tokio::select! {
+                _ = canceled.cancelled() => break,
+                event = events.next() => event,
+            };
+            let (settled, backlog) = match event {
+                Some(Ok(watcher::Event::Init | watcher::Event::Delete(_))) => continue,
+                Some(Ok(watcher::Event::InitApply(event))) => {
+                    listed.push(event);
+                    continue;
+                }
+                // Only the first listing of each watch is the backlog, not a restarted one.
+                Some(Ok(watcher::Event::InitDone)) => {
+                    let backlog = listing > 0;
+                    listing = listing.saturating_sub(1);
+                    (
+                        self.settle(std::mem::take(&mut listed), &streamer, style),
+                        backlog,
+                    )
+                }
+                Some(Ok(watcher::Event::Apply(event))) => {
+                    (self.settle(vec![event], &streamer, style), false)
+                }
+                Some(Err(e)) if is_watch_forbidden(&e) => {
+                    let _ = notice_tx.try_send(Notice::warning(format!(
+                        "stopped showing events, not allowed to watch them: {e}"
+                    )));
+                    break;
+                }
+                Some(Err(e)) => {
+                    if !failing {
+                        let _ = notice_tx
+                            .try_send(Notice::warning(format!("failed to watch events: {e}")));
+                    }
+                    failing = true;
+                    continue;
+                }
+                None => break,
+            };
+            failing = false;
+
+            for log in settled {
+                // Held like the lines of the streams, and let through once they are flushed.
+                let log = match &backfill {
+                    Some((backfill, stream)) => match backfill.hold(*stream, log.at, log).await {
+                        Some(log) => log,
+                        None => continue,
+                    },
+                    None => log,
+                };
+                if log_stream_tx.send(log).await.is_err() {
+                    return;
+                }
+            }
+            if backlog && listing == 0 {
+                if let Some((backfill, _)) = &backfill {
+                    backfill.caught_up().await;
+                }
+            }
+        }
+        // Not to hold back the backlogs of the streams until the window elapses.
+        if listing > 0 {
+            if let Some((backfill, _)) = &backfill {
+                backfill.caught_up().await;
+            }
+        }
+    }
+
+    /// Lines of the events of the streamed Pods not shown yet with their count,
+    /// ordered by the time they happened.
+    /// The events of the other Pods aren't marked as shown, to show them once their Pod
+    /// is streamed and they are updated.
+    fn settle(
+        &self,
+        mut events: Vec<Event>,
+        streamer: &ContainerLogStreamer,
+        style: &StyleConfig,
+    ) -> Vec<ContainerLog> {
+        events.retain(|event| {
+            streamer.is_streamed(
+                event
+                    .involved_object
+                    .namespace
+                    .as_deref()
+                    .unwrap_or_default(),
+                event.involved_object.name.as_deref().unwrap_or_default(),
+            )
+        });
+        self.unseen(events, style)
+    }
+
+    /// Lines of the events not shown yet with their count, ordered by the time they happened.
+    fn unseen(&self, mut events: Vec<Event>, style: &StyleConfig) -> Vec<ContainerLog> {
+        let mut shown = self.shown.lock().unwrap();
+        events.retain(|event| {
+            let uid = event.metadata.uid.clone().unwrap_or_default();
+            let count = event.count.unwrap_or(1);
+            shown.insert(uid, count) != Some(count)
+        });
+        events.sort_by_key(happened_at);
+        events.iter().map(|event| to_log(event, style)).collect()
+    }
+}
+
+/// Line of the event, timed by when it happened.
+fn to_log(event: &Event, style: &StyleConfig) -> ContainerLog {
+    let pod = event.involved_object.name.clone().unwrap_or_default();
+    let warning = event.type_.as_deref() == Some("Warning");
+    let mut text = format!(
+        "{} {}: {}",
+        event.type_.as_deref().unwrap_or("Normal"),
+        event.reason.as_deref().unwrap_or_default(),
+        event.message.as_deref().unwrap_or_default().trim_end(),
+    );
+    if let Some(count) = event.count.filter(|&count| count > 1) {
+        text.push_str(&format!(" (x{count})"));
+    }
+    ContainerLog {
+        namespace: event.involved_object.namespace.clone().unwrap_or_default(),
+        meta: StyledGraphemes::from_str(format!("{pod} event"), style.event),
+        pod,
+        container: String::new(),
+        restarts: 0,
+        stream: None,
+        kind: LogKind::Event,
+        body: StyledGraphemes::from_str(text, if warning { style.warning } else { style.event }),
+        continuation: Vec::new(),
+        received: None,
+        at: happened_at(event),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+    use tokio::time::Duration;
+
+    use super::*;
+
+    fn at(seconds: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2024-05-01T12:00:{seconds:02}Z"))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn event(uid: &str, reason: &str, count: i32, seconds: u32) -> Event {
+        let mut event = Event {
+            reason: Some(String::from(reason)),
+            message: Some(String::from("on api-0")),
+            type_: Some(String::from("Normal")),
+            count: Some(count),
+            last_timestamp: Some(Time(at(seconds))),
+            ..Default::default()
+        };
+        event.metadata.uid = Some(String::from(uid));
+        event.involved_object.namespace = Some(String::from("default"));
+        event.involved_object.name = Some(String::from("api-0"));
+        event
+    }
+
+    fn bodies(logs: &[ContainerLog]) -> Vec<String> {
+        logs.iter().map(|log| log.body.to_string()).collect()
+    }
+
+    #[test]
+    fn events_are_shown_once_per_count_across_relaunches() {
+        let style = StyleConfig::default();
+        let watcher = Watcher::new();
+        let listed = vec![event("a", "Pulled", 1, 3), event("b", "BackOff", 2, 5)];
+        assert_eq!(
+            bodies(&watcher.unseen(listed.clone(), &style)),
+            ["Normal Pulled: on api-0", "Normal BackOff: on api-0 (x2)"]
+        );
+
+        // Listed again by the watch of a relaunch, with the back-off repeated since.
+        let relaunched = watcher.clone();
+        let listed = vec![listed[0].clone(), event("b", "BackOff", 3, 9)];
+        assert_eq!(
+            bodies(&relaunched.unseen(listed, &style)),
+            ["Normal BackOff: on api-0 (x3)"]
+        );
+    }
+
+    #[test]
+    fn events_are_ordered_and_timed_by_when_they_happened() {
+        let style = StyleConfig::default();
+        let logs = Watcher::new().unseen(
+            vec![
+                event("c", "Started", 1, 7),
+                event("a", "Scheduled", 1, 1),
+                event("b", "Pulled", 1, 4),
+            ],
+            &style,
+        );
+        assert_eq!(
+            bodies(&logs),
+            [
+                "Normal Scheduled: on api-0",
+                "Normal Pulled: on api-0",
+                "Normal Started: on api-0"
+            ]
+        );
+        let times: Vec<_> = logs.iter().map(|log| log.at).collect();
+        assert_eq!(times, [Some(at(1)), Some(at(4)), Some(at(7))]);
+    }
+
+    #[tokio::test]
+    async fn past_events_are_merged_with_the_backlogs_by_time() {
+        let style = StyleConfig::default();
+        let backfill = Backfill::new(2);
+        let mut line = to_log(&event("x", "unused", 1, 0), &style);
+        line.kind = LogKind::Log;
+        line.body = StyledGraphemes::from("GET /orders 200");
+        assert!(backfill.hold(0, Some(at(2)), line).await.is_none());
+        for log in Watcher::new().unseen(
+            vec![event("a", "Scheduled", 1, 1), event("b", "Pulled", 1, 3)],
+            &style,
+        ) {
+            let at = log.at;
+            assert!(backfill.hold(1, at, log).await.is_none());
+        }
+        backfill.caught_up().await;
+        backfill.caught_up().await;
+
+        let (log_stream_tx, mut log_stream_rx) = mpsc::channel(8);
+        backfill
+            .flush(
+                &log_stream_tx,
+                Duration::from_secs(60),
+                &CancellationToken::new(),
+            )
+            .await;
+        drop(log_stream_tx);
+        let mut flushed = Vec::new();
+        while let Some(log) = log_stream_rx.recv().await {
+            flushed.push(log);
+        }
+        assert_eq!(
+            bodies(&flushed),
+            [
+                "Normal Scheduled: on api-0",
+                "GET /orders 200",
+                "Normal Pulled: on api-0"
+            ]
+        );
+    }
+}
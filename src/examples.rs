@@ -0,0 +1,117 @@
+use std::io::{self, Write};
+
+/// Invocation printed by `bul examples`, to copy and adapt.
+pub struct Example {
+    pub description: &'static str,
+    pub command: &'static str,
+}
+
+/// Kept to the flags of `Args`, so that they keep working when copied.
+/// The ones using flags of a feature are left out of the builds without it.
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        description: "Tail the Pods of a deployment, annotating the progress of its rollouts",
+        command: "bul -n payments -p '^payments-api-' --watch-rollout payments-api",
+    },
+    Example {
+        description: "Dig in the last 10 minutes of errors: press Ctrl+F once the backlog is in, and type error",
+        command: "bul -n payments -p '^payments-api-' --since-seconds 10m --queue-capacity 100000",
+    },
+    #[cfg(feature = "json")]
+    Example {
+        description: "Pipe the lines to jq, e.g. to keep the messages written to stderr",
+        command: "bul -p '^payments-' --raw-cri --print --output json | jq -r 'select(.stream == \"stderr\") | .message'",
+    },
+    Example {
+        description: "Follow the Pods of every namespace of a team, showing the namespace of each line",
+        command: "bul --namespace-query '^team-payments-' -p '^api-'",
+    },
+    Example {
+        description: "Check which containers would be tailed, and how their streams would be opened",
+        command: "bul -p '^payments-' --dry-run --log-params",
+    },
+    #[cfg(feature = "json")]
+    Example {
+        description: "Keep the lines to dig in them later without the cluster",
+        command: "bul -p '^payments-' --save-session payments.session && bul --load-session payments.session",
+    },
+];
+
+/// Prints the examples, each command under its description.
+pub fn print() -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    for (i, example) in EXAMPLES.iter().enumerate() {
+        if i > 0 {
+            writeln!(stdout)?;
+        }
+        writeln!(stdout, "# {}", example.description)?;
+        writeln!(stdout, "{}", example.command)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::Args;
+
+    /// Arguments of each invocation of bul in the command, split as a shell would
+    /// for these commands: at whitespace outside of single and double quotes,
+    /// and into commands at `|` and `&&`.
+    fn invocations(command: &str) -> Vec<Vec<String>> {
+        let mut commands = vec![vec![]];
+        let mut word: Option<String> = None;
+        let mut quote = None;
+        for ch in command.chars() {
+            match (quote, ch) {
+                (Some(open), _) if ch == open => quote = None,
+                (Some(_), _) => word.get_or_insert_with(String::new).push(ch),
+                (None, '\'' | '"') => {
+                    quote = Some(ch);
+                    word.get_or_insert_with(String::new);
+                }
+                (None, ' ') => commands.last_mut().unwrap().extend(word.take()),
+                (None, _) => word.get_or_insert_with(String::new).push(ch),
+            }
+            let args = commands.last_mut().unwrap();
+            if quote.is_none()
+                && word.is_none()
+                && matches!(args.last().map(String::as_str), Some("|" | "&&"))
+            {
+                args.pop();
+                commands.push(vec![]);
+            }
+        }
+        commands.last_mut().unwrap().extend(word);
+        commands
+            .into_iter()
+            .filter(|args| args.first().is_some_and(|program| program == "bul"))
+            .collect()
+    }
+
+    #[test]
+    fn invocations_of_a_command() {
+        assert_eq!(
+            invocations("bul -p '^a b' --print | jq -r '.x | .y' && bul --load-session \"s 1\""),
+            [
+                vec!["bul", "-p", "^a b", "--print"],
+                vec!["bul", "--load-session", "s 1"],
+            ]
+        );
+    }
+
+    #[test]
+    fn examples_parse() {
+        for example in EXAMPLES {
+            let invocations = invocations(example.command);
+            assert!(!invocations.is_empty(), "{}", example.command);
+            for args in invocations {
+                if let Err(e) = Args::try_parse_from(&args) {
+                    panic!("{}: {e}", example.command);
+                }
+            }
+        }
+    }
+}
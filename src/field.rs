@@ -0,0 +1,18 @@
+use regex::Regex;
+
+/// Builds the regex capturing the value of the field in a message,
+/// written as `key=value` (logfmt) or `"key":"value"` (JSON).
+pub fn pattern(name: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!(
+        r#"(?:^|[^\w.-])"?{}"?\s*[=:]\s*"?([^"\s,}}]*)"#,
+        regex::escape(name)
+    ))
+}
+
+/// Value of the field captured by `pattern` in the message, if any.
+pub fn value<'a>(pattern: &Regex, message: &'a str) -> Option<&'a str> {
+    pattern
+        .captures(message)
+        .and_then(|captures| captures.get(1))
+        .map(|value| value.as_str())
+}
@@ -1,16 +1,27 @@
-use std::io;
+use std::{
+    fs::OpenOptions,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
-use clap::Parser;
-use k8s_openapi::api::core::v1::Pod;
+use clap::{Parser, Subcommand};
 use kube::{
     config::{KubeConfigOptions, Kubeconfig},
-    Api, Client, Config,
+    Client, Config,
+};
+use regex::Regex;
+use tokio::{
+    sync::oneshot,
+    time::{Duration, Instant},
 };
-use tokio::time::Duration;
+use tracing::{info, warn};
 
 use promkit::{
     crossterm::{
-        self, cursor, execute,
+        self, cursor,
+        event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+        execute,
         style::Color,
         terminal::{disable_raw_mode, enable_raw_mode},
     },
@@ -19,34 +30,204 @@ use promkit::{
     text_editor,
 };
 
+mod alias;
+#[cfg(test)]
+mod allocations;
+use alias::Aliases;
+mod backfill;
+mod baseline;
+use baseline::Baseline;
 mod bul;
+mod change;
+use change::{ChangeFilter, ChangeScope};
+#[cfg(feature = "clipboard")]
+mod clipboard;
 mod container;
-use container::{ContainerState, ContainerStateMatcher};
+use container::{
+    AnnotationSelector, ContainerLogStreamer, ContainerState, ContainerStateMatcher, FieldSelector,
+    LabelSelector, LineMerge, MatchScope, NamespaceSelector, PodSelector, StreamSettings,
+};
+mod dedup;
 mod dig;
+mod duration;
+mod events;
+mod examples;
+mod field;
+mod meta;
+use meta::{MetaFormat, OutputTemplate};
+mod notice;
+mod outbox;
+mod plain;
+mod print;
+mod queue;
+use queue::Capture;
+mod record;
+mod rollout;
+use record::Recorder;
+#[cfg(feature = "json")]
+mod session;
+#[cfg(feature = "json")]
+use session::Session;
+mod sink;
+mod skew;
+#[cfg(feature = "json")]
+use sink::JsonSink;
+use sink::{OutputSink, TemplateSink};
+mod style;
+use style::StyleConfig;
+mod suggest;
 mod terminal;
+mod workspace;
+use workspace::{Counters, Workspace};
+mod wrap;
+
+/// Time after a re-authentication within which the rebuilt client being rejected again
+/// is taken as the fresh credentials being invalid rather than as another expiry.
+const REAUTH_GRACE: Duration = Duration::from_secs(60);
 
 #[derive(PartialEq, Eq)]
 pub enum Signal {
     Continue,
     GoToDig,
     GoToBul,
+    Reauthenticate,
+    Interrupt,
+    DismissNotices,
+    ToggleHold,
+    ToggleFollow,
+    CopyCommands,
+    SwitchFocus,
+    CycleStates,
 }
 
 /// Interactive Kubernetes log viewer
 #[derive(Parser)]
-#[command(name = "bul", version)]
+#[command(name = "bul", version, args_conflicts_with_subcommands = true)]
+#[command(group = clap::ArgGroup::new("plain").args(["print", "plain_follow"]))]
 pub struct Args {
-    #[arg(long = "context", help = "Kubernetes context.")]
+    #[arg(
+        long = "context",
+        help_heading = "Selection",
+        help = "Kubernetes context."
+    )]
     pub context: Option<String>,
 
-    #[arg(short = 'n', long = "namespace", help = "Kubernetes namespace.")]
-    pub namespace: Option<String>,
+    #[arg(
+        short = 'n',
+        long = "namespace",
+        help_heading = "Selection",
+        value_delimiter = ',',
+        help = "Kubernetes namespace.",
+        long_help = "Kubernetes namespace.
+        Repeat it or separate the names with commas to stream the Pods of several namespaces,
+        showing the namespace in the meta of each line."
+    )]
+    pub namespace: Vec<String>,
+
+    #[arg(
+        long = "namespace-query",
+        help_heading = "Selection",
+        conflicts_with = "namespace",
+        help = "query to filter namespaces.",
+        long_help = "query to filter namespaces.
+        Follows the Pods in all namespaces whose names match the regex,
+        showing the namespace in the meta of each line.
+        Namespaces whose Pods are not allowed to be listed are reported and skipped."
+    )]
+    pub namespace_query: Option<String>,
+
+    #[arg(
+        short = 'A',
+        long = "all-namespaces",
+        help_heading = "Selection",
+        conflicts_with_all = ["namespace", "namespace_query"],
+        help = "Stream the Pods in all namespaces.",
+        long_help = "Stream the Pods in all namespaces.
+        The Pods are listed at once across the cluster, where --pod-query and the selectors apply,
+        and the namespace is shown in the meta of each line."
+    )]
+    pub all_namespaces: bool,
 
-    #[arg(short = 'p', long = "pod-query", help = "query to filter Pods.")]
+    #[arg(
+        value_name = "POD",
+        help_heading = "Selection",
+        conflicts_with = "pod_query",
+        help = "Exact name of the Pod to stream, instead of --pod-query."
+    )]
+    pub pod: Option<String>,
+
+    #[arg(
+        short = 'p',
+        long = "pod-query",
+        help_heading = "Selection",
+        help = "query to filter Pods."
+    )]
     pub pod_query: Option<String>,
 
+    #[arg(
+        long = "match-labels-in-query",
+        help_heading = "Selection",
+        requires = "pod_query",
+        help = "Also select the Pods whose label values match --pod-query.",
+        long_help = "Also select the Pods whose label values match --pod-query, besides their names,
+        e.g. a git SHA in `app.kubernetes.io/version`."
+    )]
+    pub match_labels_in_query: bool,
+
+    #[arg(
+        long = "match-annotations-in-query",
+        help_heading = "Selection",
+        requires = "pod_query",
+        help = "Also select the Pods whose annotation values match --pod-query."
+    )]
+    pub match_annotations_in_query: bool,
+
+    #[arg(
+        short = 'l',
+        long = "selector",
+        help_heading = "Selection",
+        value_name = "SELECTOR",
+        value_parser = LabelSelector::parse,
+        help = "Only stream the Pods with these labels, e.g. app=nginx,tier=frontend.",
+        long_help = "Only stream the Pods with these labels, e.g. app=nginx,tier=frontend,
+        as a label selector of kubectl with =, ==, !=, in, notin and exists (KEY or !KEY).
+        The API server selects the Pods by it, on top of the Pod name or --pod-query.
+        It is validated before contacting the cluster, and tried once against the API server
+        before the view opens, so that a rejected selector fails with the reason right away."
+    )]
+    pub selector: Option<LabelSelector>,
+
+    #[arg(
+        long = "field-selector",
+        help_heading = "Selection",
+        value_name = "SELECTOR",
+        value_parser = FieldSelector::parse,
+        help = "Only stream the Pods with these fields, e.g. spec.nodeName=node-1,status.phase=Running.",
+        long_help = "Only stream the Pods with these fields, e.g. spec.nodeName=node-1,status.phase=Running,
+        as a field selector of kubectl with =, == and !=. The API server selects the Pods by it,
+        on top of the Pod name, --pod-query, --selector and --container-states.
+        It is tried once against the API server before the view opens,
+        so that a field the API server doesn't support fails with the reason right away."
+    )]
+    pub field_selector: Option<FieldSelector>,
+
+    #[arg(
+        long = "annotation-selector",
+        help_heading = "Selection",
+        value_name = "SELECTOR",
+        value_parser = AnnotationSelector::parse,
+        help = "Only stream the Pods with these annotations, e.g. team=payments,tier!=batch.",
+        long_help = "Only stream the Pods with these annotations, e.g. team=payments,tier!=batch,
+        in the syntax of label selectors with =, == and !=. All the requirements must be met,
+        and a missing annotation differs from any value.
+        The API server doesn't select by annotations, so it is evaluated client-side
+        after listing the Pods, on top of the Pod name or --pod-query."
+    )]
+    pub annotation_selector: Option<AnnotationSelector>,
+
     #[arg(
         long = "container-states",
+        help_heading = "Selection",
         help = "Container states to filter containers.",
         value_delimiter = ',',
         default_value = "all"
@@ -54,24 +235,454 @@ pub struct Args {
     pub container_status: Vec<ContainerState>,
 
     #[arg(
-        long = "log-retrieval-timeout",
+        long = "ready-only",
+        help_heading = "Selection",
+        help = "Only stream containers which are ready.",
+        long_help = "Only stream containers which are ready.
+        Applied in addition to --container-states,
+        which skips containers that are still starting up."
+    )]
+    pub ready_only: bool,
+
+    #[arg(
+        long = "prefer-container",
+        help_heading = "Selection",
+        value_name = "REGEX",
+        help = "Only stream the containers matching the regex of the Pods which have any.",
+        long_help = "Only stream the containers matching the regex of the Pods which have any,
+        e.g. '-log$' for the sidecars carrying the logs of quiet app containers.
+        The Pods without any matching container are streamed as a whole.
+        Applied after --container-states and --ready-only."
+    )]
+    pub prefer_container: Option<String>,
+
+    #[arg(
+        long = "all-containers",
+        help_heading = "Selection",
+        help = "Stream all the containers of the Pods, overriding --prefer-container."
+    )]
+    pub all_containers: bool,
+
+    #[arg(
+        long = "stop-on-terminating",
+        help_heading = "Selection",
+        help = "Leave out the Pods being deleted instead of streaming them until they are gone.",
+        long_help = "Leave out the Pods being deleted instead of streaming them until they are gone.
+        By default, the containers of a Pod being deleted are streamed whatever their states,
+        and a marker line tells when it started terminating, as seen when the Pods are listed."
+    )]
+    pub stop_on_terminating: bool,
+
+    #[arg(
+        long = "enforce-states",
+        help_heading = "Selection",
+        num_args = 0..=1,
+        default_missing_value = "5",
+        value_name = "INTERVAL",
+        value_parser = duration::seconds,
+        help = "Keep applying --container-states while streaming, re-checking every few seconds.",
+        long_help = "Keep applying --container-states (and --ready-only) while streaming.
+        The containers are re-checked at the given interval (5s if omitted):
+        streams are attached when containers enter the accepted states,
+        and detached with a marker line when they leave them."
+    )]
+    pub enforce_states: Option<Duration>,
+
+    #[arg(
+        long = "since-seconds",
+        help_heading = "Log options",
+        value_name = "DURATION",
+        value_parser = duration::seconds,
+        help = "Only return logs newer than a relative duration, e.g. 5m (bare numbers are seconds)."
+    )]
+    pub since: Option<Duration>,
+
+    #[arg(
+        long = "discovery-since-seconds",
+        help_heading = "Log options",
         default_value = "10",
-        help = "Timeout to read a next line from the log stream in milliseconds."
+        value_name = "DURATION",
+        value_parser = duration::seconds,
+        help = "Relative duration to look back for Pods discovered after startup (bare numbers are seconds).",
+        long_help = "Relative duration to look back for Pods discovered after startup,
+        e.g. 30s (bare numbers are seconds).
+        Pods which have existed for a while (e.g. only just started matching the state filter)
+        would otherwise replay their entire history into the live view.
+        This is independent of --since-seconds which applies at startup."
+    )]
+    pub discovery_since: Duration,
+
+    #[arg(
+        long = "log-retrieval-timeout",
+        help_heading = "Performance",
+        value_name = "DURATION",
+        value_parser = duration::millis,
+        help = "Deprecated, has no effect: streams wait for their next line without a timeout.",
+        long_help = "Deprecated, has no effect: streams wait for their next line without a timeout.
+        It used to be how long a stream waited for its next line before trying again,
+        which also told when its backlog was received. See --backlog-quiet-time for the latter."
+    )]
+    pub log_retrieval_timeout: Option<Duration>,
+
+    #[arg(
+        long = "backlog-quiet-time",
+        help_heading = "Performance",
+        default_value = "100",
+        value_name = "DURATION",
+        value_parser = duration::millis,
+        help = "Time without any line after which the backlog of a stream is taken as received (bare numbers are milliseconds).",
+        long_help = "Time without any line after which the backlog of a stream is taken as received,
+        so that the following lines are live and used to estimate the clock skew
+        (bare numbers are milliseconds).
+        Streams wait for their next line without polling, so this doesn't need tuning:
+        below the latency of the API server (tens of milliseconds), the backlog is taken
+        as live while it is still coming."
     )]
-    pub log_retrieval_timeout_millis: u64,
+    pub backlog_quiet_time: Duration,
+
+    #[arg(
+        long = "stream-idle-timeout",
+        help_heading = "Performance",
+        value_name = "DURATION",
+        value_parser = duration::seconds,
+        help = "Time without any line after which a log stream is reopened (bare numbers are seconds).",
+        long_help = "Time without any line after which a log stream is reopened,
+        e.g. 2m (bare numbers are seconds).
+        On flaky networks a stream can stall without failing,
+        which can't be told apart from a quiet container.
+        Reopening looks back to the last received line, so no line is lost.
+        Disabled by default."
+    )]
+    pub stream_idle_timeout: Option<Duration>,
+
+    #[arg(
+        long = "max-concurrent-streams",
+        help_heading = "Performance",
+        default_value = "16",
+        help = "Maximum number of log streams being opened at once when launching them."
+    )]
+    pub max_concurrent_streams: usize,
+
+    #[arg(
+        long = "stream-buffer",
+        help_heading = "Performance",
+        default_value = "1024",
+        value_name = "LINES",
+        help = "Number of lines each stream keeps waiting while the view is busy.",
+        long_help = "Number of lines each stream keeps waiting while the view is busy,
+        e.g. drawing a large frame or entering dig, instead of pausing its reads.
+        The lines keep the time they arrived, and are passed on in order once the view catches up.
+        What happens to the lines beyond is set by --stream-overflow."
+    )]
+    pub stream_buffer: usize,
+
+    #[arg(
+        long = "stream-overflow",
+        help_heading = "Performance",
+        default_value = "block",
+        help = "What a stream does with its lines beyond --stream-buffer.",
+        long_help = "What a stream does with its lines beyond --stream-buffer:
+        block pauses its reads until the view catches up, losing nothing,
+        while drop-oldest and drop-newest keep reading and drop the oldest waiting line
+        or the new one. The lines dropped are counted in the summary of dig (Ctrl+G)."
+    )]
+    pub stream_overflow: outbox::Overflow,
+
+    #[arg(
+        long = "backfill-window",
+        help_heading = "Performance",
+        default_value = "3",
+        value_name = "DURATION",
+        value_parser = duration::seconds,
+        help = "Time to hold back the backlogs of the streams to show them merged by time (bare numbers are seconds). 0 disables it.",
+        long_help = "Time to hold back the backlogs of the streams launched together,
+        to show them merged by time instead of interleaved by the timing of their connections
+        (bare numbers are seconds).
+        The backlogs are shown once all the streams received theirs, or the window elapsed.
+        0 shows the lines as they come."
+    )]
+    pub backfill_window: Duration,
+
+    #[arg(
+        long = "dedup-window",
+        help_heading = "Log options",
+        default_value = "256",
+        help = "Number of recent lines per container remembered to drop the ones replayed on reconnect.",
+        long_help = "Number of recent lines per container remembered to drop the ones replayed
+        when its stream is resumed, e.g. after reconnecting or returning from dig.
+        Resuming only has a precision of seconds, so the lines of that second come again;
+        they are told apart from new lines by their timestamp and content.
+        This is best effort: a line replayed after more newer lines than the window is shown again,
+        and identical lines with the same timestamp are shown once.
+        0 drops every line up to the timestamp of the last one instead."
+    )]
+    pub dedup_window: usize,
+
+    #[arg(
+        long = "split-line-size",
+        help_heading = "Log options",
+        default_value = "16384",
+        help = "Size in bytes of the chunks the kubelet splits long lines into. 0 disables merging.",
+        long_help = "Size in bytes of the chunks the kubelet splits long lines into.
+        Some runtimes split lines longer than 16KiB into several lines.
+        A line of exactly this size is joined with the next line of the same container
+        if it arrives within --split-line-window. 0 disables merging."
+    )]
+    pub split_line_size: usize,
+
+    #[arg(
+        long = "split-line-window",
+        help_heading = "Log options",
+        default_value = "100",
+        value_name = "DURATION",
+        value_parser = duration::millis,
+        help = "Time to wait for the continuation of a split line (bare numbers are milliseconds)."
+    )]
+    pub split_line_window: Duration,
+
+    #[arg(
+        long = "max-merged-line-size",
+        help_heading = "Log options",
+        default_value = "1048576",
+        help = "Size in bytes beyond which a merged line is no longer extended."
+    )]
+    pub max_merged_line_size: usize,
+
+    #[arg(
+        long = "raw-cri",
+        help_heading = "Log options",
+        help = "Parse the CRI log prefix of lines to tell stdout and stderr apart.",
+        long_help = "Parse the CRI log prefix (`<time> <stdout|stderr> <P|F> `) of lines,
+        for clusters which pass it through, to tell stdout and stderr apart.
+        Lines written to stderr are tinted, and can be dug with `stream:stderr` in the query mode."
+    )]
+    pub raw_cri: bool,
+
+    #[arg(
+        long = "no-raw-strip",
+        help_heading = "Log options",
+        help = "Show control characters of lines escaped instead of stripping them.",
+        long_help = "Show control characters of lines escaped, e.g. `\\x1b` and `\\t`,
+        instead of replacing newlines and tabs and stripping ANSI escape sequences,
+        to inspect what a container actually emits."
+    )]
+    pub no_raw_strip: bool,
+
+    #[arg(
+        long = "skip-empty",
+        help_heading = "Log options",
+        help = "Drop the lines which are empty or only whitespace.",
+        long_help = "Drop the lines which are empty or only whitespace, including the ones left empty
+        once their control characters are stripped. Otherwise they are shown as a dim ⏎,
+        and dig only lists them for queries asking for something to be there."
+    )]
+    pub skip_empty: bool,
+
+    #[arg(
+        long = "keep-trailing-whitespace",
+        help_heading = "Log options",
+        help = "Keep the trailing whitespace of the lines.",
+        long_help = "Keep the trailing whitespace of the lines, which is trimmed otherwise.
+        The `\\r` left by CRLF line endings is stripped either way.
+        The lines changed are counted in the summary of dig (Ctrl+G)."
+    )]
+    pub keep_trailing_whitespace: bool,
+
+    #[arg(
+        long = "group-stacktraces",
+        help_heading = "Log options",
+        help = "Group the lines of a stack trace into the line which started it.",
+        long_help = "Group the lines continuing a stack trace (indented lines, `at ...`, `Caused by:`
+        and Python tracebacks) into the line which started it, per container.
+        Grouped lines are shown collapsed as '(+N lines)', are expanded in the context of dig (Tab),
+        and are searched along with the line."
+    )]
+    pub group_stacktraces: bool,
+
+    #[arg(
+        long = "watch-rollout",
+        help_heading = "Log options",
+        value_name = "[NAMESPACE/]DEPLOYMENT",
+        help = "Deployment whose rollout progress is annotated in the log stream.",
+        long_help = "Deployment whose rollout progress is annotated in the log stream,
+        e.g. 'deployment payments-api: 3/5 updated, 2 available', once per change,
+        followed by 'rollout complete' or 'rollout failed'.
+        The namespace defaults to that of --namespace or the context,
+        and has to be given with more than one --namespace, --namespace-query or -A.
+        The annotations can be dug with `type:rollout`, or left out with `NOT type:rollout`,
+        in the query mode of dig."
+    )]
+    pub watch_rollout: Option<String>,
+
+    #[arg(
+        long = "with-events",
+        help_heading = "Log options",
+        help = "Merge the Kubernetes Events of the streamed Pods into the log stream.",
+        long_help = "Merge the Kubernetes Events of the streamed Pods into the log stream,
+        e.g. scheduling, image pulls and failed probes, as the API server reports them,
+        for a single timeline of what happened to the Pods and what they logged,
+        with the past events ordered by time among the backlogs of the streams.
+        The events can be dug with `type:event`, or left out with `NOT type:event`,
+        in the query mode of dig."
+    )]
+    pub with_events: bool,
 
     #[arg(
         long = "render-interval",
+        help_heading = "Performance",
         default_value = "10",
-        help = "Interval to render a log line in milliseconds.",
+        value_name = "DURATION",
+        value_parser = duration::millis,
+        help = "Interval to render a log line (bare numbers are milliseconds).",
         long_help = "Adjust this value to prevent screen flickering
-        when a large volume of logs is rendered in a short period."
+        when a large volume of logs is rendered in a short period.
+        The lines arriving in between are drawn at once, so they lag by up to the interval:
+        values up to about 100ms reduce flickering without a noticeable lag."
     )]
-    pub render_interval_millis: u64,
+    pub render_interval: Duration,
+
+    #[arg(
+        long = "burst-counter",
+        help_heading = "Display",
+        help = "Show how many lines were drawn at once when a burst is rendered.",
+        long_help = "Show a transient counter like '(+142 lines)' next to the filter
+        when a burst of lines is rendered in one interval,
+        so that it is clear the display jumped without losing lines."
+    )]
+    pub burst_counter: bool,
+
+    #[arg(
+        long = "follow-matches",
+        help_heading = "Display",
+        help = "Follow again when a line matching the filter arrives while paused.",
+        long_help = "Follow the tail again when a line matching the filter arrives while paused (Ctrl+P),
+        jumping to it instead of staying where you are. Can be toggled with Ctrl+O."
+    )]
+    pub follow_matches: bool,
+
+    #[arg(
+        long = "follow-throttle",
+        help_heading = "Display",
+        default_value = "1000",
+        value_name = "DURATION",
+        value_parser = duration::millis,
+        help = "Minimum interval between jumps to new matches (bare numbers are milliseconds)."
+    )]
+    pub follow_throttle: Duration,
+
+    #[arg(
+        long = "single",
+        help_heading = "Display",
+        help = "Show more detail while exactly one container is streamed.",
+        long_help = "Show more detail while exactly one container is streamed:
+        the meta is hidden since it can't vary, and a header row shows the pod, container,
+        node, state, uptime and restarts, refreshed every few seconds.
+        With more than one container, lines are rendered as usual."
+    )]
+    pub single: bool,
+
+    #[arg(
+        long = "separator",
+        help_heading = "Display",
+        default_value = "none",
+        help = "Separator drawn when the container of consecutive lines changes."
+    )]
+    pub separator: bul::Separator,
+
+    #[arg(
+        long = "orientation",
+        help_heading = "Display",
+        default_value = "bottom",
+        conflicts_with = "split",
+        help = "Edge of the screen where the newest lines are drawn, with the filter at the other one.",
+        long_help = "Edge of the screen where the newest lines are drawn, with the filter at the other one.
+        From the top, new lines push the older ones down and off the screen,
+        and the filter stays on the first rows, e.g. in short tmux panes.
+        Pausing and resuming from dig keep the orientation. Not available with --split."
+    )]
+    pub orientation: bul::Orientation,
+
+    #[arg(
+        long = "split",
+        help_heading = "Display",
+        num_args = 0..=1,
+        default_missing_value = "50",
+        value_name = "RATIO",
+        value_parser = clap::value_parser!(u16).range(10..=90),
+        help = "Split the live view into two columns, each with its own filter.",
+        long_help = "Split the live view into two columns, each with its own filter
+        over the same stream, e.g. to watch errors next to a trace ID.
+        RATIO is the percentage of the width taken by the left column (50 if omitted).
+        Tab switches the filter being edited. Separators and the burst counter
+        are not drawn in the split view, and --record records the new rows
+        of the left column followed by those of the right one."
+    )]
+    pub split: Option<u16>,
+
+    #[arg(
+        long = "wrap-glyph",
+        help_heading = "Display",
+        default_value = "↪ ",
+        help = "Glyph at the start of the rows continuing a wrapped line. Empty disables it.",
+        long_help = "Glyph drawn dim at the start of the rows continuing a wrapped line,
+        in the live view and dig, to tell a wrapped line from several lines.
+        It is left out of the lines recorded with --record. Empty disables it."
+    )]
+    pub wrap_glyph: String,
+
+    #[arg(
+        long = "only-changes",
+        help_heading = "Display",
+        value_name = "FIELD",
+        help = "Only show the lines where the value of the field changes.",
+        long_help = "Only show the lines where the value of the field, parsed from the line
+        as `key=value` (logfmt) or `\"key\":\"value\"` (JSON), differs from the previous one,
+        e.g. only when `state` changes. Lines without the field are not shown either.
+        The suppressed lines are still kept for dig."
+    )]
+    pub only_changes: Option<String>,
+
+    #[arg(
+        long = "change-scope",
+        help_heading = "Display",
+        default_value = "container",
+        requires = "only_changes",
+        help = "Scope in which --only-changes compares values with the previous one."
+    )]
+    pub change_scope: ChangeScope,
+
+    #[arg(
+        long = "quit-confirm-window",
+        help_heading = "Display",
+        default_value = "0",
+        value_name = "DURATION",
+        value_parser = duration::millis,
+        help = "Window to press Ctrl+C again to quit (bare numbers are milliseconds). 0 quits immediately.",
+        long_help = "Window to press Ctrl+C again to quit, e.g. 2s (bare numbers are milliseconds).
+        When set, the first Ctrl+C pauses streaming and asks for confirmation,
+        and only a second Ctrl+C within this window quits.
+        Any other key, or the window expiring, resumes streaming.
+        0 quits immediately on the first Ctrl+C."
+    )]
+    pub quit_confirm_window: Duration,
+
+    #[arg(
+        long = "error-rows",
+        help_heading = "Display",
+        default_value = "2",
+        help = "Number of recent stream warnings/errors kept above the filter. 0 disables them.",
+        long_help = "Number of recent stream warnings/errors kept above the filter.
+        Warnings and errors (e.g. stream ended, failed to launch streams)
+        stay in a sticky region instead of scrolling away with the logs,
+        until they are dismissed with Esc. The region is hidden while empty.
+        0 disables the region."
+    )]
+    pub error_rows: usize,
 
     #[arg(
         short = 'q',
         long = "queue-capacity",
+        help_heading = "Performance",
         default_value = "1000",
         help = "Queue capacity to store the logs.",
         long_help = "Queue capacity for storing logs.
@@ -81,6 +692,392 @@ pub struct Args {
         which can be beneficial when digging deeper into logs with the digger."
     )]
     pub queue_capacity: usize,
+
+    #[cfg(feature = "compress")]
+    #[arg(
+        long = "compress-queue",
+        help_heading = "Performance",
+        num_args = 0..=1,
+        default_missing_value = "10000",
+        value_name = "RECENT",
+        help = "Compress the lines in the queue older than the most recent ones.",
+        long_help = "Compress the lines in the queue older than the given number of most recent ones
+        (10000 if omitted), to keep a large --queue-capacity in less memory.
+        They are packed into compressed blocks of 256 lines, which stay packed in dig
+        and are only unpacked as their lines are shown, while dig keeps their text
+        to filter them."
+    )]
+    pub compress_queue: Option<usize>,
+
+    #[arg(
+        long = "query-size-limit",
+        help_heading = "Performance",
+        default_value = "1048576",
+        value_name = "BYTES",
+        help = "Size limit of a compiled regex of a dig query, beyond which it is matched as a literal."
+    )]
+    pub query_size_limit: usize,
+
+    #[arg(
+        long = "query-dfa-size-limit",
+        help_heading = "Performance",
+        default_value = "2097152",
+        value_name = "BYTES",
+        help = "Size limit of the DFA cache of a regex of a dig query."
+    )]
+    pub query_dfa_size_limit: usize,
+
+    #[arg(
+        long = "query-time-limit",
+        help_heading = "Performance",
+        default_value = "200",
+        value_name = "DURATION",
+        value_parser = duration::millis,
+        help = "Time the regexes of a dig query may take over the first lines (bare numbers are milliseconds).",
+        long_help = "Time the regexes of a dig query may take over the first 1024 lines
+        (bare numbers are milliseconds). Beyond it, or beyond --query-size-limit,
+        the regexes are matched as literals rather than freezing dig,
+        which the prompt tells with [literal: regex too slow] or [literal: regex too large]."
+    )]
+    pub query_time_limit: Duration,
+
+    #[arg(
+        long = "baseline",
+        help_heading = "Display",
+        help = "Baseline container to compare the logs of the others with, as [<NAMESPACE>/]<POD>/<CONTAINER>.",
+        long_help = "Baseline container to compare the logs of the others with, as [<NAMESPACE>/]<POD>/<CONTAINER>.
+        The container must be selected by the other filters as well. Without the namespace,
+        Pods of the same name in all the streamed namespaces are taken as the baseline.
+        Lines whose bodies also appear in the baseline are dimmed,
+        and lines never emitted by the baseline are marked with '+'.
+        This is useful to spot what is unique to a canary compared with a reference Pod."
+    )]
+    pub baseline: Option<String>,
+
+    #[arg(
+        long = "baseline-normalize",
+        help_heading = "Display",
+        default_value = r"\d{4}-\d{2}-\d{2}[T ][\d:.]+(Z|[+-][\d:]+)?|[0-9a-f]{8,}|\d+",
+        help = "Regex of the parts to ignore when comparing lines with the baseline.",
+        long_help = "Regex of the parts to ignore when comparing lines with the baseline,
+        such as timestamps or request ids.
+        By default, timestamps, hexadecimal ids and numbers are ignored.
+        Pass an empty string to compare lines as they are."
+    )]
+    pub baseline_normalize: String,
+
+    #[arg(
+        long = "meta-format",
+        help_heading = "Display",
+        help = "Format of the meta shown in front of each log line.",
+        long_help = "Format of the meta shown in front of each log line.
+        Available tokens are {namespace}, {pod}, {container} and {restarts},
+        where {restarts} is the restart count of the container when its stream was attached,
+        and {label.NAME} for the labels in --label-columns.
+        Defaults to '{pod} {container}', or '{namespace}/{pod} {container}'
+        when logs of multiple namespaces are merged, followed by the labels in --label-columns."
+    )]
+    pub meta_format: Option<String>,
+
+    #[arg(
+        long = "label-columns",
+        help_heading = "Display",
+        value_delimiter = ',',
+        help = "Pod labels to show in the meta, e.g. version,region.",
+        long_help = "Pod labels to show in the meta, e.g. version,region, like `kubectl get -L`.
+        They are appended to the default meta format,
+        or can be placed with {label.NAME} in --meta-format. Missing labels render as empty."
+    )]
+    pub label_columns: Vec<String>,
+
+    #[arg(
+        long = "extended-colors",
+        help_heading = "Display",
+        help = "Combine the meta colors with bold, dim and underline to tell more containers apart.",
+        long_help = "Combine the meta colors with bold, dim and underline to tell more containers apart.
+        With only 12 colors, containers often share one in large namespaces.
+        The attribute is derived from the same container identity as the color,
+        so each container keeps its look across runs."
+    )]
+    pub extended_colors: bool,
+
+    #[arg(
+        long = "namespace-color",
+        help_heading = "Display",
+        value_name = "NAMESPACE=COLOR",
+        value_parser = style::parse_namespace_color,
+        help = "Pin the color of the badge of a namespace, e.g. prod=red. Can be given multiple times.",
+        long_help = "Pin the color of the badge of a namespace, e.g. prod=red or prod=196 (ANSI value).
+        While streaming multiple namespaces (repeated --namespace, --namespace-query or -A),
+        each line starts with a badge of the initials of its namespace,
+        on a color picked by hashing its name otherwise.
+        The badge is only shown; queries match the namespace with ns: in dig,
+        and --print and --sink-file write it as the namespace field. Can be given multiple times."
+    )]
+    pub namespace_colors: Vec<(String, Color)>,
+
+    #[arg(
+        long = "alias",
+        help_heading = "Display",
+        value_name = "POD_GLOB=ALIAS",
+        value_parser = alias::parse,
+        help = "Show an alias in front of the lines of the Pods matching the glob, e.g. 'payments-api-7d9f*=canary'.",
+        long_help = "Show an alias in front of the lines of the Pods matching the glob,
+        e.g. 'payments-api-7d9f*=canary', where * matches any characters and ? any one.
+        The first matching glob applies. The alias is drawn in the live view and dig,
+        and written as the alias field with --output json and --sink-file.
+        Giving an alias to two globs, or two aliases to a glob, is refused.
+        Can be given multiple times."
+    )]
+    pub aliases: Vec<(String, String)>,
+
+    #[arg(
+        long = "context-lines",
+        help_heading = "Display",
+        default_value = "5",
+        help = "Number of lines shown before and after the selected line when expanding its context in dig."
+    )]
+    pub context_lines: usize,
+
+    #[arg(
+        long = "bell",
+        help_heading = "Display",
+        help = "Ring the terminal bell when a dig query narrows the lines down to none."
+    )]
+    pub bell: bool,
+
+    #[arg(
+        long = "dig-scrolloff",
+        help_heading = "Display",
+        default_value = "3",
+        help = "Number of lines kept visible above and below the selected line while scrolling in dig."
+    )]
+    pub dig_scrolloff: usize,
+
+    #[arg(
+        long = "word-break-chars",
+        help_heading = "Display",
+        default_value = "\"'`,;=()[]{}<>|",
+        help = "Characters, besides whitespace, ending the words picked from the selected line in dig."
+    )]
+    pub word_break_chars: String,
+
+    #[arg(
+        long = "signatures",
+        help_heading = "Display",
+        default_value = "20",
+        help = "Number of the most frequent signatures of the matching lines shown by Ctrl+O in dig."
+    )]
+    pub signatures: usize,
+
+    #[arg(
+        long = "print",
+        help_heading = "Output/Export",
+        help = "Print the logs as plain text to stdout without the interactive UI."
+    )]
+    pub print: bool,
+
+    #[arg(
+        long = "plain-follow",
+        help_heading = "Output/Export",
+        help = "Follow the logs as plain lines, with the filter typed as commands on stdin.",
+        long_help = "Follow the logs as plain lines on stdout, e.g. for screen readers,
+        without colors, raw mode or any other escape sequence, and without the need for a TTY.
+        The filter is set by typing lines on stdin: /TEXT shows only the lines containing TEXT,
+        / alone shows all lines, ? tells the filter, help lists the commands and quit stops.
+        The answers to the commands and the warnings about the streams go to stderr."
+    )]
+    pub plain_follow: bool,
+
+    #[arg(
+        long = "spoken",
+        help_heading = "Output/Export",
+        requires = "plain_follow",
+        conflicts_with = "output_template",
+        help = "Prefix the lines of --plain-follow to be read aloud, e.g. 'pod payments api says: ...'."
+    )]
+    pub spoken: bool,
+
+    #[arg(
+        long = "output-template",
+        help_heading = "Output/Export",
+        requires = "plain",
+        help = "Format of each line printed with --print or --plain-follow.",
+        long_help = "Format of each line printed with --print or --plain-follow.
+        Available tokens are those of --meta-format except labels, {message},
+        and {timestamp}, the time the line was logged (or else received) in RFC 3339.
+        Defaults to '{pod} {container} {message}', or '{namespace}/{pod} {container} {message}'
+        when logs of multiple namespaces are merged."
+    )]
+    pub output_template: Option<String>,
+
+    #[arg(
+        long = "dry-run",
+        help_heading = "Output/Export",
+        help = "List the containers which would be tailed and exit without streaming."
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long = "log-params",
+        help_heading = "Output/Export",
+        requires = "dry_run",
+        help = "Also list the parameters the log stream of each container would be opened with.",
+        long_help = "Also list the parameters the log stream of each container would be opened with,
+        e.g. follow and since, as with --since-seconds, to find out why a stream shows what it does."
+    )]
+    pub log_params: bool,
+
+    #[arg(
+        long = "output",
+        help_heading = "Output/Export",
+        default_value = "table",
+        help = "Format of the containers listed with --dry-run, or of the lines printed with --print.",
+        long_help = "Format of the containers listed with --dry-run,
+        or of the lines printed with --print, where table prints them with --output-template
+        and json prints one JSON object per line."
+    )]
+    pub output: print::Output,
+
+    #[cfg(feature = "json")]
+    #[arg(
+        long = "sink-file",
+        help_heading = "Output/Export",
+        help = "File to append each received line to as JSON. Can be given multiple times.",
+        long_help = "File to append each received line to as a JSON object per line,
+        regardless of the filter, e.g. to feed them into another tool. Can be given multiple times.
+        Without it, the lines go to capture.ndjson in the --workspace directory, if given."
+    )]
+    pub sink_files: Vec<PathBuf>,
+
+    #[arg(
+        long = "record",
+        help_heading = "Output/Export",
+        value_name = "FILE",
+        num_args = 0..=1,
+        help = "File to append the lines displayed in the live view to, with the time they were drawn.",
+        long_help = "File to append the lines displayed in the live view to,
+        exactly as drawn after filtering and wrapping (without the continuation glyph),
+        each prefixed with the time it was drawn (RFC 3339) and a tab.
+        Without a file, they are appended to record.log in the workspace (see --workspace)."
+    )]
+    pub record: Option<Option<PathBuf>>,
+
+    #[cfg(feature = "json")]
+    #[arg(
+        long = "save-session",
+        help_heading = "Output/Export",
+        value_name = "FILE",
+        num_args = 0..=1,
+        help = "File to save the captured lines and the queries to, to dig in them later with --load-session.",
+        long_help = "File to save the captured lines and the queries to, to dig in them later with --load-session.
+        The session is saved when entering and leaving dig and when quitting,
+        replacing the file each time, along with the query, mode and selected line of dig.
+        Without a file, it is saved to dig.session in the workspace (see --workspace)."
+    )]
+    pub save_session: Option<Option<PathBuf>>,
+
+    #[cfg(feature = "json")]
+    #[arg(
+        long = "load-session",
+        help_heading = "Output/Export",
+        value_name = "FILE",
+        conflicts_with_all = ["print", "plain_follow", "dry_run"],
+        help = "Dig in the lines of a session saved with --save-session, without the cluster.",
+        long_help = "Dig in the lines of a session saved with --save-session, without the cluster.
+        Dig starts with the queries and the selected line as they were left,
+        and exits when left. With --save-session, the view is saved when leaving."
+    )]
+    pub load_session: Option<PathBuf>,
+
+    #[arg(
+        long = "workspace",
+        help_heading = "Output/Export",
+        value_name = "DIR",
+        help = "Directory to write the artifacts of the run to, under standard names.",
+        long_help = "Directory to write the artifacts of the run to, under standard names:
+        capture.ndjson for every line received (unless --sink-file is given), and record.log,
+        dig.session and debug.log for --record, --save-session and --debug-log given without a file.
+        A session.json describing the run (arguments, context, namespaces, start and end times,
+        and line counts) is written there on exit. Without it, the artifact flags given without
+        a file write to $XDG_DATA_HOME/bul/sessions/<start time>/ (~/.local/share by default).
+        The directory is created on the first artifact written to it."
+    )]
+    pub workspace: Option<PathBuf>,
+
+    #[arg(
+        long = "proxy-url",
+        help_heading = "Advanced",
+        value_name = "URL",
+        help = "Proxy to reach the API server through, e.g. http://proxy:3128 or socks5://bastion:1080.",
+        long_help = "Proxy to reach the API server through, e.g. http://proxy:3128 or socks5://bastion:1080.
+        Defaults to the proxy-url of the cluster in the kubeconfig, then to HTTPS_PROXY.
+        The API server is reached once through the proxy before starting, to fail early."
+    )]
+    pub proxy_url: Option<String>,
+
+    #[arg(
+        long = "prod-pattern",
+        help_heading = "Advanced",
+        help = "Regex of the contexts to confirm before streaming from.",
+        long_help = "Regex of the contexts to confirm before streaming from.
+        When the context matches, bul asks for confirmation showing the context and namespace
+        before contacting the cluster, and shows the context in red for the whole session."
+    )]
+    pub prod_pattern: Option<String>,
+
+    #[arg(
+        short = 'y',
+        long = "yes",
+        help_heading = "Advanced",
+        help = "Skip the confirmation for contexts matching --prod-pattern."
+    )]
+    pub yes: bool,
+
+    #[arg(
+        long = "debug-log",
+        help_heading = "Advanced",
+        value_name = "FILE",
+        num_args = 0..=1,
+        help = "File to append the diagnostics of bul itself to.",
+        long_help = "File to append the diagnostics of bul itself to,
+        e.g. the containers discovered, the filters applied, the streams opened and closed
+        and the reconnects, to find out why a stream didn't attach or a line didn't show up.
+        These are unrelated to the container logs, and are never drawn on the screen.
+        Without a file, they are appended to debug.log in the workspace (see --workspace)."
+    )]
+    pub debug_log: Option<Option<PathBuf>>,
+
+    #[arg(
+        long = "no-skew-correction",
+        help_heading = "Advanced",
+        help = "Don't correct the times compared with the cluster by the estimated clock skew.",
+        long_help = "Don't correct the times compared with the cluster (e.g. the uptime in the header of --single)
+        by the skew of the cluster clock, estimated from the timestamps of live lines.
+        The skew is still reported when the clocks appear more than 5 seconds apart."
+    )]
+    pub no_skew_correction: bool,
+
+    #[arg(
+        long = "no-watch-pods",
+        help_heading = "Advanced",
+        help = "Don't watch for the Pods created after startup.",
+        long_help = "Don't watch for the Pods created after startup, e.g. by a rollout or a scale-up.
+        By default, their containers are attached with a marker line as soon as they run,
+        which needs the permission to watch the Pods.
+        Without it, --print ends once the streams at startup end.
+        --enforce-states discovers the new Pods on every check instead."
+    )]
+    pub no_watch_pods: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print examples of invocations to copy and adapt.
+    Examples,
 }
 
 /// Detects the Kubernetes context based on the provided `Args`.
@@ -90,27 +1087,53 @@ pub struct Args {
 /// 2. Retrieves the current context from the kubeconfig file.
 ///
 /// # Errors
-/// Returns an error if the kubeconfig file cannot be read or if no current context is set in the kubeconfig.
-fn detect_context(args: &Args) -> anyhow::Result<String> {
+/// Returns an error if no current context is set in the kubeconfig.
+fn detect_context(args: &Args, kubeconfig: &Kubeconfig) -> anyhow::Result<String> {
     match &args.context {
         Some(context) => Ok(context.clone()),
-        None => {
-            let kubeconfig = Kubeconfig::read()?;
-            Ok(kubeconfig
-                .current_context
-                .ok_or_else(|| anyhow::anyhow!("current_context is not set"))?)
+        None => Ok(kubeconfig
+            .current_context
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("current_context is not set"))?),
+    }
+}
+
+/// Namespace and name of the Deployment of --watch-rollout, given as `[NAMESPACE/]DEPLOYMENT`.
+/// The namespace defaults to the only one streamed, and has to be given otherwise,
+/// be it with several --namespace, --namespace-query or --all-namespaces.
+fn rollout_deployment(
+    deployment: &str,
+    namespaces: &NamespaceSelector,
+) -> anyhow::Result<(String, String)> {
+    if let Some((namespace, name)) = deployment.split_once('/') {
+        return Ok((namespace.to_string(), name.to_string()));
+    }
+    match namespaces {
+        NamespaceSelector::Names(names) if names.len() == 1 => {
+            Ok((names[0].clone(), deployment.to_string()))
         }
+        _ => Err(anyhow::anyhow!(
+            "--watch-rollout needs NAMESPACE/{deployment} with several namespaces"
+        )),
     }
 }
 
-/// Detects the Kubernetes namespace based on the provided `Args`.
+/// Detects the Kubernetes namespaces based on the provided `Args`.
 ///
 /// Namespace determination follows this priority:
-/// 1. Uses the namespace explicitly specified in the `Args` structure.
+/// 1. Uses the namespaces explicitly specified in the `Args` structure, each once.
 /// 2. Retrieves the default namespace associated with the current context from kubeconfig.
 /// 3. Uses "default".
-fn detect_namespace(args: &Args, context: &str) -> anyhow::Result<String> {
-    let kubeconfig = Kubeconfig::read()?;
+fn detect_namespaces(args: &Args, context: &str, kubeconfig: &Kubeconfig) -> Vec<String> {
+    if !args.namespace.is_empty() {
+        let mut namespaces: Vec<String> = Vec::new();
+        for namespace in args.namespace.iter() {
+            if !namespaces.contains(namespace) {
+                namespaces.push(namespace.clone());
+            }
+        }
+        return namespaces;
+    }
     let default_namespace = kubeconfig
         .contexts
         .iter()
@@ -122,48 +1145,772 @@ fn detect_namespace(args: &Args, context: &str) -> anyhow::Result<String> {
                 .and_then(|ctx| ctx.namespace.clone())
         })
         .unwrap_or_else(|| String::from("default"));
-    Ok(args.namespace.clone().unwrap_or(default_namespace))
+    vec![default_namespace]
+}
+
+/// Builds a client for the given context.
+///
+/// The kubeconfig is taken from the caller, which reads it from scratch
+/// when the client has to be rebuilt after its token expired,
+/// so that exec-based credentials (e.g. `aws eks get-token`) are re-issued.
+///
+/// The proxy is taken from `proxy_url`, then the `proxy-url` of the cluster in the kubeconfig,
+/// then `HTTPS_PROXY`, and returned with the client to be checked with [`check_proxy`].
+async fn new_client(
+    kubeconfig: Kubeconfig,
+    context: &str,
+    proxy_url: Option<&str>,
+) -> anyhow::Result<(Client, Option<String>)> {
+    let options = KubeConfigOptions {
+        context: Some(context.to_string()),
+        ..Default::default()
+    };
+    let mut config = Config::from_custom_kubeconfig(kubeconfig, &options).await?;
+    let proxy_url = match proxy_url {
+        Some(proxy_url) => Some(proxy_url.to_string()),
+        None if config.proxy_url.is_none() => ["HTTPS_PROXY", "https_proxy"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty())),
+        None => None,
+    };
+    if let Some(proxy_url) = proxy_url {
+        config.proxy_url = Some(
+            proxy_url
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid proxy url '{proxy_url}': {e}"))?,
+        );
+    }
+    let proxy_url = config.proxy_url.as_ref().map(|url| url.to_string());
+    Ok((Client::try_from(config)?, proxy_url))
+}
+
+/// Reaches the API server once through the proxy, if any, to fail early.
+async fn check_proxy(client: &Client, proxy_url: Option<&str>) -> anyhow::Result<()> {
+    if let Some(proxy_url) = proxy_url {
+        client.apiserver_version().await.map_err(|e| {
+            anyhow::anyhow!("failed to reach the API server through the proxy {proxy_url}: {e}")
+        })?;
+    }
+    Ok(())
+}
+
+/// Builds the client and the streamer over it, and checks them against the API server
+/// so that a proxy it can't be reached through or selectors it rejects fail before streaming.
+///
+/// The kubeconfig is read from scratch if not given, e.g. to retry after it was fixed.
+async fn connect(
+    kubeconfig: Option<Kubeconfig>,
+    context: String,
+    proxy_url: Option<String>,
+    new_streamer: impl FnOnce(Client) -> ContainerLogStreamer,
+) -> anyhow::Result<ContainerLogStreamer> {
+    let kubeconfig = match kubeconfig {
+        Some(kubeconfig) => kubeconfig,
+        None => Kubeconfig::read()?,
+    };
+    let (client, proxy_url) = new_client(kubeconfig, &context, proxy_url.as_deref()).await?;
+    let streamer = new_streamer(client.clone());
+    // Both go to the API server, so they overlap rather than add up against far-away clusters.
+    tokio::try_join!(
+        check_proxy(&client, proxy_url.as_deref()),
+        streamer.check_selectors(),
+    )?;
+    Ok(streamer)
+}
+
+/// Connects in the background, handing the streamer (or the error) over to the live view,
+/// which is drawn meanwhile.
+fn spawn_connect(
+    kubeconfig: Option<Kubeconfig>,
+    context: String,
+    proxy_url: Option<String>,
+    new_streamer: impl FnOnce(Client) -> ContainerLogStreamer + Send + 'static,
+) -> bul::Connection {
+    let (connected_tx, connected_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        let connected = connect(kubeconfig, context, proxy_url, new_streamer).await;
+        let _ = connected_tx.send(connected);
+    });
+    bul::Connection::Pending(connected_rx)
+}
+
+/// Error giving up on the credentials being rejected again, if it is within `REAUTH_GRACE`
+/// of the last re-authentication, rather than re-authenticating once more.
+fn reauthentication_failed(reauthenticated_at: Option<Instant>) -> Option<anyhow::Error> {
+    reauthenticated_at
+        .is_some_and(|at| at.elapsed() < REAUTH_GRACE)
+        .then(|| {
+            anyhow::anyhow!("re-authentication failed: credentials were rejected by the API server")
+        })
+}
+
+/// Writes the diagnostics of bul to the file, appending to it.
+///
+/// Nothing is ever written to the terminal, whose raw mode the diagnostics would otherwise break.
+fn init_debug_log(path: &Path) -> anyhow::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(tracing::Level::DEBUG)
+        .init();
+    Ok(())
+}
+
+/// Asks for an explicit confirmation before streaming from a context which looks like production.
+///
+/// This runs before the cluster is contacted and before any artifact is written,
+/// so declining leaves both untouched.
+/// On a terminal a single key answers; otherwise the answer is read as a line.
+fn confirm_production(context: &str, namespaces: &NamespaceSelector) -> anyhow::Result<bool> {
+    eprint!(
+        "context '{context}' (namespace {namespaces}) matches --prod-pattern. Stream logs from it? [y/N] "
+    );
+    io::stderr().flush()?;
+    if !io::stdin().is_terminal() {
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        return Ok(matches!(answer.trim(), "y" | "Y" | "yes"));
+    }
+    enable_raw_mode()?;
+    let key = loop {
+        match event::read() {
+            Ok(Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            })) => break Ok(code),
+            Ok(_) => continue,
+            Err(e) => break Err(e),
+        }
+    };
+    disable_raw_mode()?;
+    let yes = matches!(key?, KeyCode::Char('y' | 'Y'));
+    eprintln!("{}", if yes { "y" } else { "n" });
+    Ok(yes)
+}
+
+/// Resolves the paths of the artifacts, starting the debug log if asked for.
+fn open_artifacts(args: &Args, workspace: &Workspace) -> anyhow::Result<Artifacts> {
+    let artifacts = Artifacts::resolve(args, workspace)?;
+    if let Some(path) = &artifacts.debug_log {
+        init_debug_log(path)?;
+    }
+    Ok(artifacts)
+}
+
+/// Writes out what the sinks and the recording buffer, as far as they still can,
+/// e.g. once the terminal is gone.
+fn keep_captures(sinks: &sink::Sinks, recorder: Option<&Arc<Mutex<Recorder>>>) {
+    for sink in sinks.lock().unwrap().iter_mut() {
+        let _ = sink.flush();
+    }
+    if let Some(recorder) = recorder {
+        let _ = recorder.lock().unwrap().flush();
+    }
+}
+
+/// Warnings about timing flags set to values which work against each other,
+/// e.g. a render interval so long that the lines visibly lag.
+fn timing_warnings(args: &Args) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if args.log_retrieval_timeout.is_some() {
+        warnings.push(String::from(
+            "--log-retrieval-timeout is deprecated and has no effect, \
+            as streams wait for their next line without a timeout; \
+            use --backlog-quiet-time to tell when the backlog is received",
+        ));
+    }
+    if args.backlog_quiet_time.is_zero() {
+        warnings.push(String::from(
+            "--backlog-quiet-time of 0 takes the backlog of the streams as live lines, \
+            which throws off the estimated clock skew",
+        ));
+    } else if args.backlog_quiet_time < Duration::from_millis(20) {
+        warnings.push(format!(
+            "--backlog-quiet-time of {:?} is shorter than a typical API latency, \
+            so the backlog of the streams may be taken as live lines",
+            args.backlog_quiet_time
+        ));
+    }
+    if args.render_interval > Duration::from_millis(500) {
+        warnings.push(format!(
+            "--render-interval of {:?} delays every line by up to as long; \
+            around 100ms is enough to reduce flickering",
+            args.render_interval
+        ));
+    }
+    warnings
+}
+
+/// Styles of the session, before the production indicator is applied.
+fn style_config(args: &Args) -> StyleConfig {
+    StyleConfig {
+        meta_attributes: if args.extended_colors {
+            StyleConfig::extended_meta_attributes()
+        } else {
+            vec![]
+        },
+        continuation_glyph: args.wrap_glyph.clone(),
+        namespace_colors: args.namespace_colors.iter().cloned().collect(),
+        ..Default::default()
+    }
+}
+
+/// Settings of dig, which stay the same across visits.
+fn dig_settings(args: &Args, aliases: &Aliases, style: &StyleConfig) -> dig::Settings {
+    dig::Settings {
+        context_lines: args.context_lines,
+        bell: args.bell,
+        scrolloff: args.dig_scrolloff,
+        query_limits: dig::QueryLimits {
+            size: args.query_size_limit,
+            dfa_size: args.query_dfa_size_limit,
+            first_batch: args.query_time_limit,
+        },
+        signatures: args.signatures,
+        aliases: aliases.clone(),
+        style: style.clone(),
+    }
+}
+
+/// Text editor of the dig query, behind the production indicator, if any.
+fn dig_text_editor(indicator: &str, args: &Args, style: &StyleConfig) -> text_editor::State {
+    text_editor::State {
+        texteditor: Default::default(),
+        history: Default::default(),
+        prefix: format!("{indicator}❯❯❯ "),
+        mask: Default::default(),
+        prefix_style: style.dig_prefix,
+        active_char_style: style.cursor,
+        inactive_char_style: StyleBuilder::new().build(),
+        edit_mode: Default::default(),
+        word_break_chars: args.word_break_chars.chars().collect(),
+        lines: Default::default(),
+    }
+}
+
+/// Listbox of the lines matching the dig query.
+fn dig_listbox() -> listbox::State {
+    listbox::State {
+        listbox: listbox::Listbox::default(),
+        cursor: String::from("❯ "),
+        active_item_style: None,
+        inactive_item_style: None,
+        lines: Default::default(),
+    }
+}
+
+/// Paths of the artifacts of the run, given with their flags or resolved into the workspace.
+#[derive(Default)]
+#[cfg_attr(not(feature = "json"), allow(dead_code))]
+struct Artifacts {
+    record: Option<PathBuf>,
+    session: Option<PathBuf>,
+    debug_log: Option<PathBuf>,
+    /// Files to append each received line to as JSON.
+    sinks: Vec<PathBuf>,
+}
+
+impl Artifacts {
+    fn resolve(args: &Args, workspace: &Workspace) -> anyhow::Result<Self> {
+        #[cfg_attr(not(feature = "json"), allow(unused_mut))]
+        let mut artifacts = Self {
+            record: workspace.resolve(&args.record, workspace::RECORD)?,
+            debug_log: workspace.resolve(&args.debug_log, workspace::DEBUG_LOG)?,
+            ..Default::default()
+        };
+        #[cfg(feature = "json")]
+        {
+            artifacts.session = workspace.resolve(&args.save_session, workspace::SESSION)?;
+            artifacts.sinks = args.sink_files.clone();
+            // Every line is captured into a workspace given explicitly, unless sent elsewhere.
+            if artifacts.sinks.is_empty() && workspace.is_explicit() && args.load_session.is_none()
+            {
+                artifacts
+                    .sinks
+                    .push(workspace.artifact(workspace::CAPTURE)?);
+            }
+        }
+        Ok(artifacts)
+    }
+
+    /// Artifacts by kind, as listed in the manifest.
+    #[cfg(feature = "json")]
+    fn listed(&self) -> Vec<(&'static str, &Path)> {
+        let mut listed = Vec::new();
+        for (kind, path) in [
+            ("record", &self.record),
+            ("session", &self.session),
+            ("debug_log", &self.debug_log),
+        ] {
+            if let Some(path) = path {
+                listed.push((kind, path.as_path()));
+            }
+        }
+        listed.extend(self.sinks.iter().map(|path| ("capture", path.as_path())));
+        listed
+    }
+}
+
+/// Writes the manifest of the run to the workspace, if any artifact was written to it.
+#[cfg(feature = "json")]
+fn write_manifest(
+    workspace: &Workspace,
+    artifacts: &Artifacts,
+    context: &str,
+    namespaces: String,
+    started: chrono::DateTime<chrono::Local>,
+    counters: Counters,
+) -> anyhow::Result<()> {
+    if !workspace.is_used() {
+        return Ok(());
+    }
+    workspace::Manifest {
+        args: std::env::args().collect(),
+        context,
+        namespaces,
+        started,
+        counters,
+        artifacts: artifacts.listed(),
+    }
+    .write(workspace)
+}
+
+/// Manifests are written as JSON, which this build leaves out.
+#[cfg(not(feature = "json"))]
+fn write_manifest(
+    _: &Workspace,
+    _: &Artifacts,
+    _: &str,
+    _: String,
+    _: chrono::DateTime<chrono::Local>,
+    _: Counters,
+) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Digs in the lines of a saved session without contacting the cluster,
+/// saving the view back to the file of --save-session, if given, when left.
+#[cfg(feature = "json")]
+fn replay_session(path: &Path, args: &Args, artifacts: &Artifacts) -> anyhow::Result<()> {
+    let style = style_config(args);
+    let aliases = Aliases::new(args.aliases.clone())?;
+    let session = Session::load(path, &style)?;
+    info!(lines = session.lines.len(), "loaded session");
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), cursor::Hide)?;
+    let ret = dig::run(
+        dig_text_editor("", args, &style),
+        Capture {
+            lines: session.lines.into(),
+            stats: Default::default(),
+            seen: session.seen,
+        },
+        dig_listbox(),
+        None,
+        session.query.clone(),
+        session.dig,
+        &dig_settings(args, &aliases, &style),
+    );
+    let _ = execute!(io::stdout(), cursor::Show);
+    let _ = disable_raw_mode();
+
+    let (capture, _, view) = ret?;
+    if let Some(path) = &artifacts.session {
+        session::save(
+            path,
+            &capture.lines,
+            &capture.seen,
+            &session.query,
+            Some(&view),
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether the session is saved, with --save-session.
+#[cfg(feature = "json")]
+fn saves_session(artifacts: &Artifacts) -> bool {
+    artifacts.session.is_some()
+}
+
+/// Sessions are saved as JSON, which this build leaves out.
+#[cfg(not(feature = "json"))]
+fn saves_session(_: &Artifacts) -> bool {
+    false
+}
+
+/// Saves the lines and the view of dig to the file of --save-session, if given.
+#[cfg(feature = "json")]
+fn save_session(
+    artifacts: &Artifacts,
+    lines: &queue::Lines,
+    seen: &queue::Seen,
+    query: &str,
+    view: Option<&dig::View>,
+) -> anyhow::Result<()> {
+    match &artifacts.session {
+        Some(path) => session::save(path, lines, seen, query, view),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(feature = "json"))]
+fn save_session(
+    _: &Artifacts,
+    _: &queue::Lines,
+    _: &queue::Seen,
+    _: &str,
+    _: Option<&dig::View>,
+) -> anyhow::Result<()> {
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let context = detect_context(&args)?;
-    let namespace = detect_namespace(&args, &context)?;
-
+    if let Some(Command::Examples) = args.command {
+        examples::print()?;
+        return Ok(());
+    }
+    let started = chrono::Local::now();
+    let workspace = Workspace::new(args.workspace.clone(), started);
+    #[cfg(feature = "json")]
+    if let Some(path) = &args.load_session {
+        let artifacts = open_artifacts(&args, &workspace)?;
+        return replay_session(path, &args, &artifacts);
+    }
+    // Read once for the context, the namespace and the client alike.
     let kubeconfig = Kubeconfig::read()?;
-    let options = KubeConfigOptions {
-        context: Some(context),
-        ..Default::default()
+    let context = detect_context(&args, &kubeconfig)?;
+
+    // Asked before any artifact is written, so that declining leaves nothing behind.
+    let production = match &args.prod_pattern {
+        Some(pattern) => Regex::new(pattern)?.is_match(&context),
+        None => false,
+    };
+    let namespaces = match &args.namespace_query {
+        _ if args.all_namespaces => NamespaceSelector::All,
+        Some(query) => NamespaceSelector::Regex(Regex::new(query)?),
+        None => NamespaceSelector::Names(detect_namespaces(&args, &context, &kubeconfig)),
+    };
+    if production && !args.yes && !confirm_production(&context, &namespaces)? {
+        return Ok(());
+    }
+
+    let artifacts = open_artifacts(&args, &workspace)?;
+    for warning in timing_warnings(&args) {
+        warn!(warning, "questionable timing flags");
+        eprintln!("warning: {warning}");
+    }
+    info!(context, "starting");
+    // Told in the manifest, as the selector goes to the streamer.
+    let namespaces_text = namespaces.to_string();
+    let meta_format = match &args.meta_format {
+        Some(format) => format.clone(),
+        None => {
+            let mut format = if namespaces.is_multi() {
+                String::from("{namespace}/{pod} {container}")
+            } else {
+                String::from("{pod} {container}")
+            };
+            for label in args.label_columns.iter() {
+                format.push_str(&format!(" {{label.{label}}}"));
+            }
+            format
+        }
+    };
+    let meta_format = MetaFormat::parse(&meta_format, &args.label_columns)?;
+    let output_template = match &args.output_template {
+        Some(template) => OutputTemplate::parse(template)?,
+        None if args.spoken && namespaces.is_multi() => {
+            OutputTemplate::parse("pod {pod} {container} in {namespace} says: {message}")?
+        }
+        None if args.spoken => OutputTemplate::parse("pod {pod} {container} says: {message}")?,
+        None if namespaces.is_multi() => {
+            OutputTemplate::parse("{namespace}/{pod} {container} {message}")?
+        }
+        None => OutputTemplate::parse("{pod} {container} {message}")?,
+    };
+
+    let mut style = style_config(&args);
+    let aliases = Aliases::new(args.aliases.clone())?;
+    #[cfg_attr(not(feature = "json"), allow(unused_mut))]
+    let mut sinks: Vec<Box<dyn OutputSink>> = Vec::new();
+    #[cfg(feature = "json")]
+    for path in artifacts.sinks.iter() {
+        sinks.push(Box::new(
+            JsonSink::append(path)?.with_aliases(aliases.clone()),
+        ));
+    }
+
+    let baseline = match &args.baseline {
+        Some(target) => Some(Arc::new(Mutex::new(Baseline::try_new(
+            target,
+            &args.baseline_normalize,
+        )?))),
+        None => None,
+    };
+
+    // Keep reminding that the session is on production by showing the context in red.
+    let indicator = if production {
+        style.bul_prefix = style.production;
+        style.dig_prefix = style.production;
+        format!("{context} ")
+    } else {
+        String::new()
+    };
+    let mut settings = bul::Settings {
+        stream: StreamSettings {
+            backlog_quiet_time: args.backlog_quiet_time,
+            idle_timeout: args.stream_idle_timeout,
+            line_merge: match args.split_line_size {
+                0 => None,
+                chunk_size => Some(LineMerge {
+                    chunk_size,
+                    window: args.split_line_window,
+                    max_size: args.max_merged_line_size,
+                }),
+            },
+            raw_cri: args.raw_cri,
+            escape_control: args.no_raw_strip,
+            single: args.single,
+            group_stacktraces: args.group_stacktraces,
+            enforce_interval: args.enforce_states,
+            discovered: false,
+            watch_pods: !args.no_watch_pods,
+            dedup_window: args.dedup_window,
+            skip_empty: args.skip_empty,
+            keep_trailing_whitespace: args.keep_trailing_whitespace,
+            stream_buffer: args.stream_buffer,
+            overflow: args.stream_overflow,
+            max_concurrent_streams: args.max_concurrent_streams,
+            backfill_window: Some(args.backfill_window),
+        },
+        render_interval: args.render_interval,
+        quit_confirm_window: args.quit_confirm_window,
+        queue_capacity: args.queue_capacity,
+        #[cfg(feature = "compress")]
+        compress_queue: args.compress_queue,
+        #[cfg(not(feature = "compress"))]
+        compress_queue: None,
+        burst_counter: args.burst_counter,
+        follow_matches: args.follow_matches,
+        follow_throttle: args.follow_throttle,
+        separator: args.separator,
+        orientation: args.orientation,
+        split: args.split,
+        notice_capacity: args.error_rows,
+        header_interval: args.single.then(|| Duration::from_secs(5)),
+        sinks: Arc::new(Mutex::new(sinks)),
+        recorder: match &artifacts.record {
+            Some(path) => Some(Arc::new(Mutex::new(Recorder::append(path)?))),
+            None => None,
+        },
+        changes: match &args.only_changes {
+            Some(field) => Some(Arc::new(Mutex::new(ChangeFilter::new(
+                field,
+                args.change_scope,
+            )?))),
+            None => None,
+        },
+        context: context.clone(),
+        rollout: None,
+        events: args.with_events.then(events::Watcher::new),
+        baseline,
+        aliases: aliases.clone(),
+        style: style.clone(),
+    };
+    let dig_settings = dig_settings(&args, &aliases, &style);
+
+    let pods = match (&args.pod, &args.pod_query) {
+        (Some(name), _) => PodSelector::Name(name.clone()),
+        (None, Some(query)) => PodSelector::Regex(
+            Regex::new(query)?,
+            MatchScope {
+                labels: args.match_labels_in_query,
+                annotations: args.match_annotations_in_query,
+            },
+        ),
+        (None, None) => PodSelector::All,
+    };
+    let preference = match (&args.prefer_container, args.all_containers) {
+        (Some(preference), false) => Some(Regex::new(preference)?),
+        _ => None,
+    };
+    if let Some(deployment) = &args.watch_rollout {
+        let (namespace, name) = rollout_deployment(deployment, &namespaces)?;
+        settings.rollout = Some(rollout::Watcher::new(
+            namespace,
+            name,
+            Duration::from_secs(2),
+        ));
+    }
+    // Called with the client once it's built, again if connecting is retried.
+    let new_streamer = {
+        let container_states =
+            ContainerStateMatcher::new(args.container_status.clone(), args.ready_only);
+        let since_seconds = args.since.map(|since| since.as_secs() as i64);
+        let discovery_since_seconds = args.discovery_since.as_secs() as i64;
+        let skew_correction = !args.no_skew_correction;
+        let stop_on_terminating = args.stop_on_terminating;
+        let label_selector = args.selector.clone();
+        let field_selector = args.field_selector.clone();
+        let annotation_selector = args.annotation_selector.clone();
+        move |client: Client| {
+            let mut container_log_streamer = ContainerLogStreamer::new(
+                client,
+                namespaces.clone(),
+                pods.clone(),
+                container_states.clone(),
+                since_seconds,
+                discovery_since_seconds,
+                meta_format.clone(),
+            );
+            container_log_streamer.set_skew_correction(skew_correction);
+            container_log_streamer.set_stop_on_terminating(stop_on_terminating);
+            if let Some(selector) = &label_selector {
+                container_log_streamer.set_label_selector(selector.clone());
+            }
+            if let Some(selector) = &field_selector {
+                container_log_streamer.set_field_selector(selector.clone());
+            }
+            if let Some(selector) = &annotation_selector {
+                container_log_streamer.set_annotation_selector(selector.clone());
+            }
+            if let Some(preference) = &preference {
+                container_log_streamer.set_container_preference(preference.clone());
+            }
+            container_log_streamer
+        }
     };
-    let config = Config::from_custom_kubeconfig(kubeconfig, &options).await?;
-    let api_pod: Api<Pod> = Api::namespaced(Client::try_from(config)?, &namespace);
+
+    if args.dry_run || args.print || args.plain_follow {
+        let connecting = io::stderr().is_terminal();
+        if connecting {
+            eprint!("connecting to {context}…");
+        }
+        let connected = connect(
+            Some(kubeconfig),
+            context.clone(),
+            args.proxy_url.clone(),
+            new_streamer,
+        )
+        .await;
+        if connecting {
+            eprint!("\r\x1b[K");
+        }
+        let container_log_streamer = connected?;
+
+        if args.dry_run {
+            return print::dry_run(container_log_streamer, args.output, args.log_params).await;
+        }
+        let ret = if args.print {
+            let stdout: Box<dyn OutputSink> = match args.output {
+                print::Output::Table => Box::new(TemplateSink::new(output_template, io::stdout())),
+                #[cfg(feature = "json")]
+                print::Output::Json => {
+                    Box::new(JsonSink::new(io::stdout()).with_aliases(aliases.clone()))
+                }
+            };
+            let mut sinks = std::mem::take(&mut *settings.sinks.lock().unwrap());
+            sinks.insert(0, stdout);
+            print::run(container_log_streamer, settings.stream, sinks).await
+        } else {
+            let stdout = Box::new(TemplateSink::new(output_template, io::stdout()));
+            let sinks = std::mem::take(&mut *settings.sinks.lock().unwrap());
+            plain::run(container_log_streamer, settings.stream, stdout, sinks).await
+        };
+        write_manifest(
+            &workspace,
+            &artifacts,
+            &context,
+            namespaces_text,
+            started,
+            Counters::default(),
+        )?;
+        return ret;
+    }
+
+    // The view is drawn right away, and the streams start once the client is ready.
+    let mut connection = spawn_connect(
+        Some(kubeconfig),
+        context.clone(),
+        args.proxy_url.clone(),
+        new_streamer.clone(),
+    );
 
     enable_raw_mode()?;
     execute!(io::stdout(), cursor::Hide)?;
 
-    while let Ok((signal, queue)) = bul::run(
-        text_editor::State {
-            texteditor: Default::default(),
-            history: Default::default(),
-            prefix: String::from("❯❯ "),
-            mask: Default::default(),
-            prefix_style: StyleBuilder::new().fgc(Color::DarkGreen).build(),
-            active_char_style: StyleBuilder::new().bgc(Color::DarkCyan).build(),
-            inactive_char_style: StyleBuilder::new().build(),
-            edit_mode: Default::default(),
-            word_break_chars: Default::default(),
-            lines: Default::default(),
-        },
-        api_pod.clone(),
-        args.pod_query.clone(),
-        ContainerStateMatcher::new(args.container_status.clone()),
-        Duration::from_millis(args.log_retrieval_timeout_millis),
-        Duration::from_millis(args.render_interval_millis),
-        args.queue_capacity,
-    )
-    .await
-    {
+    let mut ret = Ok(());
+    let mut notice = None;
+    // Lines captured so far, kept across the relaunches of the streams.
+    let mut capture = Capture::default();
+    // Line selected in dig to go live from.
+    let mut anchor = None;
+    let mut reauthenticated_at: Option<Instant> = None;
+    // Query typed in the live view, restored once the client is rebuilt.
+    let mut restored_query = String::new();
+    // View of dig as last left, saved with the session.
+    let mut dig_view = None;
+    // Counts of the lines as of the last time the live view was left, for the manifest.
+    let mut counters = Counters::default();
+    loop {
+        let exit = bul::run(
+            text_editor::State {
+                texteditor: text_editor::TextEditor::new(std::mem::take(&mut restored_query)),
+                history: Default::default(),
+                prefix: format!("{indicator}❯❯ "),
+                mask: Default::default(),
+                prefix_style: style.bul_prefix,
+                active_char_style: style.cursor,
+                inactive_char_style: StyleBuilder::new().build(),
+                edit_mode: Default::default(),
+                word_break_chars: Default::default(),
+                lines: Default::default(),
+            },
+            notice.take(),
+            std::mem::take(&mut capture),
+            anchor.take(),
+            &mut connection,
+            &settings,
+        )
+        .await;
+        let (signal, query) = match exit {
+            Ok((signal, captured, query)) => {
+                capture = captured;
+                (signal, query)
+            }
+            // The terminal went away (e.g. with the SSH connection or the tmux pane),
+            // or the view failed otherwise, so keep at least what was captured.
+            Err(failure) => {
+                keep_captures(&settings.sinks, settings.recorder.as_ref());
+                if failure.error.downcast_ref::<io::Error>().is_some() {
+                    info!(error = %failure.error, "lost the terminal");
+                }
+                if let Some(captured) = &failure.capture {
+                    let view = dig_view.map(|view| dig::View {
+                        selected: None,
+                        ..view
+                    });
+                    let saved = save_session(
+                        &artifacts,
+                        &captured.lines,
+                        &captured.seen,
+                        &failure.query,
+                        view.as_ref(),
+                    );
+                    if let Err(e) = saved {
+                        warn!(error = %e, "failed to save the session");
+                    }
+                }
+                ret = Err(failure.error);
+                break;
+            }
+        };
         crossterm::execute!(
             io::stdout(),
             crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
@@ -171,30 +1918,114 @@ async fn main() -> anyhow::Result<()> {
             cursor::MoveTo(0, 0),
         )?;
 
+        counters = Counters::of(&capture);
+
         match signal {
+            Signal::Interrupt => {
+                // The line selected in dig was in the queue of then.
+                let view = dig_view.map(|view| dig::View {
+                    selected: None,
+                    ..view
+                });
+                if let Err(e) = save_session(
+                    &artifacts,
+                    &capture.lines,
+                    &capture.seen,
+                    &query,
+                    view.as_ref(),
+                ) {
+                    ret = Err(e);
+                }
+                break;
+            }
+            Signal::Reauthenticate => {
+                // Rebuild the client every time the credentials expire,
+                // but give up if the fresh ones are rejected right away.
+                if let Some(e) = reauthentication_failed(reauthenticated_at) {
+                    ret = Err(e);
+                    break;
+                }
+                info!("re-authenticating");
+                let kubeconfig = match Kubeconfig::read() {
+                    Ok(kubeconfig) => kubeconfig,
+                    Err(e) => {
+                        ret = Err(e.into());
+                        break;
+                    }
+                };
+                if let bul::Connection::Ready(streamer) = &mut connection {
+                    let client =
+                        match new_client(kubeconfig, &context, args.proxy_url.as_deref()).await {
+                            Ok((client, proxy_url)) => check_proxy(&client, proxy_url.as_deref())
+                                .await
+                                .map(|()| client),
+                            Err(e) => Err(e),
+                        };
+                    match client {
+                        Ok(client) => streamer.set_client(client),
+                        Err(e) => {
+                            ret = Err(e);
+                            break;
+                        }
+                    }
+                } else {
+                    // Not connected with the expired credentials yet, so connect with the fresh ones.
+                    connection = spawn_connect(
+                        Some(kubeconfig),
+                        context.clone(),
+                        args.proxy_url.clone(),
+                        new_streamer.clone(),
+                    );
+                }
+                reauthenticated_at = Some(Instant::now());
+                notice = Some(format!(
+                    "re-authenticated at {}",
+                    chrono::Local::now().format("%H:%M")
+                ));
+                // The capture is kept as well, and the streams resume after its last lines.
+                restored_query = query;
+            }
             Signal::GoToDig => {
-                dig::run(
-                    text_editor::State {
-                        texteditor: Default::default(),
-                        history: Default::default(),
-                        prefix: String::from("❯❯❯ "),
-                        mask: Default::default(),
-                        prefix_style: StyleBuilder::new().fgc(Color::DarkBlue).build(),
-                        active_char_style: StyleBuilder::new().bgc(Color::DarkCyan).build(),
-                        inactive_char_style: StyleBuilder::new().build(),
-                        edit_mode: Default::default(),
-                        word_break_chars: Default::default(),
-                        lines: Default::default(),
-                    },
-                    queue,
-                    listbox::State {
-                        listbox: listbox::Listbox::default(),
-                        cursor: String::from("❯ "),
-                        active_item_style: None,
-                        inactive_item_style: None,
-                        lines: Default::default(),
-                    },
+                info!(lines = capture.lines.len(), "entering dig");
+                // Saved before digging as well, so that quitting from dig keeps the lines.
+                let saves = saves_session(&artifacts);
+                if saves {
+                    let saved = save_session(
+                        &artifacts,
+                        &capture.lines,
+                        &capture.seen,
+                        &query,
+                        dig_view.as_ref(),
+                    );
+                    if let Err(e) = saved {
+                        notice = Some(format!("failed to save the session: {e}"));
+                    }
+                }
+                let (dug, selected, view) = dig::run(
+                    dig_text_editor(&indicator, &args, &style),
+                    std::mem::take(&mut capture),
+                    dig_listbox(),
+                    settings.baseline.clone(),
+                    query.clone(),
+                    None,
+                    &dig_settings,
                 )?;
+                // The queue is given back to bul, which keeps it.
+                capture = dug;
+                anchor = selected;
+                if saves {
+                    let saved = save_session(
+                        &artifacts,
+                        &capture.lines,
+                        &capture.seen,
+                        &query,
+                        Some(&view),
+                    );
+                    if let Err(e) = saved {
+                        notice = Some(format!("failed to save the session: {e}"));
+                    }
+                }
+                dig_view = Some(view);
 
                 // Re-enable raw mode and hide the cursor again here
                 // because they are disabled and shown, respectively, by promkit.
@@ -209,14 +2040,267 @@ async fn main() -> anyhow::Result<()> {
                 )?;
             }
             Signal::GoToBul => {
+                if let bul::Connection::Failed = connection {
+                    info!("retrying to connect");
+                    connection = spawn_connect(
+                        None,
+                        context.clone(),
+                        args.proxy_url.clone(),
+                        new_streamer.clone(),
+                    );
+                } else {
+                    info!("reconnecting");
+                }
+                continue;
+            }
+            Signal::CycleStates => {
+                let states = match &connection {
+                    bul::Connection::Ready(streamer) => streamer.container_states(),
+                    _ => None,
+                };
+                info!(states = ?states, "relaunching streams");
+                notice = Some(format!(
+                    "streaming {} containers",
+                    states.unwrap_or_else(|| String::from("all"))
+                ));
                 continue;
             }
             _ => {}
         }
     }
 
-    execute!(io::stdout(), cursor::Show)?;
-    disable_raw_mode()?;
+    // Best effort, since the terminal may be gone.
+    let _ = execute!(io::stdout(), cursor::Show);
+    let _ = disable_raw_mode();
 
-    Ok(())
+    if let Err(e) = write_manifest(
+        &workspace,
+        &artifacts,
+        &context,
+        namespaces_text,
+        started,
+        counters,
+    ) {
+        ret = ret.and(Err(e));
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::grapheme::StyledGraphemes;
+
+    use super::*;
+    use container::ContainerLog;
+
+    #[test]
+    fn credentials_rejected_again_right_away_are_fatal() {
+        // The first rejection re-authenticates.
+        assert!(reauthentication_failed(None).is_none());
+        // The fresh credentials are rejected as well.
+        let e = reauthentication_failed(Some(Instant::now())).unwrap();
+        assert_eq!(
+            e.to_string(),
+            "re-authentication failed: credentials were rejected by the API server"
+        );
+        // They expired again since.
+        if let Some(at) = Instant::now().checked_sub(REAUTH_GRACE + Duration::from_secs(1)) {
+            assert!(reauthentication_failed(Some(at)).is_none());
+        }
+    }
+
+    #[test]
+    fn rollout_deployment_in_the_only_namespace() {
+        let namespaces = NamespaceSelector::Names(vec![String::from("payments")]);
+        assert_eq!(
+            rollout_deployment("api", &namespaces).unwrap(),
+            (String::from("payments"), String::from("api"))
+        );
+        assert_eq!(
+            rollout_deployment("billing/api", &namespaces).unwrap(),
+            (String::from("billing"), String::from("api"))
+        );
+    }
+
+    #[test]
+    fn rollout_deployment_needs_the_namespace_with_several() {
+        for namespaces in [
+            NamespaceSelector::Names(vec![String::from("payments"), String::from("billing")]),
+            NamespaceSelector::Regex(Regex::new("^pay").unwrap()),
+            NamespaceSelector::All,
+        ] {
+            assert_eq!(
+                rollout_deployment("api", &namespaces)
+                    .unwrap_err()
+                    .to_string(),
+                "--watch-rollout needs NAMESPACE/api with several namespaces"
+            );
+            assert_eq!(
+                rollout_deployment("payments/api", &namespaces).unwrap(),
+                (String::from("payments"), String::from("api"))
+            );
+        }
+    }
+
+    /// Writer which keeps what is written until flushed, shared with the test.
+    #[derive(Clone, Default)]
+    struct Buffered {
+        pending: Vec<u8>,
+        flushed: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for Buffered {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.pending.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed.lock().unwrap().append(&mut self.pending);
+            Ok(())
+        }
+    }
+
+    /// Sink which can't be written to anymore, e.g. a file on a disk gone full.
+    struct Broken;
+
+    impl OutputSink for Broken {
+        fn send(&mut self, _: &ContainerLog) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::StorageFull))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::from(io::ErrorKind::StorageFull))
+        }
+    }
+
+    #[test]
+    fn captures_kept_once_the_terminal_is_gone() {
+        let writer = Buffered::default();
+        let mut sink = TemplateSink::new(
+            OutputTemplate::parse("{pod} {message}").unwrap(),
+            writer.clone(),
+        );
+        let log = ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from("api-0"),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: container::LogKind::Log,
+            meta: StyledGraphemes::from("api-0"),
+            body: StyledGraphemes::from("GET /health 200"),
+            continuation: Vec::new(),
+            received: None,
+            at: None,
+        };
+        sink.send(&log).unwrap();
+        assert!(writer.flushed.lock().unwrap().is_empty());
+
+        // A sink failing to flush doesn't keep the others from it.
+        let sinks: sink::Sinks = Arc::new(Mutex::new(vec![Box::new(Broken), Box::new(sink)]));
+        let path = std::env::temp_dir().join(format!("bul-record-{}", std::process::id()));
+        let recorder = Arc::new(Mutex::new(Recorder::append(&path).unwrap()));
+        recorder
+            .lock()
+            .unwrap()
+            .record(&[String::from("api-0 GET /health 200")]);
+        keep_captures(&sinks, Some(&recorder));
+
+        assert_eq!(
+            String::from_utf8(writer.flushed.lock().unwrap().clone()).unwrap(),
+            "api-0 GET /health 200\n"
+        );
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(recorded.ends_with("\tapi-0 GET /health 200\n"));
+    }
+
+    #[test]
+    fn styles_from_the_flags_reach_the_consumers() {
+        let args = Args::try_parse_from([
+            "bul",
+            "--extended-colors",
+            "--wrap-glyph",
+            "» ",
+            "--namespace-color",
+            "prod=red",
+            "--namespace-color",
+            "staging=208",
+        ])
+        .unwrap();
+        let style = style_config(&args);
+        assert_eq!(
+            style.meta_attributes,
+            StyleConfig::extended_meta_attributes()
+        );
+        assert_eq!(style.continuation_glyph, "» ");
+
+        // The badges of the pinned namespaces.
+        let badge = |initials: &str, color: Color| {
+            let mut badge_style = style.badge;
+            badge_style.background_color = Some(color);
+            StyledGraphemes::from_str(initials, badge_style)
+        };
+        assert!(container::namespace_badge("prod", &style)
+            .iter()
+            .eq(badge(" pr ", Color::Red).iter()));
+        assert!(container::namespace_badge("staging", &style)
+            .iter()
+            .eq(badge(" st ", Color::AnsiValue(208)).iter()));
+        // The meta of the containers, in a color and an attribute of the extended palette.
+        let meta = container::meta_style("default/web-0 web", &style);
+        assert!(style.palette.contains(&meta.foreground_color.unwrap()));
+        assert!(style.meta_attributes.contains(&meta.attributes));
+        // The editors and dig.
+        let editor = dig_text_editor("", &args, &style);
+        assert_eq!(editor.prefix_style, style.dig_prefix);
+        assert_eq!(editor.active_char_style, style.cursor);
+        assert_eq!(
+            dig_settings(&args, &Aliases::default(), &style)
+                .style
+                .highlight,
+            style.highlight
+        );
+    }
+
+    #[test]
+    fn default_styles_without_the_flags() {
+        let args = Args::try_parse_from(["bul"]).unwrap();
+        let style = style_config(&args);
+        let default = StyleConfig::default();
+        assert!(style.meta_attributes.is_empty());
+        assert_eq!(style.continuation_glyph, default.continuation_glyph);
+        assert!(style.namespace_colors.is_empty());
+        assert_eq!(
+            container::meta_style("default/web-0 web", &style),
+            container::meta_style("default/web-0 web", &default)
+        );
+    }
+
+    #[test]
+    fn timing_warnings_only_for_conflicting_values() {
+        let warnings = |flags: &[&str]| {
+            let args = Args::try_parse_from(["bul"].iter().chain(flags)).unwrap();
+            timing_warnings(&args)
+        };
+        assert!(warnings(&[]).is_empty());
+        assert_eq!(warnings(&["--backlog-quiet-time", "0ms"]).len(), 1);
+        assert_eq!(warnings(&["--backlog-quiet-time", "5ms"]).len(), 1);
+        assert_eq!(warnings(&["--render-interval", "1s"]).len(), 1);
+        assert_eq!(
+            warnings(&["--backlog-quiet-time", "5ms", "--render-interval", "1s"]).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn log_retrieval_timeout_is_accepted_but_ignored() {
+        // Invocations from before it was deprecated, e.g. with its former default.
+        let args = Args::try_parse_from(["bul", "--log-retrieval-timeout", "10"]).unwrap();
+        assert_eq!(args.backlog_quiet_time, Duration::from_millis(100));
+        let warnings = timing_warnings(&args);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("deprecated"));
+    }
 }
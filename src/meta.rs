@@ -0,0 +1,275 @@
+use chrono::SecondsFormat;
+
+use crate::container::{ContainerLog, Target};
+
+#[derive(Clone)]
+enum Token {
+    Namespace,
+    Pod,
+    Container,
+    Restarts,
+    Message,
+    /// Time the line was logged, or else received.
+    Timestamp,
+    /// Value of the Pod label, one of `--label-columns`.
+    Label(String),
+}
+
+const META_TOKENS: [(&str, Token); 4] = [
+    ("namespace", Token::Namespace),
+    ("pod", Token::Pod),
+    ("container", Token::Container),
+    ("restarts", Token::Restarts),
+];
+
+const OUTPUT_TOKENS: [(&str, Token); 6] = [
+    ("namespace", Token::Namespace),
+    ("pod", Token::Pod),
+    ("container", Token::Container),
+    ("restarts", Token::Restarts),
+    ("message", Token::Message),
+    ("timestamp", Token::Timestamp),
+];
+
+impl Token {
+    /// Parses the token, where `label.NAME` is accepted for the names in `labels`.
+    fn parse(name: &str, accepted: &[(&str, Token)], labels: &[String]) -> anyhow::Result<Self> {
+        if let Some(label) = name.strip_prefix("label.") {
+            return if labels.iter().any(|column| column == label) {
+                Ok(Token::Label(label.to_string()))
+            } else {
+                Err(anyhow::anyhow!(
+                    "unknown token '{{{name}}}', add '{label}' to --label-columns to use it"
+                ))
+            };
+        }
+        accepted
+            .iter()
+            .find(|(accept, _)| *accept == name)
+            .map(|(_, token)| token.clone())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "unknown token '{{{name}}}', expected one of {}",
+                    accepted
+                        .iter()
+                        .map(|(accept, _)| format!("{{{accept}}}"))
+                        .chain(labels.iter().map(|label| format!("{{label.{label}}}")))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+#[derive(Clone)]
+enum Segment {
+    Literal(String),
+    Token(Token),
+}
+
+/// Splits the format into literals and the accepted tokens,
+/// failing on unknown or unterminated tokens.
+fn parse(
+    format: &str,
+    accepted: &[(&str, Token)],
+    labels: &[String],
+) -> anyhow::Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            segments.push(Segment::Literal(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("unterminated token in '{format}'"))?;
+        segments.push(Segment::Token(Token::parse(
+            &rest[start + 1..start + end],
+            accepted,
+            labels,
+        )?));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest.to_string()));
+    }
+    Ok(segments)
+}
+
+/// Format of the meta shown in front of each log line, e.g. `{pod} {container}`.
+#[derive(Clone)]
+pub struct MetaFormat(Vec<Segment>);
+
+impl MetaFormat {
+    /// Parses the format, failing on unknown or unterminated tokens.
+    /// `{label.NAME}` is accepted for the names in `labels`.
+    pub fn parse(format: &str, labels: &[String]) -> anyhow::Result<Self> {
+        parse(format, &META_TOKENS, labels).map(Self)
+    }
+
+    /// Names of the Pod labels used in the format.
+    pub fn labels(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|segment| match segment {
+            Segment::Token(Token::Label(label)) => Some(label.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Renders the meta of the target. Missing labels render as empty.
+    pub fn render(&self, target: &Target) -> String {
+        self.0
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => literal.clone(),
+                Segment::Token(Token::Namespace) => target.namespace.clone(),
+                Segment::Token(Token::Pod) => target.pod.clone(),
+                Segment::Token(Token::Container) => target.container.clone(),
+                Segment::Token(Token::Restarts) => target.restarts.to_string(),
+                Segment::Token(Token::Label(label)) => {
+                    target.labels.get(label).cloned().unwrap_or_default()
+                }
+                // Not accepted in the meta format.
+                Segment::Token(Token::Message | Token::Timestamp) => String::new(),
+            })
+            .collect()
+    }
+}
+
+/// Format of each line printed as plain text, e.g. `[{pod}/{container}] {message}`.
+/// Accepts the tokens of the meta format, `{message}` and `{timestamp}`.
+#[derive(Clone)]
+pub struct OutputTemplate(Vec<Segment>);
+
+impl OutputTemplate {
+    /// Parses the template, failing on unknown or unterminated tokens.
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        parse(template, &OUTPUT_TOKENS, &[]).map(Self)
+    }
+
+    /// Renders the log as a plain text line.
+    pub fn render(&self, log: &ContainerLog) -> String {
+        self.0
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => literal.clone(),
+                Segment::Token(Token::Namespace) => log.namespace.clone(),
+                Segment::Token(Token::Pod) => log.pod.clone(),
+                Segment::Token(Token::Container) => log.container.clone(),
+                Segment::Token(Token::Restarts) => log.restarts.to_string(),
+                Segment::Token(Token::Message) => log.message(),
+                Segment::Token(Token::Timestamp) => match (log.at, log.received) {
+                    (Some(at), _) => at.to_rfc3339_opts(SecondsFormat::Millis, true),
+                    (None, Some(received)) => {
+                        received.to_rfc3339_opts(SecondsFormat::Millis, false)
+                    }
+                    (None, None) => String::new(),
+                },
+                // Not accepted in the output template.
+                Segment::Token(Token::Label(_)) => String::new(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, TimeZone, Utc};
+    use promkit::grapheme::StyledGraphemes;
+
+    use super::*;
+    use crate::container::LogKind;
+
+    fn log(at: Option<DateTime<Utc>>, received: Option<DateTime<Local>>) -> ContainerLog {
+        ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from("api-0"),
+            container: String::from("api"),
+            restarts: 2,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from("api-0"),
+            body: StyledGraphemes::from("GET /orders 200"),
+            continuation: Vec::new(),
+            received,
+            at,
+        }
+    }
+
+    fn error<T>(parsed: anyhow::Result<T>) -> String {
+        parsed.err().unwrap().to_string()
+    }
+
+    #[test]
+    fn unknown_tokens_are_rejected_with_the_accepted_ones() {
+        assert_eq!(
+            error(MetaFormat::parse("{pod} {node}", &[String::from("app")])),
+            "unknown token '{node}', expected one of {namespace}, {pod}, {container}, {restarts}, {label.app}"
+        );
+        // The message is only printed, not shown in front of the lines.
+        assert!(MetaFormat::parse("{message}", &[]).is_err());
+        assert!(OutputTemplate::parse("{message}").is_ok());
+    }
+
+    #[test]
+    fn unterminated_tokens_are_rejected() {
+        assert_eq!(
+            error(MetaFormat::parse("{pod} {container", &[])),
+            "unterminated token in '{pod} {container'"
+        );
+        assert!(OutputTemplate::parse("[{pod}/{container] {message}").is_err());
+    }
+
+    #[test]
+    fn labels_must_be_columns() {
+        assert_eq!(
+            error(MetaFormat::parse("{label.team}", &[String::from("app")])),
+            "unknown token '{label.team}', add 'team' to --label-columns to use it"
+        );
+        let format = MetaFormat::parse("{pod} {label.app}", &[String::from("app")]).unwrap();
+        assert_eq!(format.labels().collect::<Vec<_>>(), ["app"]);
+    }
+
+    #[test]
+    fn meta_is_rendered_from_the_target() {
+        let format = MetaFormat::parse(
+            "{namespace}/{pod} {container}#{restarts} {label.app}",
+            &[String::from("app"), String::from("team")],
+        )
+        .unwrap();
+        let target = Target {
+            namespace: String::from("default"),
+            pod: String::from("api-0"),
+            container: String::from("api"),
+            restarts: 2,
+            state: "running",
+            labels: [(String::from("app"), String::from("orders"))].into(),
+            preferred_over: Vec::new(),
+            terminating: None,
+        };
+        assert_eq!(format.render(&target), "default/api-0 api#2 orders");
+    }
+
+    #[test]
+    fn output_is_rendered_with_the_timestamp() {
+        let template = OutputTemplate::parse("{timestamp} [{pod}/{container}] {message}").unwrap();
+        let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 3).unwrap();
+        assert_eq!(
+            template.render(&log(Some(at), Some(Local::now()))),
+            "2024-05-01T12:00:03.000Z [api-0/api] GET /orders 200"
+        );
+
+        // Falls back to when the line was received.
+        let received = at.with_timezone(&Local);
+        assert_eq!(
+            template.render(&log(None, Some(received))),
+            format!(
+                "{} [api-0/api] GET /orders 200",
+                received.to_rfc3339_opts(SecondsFormat::Millis, false)
+            )
+        );
+        assert_eq!(
+            template.render(&log(None, None)),
+            " [api-0/api] GET /orders 200"
+        );
+    }
+}
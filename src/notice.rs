@@ -0,0 +1,51 @@
+use chrono::{DateTime, Local};
+
+use promkit::grapheme::StyledGraphemes;
+
+use crate::style::StyleConfig;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+/// A warning or an error about the log streams (e.g. a stream ended or failed)
+/// to be shown in the sticky region above the text editor pane,
+/// so that it doesn't scroll away with the log flow.
+#[derive(Clone)]
+pub struct Notice {
+    pub at: DateTime<Local>,
+    pub level: Level,
+    pub message: String,
+}
+
+impl Notice {
+    pub fn warning<S: Into<String>>(message: S) -> Self {
+        Self {
+            at: Local::now(),
+            level: Level::Warning,
+            message: message.into(),
+        }
+    }
+
+    pub fn error<S: Into<String>>(message: S) -> Self {
+        Self {
+            at: Local::now(),
+            level: Level::Error,
+            message: message.into(),
+        }
+    }
+
+    /// Renders the notice as a single row truncated to `width`.
+    pub fn styled(&self, width: usize, style: &StyleConfig) -> StyledGraphemes {
+        let text = format!("[{}] {}", self.at.format("%H:%M:%S"), self.message);
+        StyledGraphemes::from_str(
+            text.chars().take(width).collect::<String>(),
+            match self.level {
+                Level::Warning => style.warning,
+                Level::Error => style.error,
+            },
+        )
+    }
+}
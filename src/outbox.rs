@@ -0,0 +1,358 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::{container::ContainerLog, queue::Stats};
+
+/// What a stream does with a line when its outbox is full.
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Overflow {
+    /// Wait for room in the channel, holding back reading the stream.
+    Block,
+    /// Drop the oldest line waiting in the outbox.
+    DropOldest,
+    /// Drop the line.
+    DropNewest,
+}
+
+/// Lines of a stream waiting for room in the channel shared by all the streams.
+///
+/// While the consumer is busy (e.g. drawing a large frame or entering dig), each stream
+/// keeps reading into its own outbox instead of blocking on the channel, so that a short stall
+/// neither holds back the kubelet reads nor stamps the lines with the time the stall ended.
+/// The waiting lines are sent in order, before any line coming after them.
+pub struct Outbox {
+    lines: VecDeque<ContainerLog>,
+    capacity: usize,
+    overflow: Overflow,
+    /// Name of the stream the dropped lines are counted under.
+    name: String,
+    dropped: Arc<Mutex<Stats>>,
+    /// Canceled when the consumer is about to stop reading, after which nothing waits for room.
+    canceled: CancellationToken,
+}
+
+impl Outbox {
+    pub fn new(
+        capacity: usize,
+        overflow: Overflow,
+        name: String,
+        dropped: Arc<Mutex<Stats>>,
+        canceled: CancellationToken,
+    ) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            // At least a line waits, so that `Overflow::Block` has one to wait for room for.
+            capacity: capacity.max(1),
+            overflow,
+            name,
+            dropped,
+            canceled,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Sends the line, or keeps it waiting if the channel is full or other lines wait already.
+    pub async fn send(
+        &mut self,
+        log_stream_tx: &mpsc::Sender<ContainerLog>,
+        log: ContainerLog,
+    ) -> anyhow::Result<()> {
+        self.drain(log_stream_tx)?;
+        let log = if self.lines.is_empty() {
+            match log_stream_tx.try_send(log) {
+                Ok(()) => return Ok(()),
+                Err(mpsc::error::TrySendError::Full(log)) => log,
+                Err(e) => return Err(e.into()),
+            }
+        } else {
+            log
+        };
+        if self.lines.len() >= self.capacity {
+            match self.overflow {
+                Overflow::Block => {
+                    let permit = tokio::select! {
+                        biased;
+                        permit = log_stream_tx.reserve() => permit?,
+                        _ = self.canceled.cancelled() => {
+                            self.count_dropped(1);
+                            return Ok(());
+                        }
+                    };
+                    self.forward(permit);
+                }
+                Overflow::DropOldest => {
+                    self.lines.pop_front();
+                    self.count_dropped(1);
+                }
+                Overflow::DropNewest => {
+                    self.count_dropped(1);
+                    return Ok(());
+                }
+            }
+        }
+        self.lines.push_back(log);
+        Ok(())
+    }
+
+    /// Sends the oldest waiting line with the room reserved for it.
+    pub fn forward(&mut self, permit: mpsc::Permit<'_, ContainerLog>) {
+        if let Some(log) = self.lines.pop_front() {
+            permit.send(log);
+        }
+    }
+
+    /// Sends the waiting lines which there is room for, without waiting.
+    fn drain(&mut self, log_stream_tx: &mpsc::Sender<ContainerLog>) -> anyhow::Result<()> {
+        while !self.lines.is_empty() {
+            match log_stream_tx.try_reserve() {
+                Ok(permit) => self.forward(permit),
+                Err(mpsc::error::TrySendError::Full(())) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends all the waiting lines, waiting for room for them, e.g. when the stream is over.
+    /// Once canceled, the consumer may not read anymore, so the lines without room are given up
+    /// and counted as dropped.
+    pub async fn flush(
+        &mut self,
+        log_stream_tx: &mpsc::Sender<ContainerLog>,
+    ) -> anyhow::Result<()> {
+        while !self.lines.is_empty() {
+            let permit = tokio::select! {
+                biased;
+                permit = log_stream_tx.reserve() => permit,
+                _ = self.canceled.cancelled() => break,
+            };
+            match permit {
+                Ok(permit) => self.forward(permit),
+                Err(e) => {
+                    self.give_up();
+                    return Err(e.into());
+                }
+            }
+        }
+        self.give_up();
+        Ok(())
+    }
+
+    /// Drops the waiting lines, counted as dropped.
+    fn give_up(&mut self) {
+        let lines = self.lines.len();
+        self.lines.clear();
+        self.count_dropped(lines);
+    }
+
+    fn count_dropped(&self, lines: usize) {
+        if lines == 0 {
+            return;
+        }
+        *self
+            .dropped
+            .lock()
+            .unwrap()
+            .overflowed
+            .entry(self.name.clone())
+            .or_default() += lines;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use promkit::grapheme::StyledGraphemes;
+    use tokio::time::{sleep, Duration, Instant};
+
+    use super::*;
+    use crate::container::LogKind;
+
+    /// Room in the channel shared by the streams, lines waiting in each outbox,
+    /// and lines written by each stream while the consumer stalls.
+    const ROOM: usize = 4;
+    const CAPACITY: usize = 5;
+    const LINES: usize = 20;
+    const PODS: [&str; 3] = ["api-0", "api-1", "api-2"];
+
+    fn log(pod: &str, line: usize) -> ContainerLog {
+        ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from(pod),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from(pod),
+            body: StyledGraphemes::from(line.to_string()),
+            continuation: Vec::new(),
+            received: None,
+            at: None,
+        }
+    }
+
+    /// What the consumer got after stalling.
+    struct Stalled {
+        /// Lines received per Pod, in order.
+        received: BTreeMap<String, Vec<usize>>,
+        /// Lines dropped per Pod.
+        dropped: BTreeMap<String, usize>,
+        /// Whether any stream was held back reading until the consumer resumed.
+        blocked: bool,
+    }
+
+    /// Streams of `PODS` writing `LINES` lines each through their outboxes,
+    /// while the consumer doesn't take any for 500ms.
+    async fn stall(overflow: Overflow) -> Stalled {
+        let (log_stream_tx, mut log_stream_rx) = mpsc::channel(ROOM);
+        let dropped = Arc::new(Mutex::new(Stats::default()));
+        let streams: Vec<_> = PODS
+            .into_iter()
+            .map(|pod| {
+                let log_stream_tx = log_stream_tx.clone();
+                let mut outbox = Outbox::new(
+                    CAPACITY,
+                    overflow,
+                    pod.to_string(),
+                    dropped.clone(),
+                    CancellationToken::new(),
+                );
+                tokio::spawn(async move {
+                    for line in 0..LINES {
+                        outbox.send(&log_stream_tx, log(pod, line)).await.unwrap();
+                    }
+                    let read = Instant::now();
+                    // The stream is over.
+                    outbox.flush(&log_stream_tx).await.unwrap();
+                    read
+                })
+            })
+            .collect();
+        drop(log_stream_tx);
+
+        sleep(Duration::from_millis(500)).await;
+        let resumed = Instant::now();
+        let mut received: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        while let Some(log) = log_stream_rx.recv().await {
+            received
+                .entry(log.pod)
+                .or_default()
+                .push(log.body.to_string().parse().unwrap());
+        }
+        let mut blocked = false;
+        for stream in streams {
+            blocked |= stream.await.unwrap() > resumed;
+        }
+        let dropped = dropped.lock().unwrap().overflowed.clone();
+        Stalled {
+            received,
+            dropped,
+            blocked,
+        }
+    }
+
+    /// Asserts that the lines of each Pod were received in order,
+    /// and the others counted as dropped.
+    fn assert_accounted(stalled: &Stalled) {
+        for pod in PODS {
+            let received = &stalled.received[pod];
+            assert!(received.windows(2).all(|pair| pair[0] < pair[1]), "{pod}");
+            let dropped = stalled.dropped.get(pod).copied().unwrap_or_default();
+            assert_eq!(received.len() + dropped, LINES, "{pod}");
+        }
+        // Only the lines which had room in the channel or the outboxes were kept.
+        let dropped: usize = stalled.dropped.values().sum();
+        assert_eq!(dropped, PODS.len() * LINES - ROOM - PODS.len() * CAPACITY);
+    }
+
+    #[tokio::test]
+    async fn block_holds_the_streams_back_until_the_consumer_resumes() {
+        let stalled = stall(Overflow::Block).await;
+        assert!(stalled.blocked);
+        assert!(stalled.dropped.is_empty());
+        for pod in PODS {
+            assert_eq!(stalled.received[pod], (0..LINES).collect::<Vec<_>>());
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_latest_lines() {
+        let stalled = stall(Overflow::DropOldest).await;
+        assert!(!stalled.blocked);
+        assert_accounted(&stalled);
+        for pod in PODS {
+            let received = &stalled.received[pod];
+            assert_eq!(
+                received[received.len() - CAPACITY..],
+                (LINES - CAPACITY..LINES).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_newest_keeps_the_earliest_lines() {
+        let stalled = stall(Overflow::DropNewest).await;
+        assert!(!stalled.blocked);
+        assert_accounted(&stalled);
+        for pod in PODS {
+            let received = &stalled.received[pod];
+            assert_eq!(*received, (0..received.len()).collect::<Vec<_>>());
+        }
+    }
+
+    #[tokio::test]
+    async fn nothing_waits_for_room_once_canceled() {
+        let (log_stream_tx, mut log_stream_rx) = mpsc::channel(ROOM);
+        let dropped = Arc::new(Mutex::new(Stats::default()));
+        let canceled = CancellationToken::new();
+        let mut outbox = Outbox::new(
+            CAPACITY,
+            Overflow::Block,
+            String::from("api-0"),
+            dropped.clone(),
+            canceled.clone(),
+        );
+        for line in 0..ROOM + CAPACITY {
+            outbox
+                .send(&log_stream_tx, log("api-0", line))
+                .await
+                .unwrap();
+        }
+        let canceling = canceled.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            canceling.cancel();
+        });
+        // Nothing reads the channel, as when the consumer is paused or gone,
+        // so the stream is held back until canceled, and then drops what has no room.
+        outbox
+            .send(&log_stream_tx, log("api-0", ROOM + CAPACITY))
+            .await
+            .unwrap();
+        outbox
+            .send(&log_stream_tx, log("api-0", ROOM + CAPACITY + 1))
+            .await
+            .unwrap();
+        outbox.flush(&log_stream_tx).await.unwrap();
+        drop(log_stream_tx);
+        let mut received = Vec::new();
+        while let Some(log) = log_stream_rx.recv().await {
+            received.push(log.body.to_string().parse::<usize>().unwrap());
+        }
+        assert_eq!(received, (0..ROOM).collect::<Vec<_>>());
+        assert_eq!(
+            dropped.lock().unwrap().overflowed["api-0"],
+            CAPACITY + 2,
+            "every line which couldn't be delivered is counted"
+        );
+    }
+}
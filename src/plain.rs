@@ -0,0 +1,176 @@
+use std::io::{self, BufRead};
+
+use futures::StreamExt;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    container::{ContainerLogStreamer, StreamSettings},
+    sink::OutputSink,
+    style::StyleConfig,
+};
+
+/// Command read from a line of stdin while following with `--plain-follow`.
+enum Command {
+    /// Only print the lines containing the query, or all of them if empty.
+    Filter(String),
+    /// Tell the filter in effect.
+    Status,
+    Help,
+    Quit,
+    Unknown(String),
+}
+
+const HELP: &str =
+    "commands: /TEXT shows only the lines containing TEXT, / alone shows all lines, \
+? tells the filter, help lists the commands, quit stops";
+
+/// Parses a line typed on stdin. Blank lines are no command.
+fn parse(line: &str) -> Option<Command> {
+    if let Some(query) = line.strip_prefix('/') {
+        return Some(Command::Filter(query.to_string()));
+    }
+    match line.trim() {
+        "" => None,
+        "?" => Some(Command::Status),
+        "help" => Some(Command::Help),
+        "quit" | "q" => Some(Command::Quit),
+        other => Some(Command::Unknown(other.to_string())),
+    }
+}
+
+/// Follows the logs as plain lines on stdout, for screen readers and terminals without
+/// the interactive UI: no colors, no cursor movement nor any other escape sequence.
+/// The filter is set by typing commands as lines on stdin (see `parse`),
+/// so neither raw mode nor a TTY is needed.
+/// Warnings and errors about the streams, and the answers to the commands, go to stderr.
+///
+/// The filter matches like the one of the live view, i.e. the lines containing the query,
+/// and applies to `stdout` only; the other sinks still get every line.
+///
+/// Runs until all the streams end, `quit` is typed, stdout's reader goes away or Ctrl+C is pressed.
+pub async fn run(
+    container_log_streamer: ContainerLogStreamer,
+    stream_settings: StreamSettings,
+    mut stdout: Box<dyn OutputSink>,
+    mut sinks: Vec<Box<dyn OutputSink>>,
+) -> anyhow::Result<()> {
+    let (log_stream_tx, mut log_stream_rx) = mpsc::channel(256);
+    let (notice_tx, mut notice_rx) = mpsc::channel(16);
+    let canceler = CancellationToken::new();
+
+    // Read on a thread of its own rather than with tokio's stdin,
+    // whose pending read would hold back the exit until another line is typed.
+    let (command_tx, mut command_rx) = mpsc::channel(16);
+    std::thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if let Some(command) = parse(&line) {
+                if command_tx.blocking_send(command).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let streams = container_log_streamer
+        .launch_log_streams(
+            log_stream_tx,
+            notice_tx,
+            stream_settings,
+            &StyleConfig::default(),
+            canceler.clone(),
+            None,
+        )
+        .await?;
+    let log_streaming = tokio::spawn(streams.collect::<Vec<_>>());
+    eprintln!("{HELP}");
+
+    let mut query = String::new();
+    let ret = loop {
+        tokio::select! {
+            maybe_log = log_stream_rx.recv() => match maybe_log {
+                Some(log) => {
+                    let sent = sinks.iter_mut().try_for_each(|sink| sink.send(&log)).and_then(|()| {
+                        if query.is_empty() || log.message().contains(&query) {
+                            stdout.send(&log)
+                        } else {
+                            Ok(())
+                        }
+                    });
+                    if let Err(e) = sent {
+                        break if e.kind() == io::ErrorKind::BrokenPipe {
+                            Ok(())
+                        } else {
+                            Err(e.into())
+                        };
+                    }
+                }
+                None => break Ok(()),
+            },
+            Some(notice) = notice_rx.recv() => {
+                eprintln!("{} {}", notice.at.format("%H:%M:%S"), notice.message);
+            }
+            // Once stdin is closed, e.g. redirected from /dev/null, the filter stays as it is.
+            Some(command) = command_rx.recv() => match command {
+                Command::Filter(text) => {
+                    query = text;
+                    eprintln!("{}", showing(&query));
+                }
+                Command::Status => eprintln!("{}", showing(&query)),
+                Command::Help => eprintln!("{HELP}"),
+                Command::Quit => break Ok(()),
+                Command::Unknown(command) => eprintln!("unknown command {command}; {HELP}"),
+            },
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+        }
+    };
+
+    canceler.cancel();
+    log_streaming.abort();
+    let _ = stdout.flush();
+    for sink in sinks.iter_mut() {
+        let _ = sink.flush();
+    }
+    ret
+}
+
+fn showing(query: &str) -> String {
+    if query.is_empty() {
+        String::from("showing all lines")
+    } else {
+        format!("showing the lines containing {query}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert!(matches!(parse("/timeout"), Some(Command::Filter(query)) if query == "timeout"));
+        // The query is taken as typed, spaces included.
+        assert!(
+            matches!(parse("/ GET /api "), Some(Command::Filter(query)) if query == " GET /api ")
+        );
+        assert!(matches!(parse("/"), Some(Command::Filter(query)) if query.is_empty()));
+        assert!(matches!(parse(" ? "), Some(Command::Status)));
+        assert!(matches!(parse("help"), Some(Command::Help)));
+        assert!(matches!(parse("quit"), Some(Command::Quit)));
+        assert!(matches!(parse("q"), Some(Command::Quit)));
+        assert!(matches!(parse(" stop "), Some(Command::Unknown(command)) if command == "stop"));
+    }
+
+    #[test]
+    fn blank_lines_are_no_command() {
+        assert!(parse("").is_none());
+        assert!(parse("   ").is_none());
+    }
+
+    #[test]
+    fn showing_tells_the_filter() {
+        assert_eq!(showing(""), "showing all lines");
+        assert_eq!(showing("timeout"), "showing the lines containing timeout");
+    }
+}
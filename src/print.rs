@@ -0,0 +1,170 @@
+use std::io::{self, Write};
+
+use futures::StreamExt;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    container::{kubectl_flags, ContainerLogStreamer, StreamSettings},
+    sink::OutputSink,
+    style::StyleConfig,
+};
+
+/// Streams the logs to the sinks (e.g. plain text lines on stdout) without the interactive UI,
+/// so that they can be piped into other tools.
+/// Warnings and errors about the streams go to stderr.
+///
+/// Runs until all the streams end, a sink's reader goes away or Ctrl+C is pressed.
+pub async fn run(
+    container_log_streamer: ContainerLogStreamer,
+    stream_settings: StreamSettings,
+    mut sinks: Vec<Box<dyn OutputSink>>,
+) -> anyhow::Result<()> {
+    let (log_stream_tx, mut log_stream_rx) = mpsc::channel(256);
+    let (notice_tx, mut notice_rx) = mpsc::channel(16);
+    let canceler = CancellationToken::new();
+
+    let streams = container_log_streamer
+        .launch_log_streams(
+            log_stream_tx,
+            notice_tx,
+            stream_settings,
+            &StyleConfig::default(),
+            canceler.clone(),
+            None,
+        )
+        .await?;
+    let log_streaming = tokio::spawn(streams.collect::<Vec<_>>());
+
+    let ret = loop {
+        tokio::select! {
+            maybe_log = log_stream_rx.recv() => match maybe_log {
+                Some(log) => {
+                    if let Err(e) = sinks.iter_mut().try_for_each(|sink| sink.send(&log)) {
+                        // Stop quietly once the reader went away (e.g. piped into head).
+                        break if e.kind() == io::ErrorKind::BrokenPipe {
+                            Ok(())
+                        } else {
+                            Err(e.into())
+                        };
+                    }
+                }
+                None => break Ok(()),
+            },
+            Some(notice) = notice_rx.recv() => {
+                eprintln!("[{}] {}", notice.at.format("%H:%M:%S"), notice.message);
+            }
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+        }
+    };
+
+    canceler.cancel();
+    log_streaming.abort();
+    ret
+}
+
+/// Format of the containers listed by `--dry-run`.
+#[derive(Clone, clap::ValueEnum)]
+pub enum Output {
+    Table,
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// Prints the containers which would be tailed with the current filters, without streaming them,
+/// and the parameters their streams would be opened with if `log_params` is set.
+pub async fn dry_run(
+    container_log_streamer: ContainerLogStreamer,
+    output: Output,
+    log_params: bool,
+) -> anyhow::Result<()> {
+    let (notice_tx, mut notice_rx) = mpsc::channel(16);
+    let targets = container_log_streamer
+        .get_pod_and_containers(&notice_tx)
+        .await?;
+    while let Ok(notice) = notice_rx.try_recv() {
+        eprintln!("[{}] {}", notice.at.format("%H:%M:%S"), notice.message);
+    }
+
+    let mut stdout = io::stdout();
+    match output {
+        Output::Table => {
+            let mut header = vec!["NAMESPACE", "POD", "CONTAINER", "STATE"];
+            let preferred = targets
+                .iter()
+                .any(|target| !target.preferred_over.is_empty());
+            if preferred {
+                header.push("PREFERRED OVER");
+            }
+            if log_params {
+                header.push("LOG PARAMS");
+            }
+            let header = header.into_iter().map(String::from).collect::<Vec<_>>();
+            let rows = targets
+                .iter()
+                .map(|target| {
+                    let mut row = vec![
+                        target.namespace.clone(),
+                        target.pod.clone(),
+                        target.container.clone(),
+                        target.state.to_string(),
+                    ];
+                    if preferred {
+                        row.push(target.preferred_over.join(","));
+                    }
+                    if log_params {
+                        let params = container_log_streamer.log_params(target, false);
+                        row.push(kubectl_flags(&params).trim_start().to_string());
+                    }
+                    row
+                })
+                .collect::<Vec<_>>();
+            let mut widths = header.iter().map(String::len).collect::<Vec<_>>();
+            for row in rows.iter() {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+            for row in std::iter::once(&header).chain(rows.iter()) {
+                let line = row
+                    .iter()
+                    .zip(widths.iter().copied())
+                    .map(|(cell, width)| format!("{cell:width$}"))
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                writeln!(stdout, "{}", line.trim_end())?;
+            }
+        }
+        #[cfg(feature = "json")]
+        Output::Json => {
+            let targets = targets
+                .iter()
+                .map(|target| {
+                    let mut entry = serde_json::json!({
+                        "namespace": target.namespace,
+                        "pod": target.pod,
+                        "container": target.container,
+                        "state": target.state,
+                    });
+                    if !target.preferred_over.is_empty() {
+                        entry["preferred_over"] = serde_json::json!(target.preferred_over);
+                    }
+                    if log_params {
+                        let params = container_log_streamer.log_params(target, false);
+                        entry["log_params"] = serde_json::json!({
+                            "follow": params.follow,
+                            "since_seconds": params.since_seconds,
+                            "since_time": params.since_time.map(|at| at.to_rfc3339()),
+                            "tail_lines": params.tail_lines,
+                            "previous": params.previous,
+                            "limit_bytes": params.limit_bytes,
+                        });
+                    }
+                    entry
+                })
+                .collect::<Vec<_>>();
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&targets)?)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,920 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+};
+
+use chrono::{DateTime, Local, Utc};
+use rayon::prelude::*;
+
+use promkit::{crossterm::style::ContentStyle, grapheme::StyledGraphemes};
+
+use crate::{
+    container::{ContainerLog, LogKind},
+    style::StyleConfig,
+};
+
+/// Number of lines packed into a compressed block.
+const BLOCK_LINES: usize = 256;
+
+/// Lines captured to dig in, with what was received but left out of them.
+#[derive(Default)]
+pub struct Capture {
+    pub lines: Lines,
+    pub stats: Stats,
+    pub seen: Seen,
+}
+
+/// Lines received per container, keyed by namespace, Pod and container,
+/// including the ones evicted since.
+pub type Seen = BTreeMap<(String, String, String), Span>;
+
+/// Number of lines received from a container, and when the first and the last of them were.
+#[derive(Clone)]
+pub struct Span {
+    pub lines: usize,
+    pub first: DateTime<Local>,
+    pub last: DateTime<Local>,
+}
+
+/// Counts of the lines received but not captured.
+#[derive(Clone, Default)]
+pub struct Stats {
+    /// Lines evicted from the queue over its capacity.
+    pub evicted: usize,
+    /// Times the oldest and the newest evicted lines were received, if known.
+    pub evicted_range: Option<(DateTime<Local>, DateTime<Local>)>,
+    /// Lines dropped per container as received already, when its stream was resumed.
+    pub replayed: BTreeMap<String, usize>,
+    /// Blank lines dropped per container with `--skip-empty`.
+    pub blank: BTreeMap<String, usize>,
+    /// Lines dropped per container over `--stream-buffer` while the view was busy,
+    /// or still waiting for room when the streams were canceled.
+    pub overflowed: BTreeMap<String, usize>,
+    /// Lines per container stripped of a trailing `\r` or whitespace, captured nonetheless.
+    pub normalized: BTreeMap<String, usize>,
+}
+
+impl Stats {
+    /// Whether every line received was captured. Normalized lines count as captured.
+    pub fn is_empty(&self) -> bool {
+        self.evicted == 0
+            && self.replayed.is_empty()
+            && self.blank.is_empty()
+            && self.overflowed.is_empty()
+    }
+}
+
+/// Captured lines from the oldest, the older of which may be packed in blocks (see `LogQueue`).
+///
+/// The blocks stay packed when the lines are handed to dig and back,
+/// and are unpacked a block at a time as the lines are read.
+#[derive(Clone, Default)]
+pub struct Lines {
+    /// Blocks of `BLOCK_LINES` lines each.
+    blocks: VecDeque<Block>,
+    /// Number of the oldest lines of the first block which were evicted.
+    evicted: usize,
+    recent: VecDeque<ContainerLog>,
+}
+
+impl Lines {
+    pub fn len(&self) -> usize {
+        self.blocks.len() * BLOCK_LINES - self.evicted + self.recent.len()
+    }
+
+    /// Block and position in it of the line at the index, or its position in the recent lines.
+    fn locate(&self, index: usize) -> Result<(usize, usize), usize> {
+        let index = index + self.evicted;
+        let packed = self.blocks.len() * BLOCK_LINES;
+        if index < packed {
+            Ok((index / BLOCK_LINES, index % BLOCK_LINES))
+        } else {
+            Err(index - packed)
+        }
+    }
+
+    /// The line at the index, unpacking it from its block if packed.
+    pub fn get(&self, index: usize) -> Option<Cow<'_, ContainerLog>> {
+        match self.locate(index) {
+            Ok((block, line)) => Some(Cow::Owned(self.blocks[block].unpack_line(line))),
+            Err(line) => self.recent.get(line).map(Cow::Borrowed),
+        }
+    }
+
+    /// The line at the index without its body and grouped lines, which are left packed.
+    pub fn header(&self, index: usize) -> Option<Cow<'_, ContainerLog>> {
+        match self.locate(index) {
+            Ok((block, line)) => Some(Cow::Owned(self.blocks[block].header(line))),
+            Err(line) => self.recent.get(line).map(Cow::Borrowed),
+        }
+    }
+
+    #[cfg_attr(not(feature = "json"), allow(dead_code))]
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'_, ContainerLog>> {
+        self.iter_from(0)
+    }
+
+    /// The lines from the index on, unpacking their blocks one at a time.
+    pub fn iter_from(&self, start: usize) -> impl Iterator<Item = Cow<'_, ContainerLog>> {
+        let (first, skip, recent) = match self.locate(start) {
+            Ok((block, line)) => (block, line, 0),
+            Err(line) => (self.blocks.len(), 0, line),
+        };
+        self.blocks
+            .range(first..)
+            .enumerate()
+            .flat_map(move |(i, block)| {
+                let skip = if i == 0 { skip } else { 0 };
+                block.unpack().into_iter().skip(skip).map(Cow::Owned)
+            })
+            .chain(
+                self.recent
+                    .range(recent.min(self.recent.len())..)
+                    .map(Cow::Borrowed),
+            )
+    }
+
+    /// Lines without their bodies and grouped lines, telling the sources of all the lines
+    /// (e.g. their containers), each at least once but not once per line.
+    pub fn sources(&self) -> impl Iterator<Item = &ContainerLog> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .flat_map(move |(i, block)| {
+                // The sources of the evicted lines only aren't captured anymore.
+                let lines = &block.lines[if i == 0 { self.evicted } else { 0 }..];
+                block
+                    .sources
+                    .iter()
+                    .enumerate()
+                    .filter(move |(index, _)| lines.contains(index))
+                    .map(|(_, (source, _))| source)
+            })
+            .chain(self.recent.iter())
+    }
+
+    /// Text of each line as `ContainerLog::message`, unpacking the blocks in parallel
+    /// without styling their lines.
+    pub fn messages(&self) -> Vec<String> {
+        let packed: Vec<Vec<String>> = self
+            .blocks
+            .par_iter()
+            .map(|block| block.messages())
+            .collect();
+        let mut messages = Vec::with_capacity(self.len());
+        messages.extend(packed.into_iter().flatten().skip(self.evicted));
+        messages.par_extend(self.recent.par_iter().map(|log| log.message()));
+        messages
+    }
+
+    /// Indices, ascending, of the lines at the ones given, ascending as well,
+    /// for which the predicate holds, checked in parallel.
+    ///
+    /// The packed lines are given without their bodies and grouped lines, as with `header`,
+    /// so that filtering them by their text kept aside doesn't unpack any block.
+    pub fn par_filter<F>(&self, indices: Vec<usize>, predicate: F) -> Vec<usize>
+    where
+        F: Fn(usize, &ContainerLog) -> bool + Sync,
+    {
+        let packed = indices.partition_point(|&index| self.locate(index).is_ok());
+        let (packed, recent) = indices.split_at(packed);
+        let blocks: Vec<&[usize]> = packed
+            .chunk_by(|&a, &b| {
+                self.locate(a).ok().map(|(block, _)| block)
+                    == self.locate(b).ok().map(|(block, _)| block)
+            })
+            .collect();
+        let mut matched: Vec<usize> = blocks
+            .into_par_iter()
+            .flat_map_iter(|indices| {
+                let Ok((block, _)) = self.locate(indices[0]) else {
+                    unreachable!("the indices are packed");
+                };
+                let block = &self.blocks[block];
+                // The source of each line, timed as the line.
+                let mut sources: Vec<ContainerLog> = block
+                    .sources
+                    .iter()
+                    .map(|(source, _)| source.clone())
+                    .collect();
+                indices
+                    .iter()
+                    .filter(|&&index| {
+                        let Ok((_, line)) = self.locate(index) else {
+                            unreachable!("the indices are packed");
+                        };
+                        let log = &mut sources[block.lines[line]];
+                        log.received = block.received[line];
+                        log.at = block.at[line];
+                        predicate(index, log)
+                    })
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        matched.par_extend(recent.par_iter().copied().filter(|&index| {
+            let Err(line) = self.locate(index) else {
+                unreachable!("the indices are recent");
+            };
+            predicate(index, &self.recent[line])
+        }));
+        matched
+    }
+}
+
+impl From<VecDeque<ContainerLog>> for Lines {
+    fn from(recent: VecDeque<ContainerLog>) -> Self {
+        Self {
+            recent,
+            ..Self::default()
+        }
+    }
+}
+
+impl FromIterator<ContainerLog> for Lines {
+    fn from_iter<I: IntoIterator<Item = ContainerLog>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<VecDeque<_>>())
+    }
+}
+
+/// Lines kept to dig in, dropping the oldest ones beyond the capacity.
+///
+/// With compression, the lines older than the most recent `uncompressed` ones are packed
+/// into blocks of `BLOCK_LINES` lines whose bodies are compressed with LZ4,
+/// since the styled bodies take most of the memory of a large queue.
+/// The most recent lines stay as they are, as they are the ones pushed and dropped the most.
+/// Without the `compress` feature, there is no `--compress-queue` and so no block.
+pub struct LogQueue {
+    capacity: usize,
+    /// Number of the most recent lines kept uncompressed, or `None` to compress none.
+    uncompressed: Option<usize>,
+    lines: Lines,
+    style: StyleConfig,
+    /// Number of the lines evicted, and the times the oldest and the newest of them were received.
+    evicted_total: usize,
+    evicted_range: Option<(DateTime<Local>, DateTime<Local>)>,
+    seen: Seen,
+}
+
+impl LogQueue {
+    pub fn new(capacity: usize, uncompressed: Option<usize>, style: StyleConfig) -> Self {
+        Self {
+            capacity,
+            uncompressed,
+            lines: Lines {
+                recent: VecDeque::with_capacity(capacity.min(uncompressed.unwrap_or(capacity))),
+                ..Lines::default()
+            },
+            style,
+            evicted_total: 0,
+            evicted_range: None,
+            seen: Seen::new(),
+        }
+    }
+
+    /// Queue starting with the lines of an earlier capture, e.g. when the streams are relaunched,
+    /// keeping the lines it received and evicted counted.
+    /// The blocks of the capture are kept as they are, not packed again.
+    pub fn with_capture(
+        capacity: usize,
+        uncompressed: Option<usize>,
+        style: StyleConfig,
+        capture: Capture,
+    ) -> Self {
+        let mut queue = Self::new(capacity, uncompressed, style);
+        queue.lines = capture.lines;
+        // The lines of a session are all recent.
+        queue.pack();
+        while queue.lines.len() > queue.capacity {
+            queue.evict();
+        }
+        // The lines were counted when first pushed, including the ones evicted since.
+        queue.seen = capture.seen;
+        queue.evicted_total += capture.stats.evicted;
+        queue.evicted_range = match (capture.stats.evicted_range, queue.evicted_range) {
+            (Some((oldest, _)), Some((_, newest))) => Some((oldest, newest)),
+            (earlier, later) => later.or(earlier),
+        };
+        queue
+    }
+
+    /// Appends the line, packing the oldest recent lines once a block of them
+    /// is beyond the uncompressed ones, and evicting the oldest line if full.
+    pub fn push(&mut self, log: ContainerLog) {
+        // Only the lines of the containers, not the annotations of bul.
+        if let (LogKind::Log, Some(at)) = (log.kind, log.received) {
+            let key = (
+                log.namespace.clone(),
+                log.pod.clone(),
+                log.container.clone(),
+            );
+            self.seen
+                .entry(key)
+                .and_modify(|span| {
+                    span.lines += 1;
+                    span.last = at;
+                })
+                .or_insert(Span {
+                    lines: 1,
+                    first: at,
+                    last: at,
+                });
+        }
+        self.lines.recent.push_back(log);
+        self.pack();
+        while self.lines.len() > self.capacity {
+            self.evict();
+        }
+    }
+
+    /// Packs the oldest recent lines into blocks while a block of them is beyond
+    /// the uncompressed ones.
+    fn pack(&mut self) {
+        let Some(uncompressed) = self.uncompressed else {
+            return;
+        };
+        while self.lines.recent.len() >= uncompressed + BLOCK_LINES {
+            let block = Block::pack(self.lines.recent.drain(..BLOCK_LINES), &self.style);
+            self.lines.blocks.push_back(block);
+        }
+    }
+
+    fn evict(&mut self) {
+        let lines = &mut self.lines;
+        let received = match lines.blocks.front() {
+            Some(block) => block.received[lines.evicted],
+            None => lines.recent.front().and_then(|log| log.received),
+        };
+        self.evicted_total += 1;
+        if let Some(at) = received {
+            let oldest = self.evicted_range.map_or(at, |(oldest, _)| oldest);
+            self.evicted_range = Some((oldest, at));
+        }
+        if lines.blocks.is_empty() {
+            lines.recent.pop_front();
+            return;
+        }
+        // A block is only dropped once all its lines are evicted.
+        lines.evicted += 1;
+        if lines.evicted == BLOCK_LINES {
+            lines.evicted = 0;
+            lines.blocks.pop_front();
+        }
+    }
+
+    /// All the lines from the oldest, the packed ones left in their blocks,
+    /// with the lines dropped before reaching the queue counted in `stats`.
+    pub fn into_capture(self, stats: Stats) -> Capture {
+        let stats = Stats {
+            evicted: self.evicted_total,
+            evicted_range: self.evicted_range,
+            ..stats
+        };
+        Capture {
+            lines: self.lines,
+            stats,
+            seen: self.seen,
+        }
+    }
+}
+
+/// Lines whose bodies and grouped lines are compressed together.
+#[derive(Clone)]
+struct Block {
+    /// Lines without their bodies and grouped lines, shared by the lines of the same source,
+    /// with the style of their bodies and grouped lines as received, e.g. of warnings.
+    sources: Vec<(ContainerLog, ContentStyle)>,
+    /// Index into `sources` of each line.
+    lines: Vec<usize>,
+    /// Time each line was received, which differs between the lines of a source.
+    received: Vec<Option<DateTime<Local>>>,
+    /// Timestamp of each line by the API server, likewise.
+    at: Vec<Option<DateTime<Utc>>>,
+    /// Bodies and grouped lines of the lines, each length-prefixed, compressed with LZ4
+    /// with the `compress` feature.
+    packed: Vec<u8>,
+}
+
+impl Block {
+    fn pack(logs: impl Iterator<Item = ContainerLog>, style: &StyleConfig) -> Self {
+        let mut sources: Vec<(ContainerLog, ContentStyle)> = Vec::new();
+        let mut lines = Vec::new();
+        let mut received = Vec::new();
+        let mut at = Vec::new();
+        let mut text = Vec::new();
+        for mut log in logs {
+            received.push(log.received);
+            at.push(log.at);
+            write_text(&mut text, &log.body.to_string());
+            text.extend_from_slice(&(log.continuation.len() as u32).to_le_bytes());
+            for line in log.continuation.iter() {
+                write_text(&mut text, &line.to_string());
+            }
+
+            let body_style = body_style(&log, style);
+            let index = sources.iter().position(|(source, source_style)| {
+                source.namespace == log.namespace
+                    && source.pod == log.pod
+                    && source.container == log.container
+                    && source.restarts == log.restarts
+                    && source.stream == log.stream
+                    && source.kind == log.kind
+                    && *source_style == body_style
+            });
+            let index = index.unwrap_or_else(|| {
+                log.body = StyledGraphemes::default();
+                log.continuation = Vec::new();
+                sources.push((log, body_style));
+                sources.len() - 1
+            });
+            lines.push(index);
+        }
+        Self {
+            sources,
+            lines,
+            received,
+            at,
+            #[cfg(feature = "compress")]
+            packed: lz4_flex::compress_prepend_size(&text),
+            #[cfg(not(feature = "compress"))]
+            packed: text,
+        }
+    }
+
+    fn text(&self) -> Cow<'_, [u8]> {
+        #[cfg(feature = "compress")]
+        return Cow::Owned(
+            lz4_flex::decompress_size_prepended(&self.packed)
+                .expect("blocks are compressed when packed"),
+        );
+        #[cfg(not(feature = "compress"))]
+        Cow::Borrowed(&self.packed)
+    }
+
+    /// The line without its body and grouped lines.
+    fn header(&self, line: usize) -> ContainerLog {
+        let mut log = self.sources[self.lines[line]].0.clone();
+        log.received = self.received[line];
+        log.at = self.at[line];
+        log
+    }
+
+    /// Restores the line, styling its body and grouped lines as they were when received.
+    fn restore(&self, line: usize, (body, continuation): (&str, Vec<&str>)) -> ContainerLog {
+        let style = self.sources[self.lines[line]].1;
+        let mut log = self.header(line);
+        log.body = StyledGraphemes::from_str(body, style);
+        log.continuation = continuation
+            .into_iter()
+            .map(|line| StyledGraphemes::from_str(line, style))
+            .collect();
+        log
+    }
+
+    fn unpack(&self) -> Vec<ContainerLog> {
+        let text = self.text();
+        texts(&text)
+            .enumerate()
+            .map(|(line, texts)| self.restore(line, texts))
+            .collect()
+    }
+
+    fn unpack_line(&self, line: usize) -> ContainerLog {
+        let text = self.text();
+        let texts = texts(&text).nth(line).expect("blocks have a text per line");
+        self.restore(line, texts)
+    }
+
+    /// Text of each line as `ContainerLog::message`.
+    fn messages(&self) -> Vec<String> {
+        texts(&self.text())
+            .map(|(body, continuation)| {
+                let mut message = String::from(body);
+                for line in continuation {
+                    message.push('\n');
+                    message.push_str(line);
+                }
+                message
+            })
+            .collect()
+    }
+}
+
+/// Style of the body of the line as received, told among the styles bodies are received with,
+/// since a line is styled as a whole when received.
+/// It is by the kind and stream of the line unless told otherwise, e.g. for a warning.
+fn body_style(log: &ContainerLog, style: &StyleConfig) -> ContentStyle {
+    let received = log.body_style(style);
+    let Some(first) = log
+        .body
+        .iter()
+        .chain(log.continuation.iter().flat_map(|line| line.iter()))
+        .next()
+    else {
+        return received;
+    };
+    [
+        received,
+        style.body,
+        style.stderr,
+        style.annotation,
+        style.event,
+        style.warning,
+        style.error,
+        style.dim,
+    ]
+    .into_iter()
+    .find(|&candidate| {
+        let mut styled = first.clone();
+        styled.apply_style(candidate);
+        styled == *first
+    })
+    .unwrap_or(received)
+}
+
+/// Body and grouped lines of each line of the text of a block.
+fn texts(text: &[u8]) -> impl Iterator<Item = (&str, Vec<&str>)> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        if offset >= text.len() {
+            return None;
+        }
+        let body = read_text(text, &mut offset);
+        let count = read_u32(text, &mut offset);
+        let continuation = (0..count).map(|_| read_text(text, &mut offset)).collect();
+        Some((body, continuation))
+    })
+}
+
+fn write_text(buf: &mut Vec<u8>, text: &str) {
+    buf.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    buf.extend_from_slice(text.as_bytes());
+}
+
+fn read_u32(buf: &[u8], offset: &mut usize) -> u32 {
+    let value = u32::from_le_bytes(buf[*offset..*offset + 4].try_into().unwrap());
+    *offset += 4;
+    value
+}
+
+fn read_text<'a>(buf: &'a [u8], offset: &mut usize) -> &'a str {
+    let len = read_u32(buf, offset) as usize;
+    let text =
+        std::str::from_utf8(&buf[*offset..*offset + len]).expect("texts are written from strings");
+    *offset += len;
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "compress")]
+    use crate::allocations;
+    use crate::container::LogKind;
+
+    fn log(pod: &str, body: &str, continuation: &[&str]) -> ContainerLog {
+        ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from(pod),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from(pod),
+            body: StyledGraphemes::from(body),
+            continuation: continuation
+                .iter()
+                .copied()
+                .map(StyledGraphemes::from)
+                .collect(),
+            received: Some(Local::now()),
+            at: None,
+        }
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn compressed_lines_round_trip() {
+        // Multi-byte graphemes: a ZWJ sequence, a combining accent, CJK and a flag.
+        let bodies = [
+            "user 👩‍👩‍👧 signed in",
+            "cafe\u{301} naïve résumé",
+            "日本語のログ行",
+            "deploy to 🇯🇵 done",
+            "",
+        ];
+        let mut queue = LogQueue::new(10_000, Some(16), StyleConfig::default());
+        let mut expected = Vec::new();
+        for line in 0..BLOCK_LINES * 3 {
+            let body = format!("{line} {}", bodies[line % bodies.len()]);
+            let continuation = if line % 7 == 0 {
+                vec!["  at handler (ハンドラ.rs:42)", "  at 🦀::main"]
+            } else {
+                vec![]
+            };
+            queue.push(log(&format!("api-{}", line % 3), &body, &continuation));
+            expected.push((format!("api-{}", line % 3), body, continuation));
+        }
+        assert!(!queue.lines.blocks.is_empty());
+
+        let lines = queue.into_capture(Stats::default()).lines;
+        assert_eq!(lines.len(), expected.len());
+        for (log, (pod, body, continuation)) in lines.iter().zip(expected) {
+            assert_eq!(log.pod, pod);
+            assert_eq!(log.body.to_string().as_bytes(), body.as_bytes());
+            let unpacked: Vec<String> = log
+                .continuation
+                .iter()
+                .map(|line| line.to_string())
+                .collect();
+            assert_eq!(unpacked, continuation);
+        }
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn compressed_lines_keep_their_received_time() {
+        let mut queue = LogQueue::new(10_000, Some(16), StyleConfig::default());
+        let start = Local::now();
+        let received = |line: usize| {
+            (!line.is_multiple_of(5)).then(|| start + chrono::Duration::milliseconds(line as i64))
+        };
+        let at = |line: usize| {
+            (!line.is_multiple_of(3)).then(|| Utc::now() + chrono::Duration::seconds(line as i64))
+        };
+        let mut expected = Vec::new();
+        for line in 0..BLOCK_LINES * 2 {
+            let at = at(line);
+            queue.push(ContainerLog {
+                received: received(line),
+                at,
+                ..log("api-0", &line.to_string(), &[])
+            });
+            expected.push(at);
+        }
+        assert!(!queue.lines.blocks.is_empty());
+
+        let lines = queue.into_capture(Stats::default()).lines;
+        for (line, log) in lines.iter().enumerate() {
+            assert_eq!(log.received, received(line));
+            assert_eq!(log.at, expected[line]);
+        }
+    }
+
+    #[test]
+    fn evicted_lines_counted_in_the_capture() {
+        let mut queue = LogQueue::new(BLOCK_LINES, Some(16), StyleConfig::default());
+        let start = Local::now();
+        let received = |line: usize| start + chrono::Duration::milliseconds(line as i64);
+        for line in 0..BLOCK_LINES + 10 {
+            queue.push(ContainerLog {
+                received: Some(received(line)),
+                ..log("api-0", &line.to_string(), &[])
+            });
+        }
+        let replayed = BTreeMap::from([(String::from("api-0 api"), 3)]);
+        let capture = queue.into_capture(Stats {
+            replayed: replayed.clone(),
+            ..Stats::default()
+        });
+        assert_eq!(capture.lines.len(), BLOCK_LINES);
+        assert_eq!(capture.lines.get(0).unwrap().body.to_string(), "10");
+        assert_eq!(capture.stats.evicted, 10);
+        assert_eq!(
+            capture.stats.evicted_range,
+            Some((received(0), received(9)))
+        );
+        // The counts of the streams are kept as they are.
+        assert_eq!(capture.stats.replayed, replayed);
+        assert!(!capture.stats.is_empty());
+    }
+
+    #[test]
+    fn lines_seen_per_container_of_each_namespace() {
+        let mut queue = LogQueue::new(2, None, StyleConfig::default());
+        let other = ContainerLog {
+            namespace: String::from("staging"),
+            ..log("api-0", "ready", &[])
+        };
+        queue.push(log("api-0", "ready", &[]));
+        queue.push(other);
+        queue.push(log("api-0", "serving", &[]));
+        let capture = queue.into_capture(Stats::default());
+        let seen: Vec<(&str, usize)> = capture
+            .seen
+            .iter()
+            .map(|((namespace, _, _), span)| (namespace.as_str(), span.lines))
+            .collect();
+        // Including the evicted lines.
+        assert_eq!(seen, [("default", 2), ("staging", 1)]);
+        assert_eq!(capture.stats.evicted, 1);
+    }
+
+    #[test]
+    fn relaunched_queue_keeps_the_capture() {
+        let mut queue = LogQueue::new(4, None, StyleConfig::default());
+        let start = Local::now();
+        let received = |line: usize| start + chrono::Duration::milliseconds(line as i64);
+        let push = |queue: &mut LogQueue, line: usize| {
+            queue.push(ContainerLog {
+                received: Some(received(line)),
+                ..log("api-0", &line.to_string(), &[])
+            });
+        };
+        for line in 0..6 {
+            push(&mut queue, line);
+        }
+        let capture = queue.into_capture(Stats::default());
+
+        let mut queue = LogQueue::with_capture(4, None, StyleConfig::default(), capture);
+        for line in 6..8 {
+            push(&mut queue, line);
+        }
+        let capture = queue.into_capture(Stats::default());
+        let bodies: Vec<String> = capture
+            .lines
+            .iter()
+            .map(|log| log.body.to_string())
+            .collect();
+        assert_eq!(bodies, ["4", "5", "6", "7"]);
+        assert_eq!(capture.stats.evicted, 4);
+        assert_eq!(
+            capture.stats.evicted_range,
+            Some((received(0), received(3)))
+        );
+        assert_eq!(
+            capture.seen.values().map(|span| span.lines).sum::<usize>(),
+            8
+        );
+    }
+
+    /// Lines of a busy service, telling apart their order and their sources.
+    #[cfg(feature = "compress")]
+    fn busy(line: usize) -> ContainerLog {
+        let body = format!(
+            "{line} GET /api/v1/orders/{} 200 {}ms user-agent=Mozilla/5.0 trace={:016x}",
+            line % 977,
+            line % 300,
+            line * 7919
+        );
+        log(&format!("api-{}", line % 4), &body, &[])
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn compressed_lines_keep_their_style() {
+        let style = StyleConfig::default();
+        let warning = ContainerLog {
+            kind: LogKind::Event,
+            body: StyledGraphemes::from_str("Warning BackOff: restarting", style.warning),
+            ..log("api-0", "", &[])
+        };
+        let event = ContainerLog {
+            kind: LogKind::Event,
+            body: StyledGraphemes::from_str("Normal Pulled: image", style.event),
+            ..log("api-0", "", &[])
+        };
+        let marker = ContainerLog {
+            kind: LogKind::Marker,
+            body: StyledGraphemes::from_str("── attached: new Pod ──", style.dim),
+            ..log("api-0", "", &[])
+        };
+        let stderr = ContainerLog {
+            stream: Some(crate::container::LogStream::Stderr),
+            body: StyledGraphemes::from_str("panic: oops", style.stderr),
+            continuation: vec![StyledGraphemes::from_str("  at main", style.stderr)],
+            ..log("api-0", "", &[])
+        };
+        let lines = [warning, event, marker, stderr];
+        let mut queue = LogQueue::new(10_000, Some(0), style);
+        for line in 0..BLOCK_LINES {
+            queue.push(lines[line % lines.len()].clone());
+        }
+        assert_eq!(queue.lines.blocks.len(), 1);
+
+        let capture = queue.into_capture(Stats::default());
+        for (line, log) in capture.lines.iter().enumerate() {
+            assert!(log.body == lines[line % lines.len()].body, "{line}");
+            assert!(log.continuation == lines[line % lines.len()].continuation);
+        }
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn relaunched_queue_keeps_the_blocks_packed() {
+        let mut queue = LogQueue::new(BLOCK_LINES * 4, Some(16), StyleConfig::default());
+        for line in 0..BLOCK_LINES * 3 {
+            queue.push(busy(line));
+        }
+        let capture = queue.into_capture(Stats::default());
+        let packed: Vec<*const u8> = capture
+            .lines
+            .blocks
+            .iter()
+            .map(|block| block.packed.as_ptr())
+            .collect();
+        assert_eq!(packed.len(), 2);
+
+        // Handed back as they were, e.g. from dig, rather than unpacked and packed again.
+        let mut queue =
+            LogQueue::with_capture(BLOCK_LINES * 4, Some(16), StyleConfig::default(), capture);
+        for line in BLOCK_LINES * 3..BLOCK_LINES * 5 {
+            queue.push(busy(line));
+        }
+        let capture = queue.into_capture(Stats::default());
+        assert_eq!(capture.lines.blocks[0].packed.as_ptr(), packed[1]);
+        assert_eq!(capture.lines.len(), BLOCK_LINES * 4);
+        assert_eq!(capture.stats.evicted, BLOCK_LINES);
+        let first = BLOCK_LINES;
+        for (line, log) in capture.lines.iter().enumerate() {
+            assert_eq!(log.body.to_string(), busy(first + line).body.to_string());
+        }
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn lines_read_across_the_blocks_and_the_evicted_lines() {
+        let mut queue = LogQueue::new(BLOCK_LINES * 2, Some(16), StyleConfig::default());
+        let evicted = 10;
+        for line in 0..BLOCK_LINES * 2 + evicted {
+            queue.push(busy(line));
+        }
+        let lines = queue.into_capture(Stats::default()).lines;
+        assert_eq!(lines.evicted, evicted);
+        let body = |index: usize| busy(evicted + index).body.to_string();
+
+        for index in [
+            0,
+            BLOCK_LINES - evicted - 1,
+            BLOCK_LINES - evicted,
+            lines.len() - 1,
+        ] {
+            assert_eq!(lines.get(index).unwrap().body.to_string(), body(index));
+            assert_eq!(lines.header(index).unwrap().pod, busy(evicted + index).pod);
+            let from: Vec<String> = lines
+                .iter_from(index)
+                .map(|log| log.body.to_string())
+                .collect();
+            assert_eq!(from.len(), lines.len() - index);
+            assert_eq!(from[0], body(index));
+        }
+        assert!(lines.get(lines.len()).is_none());
+        let messages = lines.messages();
+        assert_eq!(messages.len(), lines.len());
+        assert!((0..lines.len()).all(|index| messages[index] == body(index)));
+        let pods: std::collections::BTreeSet<&str> =
+            lines.sources().map(|log| log.pod.as_str()).collect();
+        assert_eq!(pods.len(), 4);
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn packed_lines_filtered_without_unpacking_their_bodies() {
+        let mut queue = LogQueue::new(10_000, Some(16), StyleConfig::default());
+        for line in 0..BLOCK_LINES * 3 {
+            queue.push(busy(line));
+        }
+        let lines = queue.into_capture(Stats::default()).lines;
+        let messages = lines.messages();
+        let expected: Vec<usize> = (0..lines.len())
+            .filter(|&index| {
+                let log = lines.get(index).unwrap();
+                log.pod == "api-1" && messages[index].contains(" 200 1")
+            })
+            .collect();
+        let candidates: Vec<usize> = (0..lines.len()).step_by(3).collect();
+        let filtered = lines.par_filter(candidates.clone(), |index, log| {
+            // Only the recent lines come with their bodies.
+            assert!(log.body.is_empty() || lines.locate(index).is_err());
+            log.pod == "api-1" && messages[index].contains(" 200 1")
+        });
+        let expected: Vec<usize> = expected
+            .into_iter()
+            .filter(|index| candidates.contains(index))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(filtered, expected);
+    }
+
+    /// Memory held by the queue of 20,000 lines of a busy service, compressed or not,
+    /// after entering dig and coming back:
+    /// about 91 MB uncompressed and 6 MB compressed.
+    #[cfg(feature = "compress")]
+    #[test]
+    fn compressed_queue_takes_a_fraction_of_the_memory() {
+        let held = |uncompressed: Option<usize>| {
+            let (lines, bytes) = allocations::live(|| {
+                let mut queue = LogQueue::new(20_000, uncompressed, StyleConfig::default());
+                for line in 0..20_000 {
+                    queue.push(busy(line));
+                }
+                queue.into_capture(Stats::default()).lines
+            });
+            assert_eq!(lines.len(), 20_000);
+            bytes
+        };
+        let plain = held(None);
+        let compressed = held(Some(1_000));
+        assert!(compressed * 4 < plain, "{compressed} of {plain}");
+    }
+}
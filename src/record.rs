@@ -0,0 +1,55 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, LineWriter, Write},
+    path::Path,
+};
+
+use chrono::{SecondsFormat, Utc};
+
+/// Appends the rows drawn in the stream area to a file, each prefixed with the time it was drawn
+/// and a tab, e.g. `2024-05-01T12:34:56.789Z\tapi-7d9f app GET /healthz 200`.
+///
+/// Recording stops at the first failure to write, which is kept until taken
+/// so that it can be reported without failing the drawing.
+pub struct Recorder {
+    writer: Option<LineWriter<File>>,
+    failure: Option<io::Error>,
+}
+
+impl Recorder {
+    /// Opens the file to append to, creating it if missing.
+    pub fn append(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: Some(LineWriter::new(file)),
+            failure: None,
+        })
+    }
+
+    pub fn record(&mut self, rows: &[String]) {
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+        let at = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        if let Err(e) = rows
+            .iter()
+            .try_for_each(|row| writeln!(writer, "{at}\t{row}"))
+        {
+            self.writer = None;
+            self.failure = Some(e);
+        }
+    }
+
+    /// Writes out what is buffered, e.g. before exiting on a failure.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes the failure which stopped the recording, if any.
+    pub fn take_failure(&mut self) -> Option<io::Error> {
+        self.failure.take()
+    }
+}
@@ -0,0 +1,280 @@
+use std::sync::{Arc, Mutex};
+
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::{Api, Client};
+use tokio::{
+    sync::mpsc,
+    time::{self, Duration},
+};
+use tokio_util::sync::CancellationToken;
+
+use promkit::grapheme::StyledGraphemes;
+
+use crate::{
+    container::{ContainerLog, LogKind},
+    notice::Notice,
+    style::StyleConfig,
+};
+
+/// Progress of a rollout, compared between polls to annotate only its changes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Progress {
+    Rolling {
+        desired: i32,
+        updated: i32,
+        available: i32,
+    },
+    Complete,
+    Failed(String),
+}
+
+impl Progress {
+    fn of(deployment: &Deployment) -> Self {
+        let desired = deployment
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.replicas)
+            .unwrap_or(1);
+        let Some(status) = deployment.status.as_ref() else {
+            return Progress::Rolling {
+                desired,
+                updated: 0,
+                available: 0,
+            };
+        };
+        if let Some(condition) = status
+            .conditions
+            .iter()
+            .flatten()
+            .find(|condition| condition.type_ == "Progressing" && condition.status == "False")
+        {
+            return Progress::Failed(condition.reason.clone().unwrap_or_default());
+        }
+        let updated = status.updated_replicas.unwrap_or(0);
+        let available = status.available_replicas.unwrap_or(0);
+        let observed = status.observed_generation >= deployment.metadata.generation;
+        if observed
+            && updated == desired
+            && available == desired
+            && status.replicas.unwrap_or(0) == desired
+        {
+            Progress::Complete
+        } else {
+            Progress::Rolling {
+                desired,
+                updated,
+                available,
+            }
+        }
+    }
+}
+
+/// Watches the rollout of a Deployment by polling its status,
+/// annotating the log stream with a line on each change of the progress,
+/// e.g. `deployment payments-api: 3/5 updated, 2 available`, and when it completes or fails.
+///
+/// The annotations are `ContainerLog`s of the kind `LogKind::Rollout`,
+/// which can be dug with `type:rollout` (or left out with `NOT type:rollout`) in the query mode.
+/// The progress is annotated once across relaunches of the streams, which run it again.
+#[derive(Clone)]
+pub struct Watcher {
+    namespace: String,
+    name: String,
+    interval: Duration,
+    /// Progress last annotated, shared by the clones of the relaunches.
+    last: Arc<Mutex<Option<Progress>>>,
+}
+
+impl Watcher {
+    pub fn new(namespace: String, name: String, interval: Duration) -> Self {
+        Self {
+            namespace,
+            name,
+            interval,
+            last: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Whether the progress differs from the one last annotated, taking it as annotated if so.
+    fn changed(&self, progress: &Progress) -> bool {
+        let mut last = self.last.lock().unwrap();
+        if last.as_ref() == Some(progress) {
+            return false;
+        }
+        *last = Some(progress.clone());
+        true
+    }
+
+    /// Polls the Deployment through the client until canceled.
+    /// Failures to get it are reported as warnings and retried at the next poll,
+    /// leaving the log streams unaffected.
+    pub async fn run(
+        &self,
+        client: Client,
+        log_stream_tx: mpsc::Sender<ContainerLog>,
+        notice_tx: mpsc::Sender<Notice>,
+        style: &StyleConfig,
+        canceled: CancellationToken,
+    ) {
+        let api = Api::<Deployment>::namespaced(client, &self.namespace);
+        let mut interval = time::interval(self.interval);
+        loop {
+            tokio::select! {
+                _ = canceled.cancelled() => break,
+                _ = interval.tick() => (),
+            }
+            let progress = match api.get(&self.name).await {
+                Ok(deployment) => Progress::of(&deployment),
+                Err(e) => {
+                    let _ = notice_tx.try_send(Notice::warning(format!(
+                        "failed to watch rollout of {}: {e}",
+                        self.name
+                    )));
+                    continue;
+                }
+            };
+            if !self.changed(&progress) {
+                continue;
+            }
+            let text = match &progress {
+                Progress::Rolling {
+                    desired,
+                    updated,
+                    available,
+                } => format!(
+                    "deployment {}: {updated}/{desired} updated, {available} available",
+                    self.name
+                ),
+                Progress::Complete => format!("deployment {}: rollout complete", self.name),
+                Progress::Failed(reason) => {
+                    format!("deployment {}: rollout failed ({reason})", self.name)
+                }
+            };
+            let annotation = ContainerLog {
+                namespace: self.namespace.clone(),
+                pod: String::new(),
+                container: String::new(),
+                restarts: 0,
+                stream: None,
+                kind: LogKind::Rollout,
+                meta: StyledGraphemes::from_str("rollout", style.annotation),
+                body: StyledGraphemes::from_str(text, style.annotation),
+                continuation: Vec::new(),
+                received: None,
+                at: None,
+            };
+            if log_stream_tx.send(annotation).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::{
+        api::apps::v1::{DeploymentCondition, DeploymentSpec, DeploymentStatus},
+        apimachinery::pkg::apis::meta::v1::ObjectMeta,
+    };
+
+    use super::*;
+
+    fn deployment(generation: i64, status: DeploymentStatus) -> Deployment {
+        Deployment {
+            metadata: ObjectMeta {
+                generation: Some(generation),
+                ..Default::default()
+            },
+            spec: Some(DeploymentSpec {
+                replicas: Some(3),
+                ..Default::default()
+            }),
+            status: Some(status),
+        }
+    }
+
+    fn status(observed: i64, replicas: i32, updated: i32, available: i32) -> DeploymentStatus {
+        DeploymentStatus {
+            observed_generation: Some(observed),
+            replicas: Some(replicas),
+            updated_replicas: Some(updated),
+            available_replicas: Some(available),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn progress_of_a_rolling_deployment() {
+        // The old replica is still there.
+        assert_eq!(
+            Progress::of(&deployment(2, status(2, 4, 3, 2))),
+            Progress::Rolling {
+                desired: 3,
+                updated: 3,
+                available: 2
+            }
+        );
+        assert_eq!(
+            Progress::of(&Deployment {
+                status: None,
+                ..deployment(1, DeploymentStatus::default())
+            }),
+            Progress::Rolling {
+                desired: 3,
+                updated: 0,
+                available: 0
+            }
+        );
+    }
+
+    #[test]
+    fn progress_of_a_complete_deployment() {
+        assert_eq!(
+            Progress::of(&deployment(2, status(2, 3, 3, 3))),
+            Progress::Complete
+        );
+    }
+
+    #[test]
+    fn progress_of_a_failed_deployment() {
+        let mut status = status(2, 4, 1, 3);
+        status.conditions = Some(vec![DeploymentCondition {
+            type_: String::from("Progressing"),
+            status: String::from("False"),
+            reason: Some(String::from("ProgressDeadlineExceeded")),
+            ..Default::default()
+        }]);
+        assert_eq!(
+            Progress::of(&deployment(2, status)),
+            Progress::Failed(String::from("ProgressDeadlineExceeded"))
+        );
+    }
+
+    #[test]
+    fn progress_of_an_unobserved_generation_is_rolling() {
+        // The status is of the previous generation, complete before the update.
+        assert_eq!(
+            Progress::of(&deployment(3, status(2, 3, 3, 3))),
+            Progress::Rolling {
+                desired: 3,
+                updated: 3,
+                available: 3
+            }
+        );
+    }
+
+    #[test]
+    fn progress_is_annotated_once_across_relaunches() {
+        let watcher = Watcher::new(
+            String::from("payments"),
+            String::from("api"),
+            Duration::from_secs(2),
+        );
+        assert!(watcher.changed(&Progress::Complete));
+        assert!(!watcher.changed(&Progress::Complete));
+        let relaunched = watcher.clone();
+        assert!(!relaunched.changed(&Progress::Complete));
+        assert!(relaunched.changed(&Progress::Failed(String::from("Timeout"))));
+        assert!(!watcher.changed(&Progress::Failed(String::from("Timeout"))));
+    }
+}
@@ -0,0 +1,347 @@
+use std::{collections::VecDeque, fs, path::Path};
+
+use chrono::{DateTime, Local, Utc};
+use serde_json::{json, Value};
+
+use promkit::grapheme::StyledGraphemes;
+
+use crate::{
+    container::{meta_style, ContainerLog, LogKind, LogStream},
+    dig::View,
+    queue::{Lines, Seen, Span},
+    style::StyleConfig,
+};
+
+/// Version of the session file written by this build.
+/// Files of a newer version are refused rather than half restored.
+const VERSION: u64 = 1;
+
+/// Captured lines and queries of a session, saved to a file to be dug in again later
+/// without the cluster.
+///
+/// The file is a single JSON object:
+/// `{"version": 1, "query": "...", "dig": {...}, "lines": [{"namespace": ..., "body": ...}],
+/// "seen": [{"namespace": ..., "lines": ..., "first": ..., "last": ...}]}`.
+/// Only the text of the lines is kept, and their styles are derived again when loaded,
+/// so the colors of the meta may differ from the ones of the session.
+pub struct Session {
+    pub lines: VecDeque<ContainerLog>,
+    /// Lines received per container, including the ones evicted before the session was saved.
+    /// Empty for sessions saved before it was kept.
+    pub seen: Seen,
+    /// Query which was active in the live view.
+    pub query: String,
+    /// View of dig as it was last left, if it was entered.
+    pub dig: Option<View>,
+}
+
+/// Writes the session to the file, replacing it as a whole
+/// so that an interrupted save doesn't leave a truncated file behind.
+pub fn save(
+    path: &Path,
+    lines: &Lines,
+    seen: &Seen,
+    query: &str,
+    dig: Option<&View>,
+) -> anyhow::Result<()> {
+    let lines: Vec<Value> = lines
+        .iter()
+        .map(|log| {
+            json!({
+                "namespace": log.namespace,
+                "pod": log.pod,
+                "container": log.container,
+                "restarts": log.restarts,
+                "stream": log.stream.map(|stream| match stream {
+                    LogStream::Stdout => "stdout",
+                    LogStream::Stderr => "stderr",
+                }),
+                "kind": match log.kind {
+                    LogKind::Log => "log",
+                    LogKind::Rollout => "rollout",
+                    LogKind::Event => "event",
+                    LogKind::Termination => "termination",
+                    LogKind::Marker => "marker",
+                },
+                "meta": log.meta.to_string(),
+                "body": log.body.to_string(),
+                "continuation": log
+                    .continuation
+                    .iter()
+                    .map(|line| line.to_string())
+                    .collect::<Vec<_>>(),
+                "received": log.received.map(|at| at.to_rfc3339()),
+                "at": log.at.map(|at| at.to_rfc3339()),
+            })
+        })
+        .collect();
+    let seen: Vec<Value> = seen
+        .iter()
+        .map(|((namespace, pod, container), span)| {
+            json!({
+                "namespace": namespace,
+                "pod": pod,
+                "container": container,
+                "lines": span.lines,
+                "first": span.first.to_rfc3339(),
+                "last": span.last.to_rfc3339(),
+            })
+        })
+        .collect();
+    let session = json!({
+        "version": VERSION,
+        "query": query,
+        "dig": dig.map(|view| json!({
+            "query": view.query,
+            "query_mode": view.query_mode,
+            "prefiltered": view.prefiltered,
+            "inverted": view.inverted,
+            "centered": view.centered,
+            "selected": view.selected,
+        })),
+        "lines": lines,
+        "seen": seen,
+    });
+
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec(&session)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+impl Session {
+    /// Reads the session from the file, styling the lines as they would be when received.
+    ///
+    /// # Errors
+    /// Returns an error if the file is not a session, or was saved by a newer version of bul.
+    pub fn load(path: &Path, style: &StyleConfig) -> anyhow::Result<Self> {
+        let invalid =
+            |what: &str| anyhow::anyhow!("{} is not a bul session file: {what}", path.display());
+        let session: Value =
+            serde_json::from_slice(&fs::read(path)?).map_err(|e| invalid(&e.to_string()))?;
+        let version = session["version"]
+            .as_u64()
+            .ok_or_else(|| invalid("missing version"))?;
+        if version > VERSION {
+            return Err(anyhow::anyhow!(
+                "{} was saved by a newer bul (session version {version}, this build reads up to {VERSION}); \
+                upgrade bul to load it",
+                path.display()
+            ));
+        }
+
+        let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+        let time = |value: &Value| {
+            value
+                .as_str()
+                .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+                .map(|at| at.with_timezone(&Local))
+        };
+        let lines = session["lines"]
+            .as_array()
+            .ok_or_else(|| invalid("missing lines"))?
+            .iter()
+            .map(|line| {
+                let mut log = ContainerLog {
+                    namespace: text(&line["namespace"]),
+                    pod: text(&line["pod"]),
+                    container: text(&line["container"]),
+                    restarts: line["restarts"].as_i64().unwrap_or_default() as i32,
+                    stream: match line["stream"].as_str() {
+                        Some("stdout") => Some(LogStream::Stdout),
+                        Some("stderr") => Some(LogStream::Stderr),
+                        _ => None,
+                    },
+                    kind: match line["kind"].as_str() {
+                        Some("rollout") => LogKind::Rollout,
+                        Some("event") => LogKind::Event,
+                        Some("termination") => LogKind::Termination,
+                        Some("marker") => LogKind::Marker,
+                        _ => LogKind::Log,
+                    },
+                    meta: StyledGraphemes::default(),
+                    body: StyledGraphemes::default(),
+                    continuation: Vec::new(),
+                    received: time(&line["received"]),
+                    at: time(&line["at"]).map(|at| at.with_timezone(&Utc)),
+                };
+                let body_style = log.body_style(style);
+                let meta = text(&line["meta"]);
+                // The meta of the annotations is styled like their bodies,
+                // but the markers keep the one of their containers.
+                log.meta = StyledGraphemes::from_str(
+                    &meta,
+                    match log.kind {
+                        LogKind::Log | LogKind::Marker => meta_style(&meta, style),
+                        _ => body_style,
+                    },
+                );
+                log.body = StyledGraphemes::from_str(text(&line["body"]), body_style);
+                log.continuation = line["continuation"]
+                    .as_array()
+                    .map(|lines| {
+                        lines
+                            .iter()
+                            .map(|line| StyledGraphemes::from_str(text(line), body_style))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                log
+            })
+            .collect();
+
+        let seen = session["seen"]
+            .as_array()
+            .map(|seen| {
+                seen.iter()
+                    .filter_map(|entry| {
+                        let span = Span {
+                            lines: entry["lines"].as_u64()? as usize,
+                            first: time(&entry["first"])?,
+                            last: time(&entry["last"])?,
+                        };
+                        let key = (
+                            text(&entry["namespace"]),
+                            text(&entry["pod"]),
+                            text(&entry["container"]),
+                        );
+                        Some((key, span))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let view = &session["dig"];
+        let dig = view.is_object().then(|| View {
+            query: text(&view["query"]),
+            query_mode: view["query_mode"].as_bool().unwrap_or_default(),
+            prefiltered: view["prefiltered"].as_bool().unwrap_or_default(),
+            inverted: view["inverted"].as_bool().unwrap_or_default(),
+            centered: view["centered"].as_bool().unwrap_or_default(),
+            selected: view["selected"].as_u64().map(|index| index as usize),
+        });
+
+        Ok(Self {
+            lines,
+            seen,
+            query: text(&session["query"]),
+            dig,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// File under the temporary directory, unique to the test.
+    fn scratch(test: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("bul-session-{}-{test}.json", std::process::id()))
+    }
+
+    fn log(pod: &str, body: &str) -> ContainerLog {
+        ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from(pod),
+            container: String::from("api"),
+            restarts: 2,
+            stream: Some(LogStream::Stderr),
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from(format!("{pod} api")),
+            body: StyledGraphemes::from(body),
+            continuation: vec![StyledGraphemes::from("  at main")],
+            received: Some(Local::now()),
+            at: Some(Utc::now()),
+        }
+    }
+
+    #[test]
+    fn saved_session_loads_as_it_was() {
+        let path = scratch("round-trip");
+        let lines = Lines::from_iter([
+            log("api-0", "panic: oops"),
+            ContainerLog {
+                kind: LogKind::Event,
+                stream: None,
+                continuation: Vec::new(),
+                received: None,
+                at: None,
+                ..log("api-1", "Normal Pulled: image")
+            },
+        ]);
+        let now = Local::now();
+        let seen = Seen::from([(
+            (
+                String::from("default"),
+                String::from("api-0"),
+                String::from("api"),
+            ),
+            Span {
+                lines: 12,
+                first: now,
+                last: now,
+            },
+        )]);
+        let view = View {
+            query: String::from("level:error"),
+            query_mode: true,
+            selected: Some(1),
+            ..View::default()
+        };
+        save(&path, &lines, &seen, "panic", Some(&view)).unwrap();
+        let session = Session::load(&path, &StyleConfig::default()).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(session.lines.len(), 2);
+        for (loaded, saved) in session.lines.iter().zip(lines.iter()) {
+            assert_eq!(loaded.namespace, saved.namespace);
+            assert_eq!(loaded.pod, saved.pod);
+            assert_eq!(loaded.container, saved.container);
+            assert_eq!(loaded.restarts, saved.restarts);
+            assert_eq!(loaded.stream, saved.stream);
+            assert!(loaded.kind == saved.kind);
+            assert_eq!(loaded.meta.to_string(), saved.meta.to_string());
+            assert_eq!(loaded.message(), saved.message());
+            assert_eq!(loaded.received, saved.received);
+            assert_eq!(loaded.at, saved.at);
+        }
+        assert_eq!(session.seen.len(), 1);
+        assert_eq!(session.seen.values().next().unwrap().lines, 12);
+        assert_eq!(session.query, "panic");
+        let dig = session.dig.unwrap();
+        assert_eq!(dig.query, "level:error");
+        assert!(dig.query_mode && !dig.inverted);
+        assert_eq!(dig.selected, Some(1));
+    }
+
+    #[test]
+    fn session_of_a_newer_version_is_refused() {
+        let path = scratch("newer");
+        fs::write(
+            &path,
+            json!({"version": VERSION + 1, "lines": []}).to_string(),
+        )
+        .unwrap();
+        let e = Session::load(&path, &StyleConfig::default()).err().unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(e.to_string().contains(&format!(
+            "saved by a newer bul (session version {}, this build reads up to {VERSION})",
+            VERSION + 1
+        )));
+    }
+
+    #[test]
+    fn session_without_a_version_is_not_a_session() {
+        let path = scratch("unversioned");
+        fs::write(&path, json!({"lines": []}).to_string()).unwrap();
+        let e = Session::load(&path, &StyleConfig::default()).err().unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "{} is not a bul session file: missing version",
+                path.display()
+            )
+        );
+    }
+}
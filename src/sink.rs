@@ -0,0 +1,45 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::{container::ContainerLog, meta::OutputTemplate};
+
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "json")]
+pub use json::JsonSink;
+
+/// Destination which each received log line is fanned out to, besides the screen,
+/// e.g. a file or another process reading stdout.
+pub trait OutputSink: Send {
+    fn send(&mut self, log: &ContainerLog) -> io::Result<()>;
+
+    /// Writes out what is buffered, e.g. before exiting on a failure.
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Sinks shared by the live view across reconnects.
+pub type Sinks = Arc<Mutex<Vec<Box<dyn OutputSink>>>>;
+
+/// Writes each line as plain text formatted by the template.
+pub struct TemplateSink<W> {
+    template: OutputTemplate,
+    writer: W,
+}
+
+impl<W: Write + Send> TemplateSink<W> {
+    pub fn new(template: OutputTemplate, writer: W) -> Self {
+        Self { template, writer }
+    }
+}
+
+impl<W: Write + Send> OutputSink for TemplateSink<W> {
+    fn send(&mut self, log: &ContainerLog) -> io::Result<()> {
+        writeln!(self.writer, "{}", self.template.render(log))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
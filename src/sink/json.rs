@@ -0,0 +1,112 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, LineWriter, Write},
+    path::Path,
+};
+
+use super::OutputSink;
+use crate::{
+    alias::Aliases,
+    container::{ContainerLog, LogStream},
+};
+
+/// Writes each line as a JSON object on its own line.
+pub struct JsonSink<W> {
+    writer: W,
+    aliases: Aliases,
+}
+
+impl<W: Write + Send> JsonSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            aliases: Aliases::default(),
+        }
+    }
+
+    /// Writes the alias of the Pod of each line as well, if it has any.
+    pub fn with_aliases(mut self, aliases: Aliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+}
+
+impl JsonSink<LineWriter<File>> {
+    /// Opens the file to append to, creating it if missing.
+    /// Each line is flushed as soon as it is written so that readers of the file can follow it.
+    pub fn append(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(LineWriter::new(file)))
+    }
+}
+
+impl<W: Write + Send> OutputSink for JsonSink<W> {
+    fn send(&mut self, log: &ContainerLog) -> io::Result<()> {
+        let line = serde_json::json!({
+            "namespace": log.namespace,
+            "pod": log.pod,
+            "alias": self.aliases.get(&log.pod),
+            "container": log.container,
+            "restarts": log.restarts,
+            "stream": log.stream.map(|stream| match stream {
+                LogStream::Stdout => "stdout",
+                LogStream::Stderr => "stderr",
+            }),
+            "message": log.message(),
+            // Time bul received the line, not the timestamp of the container runtime.
+            "received": log.received.map(|at| at.to_rfc3339()),
+        });
+        writeln!(self.writer, "{line}")
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+    use promkit::grapheme::StyledGraphemes;
+
+    use super::*;
+    use crate::container::LogKind;
+
+    #[test]
+    fn received_time_apart_from_the_message() {
+        let received = Local.with_ymd_and_hms(2026, 10, 16, 14, 2, 13).unwrap();
+        let log = ContainerLog {
+            namespace: String::from("default"),
+            pod: String::from("api-0"),
+            container: String::from("api"),
+            restarts: 0,
+            stream: None,
+            kind: LogKind::Log,
+            meta: StyledGraphemes::from("api-0"),
+            // With the timestamp of the container runtime, as with `--timestamps`.
+            body: StyledGraphemes::from("2026-10-16T09:00:00.000000000Z connected"),
+            continuation: Vec::new(),
+            received: Some(received),
+            at: None,
+        };
+        let mut sink = JsonSink::new(Vec::new());
+        sink.send(&log).unwrap();
+        sink.send(&ContainerLog {
+            received: None,
+            ..log
+        })
+        .unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(sink.writer)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines[0]["message"],
+            "2026-10-16T09:00:00.000000000Z connected"
+        );
+        assert_eq!(lines[0]["received"], received.to_rfc3339());
+        assert!(lines[1]["received"].is_null());
+    }
+}
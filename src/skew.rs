@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Number of the most recent lines the skew is estimated from.
+const WINDOW: usize = 101;
+/// Number of lines needed before the estimate is trusted.
+const MIN_SAMPLES: usize = 11;
+/// Number of lines between two checks of the estimate against the threshold.
+const CHECK_EVERY: usize = 10;
+/// Skew in milliseconds beyond which the clocks are considered apart.
+const THRESHOLD_MILLIS: i64 = 5_000;
+
+/// Estimates how far the clock of the cluster is ahead of the local one,
+/// from the timestamps of the lines compared with the time they were received.
+///
+/// Only lines received live should be observed, since the backlog is genuinely older.
+/// The median of the recent lines is taken so that a few lines delayed on their way
+/// (or logged with a stale timestamp) don't shift the estimate.
+pub struct SkewEstimator {
+    /// Differences in milliseconds between the timestamps and the receive times.
+    samples: VecDeque<i64>,
+    observed: usize,
+    /// Whether the last check found the clocks apart, to warn only when it starts.
+    apart: bool,
+    /// Whether to correct the local times compared with the cluster by the estimate.
+    correct: bool,
+}
+
+impl SkewEstimator {
+    pub fn new(correct: bool) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(WINDOW),
+            observed: 0,
+            apart: false,
+            correct,
+        }
+    }
+
+    pub fn set_correct(&mut self, correct: bool) {
+        self.correct = correct;
+    }
+
+    /// Observes a line stamped `at` by the cluster and received at `received`.
+    /// Returns a warning when the clocks start to appear apart.
+    pub fn observe(&mut self, at: DateTime<Utc>, received: DateTime<Utc>) -> Option<String> {
+        if self.samples.len() == WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((at - received).num_milliseconds());
+        self.observed += 1;
+        if !self.observed.is_multiple_of(CHECK_EVERY) {
+            return None;
+        }
+
+        let skew = self.estimate()?;
+        let apart = skew.num_milliseconds().abs() > THRESHOLD_MILLIS;
+        let starts = apart && !self.apart;
+        self.apart = apart;
+        starts.then(|| {
+            format!(
+                "cluster clock appears ~{}s {}",
+                skew.num_seconds().abs(),
+                if skew > Duration::zero() {
+                    "ahead"
+                } else {
+                    "behind"
+                }
+            )
+        })
+    }
+
+    /// Median of the recent differences, or `None` until enough lines were observed.
+    pub fn estimate(&self) -> Option<Duration> {
+        if self.samples.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mut samples: Vec<i64> = self.samples.iter().copied().collect();
+        let middle = samples.len() / 2;
+        let (_, median, _) = samples.select_nth_unstable(middle);
+        Some(Duration::milliseconds(*median))
+    }
+
+    /// Offset to add to the local time to get the time of the cluster,
+    /// which is zero unless the correction is enabled and the clocks appear apart.
+    pub fn offset(&self) -> Duration {
+        match self.estimate() {
+            Some(skew) if self.correct && skew.num_milliseconds().abs() > THRESHOLD_MILLIS => skew,
+            _ => Duration::zero(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn received() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// Observes the lines stamped with the given skews in milliseconds,
+    /// returning the warnings given along the way.
+    fn observe(estimator: &mut SkewEstimator, skews: impl IntoIterator<Item = i64>) -> Vec<String> {
+        let received = received();
+        skews
+            .into_iter()
+            .filter_map(|skew| estimator.observe(received + Duration::milliseconds(skew), received))
+            .collect()
+    }
+
+    #[test]
+    fn no_estimate_until_enough_samples() {
+        let mut estimator = SkewEstimator::new(true);
+        assert!(observe(&mut estimator, [10_000; MIN_SAMPLES - 1]).is_empty());
+        assert_eq!(estimator.estimate(), None);
+        assert_eq!(estimator.offset(), Duration::zero());
+        observe(&mut estimator, [10_000]);
+        assert_eq!(estimator.estimate(), Some(Duration::seconds(10)));
+    }
+
+    #[test]
+    fn median_ignores_the_outliers() {
+        let mut estimator = SkewEstimator::new(true);
+        // A few lines delayed on their way, or logged with a stale timestamp.
+        let skews = (0..20).map(|i| match i % 5 {
+            0 => -60_000,
+            _ => 8_000 + i,
+        });
+        observe(&mut estimator, skews);
+        let estimate = estimator.estimate().unwrap().num_milliseconds();
+        assert!((8_000..8_020).contains(&estimate), "{estimate}");
+    }
+
+    #[test]
+    fn warns_once_when_the_clocks_drift_apart() {
+        let mut estimator = SkewEstimator::new(false);
+        assert!(observe(&mut estimator, [100; 20]).is_empty());
+        let warnings = observe(&mut estimator, [-30_000; 200]);
+        assert_eq!(warnings, ["cluster clock appears ~30s behind"]);
+        // Back in sync, then apart again.
+        assert!(observe(&mut estimator, [0; 200]).is_empty());
+        let warnings = observe(&mut estimator, [12_000; 200]);
+        assert_eq!(warnings, ["cluster clock appears ~12s ahead"]);
+    }
+
+    #[test]
+    fn within_the_threshold() {
+        let mut estimator = SkewEstimator::new(true);
+        assert!(observe(&mut estimator, [THRESHOLD_MILLIS; 100]).is_empty());
+        assert_eq!(
+            estimator.estimate(),
+            Some(Duration::milliseconds(THRESHOLD_MILLIS))
+        );
+        assert_eq!(estimator.offset(), Duration::zero());
+    }
+
+    #[test]
+    fn offset_only_when_correcting() {
+        let mut estimator = SkewEstimator::new(false);
+        observe(&mut estimator, [-7_000; 50]);
+        assert_eq!(estimator.offset(), Duration::zero());
+        estimator.set_correct(true);
+        assert_eq!(estimator.offset(), Duration::seconds(-7));
+    }
+}
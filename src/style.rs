@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use promkit::{
+    crossterm::style::{Attribute, Attributes, Color, ContentStyle},
+    style::StyleBuilder,
+};
+
+/// Styles of the user-visible elements, shared across the modes
+/// so that they are chosen in one place instead of inline where they are drawn.
+#[derive(Clone)]
+pub struct StyleConfig {
+    /// Parts of the lines matching the query.
+    pub highlight: ContentStyle,
+    /// Prefix of the text editor in bul.
+    pub bul_prefix: ContentStyle,
+    /// Prefix of the text editor in dig.
+    pub dig_prefix: ContentStyle,
+    /// Prefixes of the text editors when the context looks like production.
+    pub production: ContentStyle,
+    /// Character under the cursor of the text editors.
+    pub cursor: ContentStyle,
+    /// Body of the log lines.
+    pub body: ContentStyle,
+    /// Body of the log lines written to stderr, if told apart (see `--raw-cri`).
+    pub stderr: ContentStyle,
+    /// Colors of the meta, one of which is picked for each container.
+    pub palette: Vec<Color>,
+    /// Attributes combined with the colors of the palette to tell more containers apart,
+    /// one of which is picked for each container by a secondary hash. Unused if empty.
+    pub meta_attributes: Vec<Attributes>,
+    /// Badge of the namespace in front of the meta while streaming multiple namespaces,
+    /// on a background color picked for each namespace.
+    pub badge: ContentStyle,
+    /// Background colors of the badges pinned to namespaces, instead of picked from the palette.
+    pub namespace_colors: HashMap<String, Color>,
+    /// Auxiliary text such as rules, counters, placeholders and lines known to the baseline.
+    pub dim: ContentStyle,
+    /// Separator between the lines of different containers.
+    pub separator: ContentStyle,
+    /// Header row showing the status of the only streamed container.
+    pub header: ContentStyle,
+    /// Annotations of the stream, e.g. the progress of a rollout.
+    pub annotation: ContentStyle,
+    /// Glyph at the start of the rows continuing a wrapped line. None if empty.
+    pub continuation_glyph: String,
+    /// Continuation glyph.
+    pub continuation: ContentStyle,
+    /// Kubernetes Events merged into the stream, except warnings.
+    pub event: ContentStyle,
+    /// Warnings, including the pause prompt.
+    pub warning: ContentStyle,
+    /// Errors.
+    pub error: ContentStyle,
+    /// Marker of the lines novel to the baseline.
+    pub novel: ContentStyle,
+}
+
+impl StyleConfig {
+    /// Attributes which multiply the palette by four when combined with it.
+    pub fn extended_meta_attributes() -> Vec<Attributes> {
+        vec![
+            Attributes::default(),
+            Attribute::Bold.into(),
+            Attribute::Dim.into(),
+            Attribute::Underlined.into(),
+        ]
+    }
+}
+
+impl Default for StyleConfig {
+    fn default() -> Self {
+        Self {
+            highlight: StyleBuilder::new()
+                .bgc(Color::Yellow)
+                .fgc(Color::Black)
+                .build(),
+            bul_prefix: StyleBuilder::new().fgc(Color::DarkGreen).build(),
+            dig_prefix: StyleBuilder::new().fgc(Color::DarkBlue).build(),
+            production: StyleBuilder::new().fgc(Color::Red).build(),
+            cursor: StyleBuilder::new().bgc(Color::DarkCyan).build(),
+            body: StyleBuilder::new().fgc(Color::Reset).build(),
+            stderr: StyleBuilder::new().fgc(Color::AnsiValue(217)).build(),
+            palette: vec![
+                Color::Red,
+                Color::DarkRed,
+                Color::Green,
+                Color::DarkGreen,
+                Color::Yellow,
+                Color::DarkYellow,
+                Color::Blue,
+                Color::DarkBlue,
+                Color::Magenta,
+                Color::DarkMagenta,
+                Color::Cyan,
+                Color::DarkCyan,
+            ],
+            meta_attributes: vec![],
+            badge: StyleBuilder::new().fgc(Color::Black).build(),
+            namespace_colors: HashMap::new(),
+            dim: StyleBuilder::new().fgc(Color::DarkGrey).build(),
+            separator: StyleBuilder::new().fgc(Color::DarkGrey).build(),
+            header: StyleBuilder::new()
+                .fgc(Color::Black)
+                .bgc(Color::Grey)
+                .build(),
+            annotation: StyleBuilder::new().fgc(Color::Magenta).build(),
+            continuation_glyph: String::from("↪ "),
+            continuation: StyleBuilder::new().fgc(Color::DarkGrey).build(),
+            event: StyleBuilder::new().fgc(Color::DarkCyan).build(),
+            warning: StyleBuilder::new().fgc(Color::Yellow).build(),
+            error: StyleBuilder::new().fgc(Color::Red).build(),
+            novel: StyleBuilder::new().fgc(Color::Green).build(),
+        }
+    }
+}
+
+/// Parses `NAMESPACE=COLOR`, where the color is a name such as `dark_cyan` or an ANSI value.
+pub fn parse_namespace_color(s: &str) -> Result<(String, Color), String> {
+    let (namespace, color) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAMESPACE=COLOR, got '{s}'"))?;
+    let color = match color.parse::<u8>() {
+        Ok(value) => Color::AnsiValue(value),
+        Err(_) => Color::try_from(color).map_err(|_| format!("unknown color '{color}'"))?,
+    };
+    Ok((namespace.to_string(), color))
+}
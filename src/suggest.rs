@@ -0,0 +1,101 @@
+/// Names closest to the query, most similar first, for when it matched none of them,
+/// e.g. `paymnets` suggests `payments-api-7d9f` and `payments-worker-5c2b`.
+///
+/// The regex metacharacters are stripped from the query first, so that patterns like
+/// `^payments-.*` are compared by their literal parts. Each name is scored by the fewest edits
+/// turning the query into a part of it, so the generated suffixes of Pod names don't count,
+/// and names needing more edits than half the query are not similar enough to suggest.
+pub fn similar_names<'a>(query: &str, names: &'a [String], limit: usize) -> Vec<&'a str> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|ch| !r"\^$.|?*+()[]{}".contains(*ch))
+        .collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut scored: Vec<(usize, &str)> = names
+        .iter()
+        .map(|name| (substring_distance(&query, name), name.as_str()))
+        .filter(|(distance, _)| *distance <= query.len() / 2)
+        .collect();
+    scored.sort_by_key(|(distance, name)| (*distance, name.len(), *name));
+    scored.dedup_by_key(|(_, name)| *name);
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Fewest insertions, deletions and substitutions turning the query into any part of the text.
+fn substring_distance(query: &[char], text: &str) -> usize {
+    // Distances of the query prefixes to the best part ending at the current character,
+    // where a part may start anywhere for free.
+    let mut row: Vec<usize> = (0..=query.len()).collect();
+    let mut best = row[query.len()];
+    for ch in text.chars() {
+        let mut diagonal = row[0];
+        row[0] = 0;
+        for (i, &expected) in query.iter().enumerate() {
+            let substitution = diagonal + (expected != ch) as usize;
+            diagonal = row[i + 1];
+            row[i + 1] = substitution.min(row[i] + 1).min(row[i + 1] + 1);
+        }
+        best = best.min(row[query.len()]);
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn suggests_the_closest_names_first() {
+        let names = names(&[
+            "payments-worker-5c2b",
+            "billing-7f9c",
+            "payments-api-7d9f",
+            "paymaster-0",
+        ]);
+        assert_eq!(
+            similar_names("paymnets", &names, 3),
+            ["payments-api-7d9f", "payments-worker-5c2b", "paymaster-0"]
+        );
+        assert_eq!(similar_names("paymnets", &names, 1), ["payments-api-7d9f"]);
+    }
+
+    #[test]
+    fn compares_the_literal_parts_of_a_regex() {
+        let names = names(&["payments-api-7d9f", "billing-7f9c"]);
+        assert_eq!(
+            similar_names("^paymnts-.*$", &names, 3),
+            ["payments-api-7d9f"]
+        );
+        assert!(similar_names("^.*$", &names, 3).is_empty());
+    }
+
+    #[test]
+    fn leaves_out_the_names_too_far_off() {
+        let names = names(&["billing-7f9c", "frontend-0"]);
+        assert!(similar_names("payments", &names, 3).is_empty());
+    }
+
+    #[test]
+    fn no_duplicates() {
+        let names = names(&["api-0", "api-0", "api-1"]);
+        assert_eq!(similar_names("apj", &names, 3), ["api-0", "api-1"]);
+    }
+
+    #[test]
+    fn distance_to_a_part_of_the_text() {
+        let query: Vec<char> = "api".chars().collect();
+        assert_eq!(substring_distance(&query, "payments-api-7d9f"), 0);
+        assert_eq!(substring_distance(&query, "payments-apx-7d9f"), 1);
+        assert_eq!(substring_distance(&query, ""), 3);
+    }
+}
@@ -1,4 +1,9 @@
-use std::io::{self, Write};
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
 
 use promkit::{
     crossterm::{self, cursor, style, terminal},
@@ -6,30 +11,176 @@ use promkit::{
     pane::Pane,
 };
 
+use crate::{
+    bul::Orientation, notice::Notice, record::Recorder, style::StyleConfig,
+    wrap::strip_continuation,
+};
+
+#[cfg(test)]
+pub mod grid;
+
+/// Where the terminal draws: stdout, or a grid in memory in the tests (see `grid::Screen`).
+#[derive(Clone)]
+pub enum Backend {
+    Stdout,
+    #[cfg(test)]
+    Grid(grid::Screen),
+}
+
+impl Backend {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        match self {
+            Backend::Stdout => terminal::size(),
+            #[cfg(test)]
+            Backend::Grid(screen) => Ok(screen.size()),
+        }
+    }
+
+    /// Writer of the escape sequences, flushed at the end of each frame.
+    fn writer(&self) -> Box<dyn Write> {
+        match self {
+            Backend::Stdout => Box::new(io::stdout()),
+            #[cfg(test)]
+            Backend::Grid(screen) => Box::new(screen.clone()),
+        }
+    }
+}
+
+/// Terminal of the live view, drawing the stream area, the sticky region and the pane.
+///
+/// Owned by the render task of the live view, which draws the frames sent by the other tasks
+/// one at a time, and each frame is written as one synchronized update,
+/// which supporting terminals show at once instead of row by row.
+///
+/// From the bottom, the stream area scrolls up the terminal above the sticky region and the pane.
+/// From the top, the pane and the sticky region come first, and the stream area below them
+/// is redrawn as a whole with the newest rows first, out of the rows kept in `stream`.
 pub struct Terminal {
+    orientation: Orientation,
+    /// Rows of the stream area from the top, newest first.
+    stream: VecDeque<StyledGraphemes>,
     anchor_position: (u16, u16),
+    notices: VecDeque<Notice>,
+    notice_capacity: usize,
+    /// Row pinned above the notices, e.g. the status of the only streamed container.
+    header: Option<String>,
+    /// Recorder of the rows drawn in the stream area, if recording.
+    recorder: Option<Arc<Mutex<Recorder>>>,
+    /// Fingerprint of the sticky region and the pane as last drawn, or 0 if they may have been
+    /// drawn over since, to skip redrawing them unchanged (e.g. while typing fast).
+    drawn: u64,
+    style: StyleConfig,
+    backend: Backend,
 }
 
 impl Terminal {
-    pub fn new(pane: &Pane) -> anyhow::Result<Self> {
-        let mut offset_from_bottom = terminal::size()?;
+    pub fn new(
+        pane: &Pane,
+        orientation: Orientation,
+        notice_capacity: usize,
+        recorder: Option<Arc<Mutex<Recorder>>>,
+        style: StyleConfig,
+    ) -> anyhow::Result<Self> {
+        Self::with_backend(
+            Backend::Stdout,
+            pane,
+            orientation,
+            notice_capacity,
+            recorder,
+            style,
+        )
+    }
+
+    /// Terminal drawing into the backend instead of stdout.
+    pub fn with_backend(
+        backend: Backend,
+        pane: &Pane,
+        orientation: Orientation,
+        notice_capacity: usize,
+        recorder: Option<Arc<Mutex<Recorder>>>,
+        style: StyleConfig,
+    ) -> anyhow::Result<Self> {
+        let mut offset_from_bottom = backend.size()?;
         offset_from_bottom.1 = offset_from_bottom
             .1
             .saturating_sub(1 + pane.visible_row_count() as u16);
 
         Ok(Self {
+            orientation,
+            stream: VecDeque::new(),
             anchor_position: (0, offset_from_bottom.1),
+            notices: VecDeque::with_capacity(notice_capacity),
+            notice_capacity,
+            header: None,
+            recorder,
+            drawn: 0,
+            style,
+            backend,
         })
     }
 
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Size of the screen drawn to, in columns and rows.
+    pub fn size(&self) -> io::Result<(u16, u16)> {
+        self.backend.size()
+    }
+
+    /// Keeps the notice in the sticky region above the pane,
+    /// evicting the oldest one if the region is full.
+    /// The region is disabled if its capacity is zero.
+    pub fn push_notice(&mut self, notice: Notice) {
+        if self.notice_capacity == 0 {
+            return;
+        }
+        if self.notices.len() >= self.notice_capacity {
+            self.notices.pop_front();
+        }
+        self.notices.push_back(notice);
+    }
+
+    /// Dismisses all notices, which hides the sticky region.
+    pub fn clear_notices(&mut self) {
+        self.notices.clear();
+    }
+
+    /// Pins the header above the notices, or removes it if `None`.
+    /// Takes effect on the next redraw of the pane.
+    pub fn set_header(&mut self, header: Option<String>) {
+        self.header = header;
+    }
+
+    /// Number of rows of the sticky region above the pane.
+    fn sticky_row_count(&self) -> u16 {
+        self.header.is_some() as u16 + self.notices.len() as u16
+    }
+
+    /// Draws the rows in the stream area as the newest ones, in their order,
+    /// and the sticky region and the pane.
     pub fn draw_stream_and_pane(
-        &self,
+        &mut self,
         items: Vec<StyledGraphemes>,
         pane: &Pane,
     ) -> anyhow::Result<()> {
-        let coefficient = items.len().saturating_sub(1) as u16;
+        let mut out = self.backend.writer();
+        if self.orientation == Orientation::Top {
+            self.record(&items);
+            for item in items.into_iter().rev() {
+                self.stream.push_front(item);
+            }
+            return self.draw_from_top(pane);
+        }
+        // Rows beyond the stream area would be drawn over by the sticky region and the pane.
+        let hidden = items
+            .len()
+            .saturating_sub(self.anchor_position.1 as usize + 1);
+        let shown = &items[hidden..];
+        let coefficient = shown.len().saturating_sub(1) as u16;
         crossterm::queue!(
-            io::stdout(),
+            out,
+            terminal::BeginSynchronizedUpdate,
             cursor::MoveTo(
                 self.anchor_position.0,
                 self.anchor_position.1.saturating_sub(coefficient)
@@ -38,41 +189,228 @@ impl Terminal {
             terminal::Clear(terminal::ClearType::FromCursorDown),
         )?;
 
-        for item in items.iter() {
+        for item in shown.iter() {
             crossterm::queue!(
-                io::stdout(),
+                out,
                 style::Print(item.styled_display()),
                 cursor::MoveToNextLine(1)
             )?;
         }
 
-        io::stdout().flush()?;
+        self.record(&items);
+        self.draw(pane)
+    }
+
+    /// Records the rows drawn in the stream area, if recording.
+    pub fn record(&self, items: &[StyledGraphemes]) {
+        if let Some(recorder) = &self.recorder {
+            let rows: Vec<String> = items
+                .iter()
+                .map(|row| strip_continuation(&row.to_string(), &self.style))
+                .collect();
+            recorder.lock().unwrap().record(&rows);
+        }
+    }
+
+    /// Redraws the stream area as the two columns of the split view, aligned at the bottom,
+    /// with the left one `left_width` wide followed by a divider.
+    pub fn draw_columns(
+        &mut self,
+        left: &VecDeque<StyledGraphemes>,
+        right: &VecDeque<StyledGraphemes>,
+        left_width: usize,
+        pane: &Pane,
+    ) -> anyhow::Result<()> {
+        let mut out = self.backend.writer();
+        let height = self.anchor_position.1 as usize + 1;
+        let cell = |rows: &VecDeque<StyledGraphemes>, row: usize| {
+            (rows.len() + row)
+                .checked_sub(height)
+                .and_then(|index| rows.get(index))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let divider = StyledGraphemes::from_str("│", self.style.dim);
+        crossterm::queue!(out, terminal::BeginSynchronizedUpdate)?;
+        for row in 0..height {
+            let left = cell(left, row);
+            let padding = " ".repeat(left_width.saturating_sub(left.widths()));
+            crossterm::queue!(
+                out,
+                cursor::MoveTo(0, row as u16),
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                style::Print(left.styled_display()),
+                style::Print(padding),
+                style::Print(divider.styled_display()),
+                style::Print(cell(right, row).styled_display()),
+            )?;
+        }
         self.draw(pane)
     }
 
+    /// Draws a transient counter at the right end of the first row of the pane
+    /// without moving any other rows. It is erased by the next redraw of the pane.
+    pub fn draw_counter(&mut self, counter: &str) -> anyhow::Result<()> {
+        let mut out = self.backend.writer();
+        let width = self.backend.size()?.0;
+        let row = match self.orientation {
+            Orientation::Bottom => self.anchor_position.1 + 1 + self.sticky_row_count(),
+            Orientation::Top => 0,
+        };
+        crossterm::queue!(
+            out,
+            terminal::BeginSynchronizedUpdate,
+            cursor::MoveTo(width.saturating_sub(counter.chars().count() as u16), row),
+            style::Print(StyledGraphemes::from_str(counter, self.style.dim).styled_display()),
+            terminal::EndSynchronizedUpdate,
+        )?;
+        out.flush()?;
+        self.drawn = 0;
+        Ok(())
+    }
+
+    /// Redraws the sticky region and the pane, unless they are drawn as they are already.
     pub fn draw_pane(&mut self, pane: &Pane) -> anyhow::Result<()> {
-        let size = terminal::size()?;
+        let mut out = self.backend.writer();
+        let size = self.backend.size()?;
+        if self.drawn == self.fingerprint(pane, size) {
+            return Ok(());
+        }
+        if self.orientation == Orientation::Top {
+            return self.draw_from_top(pane);
+        }
+        let anchor = size
+            .1
+            .saturating_sub(1 + pane.visible_row_count() as u16 + self.sticky_row_count());
+        crossterm::queue!(out, terminal::BeginSynchronizedUpdate)?;
+        // A taller pane or sticky region pushes the newest rows up rather than covering them,
+        // and a shorter one leaves blank rows where it was.
+        if anchor < self.anchor_position.1 {
+            crossterm::queue!(out, terminal::ScrollUp(self.anchor_position.1 - anchor))?;
+        }
         crossterm::queue!(
-            io::stdout(),
-            cursor::MoveTo(self.anchor_position.0, self.anchor_position.1 + 1),
+            out,
+            cursor::MoveTo(
+                self.anchor_position.0,
+                self.anchor_position.1.min(anchor) + 1
+            ),
             terminal::Clear(terminal::ClearType::FromCursorDown),
         )?;
-        self.anchor_position.1 = size.1.saturating_sub(1 + pane.visible_row_count() as u16);
+        self.anchor_position.1 = anchor;
         self.draw(pane)
     }
 
-    fn draw(&self, pane: &Pane) -> anyhow::Result<()> {
+    /// Hash of what `draw` draws in a terminal of the size. Never 0, which marks a stale drawing.
+    fn fingerprint(&self, pane: &Pane, size: (u16, u16)) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        size.hash(&mut hasher);
+        self.anchor_position.hash(&mut hasher);
+        self.header.hash(&mut hasher);
+        for notice in self.notices.iter() {
+            notice
+                .styled(size.0 as usize, &self.style)
+                .styled_display()
+                .to_string()
+                .hash(&mut hasher);
+        }
+        for row in pane.extract(pane.visible_row_count()) {
+            row.styled_display().to_string().hash(&mut hasher);
+        }
+        hasher.finish().max(1)
+    }
+
+    /// Redraws the whole screen from the top: the pane, the sticky region,
+    /// and the rows of the stream area which fit below them.
+    fn draw_from_top(&mut self, pane: &Pane) -> anyhow::Result<()> {
+        let mut out = self.backend.writer();
+        let size = self.backend.size()?;
+        let width = size.0 as usize;
+        let mut rows: Vec<String> = pane
+            .extract(pane.visible_row_count())
+            .iter()
+            .map(|row| row.styled_display().to_string())
+            .collect();
+        if let Some(header) = &self.header {
+            let header: String = header.chars().take(width).collect();
+            rows.push(
+                StyledGraphemes::from_str(header, self.style.header)
+                    .styled_display()
+                    .to_string(),
+            );
+        }
+        for notice in self.notices.iter() {
+            rows.push(
+                notice
+                    .styled(width, &self.style)
+                    .styled_display()
+                    .to_string(),
+            );
+        }
+        self.stream
+            .truncate((size.1 as usize).saturating_sub(rows.len()));
+        rows.extend(
+            self.stream
+                .iter()
+                .map(|row| row.styled_display().to_string()),
+        );
+
+        crossterm::queue!(out, terminal::BeginSynchronizedUpdate)?;
+        for (i, row) in rows.iter().enumerate() {
+            crossterm::queue!(
+                out,
+                cursor::MoveTo(0, i as u16),
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                style::Print(row),
+            )?;
+        }
+        if rows.len() < size.1 as usize {
+            crossterm::queue!(
+                out,
+                cursor::MoveTo(0, rows.len() as u16),
+                terminal::Clear(terminal::ClearType::FromCursorDown),
+            )?;
+        }
+        crossterm::queue!(out, terminal::EndSynchronizedUpdate)?;
+        out.flush()?;
+        self.drawn = self.fingerprint(pane, size);
+        Ok(())
+    }
+
+    /// Draws the sticky region and the pane below the stream area,
+    /// ending the synchronized update begun by the caller.
+    fn draw(&mut self, pane: &Pane) -> anyhow::Result<()> {
+        let mut out = self.backend.writer();
+        let size = self.backend.size()?;
         crossterm::queue!(
-            io::stdout(),
+            out,
             cursor::MoveTo(self.anchor_position.0, self.anchor_position.1 + 1),
             terminal::Clear(terminal::ClearType::FromCursorDown),
         )?;
 
+        let width = size.0 as usize;
+        if let Some(header) = &self.header {
+            let header: String = header.chars().take(width).collect();
+            crossterm::queue!(
+                out,
+                style::Print(StyledGraphemes::from_str(header, self.style.header).styled_display()),
+                cursor::MoveToNextLine(1)
+            )?;
+        }
+        for notice in self.notices.iter() {
+            crossterm::queue!(
+                out,
+                style::Print(notice.styled(width, &self.style).styled_display()),
+                cursor::MoveToNextLine(1)
+            )?;
+        }
+
         for row in pane.extract(pane.visible_row_count()) {
-            crossterm::queue!(io::stdout(), style::Print(row.styled_display()))?;
+            crossterm::queue!(out, style::Print(row.styled_display()))?;
         }
 
-        io::stdout().flush()?;
+        crossterm::queue!(out, terminal::EndSynchronizedUpdate)?;
+        out.flush()?;
+        self.drawn = self.fingerprint(pane, size);
         Ok(())
     }
 }
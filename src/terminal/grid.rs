@@ -0,0 +1,328 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use promkit::{
+    crossterm::{
+        self, cursor,
+        style::{self, Attribute, Color, Colored, ContentStyle},
+        terminal,
+    },
+    grapheme::StyledGraphemes,
+    pane::Pane,
+};
+
+/// Cell of the grid: the character drawn there and its style.
+/// The cell covered by the right half of a wide character holds `\0`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Cell {
+    ch: char,
+    style: ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            style: ContentStyle::default(),
+        }
+    }
+}
+
+/// Screen in memory which the escape sequences written by `Terminal` are applied to,
+/// like a terminal emulator would, so that the tests can look at what is drawn
+/// rather than at the bytes written.
+///
+/// Only the sequences which bul and promkit write are understood:
+/// cursor movements, scrolling, clearing and the colors and attributes of the text.
+/// A row written past the right edge wraps, and the screen scrolls when it wraps at the bottom.
+#[derive(Clone)]
+pub struct Screen(Arc<Mutex<Grid>>);
+
+struct Grid {
+    width: u16,
+    height: u16,
+    rows: Vec<Vec<Cell>>,
+    cursor: (u16, u16),
+    style: ContentStyle,
+    /// Bytes of an escape sequence or a character not written completely yet.
+    pending: Vec<u8>,
+    /// Number of times the writes were flushed, once per frame drawn.
+    flushes: usize,
+    /// Bottom row as each frame left it, e.g. the prompt of the live view.
+    bottoms: Vec<String>,
+    /// Number of writes left before writing fails, as if the terminal went away, if any.
+    writes_left: Option<usize>,
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self(Arc::new(Mutex::new(Grid {
+            width,
+            height,
+            rows: vec![vec![Cell::default(); width as usize]; height as usize],
+            cursor: (0, 0),
+            style: ContentStyle::default(),
+            pending: Vec::new(),
+            flushes: 0,
+            bottoms: Vec::new(),
+            writes_left: None,
+        })))
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        let grid = self.0.lock().unwrap();
+        (grid.width, grid.height)
+    }
+
+    /// Makes writing fail after the number of writes, as if the terminal went away.
+    pub fn fail_after(&self, writes: usize) {
+        self.0.lock().unwrap().writes_left = Some(writes);
+    }
+
+    /// Number of frames drawn so far, counted by the flushes which end them.
+    pub fn frames(&self) -> usize {
+        self.0.lock().unwrap().flushes
+    }
+
+    /// Bottom row as each frame drawn so far left it, in the order they were drawn.
+    pub fn bottoms(&self) -> Vec<String> {
+        self.0.lock().unwrap().bottoms.clone()
+    }
+
+    /// Text of the rows from the top, without their trailing blanks.
+    pub fn rows(&self) -> Vec<String> {
+        let grid = self.0.lock().unwrap();
+        grid.rows.iter().map(|row| text(row)).collect()
+    }
+
+    /// Style of the first occurrence of `text` in the row, if all its characters share one.
+    pub fn style_of(&self, row: usize, text: &str) -> Option<ContentStyle> {
+        let grid = self.0.lock().unwrap();
+        let cells: Vec<Cell> = grid
+            .rows
+            .get(row)?
+            .iter()
+            .copied()
+            .filter(|cell| cell.ch != '\0')
+            .collect();
+        let text: Vec<char> = text.chars().collect();
+        let found = cells
+            .windows(text.len())
+            .find(|window| window.iter().map(|cell| cell.ch).eq(text.iter().copied()))?;
+        let style = found.first()?.style;
+        found
+            .iter()
+            .all(|cell| cell.style == style)
+            .then_some(style)
+    }
+
+    /// Draws the panes of a prompt from the top of the screen,
+    /// laid out the way `promkit::Prompt` does, e.g. the listbox of dig over its query editor.
+    pub fn draw_panes(&self, panes: &[Pane]) -> io::Result<()> {
+        let height = self.size().1 as usize;
+        let panes: Vec<&Pane> = panes.iter().filter(|pane| !pane.is_empty()).collect();
+        let mut out = self.clone();
+        crossterm::queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(terminal::ClearType::FromCursorDown),
+        )?;
+        let mut used = 0;
+        for (i, pane) in panes.iter().enumerate() {
+            // Each pane below keeps at least a row.
+            let rows = pane.extract(1.max(height.saturating_sub(used + panes.len() - 1 - i)));
+            used += rows.len();
+            for row in rows {
+                crossterm::queue!(
+                    out,
+                    style::Print(row.styled_display()),
+                    cursor::MoveToNextLine(1)
+                )?;
+            }
+        }
+        out.flush()
+    }
+}
+
+impl Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut grid = self.0.lock().unwrap();
+        match grid.writes_left.as_mut() {
+            Some(0) => return Err(io::Error::from(io::ErrorKind::BrokenPipe)),
+            Some(left) => *left -= 1,
+            None => (),
+        }
+        grid.feed(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut grid = self.0.lock().unwrap();
+        grid.flushes += 1;
+        let bottom = grid.rows.last().map(|row| text(row)).unwrap_or_default();
+        grid.bottoms.push(bottom);
+        Ok(())
+    }
+}
+
+/// Text of the row, without its trailing blanks.
+fn text(row: &[Cell]) -> String {
+    let text: String = row
+        .iter()
+        .map(|cell| cell.ch)
+        .filter(|&ch| ch != '\0')
+        .collect();
+    text.trim_end().to_string()
+}
+
+impl Grid {
+    fn feed(&mut self, bytes: &[u8]) {
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.extend_from_slice(bytes);
+        let mut rest = &pending[..];
+        while let Some(len) = self.step(rest) {
+            rest = &rest[len..];
+        }
+        self.pending = rest.to_vec();
+    }
+
+    /// Applies the escape sequence or prints the character at the start of `bytes`,
+    /// returning how many bytes it took, or `None` if it isn't complete yet.
+    fn step(&mut self, bytes: &[u8]) -> Option<usize> {
+        match bytes {
+            [] | [0x1b] => None,
+            [0x1b, b'[', rest @ ..] => {
+                let end = rest.iter().position(|byte| (0x40..=0x7e).contains(byte))?;
+                let params = String::from_utf8_lossy(&rest[..end]).into_owned();
+                self.csi(&params, rest[end]);
+                Some(2 + end + 1)
+            }
+            // Sequences other than CSI aren't written by bul.
+            [0x1b, ..] => Some(1),
+            _ => {
+                let len = match bytes[0] {
+                    0x00..=0x7f => 1,
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    _ => 4,
+                };
+                if bytes.len() < len {
+                    return None;
+                }
+                let ch = std::str::from_utf8(&bytes[..len])
+                    .ok()
+                    .and_then(|text| text.chars().next())
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                self.print(ch);
+                Some(len)
+            }
+        }
+    }
+
+    fn print(&mut self, ch: char) {
+        match ch {
+            '\n' => return self.line_feed(),
+            '\r' => {
+                self.cursor.0 = 0;
+                return;
+            }
+            ch if ch.is_control() => return,
+            _ => (),
+        }
+        let width = StyledGraphemes::from(ch.to_string()).widths().max(1) as u16;
+        if self.cursor.0 + width > self.width {
+            self.cursor.0 = 0;
+            self.line_feed();
+        }
+        let (x, y) = (self.cursor.0 as usize, self.cursor.1 as usize);
+        let style = self.style;
+        self.rows[y][x] = Cell { ch, style };
+        for cell in self.rows[y].iter_mut().skip(x + 1).take(width as usize - 1) {
+            *cell = Cell { ch: '\0', style };
+        }
+        self.cursor.0 += width;
+    }
+
+    fn line_feed(&mut self) {
+        if self.cursor.1 + 1 < self.height {
+            self.cursor.1 += 1;
+        } else {
+            self.scroll_up(1);
+        }
+    }
+
+    fn scroll_up(&mut self, count: u16) {
+        for _ in 0..count.min(self.height) {
+            self.rows.remove(0);
+            self.rows.push(vec![Cell::default(); self.width as usize]);
+        }
+    }
+
+    fn csi(&mut self, params: &str, command: u8) {
+        let count = || params.parse::<u16>().unwrap_or(1).max(1);
+        let (x, y) = self.cursor;
+        match command {
+            b'H' => {
+                let mut position = params.split(';').map(|n| n.parse::<u16>().unwrap_or(1));
+                let row = position.next().unwrap_or(1).max(1) - 1;
+                let column = position.next().unwrap_or(1).max(1) - 1;
+                self.cursor = (column.min(self.width - 1), row.min(self.height - 1));
+            }
+            b'A' => self.cursor.1 = y.saturating_sub(count()),
+            b'B' => self.cursor.1 = (y + count()).min(self.height - 1),
+            b'C' => self.cursor.0 = (x + count()).min(self.width - 1),
+            b'D' => self.cursor.0 = x.saturating_sub(count()),
+            b'E' => self.cursor = (0, (y + count()).min(self.height - 1)),
+            b'F' => self.cursor = (0, y.saturating_sub(count())),
+            b'G' => self.cursor.0 = (count() - 1).min(self.width - 1),
+            b'S' => self.scroll_up(count()),
+            b'J' => {
+                let rows = match params {
+                    "" | "0" => {
+                        self.clear_line(y, x..self.width);
+                        y + 1..self.height
+                    }
+                    "1" => {
+                        self.clear_line(y, 0..x + 1);
+                        0..y
+                    }
+                    _ => 0..self.height,
+                };
+                for row in rows {
+                    self.clear_line(row, 0..self.width);
+                }
+            }
+            b'K' => match params {
+                "" | "0" => self.clear_line(y, x..self.width),
+                "1" => self.clear_line(y, 0..x + 1),
+                _ => self.clear_line(y, 0..self.width),
+            },
+            b'm' => self.sgr(params),
+            // E.g. the synchronized updates and the visibility of the cursor.
+            _ => (),
+        }
+    }
+
+    fn clear_line(&mut self, row: u16, columns: std::ops::Range<u16>) {
+        let columns = columns.start.min(self.width) as usize..columns.end.min(self.width) as usize;
+        self.rows[row as usize][columns].fill(Cell::default());
+    }
+
+    /// Applies a Select Graphic Rendition sequence, which crossterm writes one per color or attribute.
+    fn sgr(&mut self, params: &str) {
+        let color = |color: Color| (color != Color::Reset).then_some(color);
+        match Colored::parse_ansi(params) {
+            _ if params.is_empty() || params == "0" => self.style = ContentStyle::default(),
+            Some(Colored::ForegroundColor(fg)) => self.style.foreground_color = color(fg),
+            Some(Colored::BackgroundColor(bg)) => self.style.background_color = color(bg),
+            Some(Colored::UnderlineColor(ul)) => self.style.underline_color = color(ul),
+            None => {
+                if let Some(attribute) = Attribute::iterator().find(|attr| attr.sgr() == params) {
+                    self.style.attributes.set(attribute);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    cell::Cell,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+
+use crate::queue::Capture;
+
+/// Standard names of the artifacts in the workspace.
+/// The ones written as JSON are left out of the builds without it.
+#[cfg(feature = "json")]
+pub const CAPTURE: &str = "capture.ndjson";
+pub const RECORD: &str = "record.log";
+#[cfg(feature = "json")]
+pub const SESSION: &str = "dig.session";
+pub const DEBUG_LOG: &str = "debug.log";
+#[cfg(feature = "json")]
+pub const MANIFEST: &str = "session.json";
+
+/// Directory the artifacts of a run are written to under their standard names,
+/// given with `--workspace` or else `<data dir>/bul/sessions/<start time>/`
+/// for the artifact flags given without a path.
+///
+/// The directory is created on the first artifact resolved into it,
+/// so that a run without any leaves no empty directory behind.
+pub struct Workspace {
+    /// `None` if neither given nor any data directory is known.
+    dir: Option<PathBuf>,
+    /// Whether the directory was given with `--workspace`.
+    #[cfg_attr(not(feature = "json"), allow(dead_code))]
+    explicit: bool,
+    /// Whether any artifact was resolved into the directory.
+    used: Cell<bool>,
+}
+
+impl Workspace {
+    pub fn new(dir: Option<PathBuf>, started: DateTime<Local>) -> Self {
+        let explicit = dir.is_some();
+        Self {
+            dir: dir.or_else(|| {
+                data_dir().map(|data| {
+                    data.join("bul")
+                        .join("sessions")
+                        .join(started.format("%Y%m%dT%H%M%S").to_string())
+                })
+            }),
+            explicit,
+            used: Cell::new(false),
+        }
+    }
+
+    #[cfg(feature = "json")]
+    pub fn is_explicit(&self) -> bool {
+        self.explicit
+    }
+
+    /// Whether any artifact was written into the directory, and so the manifest is due.
+    #[cfg(feature = "json")]
+    pub fn is_used(&self) -> bool {
+        self.used.get()
+    }
+
+    /// Path of the artifact of the standard name, creating the directory first.
+    pub fn artifact(&self, name: &str) -> anyhow::Result<PathBuf> {
+        let dir = self.dir.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "no directory to write {name} to: neither --workspace, XDG_DATA_HOME nor HOME is set"
+            )
+        })?;
+        fs::create_dir_all(dir)?;
+        self.used.set(true);
+        Ok(dir.join(name))
+    }
+
+    /// Path of an artifact given by a flag taking an optional path:
+    /// the path given with the flag, or else the standard name in the workspace.
+    /// `None` if the flag isn't given.
+    pub fn resolve(
+        &self,
+        flag: &Option<Option<PathBuf>>,
+        name: &str,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        match flag {
+            Some(Some(path)) => Ok(Some(path.clone())),
+            Some(None) => self.artifact(name).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// `$XDG_DATA_HOME`, or `~/.local/share`.
+fn data_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")),
+    }
+}
+
+/// Counts of the lines of a run, as of the last time the live view was left.
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(not(feature = "json"), allow(dead_code))]
+pub struct Counters {
+    /// Log lines received, including the ones not captured.
+    pub received: usize,
+    /// Lines in the queue.
+    pub captured: usize,
+    /// Lines evicted from the queue over its capacity.
+    pub evicted: usize,
+    /// Lines dropped as received again, blank or over the stream buffers.
+    pub dropped: usize,
+}
+
+impl Counters {
+    pub fn of(capture: &Capture) -> Self {
+        let stats = &capture.stats;
+        Self {
+            received: capture.seen.values().map(|span| span.lines).sum(),
+            captured: capture.lines.len(),
+            evicted: stats.evicted,
+            dropped: [&stats.replayed, &stats.blank, &stats.overflowed]
+                .iter()
+                .flat_map(|dropped| dropped.values())
+                .sum(),
+        }
+    }
+}
+
+/// Description of a run, written to the workspace on exit so that tools can index past runs.
+#[cfg(feature = "json")]
+pub struct Manifest<'a> {
+    /// Command line the run was started with.
+    pub args: Vec<String>,
+    pub context: &'a str,
+    pub namespaces: String,
+    pub started: DateTime<Local>,
+    pub counters: Counters,
+    /// Artifacts of the run by kind, wherever they were written.
+    pub artifacts: Vec<(&'static str, &'a Path)>,
+}
+
+#[cfg(feature = "json")]
+impl Manifest<'_> {
+    /// Writes the manifest to `session.json` in the workspace, ending the run now.
+    pub fn write(&self, workspace: &Workspace) -> anyhow::Result<()> {
+        let artifacts: Vec<serde_json::Value> = self
+            .artifacts
+            .iter()
+            .map(|(kind, path)| {
+                serde_json::json!({"kind": kind, "path": path.display().to_string()})
+            })
+            .collect();
+        let manifest = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "args": self.args,
+            "context": self.context,
+            "namespaces": self.namespaces,
+            "started": self.started.to_rfc3339(),
+            "ended": Local::now().to_rfc3339(),
+            "counters": {
+                "received": self.counters.received,
+                "captured": self.counters.captured,
+                "evicted": self.counters.evicted,
+                "dropped": self.counters.dropped,
+            },
+            "artifacts": artifacts,
+        });
+        fs::write(
+            workspace.artifact(MANIFEST)?,
+            serde_json::to_string_pretty(&manifest)?,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directory under the temporary one, unique to the test and removed beforehand.
+    fn scratch(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("bul-workspace-{}-{test}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn resolve_prefers_the_path_of_the_flag() {
+        let dir = scratch("flag");
+        let workspace = Workspace::new(Some(dir.clone()), Local::now());
+        let given = PathBuf::from("elsewhere/record.log");
+        assert_eq!(
+            workspace
+                .resolve(&Some(Some(given.clone())), RECORD)
+                .unwrap(),
+            Some(given)
+        );
+        // Nothing was written into the workspace, which isn't created.
+        assert!(!workspace.used.get());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn resolve_falls_back_to_the_standard_name() {
+        let dir = scratch("standard");
+        let workspace = Workspace::new(Some(dir.clone()), Local::now());
+        assert_eq!(
+            workspace.resolve(&Some(None), RECORD).unwrap(),
+            Some(dir.join(RECORD))
+        );
+        assert!(workspace.used.get());
+        assert!(dir.is_dir());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resolve_without_the_flag() {
+        let dir = scratch("none");
+        let workspace = Workspace::new(Some(dir.clone()), Local::now());
+        assert_eq!(workspace.resolve(&None, RECORD).unwrap(), None);
+        assert!(!workspace.used.get());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn artifact_without_a_directory() {
+        let workspace = Workspace {
+            dir: None,
+            explicit: false,
+            used: Cell::new(false),
+        };
+        let err = workspace.artifact(RECORD).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("no directory to write record.log to"));
+        assert!(workspace.resolve(&Some(None), RECORD).is_err());
+        // A path given with the flag doesn't need the workspace.
+        assert!(workspace
+            .resolve(&Some(Some(PathBuf::from(RECORD))), RECORD)
+            .is_ok());
+    }
+
+    #[test]
+    fn new_defaults_to_the_start_time_under_the_data_dir() {
+        let started = DateTime::parse_from_rfc3339("2024-05-06T07:08:09+00:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let workspace = Workspace::new(None, started);
+        assert!(!workspace.explicit);
+        if let Some(data) = data_dir() {
+            let name = started.format("%Y%m%dT%H%M%S").to_string();
+            assert_eq!(
+                workspace.dir,
+                Some(data.join("bul").join("sessions").join(name))
+            );
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn manifest_contents() {
+        let dir = scratch("manifest");
+        let workspace = Workspace::new(Some(dir.clone()), Local::now());
+        assert!(workspace.is_explicit());
+        assert!(!workspace.is_used());
+        let started = DateTime::parse_from_rfc3339("2024-05-06T07:08:09+02:00")
+            .unwrap()
+            .with_timezone(&Local);
+        let record = dir.join(RECORD);
+        Manifest {
+            args: vec![String::from("bul"), String::from("--record")],
+            context: "kind-kind",
+            namespaces: String::from("default"),
+            started,
+            counters: Counters {
+                received: 10,
+                captured: 7,
+                evicted: 2,
+                dropped: 1,
+            },
+            artifacts: vec![("record", &record)],
+        }
+        .write(&workspace)
+        .unwrap();
+        assert!(workspace.is_used());
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST)).unwrap()).unwrap();
+        assert_eq!(manifest["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest["args"], serde_json::json!(["bul", "--record"]));
+        assert_eq!(manifest["context"], "kind-kind");
+        assert_eq!(manifest["namespaces"], "default");
+        assert_eq!(
+            DateTime::parse_from_rfc3339(manifest["started"].as_str().unwrap()).unwrap(),
+            started
+        );
+        let ended = DateTime::parse_from_rfc3339(manifest["ended"].as_str().unwrap()).unwrap();
+        assert!(ended >= started);
+        assert_eq!(
+            manifest["counters"],
+            serde_json::json!({"received": 10, "captured": 7, "evicted": 2, "dropped": 1})
+        );
+        assert_eq!(
+            manifest["artifacts"],
+            serde_json::json!([{"kind": "record", "path": record.display().to_string()}])
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
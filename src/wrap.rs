@@ -0,0 +1,126 @@
+use promkit::grapheme::StyledGraphemes;
+
+use crate::style::StyleConfig;
+
+/// Wraps the row into rows of `width`, keeping at most `height` of them,
+/// and marks the rows continuing it with the continuation glyph of the style (e.g. a dim `↪`)
+/// so that a wrapped line isn't mistaken for several lines.
+///
+/// The continuation rows are narrower by the width of the glyph, so that the glyph never wraps.
+/// No glyph is added if it is empty.
+pub fn wrap(
+    row: &StyledGraphemes,
+    width: usize,
+    height: usize,
+    style: &StyleConfig,
+) -> Vec<StyledGraphemes> {
+    let rows = row.matrixify(width, height, 0).0;
+    if rows.len() <= 1 || style.continuation_glyph.is_empty() {
+        return rows;
+    }
+
+    let glyph = StyledGraphemes::from_str(&style.continuation_glyph, style.continuation);
+    let rest: StyledGraphemes = row.iter().skip(rows[0].len()).cloned().collect();
+    let mut wrapped = vec![rows[0].clone()];
+    wrapped.extend(
+        rest.matrixify(
+            width.saturating_sub(glyph.widths()).max(1),
+            height.saturating_sub(1),
+            0,
+        )
+        .0
+        .iter()
+        .map(|row| StyledGraphemes::from_iter([&glyph, row])),
+    );
+    wrapped
+}
+
+/// Removes the continuation glyph from the start of the row, if any,
+/// so that exported rows carry only the text of the lines.
+pub fn strip_continuation(row: &str, style: &StyleConfig) -> String {
+    if style.continuation_glyph.is_empty() {
+        return row.to_string();
+    }
+    row.strip_prefix(style.continuation_glyph.as_str())
+        .unwrap_or(row)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use promkit::crossterm::style::ContentStyle;
+
+    use super::*;
+
+    fn rows(text: &str, width: usize, height: usize, style: &StyleConfig) -> Vec<String> {
+        wrap(
+            &StyledGraphemes::from_str(text, ContentStyle::default()),
+            width,
+            height,
+            style,
+        )
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+    }
+
+    #[test]
+    fn fitting_row_is_left_as_is() {
+        let style = StyleConfig::default();
+        assert_eq!(rows("ready", 10, 5, &style), ["ready"]);
+        assert_eq!(rows("0123456789", 10, 5, &style), ["0123456789"]);
+    }
+
+    #[test]
+    fn continuation_rows_start_with_the_glyph() {
+        let style = StyleConfig::default();
+        assert_eq!(
+            rows("0123456789abcdefghijklmnop", 10, 5, &style),
+            ["0123456789", "↪ abcdefgh", "↪ ijklmnop"]
+        );
+    }
+
+    #[test]
+    fn height_limits_the_rows() {
+        let style = StyleConfig::default();
+        assert_eq!(
+            rows("0123456789abcdefghijklmnopqrstuvwxyz", 10, 2, &style),
+            ["0123456789", "↪ abcdefgh"]
+        );
+    }
+
+    #[test]
+    fn wide_characters() {
+        let style = StyleConfig::default();
+        assert_eq!(
+            rows("ログが流れていません", 10, 5, &style),
+            ["ログが流れ", "↪ ていませ", "↪ ん"]
+        );
+    }
+
+    #[test]
+    fn custom_and_empty_glyphs() {
+        let mut style = StyleConfig {
+            continuation_glyph: String::from("> "),
+            ..Default::default()
+        };
+        assert_eq!(
+            rows("0123456789abcdef", 10, 5, &style),
+            ["0123456789", "> abcdef"]
+        );
+        style.continuation_glyph = String::new();
+        assert_eq!(
+            rows("0123456789abcdef", 10, 5, &style),
+            ["0123456789", "abcdef"]
+        );
+    }
+
+    #[test]
+    fn glyph_is_stripped_from_the_exported_rows() {
+        let mut style = StyleConfig::default();
+        assert_eq!(strip_continuation("↪ abcdef", &style), "abcdef");
+        assert_eq!(strip_continuation("abcdef", &style), "abcdef");
+        style.continuation_glyph = String::new();
+        assert_eq!(strip_continuation("↪ abcdef", &style), "↪ abcdef");
+    }
+}